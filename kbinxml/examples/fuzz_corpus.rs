@@ -0,0 +1,43 @@
+//! Write the documents of `kbinxml::fixtures` to the corpus of every fuzz
+//! target in `fuzz/`, as seeds for `cargo fuzz run`.
+//!
+//! ```text
+//! cargo run --example fuzz_corpus --features fixtures
+//! ```
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use kbinxml::{CompressionType, EncodingType, Options};
+
+fn write_seed(target: &str, name: &str, data: &[u8]) -> Result<(), Box<dyn Error>> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("fuzz")
+        .join("corpus")
+        .join(target);
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join(name), data)?;
+
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let uncompressed = Options::new(CompressionType::Uncompressed, EncodingType::UTF_8);
+
+    for (name, node) in kbinxml::fixtures::all() {
+        let compressed = kbinxml::to_binary(&node)?;
+        let raw = kbinxml::to_binary_with_options(uncompressed.clone(), &node)?;
+        let text = kbinxml::to_text_xml(&node)?;
+
+        for target in &["from_binary", "from_bytes_serde"] {
+            write_seed(target, &format!("{}.kbin", name), &compressed)?;
+            write_seed(target, &format!("{}_uncompressed.kbin", name), &raw)?;
+        }
+        for target in &["from_text_xml", "from_bytes_serde"] {
+            write_seed(target, &format!("{}.xml", name), &text)?;
+        }
+    }
+
+    Ok(())
+}
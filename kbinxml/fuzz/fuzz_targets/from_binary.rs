@@ -0,0 +1,18 @@
+#![no_main]
+
+use bytes::Bytes;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let (collection, _) = match kbinxml::from_binary(Bytes::copy_from_slice(data)) {
+        Ok(value) => value,
+        Err(_) => return,
+    };
+
+    // Anything that reads has to write again without panicking.
+    if let Ok(node) = collection.as_node() {
+        let _ = kbinxml::to_binary(&node);
+        let _ = kbinxml::to_text_xml(&node);
+    }
+    let _ = kbinxml::to_text_xml(&collection);
+});
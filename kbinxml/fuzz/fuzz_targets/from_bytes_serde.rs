@@ -0,0 +1,32 @@
+#![no_main]
+
+use std::collections::BTreeMap;
+
+use bytes::Bytes;
+use libfuzzer_sys::fuzz_target;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+struct Entry {
+    #[serde(rename = "@id")]
+    id: Option<String>,
+    value: Option<i64>,
+    name: Option<String>,
+    flag: Option<bool>,
+    position: Option<(f32, f32, f32)>,
+    data: Option<Vec<u8>>,
+    entry: Option<Vec<Entry>>,
+}
+
+fuzz_target!(|data: &[u8]| {
+    let node = match kbinxml::from_bytes(Bytes::copy_from_slice(data))
+        .and_then(|(collection, _)| collection.as_node())
+    {
+        Ok(node) => node,
+        Err(_) => return,
+    };
+
+    let _ = kbinxml::from_node::<Entry>(&node);
+    let _ = kbinxml::from_node::<BTreeMap<String, String>>(&node);
+});
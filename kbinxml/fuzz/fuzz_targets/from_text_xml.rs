@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let (collection, _) = match kbinxml::from_text_xml(data) {
+        Ok(value) => value,
+        Err(_) => return,
+    };
+
+    // Anything that reads has to write again without panicking.
+    let _ = kbinxml::node_collection_to_binary(&collection);
+    if let Ok(node) = collection.as_node() {
+        let _ = kbinxml::to_binary(&node);
+        let _ = kbinxml::to_text_xml(&node);
+    }
+});
@@ -0,0 +1,96 @@
+/// Tracks the memory allocated for a document as it is read or converted.
+///
+/// Sizes are estimates: each node and attribute counts the size it takes in
+/// its parent, plus the length of any name and value copied out of the input.
+/// Nothing is freed along the way, so the current total is also the peak.
+#[derive(Clone, Debug, Default)]
+pub struct Accountant {
+    budget: Option<usize>,
+    max_nodes: Option<usize>,
+    bytes: usize,
+    nodes: usize,
+}
+
+impl Accountant {
+    pub fn new(budget: Option<usize>) -> Self {
+        Self {
+            budget,
+            ..Default::default()
+        }
+    }
+
+    /// Limit the number of nodes that can be counted.
+    #[inline]
+    pub fn with_max_nodes(mut self, max_nodes: Option<usize>) -> Self {
+        self.max_nodes = max_nodes;
+        self
+    }
+
+    #[inline]
+    pub fn budget(&self) -> Option<usize> {
+        self.budget
+    }
+
+    #[inline]
+    pub fn max_nodes(&self) -> Option<usize> {
+        self.max_nodes
+    }
+
+    #[inline]
+    pub fn peak_bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// The number of nodes read, not counting attributes and end markers.
+    #[inline]
+    pub fn nodes(&self) -> usize {
+        self.nodes
+    }
+
+    /// Record an allocation of `len` bytes.
+    ///
+    /// On failure, returns the total that would have been allocated and
+    /// leaves the accountant unchanged.
+    pub(crate) fn allocate(&mut self, len: usize) -> Result<(), usize> {
+        let total = self.bytes.saturating_add(len);
+        match self.budget {
+            Some(budget) if total > budget => Err(total),
+            _ => {
+                self.bytes = total;
+                Ok(())
+            },
+        }
+    }
+
+    /// Count a node, failing if that would exceed the node limit.
+    pub(crate) fn count_node(&mut self) -> Result<(), ()> {
+        match self.max_nodes {
+            Some(max_nodes) if self.nodes >= max_nodes => Err(()),
+            _ => {
+                self.nodes += 1;
+                Ok(())
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Accountant;
+
+    #[test]
+    fn test_budget() {
+        let mut accountant = Accountant::new(Some(10));
+        assert_eq!(accountant.allocate(6), Ok(()));
+        assert_eq!(accountant.allocate(5), Err(11));
+        assert_eq!(accountant.peak_bytes(), 6);
+        assert_eq!(accountant.allocate(4), Ok(()));
+        assert_eq!(accountant.peak_bytes(), 10);
+        assert_eq!(accountant.allocate(1), Err(11));
+
+        let mut unlimited = Accountant::new(None);
+        assert_eq!(unlimited.allocate(usize::MAX), Ok(()));
+        assert_eq!(unlimited.allocate(1), Ok(()));
+        assert_eq!(unlimited.peak_bytes(), usize::MAX);
+    }
+}
@@ -8,6 +8,7 @@ use snafu::{ResultExt, Snafu};
 
 use crate::encoding_type::{EncodingError, EncodingType};
 use crate::node_types::StandardType;
+use crate::options::UnmappableCharacterPolicy;
 
 #[derive(Debug, Snafu)]
 pub enum ByteBufferError {
@@ -18,6 +19,18 @@ pub enum ByteBufferError {
     ))]
     OutOfBounds { offset: usize, size: usize },
 
+    #[snafu(display(
+        "Length prefix at offset {} declares {} byte(s) but only {} remain",
+        offset,
+        declared,
+        remaining
+    ))]
+    LengthExceedsBuffer {
+        offset: usize,
+        declared: usize,
+        remaining: usize,
+    },
+
     #[snafu(display("Failed to read {} byte(s) from data buffer", size))]
     DataRead { size: usize, source: io::Error },
 
@@ -91,11 +104,13 @@ pub(crate) fn strip_trailing_null_bytes(data: &[u8]) -> &[u8] {
     }
 }
 
+#[derive(Clone)]
 pub struct ByteBufferRead {
     cursor: Cursor<Bytes>,
     buffer: Bytes,
     offset_1: usize,
     offset_2: usize,
+    last_read: usize,
 }
 
 pub struct ByteBufferWrite {
@@ -111,9 +126,16 @@ impl ByteBufferRead {
             buffer,
             offset_1: 0,
             offset_2: 0,
+            last_read: 0,
         }
     }
 
+    /// The offset in the buffer that the most recent data read started at.
+    #[inline]
+    pub fn last_read_offset(&self) -> usize {
+        self.last_read
+    }
+
     #[inline]
     fn data_buf_offset(&self) -> usize {
         // Position is not the index of the previously read byte, it is the current
@@ -142,6 +164,7 @@ impl ByteBufferRead {
         let end = self.check_read_size(start, size)?;
 
         let data = self.buffer.slice(start..end);
+        self.last_read = start;
         trace!(
             "buf_read_size => index: {}, size: {}, data: 0x{:02x?}",
             self.cursor.position(),
@@ -156,15 +179,33 @@ impl ByteBufferRead {
         Ok(data)
     }
 
+    /// Read a length prefix and check that the buffer holds that many bytes
+    /// after it, before anything is sliced or allocated for the value.
+    pub fn read_length(&mut self) -> Result<usize, ByteBufferError> {
+        let offset = self.data_buf_offset();
+        let declared = self.cursor.read_u32::<BigEndian>().context(ReadSizeSnafu)? as usize;
+        let remaining = self.buffer.len().saturating_sub(self.data_buf_offset());
+
+        if declared > remaining {
+            return Err(ByteBufferError::LengthExceedsBuffer {
+                offset,
+                declared,
+                remaining,
+            });
+        }
+
+        Ok(declared)
+    }
+
     pub fn buf_read(&mut self) -> Result<Bytes, ByteBufferError> {
-        let size = self.cursor.read_u32::<BigEndian>().context(ReadSizeSnafu)?;
+        let size = self.read_length()?;
         debug!(
             "buf_read => index: {}, size: {}",
             self.cursor.position(),
             size
         );
 
-        let data = self.buf_read_size(size as usize)?;
+        let data = self.buf_read_size(size)?;
         self.realign_reads(None)?;
 
         Ok(data)
@@ -193,6 +234,7 @@ impl ByteBufferRead {
             1 => {
                 let end = self.check_read_size(self.offset_1, 1)?;
                 let data = self.buffer.slice(self.offset_1..end);
+                self.last_read = self.offset_1;
                 self.offset_1 += 1;
 
                 (true, data)
@@ -200,6 +242,7 @@ impl ByteBufferRead {
             2 => {
                 let end = self.check_read_size(self.offset_2, 2)?;
                 let data = self.buffer.slice(self.offset_2..end);
+                self.last_read = self.offset_2;
                 self.offset_2 += 2;
 
                 (true, data)
@@ -299,14 +342,21 @@ impl ByteBufferWrite {
         Ok(())
     }
 
-    pub fn write_str(&mut self, encoding: EncodingType, data: &str) -> Result<(), ByteBufferError> {
+    pub fn write_str(
+        &mut self,
+        encoding: EncodingType,
+        unmappable: UnmappableCharacterPolicy,
+        data: &str,
+    ) -> Result<(), ByteBufferError> {
         trace!(
             "write_str => input: {}, data: 0x{:02x?}",
             data,
             data.as_bytes()
         );
 
-        let bytes = encoding.encode_bytes(data).context(StringEncodeSnafu)?;
+        let bytes = encoding
+            .encode_bytes_with(data, unmappable)
+            .context(StringEncodeSnafu)?;
         self.buf_write(&bytes)?;
 
         Ok(())
@@ -0,0 +1,87 @@
+use crate::compression_type::CompressionType;
+use crate::encoding_type::EncodingType;
+use crate::node_types::StandardType;
+
+/// The optional Cargo features of this crate.
+const FEATURES: &[(&str, bool)] = &[
+    ("fixtures", cfg!(feature = "fixtures")),
+    ("profile", cfg!(feature = "profile")),
+    ("test_support", cfg!(feature = "test_support")),
+];
+
+/// What this build of the crate can read and write, see `capabilities`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    /// The version of the crate.
+    pub version: &'static str,
+
+    /// Every node type the reader and writer know, in order of their IDs.
+    pub node_types: Vec<StandardType>,
+
+    pub encodings: Vec<EncodingType>,
+    pub compressions: Vec<CompressionType>,
+
+    /// The optional Cargo features compiled in.
+    pub features: Vec<&'static str>,
+}
+
+impl Capabilities {
+    /// Is the node type with this ID, without the array bit, supported?
+    pub fn supports_type_id(&self, id: u8) -> bool {
+        self.node_types.iter().any(|node_type| node_type.id == id)
+    }
+
+    #[inline]
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.features.contains(&feature)
+    }
+}
+
+/// Describe the node types, encodings, compressions and optional features
+/// available at runtime, so tools can adapt to the build they link against.
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        node_types: (0..=u8::MAX)
+            .filter_map(|id| StandardType::from_u8(id).ok())
+            .collect(),
+        encodings: vec![
+            EncodingType::None,
+            EncodingType::ASCII,
+            EncodingType::ISO_8859_1,
+            EncodingType::EUC_JP,
+            EncodingType::SHIFT_JIS,
+            EncodingType::UTF_8,
+        ],
+        compressions: vec![CompressionType::Compressed, CompressionType::Uncompressed],
+        features: FEATURES
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|&(feature, _)| feature)
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::node_types::StandardType;
+
+    use super::capabilities;
+
+    #[test]
+    fn test_capabilities() {
+        let capabilities = capabilities();
+        assert_eq!(capabilities.version, env!("CARGO_PKG_VERSION"));
+        assert!(capabilities.supports_type_id(StandardType::Ip4.id));
+        assert!(!capabilities.supports_type_id(47));
+        assert!(capabilities
+            .node_types
+            .windows(2)
+            .all(|pair| pair[0].id < pair[1].id));
+        assert_eq!(capabilities.encodings.len(), 6);
+        assert_eq!(
+            capabilities.has_feature("profile"),
+            cfg!(feature = "profile")
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use bytes::Bytes;
+
+use crate::diff::Paths;
+use crate::error::KbinError;
+use crate::node::NodeDefinition;
+use crate::node_types::StandardType;
+use crate::reader::Reader;
+
+/// Where a token was read from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenLocation {
+    /// The path of the node the token belongs to. Attributes are written as
+    /// `@name` after the path of their node.
+    pub path: String,
+
+    /// The absolute offset of the token in the node buffer.
+    pub node_offset: u64,
+
+    /// The absolute offset of the value of the token in the data buffer.
+    pub data_offset: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DivergenceKind {
+    /// The node types or array flags differ, including one document ending
+    /// before the other.
+    Type,
+    Name,
+    Value,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    pub kind: DivergenceKind,
+    pub a: TokenLocation,
+    pub b: TokenLocation,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CompareReport {
+    Identical,
+    Diverged(Divergence),
+}
+
+fn has_value(def: &NodeDefinition) -> bool {
+    !matches!(
+        def.node_type,
+        StandardType::NodeStart | StandardType::NodeEnd | StandardType::FileEnd
+    )
+}
+
+struct Token {
+    def: NodeDefinition,
+    name: Option<String>,
+    location: TokenLocation,
+}
+
+/// Reads the tokens of a document along with their locations.
+struct Tokens {
+    reader: Reader,
+    paths: Paths,
+}
+
+impl Tokens {
+    fn new(input: &[u8]) -> Result<Self, KbinError> {
+        Ok(Self {
+            reader: Reader::new(Bytes::from(input.to_vec()))?,
+            paths: Paths::new(),
+        })
+    }
+
+    fn next(&mut self) -> Result<Token, KbinError> {
+        let node_offset = self.reader.node_offset();
+        let def = self.reader.read_node_definition()?;
+        let name = def.key()?;
+
+        let path = match (def.node_type, name.as_ref()) {
+            (StandardType::NodeEnd, _) => {
+                let path = self.paths.current();
+                self.paths.leave();
+                path
+            },
+            (StandardType::Attribute, Some(name)) => {
+                format!("{}/@{}", self.paths.current(), name)
+            },
+            (_, Some(name)) => self.paths.enter(name),
+            (_, None) => self.paths.current(),
+        };
+        let data_offset = if has_value(&def) {
+            Some(self.reader.last_data_offset())
+        } else {
+            None
+        };
+
+        Ok(Token {
+            def,
+            name,
+            location: TokenLocation {
+                path,
+                node_offset,
+                data_offset,
+            },
+        })
+    }
+}
+
+/// Compare the token streams of two binary documents and report the first
+/// token that differs.
+///
+/// Names and values are compared after decoding, so differences in padding
+/// or string encoding alone do not count as divergences.
+pub fn compare_binaries(a: &[u8], b: &[u8]) -> Result<CompareReport, KbinError> {
+    let mut a_tokens = Tokens::new(a)?;
+    let mut b_tokens = Tokens::new(b)?;
+
+    loop {
+        let a = a_tokens.next()?;
+        let b = b_tokens.next()?;
+
+        let kind = if a.def.node_type_tuple() != b.def.node_type_tuple() {
+            Some(DivergenceKind::Type)
+        } else if a.name != b.name {
+            Some(DivergenceKind::Name)
+        } else if has_value(&a.def) && a.def.value()? != b.def.value()? {
+            Some(DivergenceKind::Value)
+        } else {
+            None
+        };
+
+        if let Some(kind) = kind {
+            return Ok(CompareReport::Diverged(Divergence {
+                kind,
+                a: a.location,
+                b: b.location,
+            }));
+        }
+        if a.def.node_type == StandardType::FileEnd {
+            return Ok(CompareReport::Identical);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding_type::EncodingType;
+    use crate::node::Node;
+    use crate::options::Options;
+    use crate::value::Value;
+
+    use super::{compare_binaries, CompareReport, Divergence, DivergenceKind, TokenLocation};
+
+    fn document(name: &str, values: [Value; 3], encoding: EncodingType) -> Vec<u8> {
+        let [x, first, second] = values;
+        let root = Node::with_nodes("root", [
+            Node::with_value(name, x),
+            Node::with_value("music", first),
+            Node::with_value("music", second),
+        ]);
+
+        crate::to_binary_with_options(Options::with_encoding(encoding), &root)
+            .expect("Failed to encode")
+    }
+
+    fn divergence(a: &[u8], b: &[u8]) -> Divergence {
+        match compare_binaries(a, b).expect("Failed to compare") {
+            CompareReport::Diverged(divergence) => divergence,
+            CompareReport::Identical => panic!("Documents are identical"),
+        }
+    }
+
+    #[test]
+    fn test_compare_binaries() {
+        let values = || [Value::U32(1), Value::U32(1), Value::U32(2)];
+        let base = document("x", values(), EncodingType::SHIFT_JIS);
+
+        // Only the encoding differs
+        let utf8 = document("x", values(), EncodingType::UTF_8);
+        assert_ne!(utf8, base);
+        let report = compare_binaries(&base, &utf8).expect("Failed to compare");
+        assert_eq!(report, CompareReport::Identical);
+
+        // The node buffer is padded to end at 36, so the data buffer starts
+        // after its length at 40
+        let changed = [Value::U32(1), Value::U32(1), Value::U32(3)];
+        let value = document("x", changed, EncodingType::SHIFT_JIS);
+        let location = |path: &str, node_offset, data_offset| TokenLocation {
+            path: path.to_owned(),
+            node_offset,
+            data_offset,
+        };
+        assert_eq!(divergence(&base, &value), Divergence {
+            kind: DivergenceKind::Value,
+            a: location("/root/music[1]", 24, Some(48)),
+            b: location("/root/music[1]", 24, Some(48)),
+        });
+
+        let name = document("y", values(), EncodingType::SHIFT_JIS);
+        assert_eq!(divergence(&base, &name), Divergence {
+            kind: DivergenceKind::Name,
+            a: location("/root/x", 13, Some(40)),
+            b: location("/root/y", 13, Some(40)),
+        });
+
+        let types = [Value::U16(1), Value::U32(1), Value::U32(2)];
+        let types = document("x", types, EncodingType::SHIFT_JIS);
+        let found = divergence(&base, &types);
+        assert_eq!(found.kind, DivergenceKind::Type);
+        assert_eq!(found.a.path, "/root/x");
+    }
+}
@@ -4,6 +4,11 @@ use std::fmt;
 use crate::{SIG_COMPRESSED, SIG_UNCOMPRESSED};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "profile",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum CompressionType {
     Compressed,
     Uncompressed,
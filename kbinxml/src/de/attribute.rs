@@ -0,0 +1,117 @@
+use serde::de::{self, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+
+use crate::de::DeError;
+
+/// Deserializes the text of an attribute, parsing it when a number or
+/// boolean is requested.
+pub(crate) struct AttributeDeserializer<'de> {
+    value: &'de str,
+    path: String,
+}
+
+impl<'de> AttributeDeserializer<'de> {
+    pub(crate) fn new(value: &'de str, path: String) -> Self {
+        Self { value, path }
+    }
+
+    fn invalid(&self, target: &'static str) -> DeError {
+        DeError::InvalidAttribute {
+            path: self.path.clone(),
+            value: self.value.to_owned(),
+            target,
+        }
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident, $ty:ty;)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, DeError>
+            where
+                V: Visitor<'de>,
+            {
+                match self.value.trim().parse::<$ty>() {
+                    Ok(value) => visitor.$visit(value),
+                    Err(_) => Err(self.invalid(stringify!($ty))),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for AttributeDeserializer<'de> {
+    type Error = DeError;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_borrowed_str(self.value)
+    }
+
+    /// Accepts the `1` and `0` written for `bool` values as well as `true`
+    /// and `false`.
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value.trim() {
+            "1" | "true" => visitor.visit_bool(true),
+            "0" | "false" => visitor.visit_bool(false),
+            _ => Err(self.invalid("bool")),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_enum(self.value.into_deserializer())
+    }
+
+    deserialize_parsed! {
+        deserialize_i8 => visit_i8, i8;
+        deserialize_u8 => visit_u8, u8;
+        deserialize_i16 => visit_i16, i16;
+        deserialize_u16 => visit_u16, u16;
+        deserialize_i32 => visit_i32, i32;
+        deserialize_u32 => visit_u32, u32;
+        deserialize_i64 => visit_i64, i64;
+        deserialize_u64 => visit_u64, u64;
+        deserialize_f32 => visit_f32, f32;
+        deserialize_f64 => visit_f64, f64;
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 char str string bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
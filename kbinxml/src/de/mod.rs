@@ -0,0 +1,1047 @@
+use std::fmt;
+
+use serde::de::{self, Deserialize, DeserializeSeed, SeqAccess, Visitor};
+use serde::forward_to_deserialize_any;
+use snafu::Snafu;
+
+use crate::error::KbinError;
+use crate::node::Node;
+use crate::node_types::StandardType;
+use crate::options::Options;
+use crate::value::{integer_bounds, Value, ValueArray};
+
+mod attribute;
+mod structure;
+
+use self::attribute::AttributeDeserializer;
+use self::structure::Struct;
+
+#[derive(Debug, Snafu)]
+pub enum DeError {
+    #[snafu(display("{}", message))]
+    Custom { message: String },
+
+    #[snafu(display("Deserializing {} is not supported", kind))]
+    Unsupported { kind: &'static str },
+
+    #[snafu(display("Failed to parse attribute {} value {:?} as {}", path, value, target))]
+    InvalidAttribute {
+        path: String,
+        value: String,
+        target: &'static str,
+    },
+
+    #[snafu(display("Expected a single node named {}, found {}", key, count))]
+    MultipleNodes { key: String, count: usize },
+
+    #[snafu(display("Attribute {} has the same name as a child node", path))]
+    NameConflict { path: String },
+
+    #[snafu(display(
+        "Narrowing {} to {} at {} requires `allow_lossless_narrowing`",
+        stored,
+        target,
+        path
+    ))]
+    NarrowingDisabled {
+        path: String,
+        stored: StandardType,
+        target: StandardType,
+    },
+
+    #[snafu(display(
+        "Value {} stored as {} at {} does not fit in {}",
+        value,
+        stored,
+        path,
+        target
+    ))]
+    OutOfRange {
+        path: String,
+        value: i128,
+        stored: StandardType,
+        target: StandardType,
+    },
+
+    #[snafu(display(
+        "Expected {} elements at {}, found {} in {}",
+        expected,
+        path,
+        found,
+        node_type
+    ))]
+    CountMismatch {
+        path: String,
+        node_type: StandardType,
+        expected: usize,
+        found: usize,
+    },
+
+    #[snafu(display("{}: {}", path, source))]
+    Path {
+        path: String,
+        #[snafu(source(from(DeError, Box::new)))]
+        source: Box<DeError>,
+    },
+}
+
+impl DeError {
+    /// The path of the node or attribute the error occurred at.
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            DeError::InvalidAttribute { path, .. } |
+            DeError::NameConflict { path } |
+            DeError::NarrowingDisabled { path, .. } |
+            DeError::OutOfRange { path, .. } |
+            DeError::CountMismatch { path, .. } |
+            DeError::Path { path, .. } => Some(path),
+            DeError::Custom { .. } |
+            DeError::Unsupported { .. } |
+            DeError::MultipleNodes { .. } => None,
+        }
+    }
+
+    /// Attach `path` to errors that do not have one yet, like the errors
+    /// of `Deserialize` implementations.
+    pub(crate) fn at(self, path: &str) -> Self {
+        if self.path().is_some() {
+            return self;
+        }
+
+        DeError::Path {
+            path: path.to_owned(),
+            source: Box::new(self),
+        }
+    }
+}
+
+lazy_static! {
+    static ref DEFAULT_OPTIONS: Options = Options::default();
+}
+
+impl de::Error for DeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        DeError::Custom {
+            message: msg.to_string(),
+        }
+    }
+}
+
+/// Convert a `Node` tree to a deserializable value.
+///
+/// This is the inverse of `to_node`. Child nodes are matched to struct fields
+/// and map entries by name, repeated child nodes and arrays are read as
+/// sequences and the key of the root node is ignored.
+///
+/// Errors carry the path of the node or attribute they occurred at, see
+/// `DeError::path`.
+///
+/// `Option` fields are `None` when their node is missing or empty, without a
+/// value, attributes or children.
+///
+/// Structs with `#[serde(flatten)]` fields are read as maps, so their
+/// attribute fields need `NameConflictPolicy::PrefixAllAttributes`. The
+/// values of flattened fields are read without their types, so attributes in
+/// them have to be strings and single nodes cannot be sequences.
+#[inline]
+pub fn from_node<'de, T>(node: &'de Node) -> Result<T, KbinError>
+where
+    T: Deserialize<'de>,
+{
+    from_node_with_options(node, &DEFAULT_OPTIONS)
+}
+
+pub fn from_node_with_options<'de, T>(
+    node: &'de Node,
+    options: &'de Options,
+) -> Result<T, KbinError>
+where
+    T: Deserialize<'de>,
+{
+    options.validate_read()?;
+
+    let path = format!("/{}", node.key());
+    T::deserialize(NodeDeserializer::new(node, options, path.clone()))
+        .map_err(|error| error.at(&path).into())
+}
+
+/// Deserializes a single node.
+pub(crate) struct NodeDeserializer<'de> {
+    node: &'de Node,
+    options: &'de Options,
+    path: String,
+}
+
+impl<'de> NodeDeserializer<'de> {
+    pub(crate) fn new(node: &'de Node, options: &'de Options, path: String) -> Self {
+        Self {
+            node,
+            options,
+            path,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.node.value().is_none() &&
+            self.node.attributes().is_empty() &&
+            self.node.children().is_empty()
+    }
+
+    fn value(&self) -> Option<ValueDeserializer<'de>> {
+        self.node
+            .value()
+            .cloned()
+            .map(|value| ValueDeserializer::new(value, self.options, self.path.clone()))
+    }
+}
+
+macro_rules! forward_to_value {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, DeError>
+            where
+                V: Visitor<'de>,
+            {
+                match self.value() {
+                    Some(value) => value.$method(visitor),
+                    None => self.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for NodeDeserializer<'de> {
+    type Error = DeError;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        if let Some(value) = self.value() {
+            value.deserialize_any(visitor)
+        } else if !self.is_empty() {
+            visitor.visit_map(Struct::map(self.node, self.options, &self.path)?)
+        } else {
+            visitor.visit_unit()
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        if self.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value() {
+            Some(value) => value.deserialize_seq(visitor),
+            // A single node is a sequence with one element when it is one of a
+            // set of repeated nodes.
+            None => visitor.visit_seq(NodeSeq::new(self.options, vec![(self.node, self.path)])),
+        }
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value() {
+            Some(value) => value.deserialize_tuple(len, visitor),
+            None => self.deserialize_seq(visitor),
+        }
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    /// Attributes are entries of the map too, see `NameConflictPolicy`.
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_map(Struct::map(self.node, self.options, &self.path)?)
+    }
+
+    /// Fields named with a leading `@` or `attr_` are read from the
+    /// attributes of the node.
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_map(Struct::new(self.node, self.options, &self.path, fields))
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        Err(DeError::Unsupported { kind: "enums" })
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    forward_to_value! {
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_bytes deserialize_byte_buf
+    }
+
+    forward_to_deserialize_any! {
+        bool i128 u128 f32 f64 char str string identifier
+    }
+}
+
+/// Deserializes all the child nodes of a parent that share a name.
+pub(crate) struct NodesDeserializer<'de> {
+    key: &'de str,
+    nodes: Vec<&'de Node>,
+    options: &'de Options,
+    parent_path: String,
+}
+
+impl<'de> NodesDeserializer<'de> {
+    pub(crate) fn new(
+        key: &'de str,
+        nodes: Vec<&'de Node>,
+        options: &'de Options,
+        parent_path: String,
+    ) -> Self {
+        Self {
+            key,
+            nodes,
+            options,
+            parent_path,
+        }
+    }
+
+    fn single(&self) -> Result<NodeDeserializer<'de>, DeError> {
+        match self.nodes.as_slice() {
+            [node] => {
+                let path = format!("{}/{}", self.parent_path, self.key);
+                Ok(NodeDeserializer::new(node, self.options, path))
+            },
+            nodes => Err(DeError::MultipleNodes {
+                key: self.key.to_owned(),
+                count: nodes.len(),
+            }),
+        }
+    }
+
+    fn seq(self) -> NodeSeq<'de> {
+        let Self {
+            key,
+            nodes,
+            options,
+            parent_path,
+        } = self;
+        let nodes = nodes
+            .into_iter()
+            .enumerate()
+            .map(|(i, node)| (node, format!("{}/{}[{}]", parent_path, key, i)))
+            .collect();
+
+        NodeSeq::new(options, nodes)
+    }
+}
+
+macro_rules! forward_to_single {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, DeError>
+            where
+                V: Visitor<'de>,
+            {
+                self.single()?.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for NodesDeserializer<'de> {
+    type Error = DeError;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        if self.nodes.len() == 1 {
+            self.single()?.deserialize_any(visitor)
+        } else {
+            visitor.visit_seq(self.seq())
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        if self.nodes.len() == 1 {
+            self.single()?.deserialize_option(visitor)
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        if self.nodes.len() == 1 {
+            self.single()?.deserialize_seq(visitor)
+        } else {
+            visitor.visit_seq(self.seq())
+        }
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        if self.nodes.len() == 1 {
+            self.single()?.deserialize_tuple(len, visitor)
+        } else {
+            visitor.visit_seq(self.seq())
+        }
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_unit_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        self.single()?.deserialize_unit_struct(name, visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        if self.nodes.len() == 1 {
+            self.single()?.deserialize_newtype_struct(name, visitor)
+        } else {
+            visitor.visit_newtype_struct(self)
+        }
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        self.single()?.deserialize_struct(name, fields, visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        self.single()?.deserialize_enum(name, variants, visitor)
+    }
+
+    forward_to_single! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_f32
+        deserialize_f64 deserialize_char deserialize_str deserialize_string deserialize_bytes
+        deserialize_byte_buf deserialize_unit deserialize_map deserialize_identifier
+        deserialize_ignored_any
+    }
+}
+
+/// Sequence access over a list of nodes and their paths.
+struct NodeSeq<'de> {
+    nodes: std::vec::IntoIter<(&'de Node, String)>,
+    options: &'de Options,
+}
+
+impl<'de> NodeSeq<'de> {
+    fn new(options: &'de Options, nodes: Vec<(&'de Node, String)>) -> Self {
+        Self {
+            nodes: nodes.into_iter(),
+            options,
+        }
+    }
+}
+
+impl<'de> SeqAccess<'de> for NodeSeq<'de> {
+    type Error = DeError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, DeError>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.nodes.next() {
+            Some((node, path)) => seed
+                .deserialize(NodeDeserializer::new(node, self.options, path.clone()))
+                .map(Some)
+                .map_err(|error| error.at(&path)),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.nodes.len())
+    }
+}
+
+/// Deserializes a single node value.
+pub(crate) struct ValueDeserializer<'de> {
+    value: Value,
+    options: &'de Options,
+    path: String,
+}
+
+impl<'de> ValueDeserializer<'de> {
+    pub(crate) fn new(value: Value, options: &'de Options, path: String) -> Self {
+        Self {
+            value,
+            options,
+            path,
+        }
+    }
+
+    /// Check that the stored integer can be converted to `target`.
+    ///
+    /// Widening conversions are always allowed. Narrowing conversions are
+    /// only allowed with `allow_lossless_narrowing` and when the value fits.
+    fn check_integer(&self, target: StandardType) -> Result<(), DeError> {
+        let (value, stored) = match self.value.as_integer() {
+            Some(value) => (value, self.value.standard_type()),
+            None => return Ok(()),
+        };
+        let (stored_min, stored_max) = integer_bounds(stored).unwrap_or_default();
+        let (target_min, target_max) = integer_bounds(target).unwrap_or_default();
+
+        if stored_min >= target_min && stored_max <= target_max {
+            Ok(())
+        } else if value < target_min || value > target_max {
+            Err(DeError::OutOfRange {
+                path: self.path.clone(),
+                value,
+                stored,
+                target,
+            })
+        } else if self.options.allow_lossless_narrowing {
+            Ok(())
+        } else {
+            Err(DeError::NarrowingDisabled {
+                path: self.path.clone(),
+                stored,
+                target,
+            })
+        }
+    }
+}
+
+macro_rules! deserialize_integer {
+    ($($method:ident => $konst:ident, $visit:ident, $int:ty;)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, DeError>
+            where
+                V: Visitor<'de>,
+            {
+                self.check_integer(StandardType::$konst)?;
+
+                match self.value.as_integer() {
+                    // `check_integer` guarantees the value fits.
+                    Some(value) => visitor.$visit(value as $int),
+                    None => self.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+/// Sequence access over the elements of an array or multi-count value.
+struct ValueSeq<'de> {
+    values: std::vec::IntoIter<Value>,
+    options: &'de Options,
+    path: String,
+}
+
+impl<'de> SeqAccess<'de> for ValueSeq<'de> {
+    type Error = DeError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, DeError>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.values.next() {
+            Some(value) => seed
+                .deserialize(ValueDeserializer::new(
+                    value,
+                    self.options,
+                    self.path.clone(),
+                ))
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.values.len())
+    }
+}
+
+fn visit_values<'de, V>(
+    values: Vec<Value>,
+    options: &'de Options,
+    path: String,
+    visitor: V,
+) -> Result<V::Value, DeError>
+where
+    V: Visitor<'de>,
+{
+    let len = values.len();
+    let mut seq = ValueSeq {
+        values: values.into_iter(),
+        options,
+        path,
+    };
+    let value = visitor.visit_seq(&mut seq)?;
+
+    match seq.values.len() {
+        0 => Ok(value),
+        remaining => Err(de::Error::invalid_length(
+            len,
+            &format!("{} fewer elements", remaining).as_str(),
+        )),
+    }
+}
+
+macro_rules! visit_value {
+    (
+        $value:expr, $options:expr, $path:expr, $visitor:expr;
+        scalar: [$($scalar:ident => $visit:ident),*];
+        multi: [$($multi:ident => $element:ident),*];
+    ) => {
+        match $value {
+            $(
+                Value::$scalar(v) => $visitor.$visit(v),
+            )*
+            $(
+                Value::$multi(v) => visit_values(v.iter().copied().map(Value::$element).collect(), $options, $path, $visitor),
+            )*
+            Value::String(v) | Value::Attribute(v) => $visitor.visit_string(v),
+            Value::Ip4(v) => $visitor.visit_string(v.to_string()),
+            Value::Binary(v) | Value::Unknown { bytes: v, .. } => $visitor.visit_byte_buf(v),
+            Value::Array(values) => visit_values(values.to_values(), $options, $path, $visitor),
+        }
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = DeError;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        visit_value! {
+            self.value, self.options, self.path, visitor;
+            scalar: [
+                S8 => visit_i8, U8 => visit_u8, S16 => visit_i16, U16 => visit_u16,
+                S32 => visit_i32, U32 => visit_u32, S64 => visit_i64, U64 => visit_u64,
+                Float => visit_f32, Double => visit_f64, Boolean => visit_bool, Time => visit_u32
+            ];
+            multi: [
+                S8_2 => S8, S8_3 => S8, S8_4 => S8, Vs8 => S8,
+                U8_2 => U8, U8_3 => U8, U8_4 => U8, Vu8 => U8,
+                S16_2 => S16, S16_3 => S16, S16_4 => S16, Vs16 => S16,
+                U16_2 => U16, U16_3 => U16, U16_4 => U16, Vu16 => U16,
+                S32_2 => S32, S32_3 => S32, S32_4 => S32,
+                U32_2 => U32, U32_3 => U32, U32_4 => U32,
+                S64_2 => S64, S64_3 => S64, S64_4 => S64,
+                U64_2 => U64, U64_3 => U64, U64_4 => U64,
+                Float2 => Float, Float3 => Float, Float4 => Float,
+                Double2 => Double, Double3 => Double, Double4 => Double,
+                Boolean2 => Boolean, Boolean3 => Boolean, Boolean4 => Boolean, Vb => Boolean
+            ];
+        }
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::Array(ValueArray::U8(values)) => visitor.visit_byte_buf(values),
+            value => {
+                ValueDeserializer::new(value, self.options, self.path).deserialize_any(visitor)
+            },
+        }
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_bytes(visitor)
+    }
+
+    /// Multi-count values, like `3f`, and arrays are read as tuples with the
+    /// same number of elements. `Ip4` values are read as their octets, which
+    /// is how `Ipv4Addr` deserializes from a compact format.
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        let node_type = self.value.standard_type();
+        let found = match self.value {
+            Value::Array(ref values) => Some(values.len()),
+            Value::Ip4(_) => Some(4),
+            _ if node_type.count > 1 => Some(node_type.count),
+            _ => None,
+        };
+        if let Some(found) = found.filter(|&found| found != len) {
+            return Err(DeError::CountMismatch {
+                path: self.path,
+                node_type,
+                expected: len,
+                found,
+            });
+        }
+
+        match self.value {
+            Value::Ip4(addr) => {
+                let octets = addr.octets().iter().copied().map(Value::U8).collect();
+                visit_values(octets, self.options, self.path, visitor)
+            },
+            value => {
+                ValueDeserializer::new(value, self.options, self.path).deserialize_any(visitor)
+            },
+        }
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        Err(DeError::Unsupported { kind: "enums" })
+    }
+
+    deserialize_integer! {
+        deserialize_i8 => S8, visit_i8, i8;
+        deserialize_u8 => U8, visit_u8, u8;
+        deserialize_i16 => S16, visit_i16, i16;
+        deserialize_u16 => U16, visit_u16, u16;
+        deserialize_i32 => S32, visit_i32, i32;
+        deserialize_u32 => U32, visit_u32, u32;
+        deserialize_i64 => S64, visit_i64, i64;
+        deserialize_u64 => U64, visit_u64, u64;
+    }
+
+    forward_to_deserialize_any! {
+        bool i128 u128 f32 f64 char str string
+        unit unit_struct seq map struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::Deserialize;
+
+    use crate::error::KbinError;
+    use crate::node::Node;
+    use crate::options::{NameConflictPolicy, Options};
+    use crate::value::Value;
+
+    use super::{from_node, from_node_with_options, DeError};
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Root {
+        field: u8,
+    }
+
+    #[test]
+    fn test_narrowing() {
+        let mut root = Node::new("Root");
+        root.append_child(Node::with_value("field", Value::U32(5)));
+
+        match from_node::<Root>(&root) {
+            Err(KbinError::Deserialize {
+                source: DeError::NarrowingDisabled { path, .. },
+            }) => assert_eq!(path, "/Root/field"),
+            result => panic!("Unexpected result: {:?}", result),
+        };
+
+        let options = Options::builder().allow_lossless_narrowing(true).build();
+        let value: Root = from_node_with_options(&root, &options).expect("Failed to narrow");
+        assert_eq!(value, Root { field: 5 });
+
+        let mut root = Node::new("Root");
+        root.append_child(Node::with_value("field", Value::U32(300)));
+        match from_node_with_options::<Root>(&root, &options) {
+            Err(KbinError::Deserialize {
+                source: DeError::OutOfRange { value: 300, .. },
+            }) => {},
+            result => panic!("Unexpected result: {:?}", result),
+        };
+    }
+
+    #[test]
+    fn test_name_conflicts() {
+        let mut root = Node::with_attrs("root", &[("id", "1"), ("name", "a")]);
+        root.append_child(Node::with_value("id", Value::String("2".into())));
+        assert_eq!(root.name_conflicts(), vec!["id"]);
+        assert_eq!(root.attr("id"), Some("1"));
+
+        match from_node::<BTreeMap<String, String>>(&root) {
+            Err(KbinError::Deserialize {
+                source: DeError::NameConflict { path },
+            }) => assert_eq!(path, "/root/@id"),
+            result => panic!("Unexpected result: {:?}", result),
+        };
+
+        let options = Options::builder()
+            .name_conflicts(NameConflictPolicy::PrefixAttribute)
+            .build();
+        let map: BTreeMap<String, String> =
+            from_node_with_options(&root, &options).expect("Failed to deserialize");
+        assert_eq!(map["@id"], "1");
+        assert_eq!(map["id"], "2");
+        assert_eq!(map["name"], "a");
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Partial {
+        id: Option<u32>,
+        name: Option<String>,
+        scores: Option<Vec<u16>>,
+        entry: Option<Root>,
+    }
+
+    #[test]
+    fn test_optional_nodes() {
+        let mut root = Node::new("Partial");
+        root.append_child(Node::with_value("unknown", Value::U8(1)));
+        root.append_child(Node::new("id"));
+        root.append_child(Node::with_value("name", Value::String(String::new())));
+
+        let partial: Partial = from_node(&root).expect("Failed to deserialize");
+        assert_eq!(partial, Partial {
+            id: None,
+            name: Some(String::new()),
+            scores: None,
+            entry: None,
+        });
+
+        root.append_child(Node::with_nodes("entry", vec![Node::with_value(
+            "field",
+            Value::U8(2),
+        )]));
+        let partial: Partial = from_node(&root).expect("Failed to deserialize");
+        assert_eq!(partial.entry, Some(Root { field: 2 }));
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Common {
+        version: u32,
+        #[serde(rename = "@id")]
+        id: String,
+        entry: Vec<Root>,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Flattened {
+        #[serde(rename = "@kind")]
+        kind: String,
+        #[serde(flatten)]
+        common: Common,
+        name: String,
+    }
+
+    #[test]
+    fn test_flatten() {
+        let root = Node::with("Flattened", &[("id", "4"), ("kind", "music")], vec![
+            Node::with_nodes("entry", vec![Node::with_value("field", Value::U8(1))]),
+            Node::with_value("name", Value::String("name".into())),
+            Node::with_value("version", Value::U32(2)),
+            Node::with_nodes("entry", vec![Node::with_value("field", Value::U8(3))]),
+        ]);
+        let options = Options::builder()
+            .name_conflicts(NameConflictPolicy::PrefixAllAttributes)
+            .build();
+
+        let value: Flattened =
+            from_node_with_options(&root, &options).expect("Failed to deserialize");
+        assert_eq!(value, Flattened {
+            kind: String::from("music"),
+            common: Common {
+                version: 2,
+                id: String::from("4"),
+                entry: vec![Root { field: 1 }, Root { field: 3 }],
+            },
+            name: String::from("name"),
+        });
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Outer {
+        #[allow(dead_code)]
+        entry: Vec<Root>,
+    }
+
+    #[test]
+    fn test_error_paths() {
+        let root = Node::with_nodes("Outer", vec![
+            Node::with_nodes("entry", vec![Node::with_value("field", Value::U8(1))]),
+            Node::with_nodes("entry", vec![Node::with_value(
+                "field",
+                Value::String("one".into()),
+            )]),
+        ]);
+        match from_node::<Outer>(&root) {
+            Err(KbinError::Deserialize { source }) => {
+                assert_eq!(source.path(), Some("/Outer/entry[1]/field"));
+                assert!(source.to_string().contains("invalid type"));
+            },
+            result => panic!("Unexpected result: {:?}", result),
+        };
+
+        let root = Node::with_nodes("Outer", vec![Node::with_nodes("entry", vec![Node::new(
+            "other",
+        )])]);
+        match from_node::<Outer>(&root) {
+            Err(KbinError::Deserialize { source }) => {
+                assert_eq!(source.path(), Some("/Outer/entry"));
+                assert!(source.to_string().contains("missing field `field`"));
+            },
+            result => panic!("Unexpected result: {:?}", result),
+        };
+    }
+}
@@ -0,0 +1,169 @@
+use std::borrow::Cow;
+
+use serde::de::{self, DeserializeSeed, MapAccess};
+
+use crate::de::{AttributeDeserializer, DeError, NodesDeserializer};
+use crate::node::Node;
+use crate::options::{NameConflictPolicy, Options};
+use crate::ser::{attribute_field, ATTRIBUTE_PREFIX};
+
+enum Entry<'de> {
+    /// The key, the name of the attribute and its value.
+    Attribute(Cow<'de, str>, &'de str, &'de str),
+    Nodes(&'de str, Vec<&'de Node>),
+}
+
+impl<'de> Entry<'de> {
+    fn key(&self) -> Cow<'de, str> {
+        match self {
+            Entry::Attribute(key, _, _) => key.clone(),
+            Entry::Nodes(key, _) => Cow::Borrowed(key),
+        }
+    }
+}
+
+/// Map access over the attribute fields and children of a node, grouping
+/// children that share a name so that repeated nodes can be read as a
+/// sequence.
+pub(crate) struct Struct<'de> {
+    entries: std::vec::IntoIter<Entry<'de>>,
+    value: Option<Entry<'de>>,
+    options: &'de Options,
+    path: String,
+}
+
+impl<'de> Struct<'de> {
+    /// `fields` starting with `@` or `attr_` are looked up in the attributes
+    /// of `node`.
+    pub(crate) fn new(
+        node: &'de Node,
+        options: &'de Options,
+        path: &str,
+        fields: &'static [&'static str],
+    ) -> Self {
+        let attributes = fields
+            .iter()
+            .filter_map(|&field| {
+                let (name, value) = node.attributes().get_key_value(attribute_field(field)?)?;
+
+                Some(Entry::Attribute(
+                    Cow::Borrowed(field),
+                    name.as_str(),
+                    value.as_str(),
+                ))
+            })
+            .collect();
+
+        Self::with_attributes(node, options, path, attributes)
+    }
+
+    /// Every attribute of `node` is an entry, named by the
+    /// `NameConflictPolicy` of `options` if a child has the same name.
+    pub(crate) fn map(node: &'de Node, options: &'de Options, path: &str) -> Result<Self, DeError> {
+        let conflicts = node.name_conflicts();
+        let mut attributes = Vec::with_capacity(node.attributes().len());
+
+        for (name, value) in node.attributes() {
+            let prefixed = match options.name_conflicts {
+                NameConflictPolicy::PrefixAllAttributes => true,
+                _ if !conflicts.contains(&name.as_str()) => false,
+                NameConflictPolicy::Error => {
+                    return Err(DeError::NameConflict {
+                        path: format!("{}/{}{}", path, ATTRIBUTE_PREFIX, name),
+                    });
+                },
+                NameConflictPolicy::PrefixAttribute => true,
+            };
+            let key = if prefixed {
+                Cow::Owned(format!("{}{}", ATTRIBUTE_PREFIX, name))
+            } else {
+                Cow::Borrowed(name.as_str())
+            };
+            attributes.push(Entry::Attribute(key, name.as_str(), value.as_str()));
+        }
+
+        Ok(Self::with_attributes(node, options, path, attributes))
+    }
+
+    fn with_attributes(
+        node: &'de Node,
+        options: &'de Options,
+        path: &str,
+        mut entries: Vec<Entry<'de>>,
+    ) -> Self {
+        let attribute_count = entries.len();
+
+        for child in node.children() {
+            let group = entries[attribute_count..]
+                .iter_mut()
+                .find(|entry| entry.key() == child.key());
+            match group {
+                Some(Entry::Nodes(_, nodes)) => nodes.push(child),
+                _ => entries.push(Entry::Nodes(child.key(), vec![child])),
+            };
+        }
+
+        Self {
+            entries: entries.into_iter(),
+            value: None,
+            options,
+            path: path.to_owned(),
+        }
+    }
+}
+
+impl<'de> MapAccess<'de> for Struct<'de> {
+    type Error = DeError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, DeError>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.entries.next() {
+            Some(entry) => {
+                let key = entry.key();
+                self.value = Some(entry);
+                match key {
+                    Cow::Borrowed(key) => {
+                        seed.deserialize(de::value::BorrowedStrDeserializer::new(key))
+                    },
+                    Cow::Owned(key) => seed.deserialize(de::value::StringDeserializer::new(key)),
+                }
+                .map(Some)
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, DeError>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let entry = self
+            .value
+            .take()
+            .ok_or_else(|| de::Error::custom("value requested before key"))?;
+
+        match entry {
+            Entry::Attribute(_, name, value) => {
+                let path = format!("{}/{}{}", self.path, ATTRIBUTE_PREFIX, name);
+                seed.deserialize(AttributeDeserializer::new(value, path.clone()))
+                    .map_err(|error| error.at(&path))
+            },
+            Entry::Nodes(key, nodes) => {
+                let path = format!("{}/{}", self.path, key);
+                seed.deserialize(NodesDeserializer::new(
+                    key,
+                    nodes,
+                    self.options,
+                    self.path.clone(),
+                ))
+                .map_err(|error| error.at(&path))
+            },
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
@@ -0,0 +1,667 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use bytes::Bytes;
+use quick_xml::escape::escape;
+
+use crate::error::KbinError;
+use crate::node::NodeDefinition;
+use crate::node_types::StandardType;
+use crate::reader::Reader;
+use crate::value::Value;
+
+/// A difference between two documents.
+///
+/// Paths are built from node names. A node that follows a sibling with the
+/// same name gets its zero-based index among those siblings appended, e.g.
+/// `/root/music`, `/root/music[1]`. Unlike `NodeCursor::path`, the first of
+/// those siblings has no index.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiffEntry {
+    /// A node and its children only exist in the first document.
+    Removed { path: String },
+
+    /// A node and its children only exist in the second document.
+    Added { path: String },
+
+    /// The values of a node differ. `None` means the node has no value.
+    Value {
+        path: String,
+        a: Option<Value>,
+        b: Option<Value>,
+    },
+
+    /// An attribute is missing from one of the documents or has a different
+    /// value.
+    Attribute {
+        path: String,
+        name: String,
+        a: Option<String>,
+        b: Option<String>,
+    },
+}
+
+enum Event {
+    Start {
+        name: String,
+        path: String,
+        value: Option<Value>,
+        attributes: Vec<(String, String)>,
+    },
+    End,
+    Eof,
+}
+
+struct Level {
+    segment: String,
+    counts: HashMap<String, usize>,
+}
+
+/// Builds the paths of nodes read in document order.
+pub(crate) struct Paths {
+    levels: Vec<Level>,
+}
+
+impl Paths {
+    pub(crate) fn new() -> Self {
+        let root = Level {
+            segment: String::new(),
+            counts: HashMap::new(),
+        };
+
+        Self { levels: vec![root] }
+    }
+
+    /// Start a child of the current node, returning its path.
+    pub(crate) fn enter(&mut self, name: &str) -> String {
+        // The root level is never removed.
+        let parent = self.levels.last_mut().unwrap();
+        let index = parent.counts.entry(name.to_owned()).or_insert(0);
+        let segment = match *index {
+            0 => name.to_owned(),
+            i => format!("{}[{}]", name, i),
+        };
+        *index += 1;
+
+        self.levels.push(Level {
+            segment,
+            counts: HashMap::new(),
+        });
+
+        self.current()
+    }
+
+    pub(crate) fn leave(&mut self) {
+        if self.levels.len() > 1 {
+            self.levels.pop();
+        }
+    }
+
+    pub(crate) fn current(&self) -> String {
+        self.levels
+            .iter()
+            .skip(1)
+            .fold(String::new(), |mut path, level| {
+                path.push('/');
+                path.push_str(&level.segment);
+                path
+            })
+    }
+}
+
+/// Reads a document as a flat stream of start and end events.
+struct Events {
+    reader: Reader,
+    pending: Option<NodeDefinition>,
+    paths: Paths,
+}
+
+impl Events {
+    fn new(input: &[u8]) -> Result<Self, KbinError> {
+        let reader = Reader::new(Bytes::from(input.to_vec()))?;
+
+        Ok(Self {
+            reader,
+            pending: None,
+            paths: Paths::new(),
+        })
+    }
+
+    fn read_definition(&mut self) -> Result<NodeDefinition, KbinError> {
+        match self.pending.take() {
+            Some(def) => Ok(def),
+            None => self.reader.read_node_definition().map_err(Into::into),
+        }
+    }
+
+    fn next(&mut self) -> Result<Event, KbinError> {
+        let def = self.read_definition()?;
+        match def.node_type {
+            StandardType::FileEnd => return Ok(Event::Eof),
+            StandardType::NodeEnd => {
+                self.paths.leave();
+                return Ok(Event::End);
+            },
+            StandardType::Attribute => return Err(KbinError::InvalidState),
+            _ => {},
+        };
+
+        let name = def.key()?.ok_or(KbinError::InvalidState)?;
+        let value = match def.node_type {
+            StandardType::NodeStart => None,
+            _ => Some(def.value()?),
+        };
+
+        // Attributes directly follow the definition of the node they belong
+        // to, keep the first definition after them for the next event.
+        let mut attributes = Vec::new();
+        let mut next = self.reader.read_node_definition()?;
+        while next.node_type == StandardType::Attribute {
+            let key = next.key()?.ok_or(KbinError::InvalidState)?;
+            attributes.push((key, next.value()?.as_attribute()?));
+            next = self.reader.read_node_definition()?;
+        }
+        self.pending = Some(next);
+
+        let path = self.paths.enter(&name);
+
+        Ok(Event::Start {
+            name,
+            path,
+            value,
+            attributes,
+        })
+    }
+
+    /// Skip the rest of the node started by the last `Start` event.
+    fn skip(&mut self) -> Result<(), KbinError> {
+        let mut depth = 1;
+        while depth > 0 {
+            match self.next()? {
+                Event::Start { .. } => depth += 1,
+                Event::End => depth -= 1,
+                Event::Eof => return Err(KbinError::InvalidState),
+            };
+        }
+
+        Ok(())
+    }
+}
+
+fn diff_attributes<F>(path: &str, a: &[(String, String)], b: &[(String, String)], sink: &mut F)
+where
+    F: FnMut(DiffEntry),
+{
+    let find = |attributes: &[(String, String)], name: &str| {
+        attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.clone())
+    };
+
+    for (name, value) in a {
+        let other = find(b, name);
+        if other.as_ref() != Some(value) {
+            sink(DiffEntry::Attribute {
+                path: path.to_owned(),
+                name: name.clone(),
+                a: Some(value.clone()),
+                b: other,
+            });
+        }
+    }
+    for (name, value) in b {
+        if find(a, name).is_none() {
+            sink(DiffEntry::Attribute {
+                path: path.to_owned(),
+                name: name.clone(),
+                a: None,
+                b: Some(value.clone()),
+            });
+        }
+    }
+}
+
+/// Compare two binary documents, passing every difference to `sink` as it
+/// is found.
+///
+/// Both documents are read in lockstep without building a node tree, so
+/// memory use does not depend on document size. Children are compared by
+/// position: when the names of the nodes at the same position differ, the
+/// node in `a` is reported as removed and the node in `b` as added.
+pub fn diff_streaming<F>(a: &[u8], b: &[u8], mut sink: F) -> Result<(), KbinError>
+where
+    F: FnMut(DiffEntry),
+{
+    compare(a, b, &mut sink, || {})
+}
+
+/// Like `diff_streaming`, also calling `unchanged` for every node present in
+/// both documents with the same value and attributes.
+fn compare<F, G>(a: &[u8], b: &[u8], sink: &mut F, mut unchanged: G) -> Result<(), KbinError>
+where
+    F: FnMut(DiffEntry),
+    G: FnMut(),
+{
+    let mut a_events = Events::new(a)?;
+    let mut b_events = Events::new(b)?;
+    let mut a_held = None;
+    let mut b_held = None;
+
+    loop {
+        let a_event = match a_held.take() {
+            Some(event) => event,
+            None => a_events.next()?,
+        };
+        let b_event = match b_held.take() {
+            Some(event) => event,
+            None => b_events.next()?,
+        };
+
+        match (a_event, b_event) {
+            (Event::Eof, Event::Eof) => break,
+            (Event::End, Event::End) => {},
+            (
+                Event::Start {
+                    name: a_name,
+                    path: a_path,
+                    value: a_value,
+                    attributes: a_attributes,
+                },
+                Event::Start {
+                    name: b_name,
+                    path: b_path,
+                    value: b_value,
+                    attributes: b_attributes,
+                },
+            ) => {
+                if a_name != b_name {
+                    sink(DiffEntry::Removed { path: a_path });
+                    a_events.skip()?;
+                    sink(DiffEntry::Added { path: b_path });
+                    b_events.skip()?;
+                    continue;
+                }
+
+                let same = a_value == b_value && a_attributes == b_attributes;
+                if a_value != b_value {
+                    sink(DiffEntry::Value {
+                        path: a_path.clone(),
+                        a: a_value,
+                        b: b_value,
+                    });
+                }
+                diff_attributes(&a_path, &a_attributes, &b_attributes, sink);
+                if same {
+                    unchanged();
+                }
+            },
+            (Event::Start { path, .. }, b_event) => {
+                sink(DiffEntry::Removed { path });
+                a_events.skip()?;
+                b_held = Some(b_event);
+            },
+            (a_event, Event::Start { path, .. }) => {
+                sink(DiffEntry::Added { path });
+                b_events.skip()?;
+                a_held = Some(a_event);
+            },
+            (Event::End, b_event) => b_held = Some(b_event),
+            (a_event, Event::End) => a_held = Some(a_event),
+        };
+    }
+
+    Ok(())
+}
+
+/// Compare two binary documents and collect the differences.
+pub fn diff(a: &[u8], b: &[u8]) -> Result<Vec<DiffEntry>, KbinError> {
+    let mut entries = Vec::new();
+    diff_streaming(a, b, |entry| entries.push(entry))?;
+
+    Ok(entries)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum ReportItem {
+    /// A run of nodes that are the same in both documents.
+    Unchanged(usize),
+    Entry(DiffEntry),
+}
+
+/// The differences between two documents in document order, with the runs
+/// of unchanged nodes between them, for rendering as a human readable
+/// report.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiffReport {
+    items: Vec<ReportItem>,
+}
+
+/// A part of the text of two differing values, see `highlight`.
+struct Span<'a> {
+    a: &'a str,
+    b: &'a str,
+}
+
+/// Join the elements of one side of `spans`, passing each to `render` with
+/// whether it changed.
+fn render_side<F>(spans: &[Span], b_side: bool, render: F) -> String
+where
+    F: Fn(&str, bool) -> String,
+{
+    spans
+        .iter()
+        .map(|span| match b_side {
+            true => render(span.b, span.a != span.b),
+            false => render(span.a, span.a != span.b),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Split the text of two values of the same type into their space separated
+/// elements when both have as many, so only the elements that differ are
+/// highlighted.
+fn highlight<'a>(a: &'a str, b: &'a str, same_type: bool) -> Vec<Span<'a>> {
+    let a_parts: Vec<_> = a.split(' ').collect();
+    let b_parts: Vec<_> = b.split(' ').collect();
+    if !same_type || a_parts.len() != b_parts.len() {
+        return vec![Span { a, b }];
+    }
+
+    a_parts
+        .into_iter()
+        .zip(b_parts)
+        .map(|(a, b)| Span { a, b })
+        .collect()
+}
+
+/// The text of a value, prefixed by its type when the types of both values
+/// differ.
+fn value_text(value: &Option<Value>, show_type: bool) -> String {
+    match value {
+        Some(value) if show_type => format!("({}) {:#}", value.standard_type().name, value),
+        Some(value) => format!("{:#}", value),
+        None => String::from("(none)"),
+    }
+}
+
+/// The text of both values and whether their types differ.
+fn value_texts(a: &Option<Value>, b: &Option<Value>) -> (String, String, bool) {
+    let show_type = match (a, b) {
+        (Some(a), Some(b)) => a.standard_type() != b.standard_type(),
+        _ => false,
+    };
+
+    (
+        value_text(a, show_type),
+        value_text(b, show_type),
+        show_type,
+    )
+}
+
+fn attribute_text(value: &Option<String>) -> String {
+    match value {
+        Some(value) => format!("{:?}", value),
+        None => String::from("(none)"),
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    String::from_utf8_lossy(&escape(text.as_bytes())).into_owned()
+}
+
+impl DiffReport {
+    /// Compare two binary documents, see `diff_streaming`.
+    pub fn new(a: &[u8], b: &[u8]) -> Result<Self, KbinError> {
+        // Unchanged nodes are counted as they are found, then moved into
+        // place before the next entry.
+        let mut items = Vec::new();
+        let unchanged = Cell::new(0);
+        compare(
+            a,
+            b,
+            &mut |entry| items.push((unchanged.get(), entry)),
+            || unchanged.set(unchanged.get() + 1),
+        )?;
+        let unchanged = unchanged.get();
+
+        let mut report = Vec::with_capacity(items.len() * 2 + 1);
+        let mut seen = 0;
+        for (count, entry) in items {
+            if count > seen {
+                report.push(ReportItem::Unchanged(count - seen));
+                seen = count;
+            }
+            report.push(ReportItem::Entry(entry));
+        }
+        if unchanged > seen {
+            report.push(ReportItem::Unchanged(unchanged - seen));
+        }
+
+        Ok(Self { items: report })
+    }
+
+    /// The differences, in document order.
+    pub fn entries(&self) -> impl Iterator<Item = &DiffEntry> {
+        self.items.iter().filter_map(|item| match item {
+            ReportItem::Entry(entry) => Some(entry),
+            ReportItem::Unchanged(_) => None,
+        })
+    }
+
+    /// Are both documents the same?
+    pub fn is_empty(&self) -> bool {
+        self.entries().next().is_none()
+    }
+
+    /// Render the report as plain text, one line per difference.
+    ///
+    /// Removed nodes start with `-`, added nodes with `+` and changed values
+    /// and attributes with `~`. The elements of changed values that differ
+    /// are wrapped in `[` and `]`.
+    pub fn to_text(&self) -> String {
+        let mut output = String::new();
+
+        for item in &self.items {
+            // Writing to a `String` never fails.
+            let _ = match item {
+                ReportItem::Unchanged(count) => {
+                    writeln!(output, "  ... {} unchanged node(s)", count)
+                },
+                ReportItem::Entry(DiffEntry::Removed { path }) => writeln!(output, "- {}", path),
+                ReportItem::Entry(DiffEntry::Added { path }) => writeln!(output, "+ {}", path),
+                ReportItem::Entry(DiffEntry::Value { path, a, b }) => {
+                    let (a, b, show_type) = value_texts(a, b);
+                    let spans = highlight(&a, &b, !show_type);
+                    let render = |text: &str, changed| match changed {
+                        true => format!("[{}]", text),
+                        false => text.to_owned(),
+                    };
+                    writeln!(
+                        output,
+                        "~ {}: {} -> {}",
+                        path,
+                        render_side(&spans, false, render),
+                        render_side(&spans, true, render)
+                    )
+                },
+                ReportItem::Entry(DiffEntry::Attribute { path, name, a, b }) => writeln!(
+                    output,
+                    "~ {}/@{}: {} -> {}",
+                    path,
+                    name,
+                    attribute_text(a),
+                    attribute_text(b)
+                ),
+            };
+        }
+
+        output
+    }
+
+    /// Render the report as an HTML fragment, a `<ul class="kbin-diff">`
+    /// with one item per difference.
+    ///
+    /// Items have a `removed`, `added`, `value`, `attribute` or `unchanged`
+    /// class. The elements of changed values that differ are wrapped in
+    /// `<del>` and `<ins>`.
+    pub fn to_html(&self) -> String {
+        let mut output = String::from("<ul class=\"kbin-diff\">\n");
+
+        for item in &self.items {
+            let _ = match item {
+                ReportItem::Unchanged(count) => writeln!(
+                    output,
+                    "<li class=\"unchanged\">{} unchanged node(s)</li>",
+                    count
+                ),
+                ReportItem::Entry(DiffEntry::Removed { path }) => writeln!(
+                    output,
+                    "<li class=\"removed\"><code>{}</code></li>",
+                    escape_html(path)
+                ),
+                ReportItem::Entry(DiffEntry::Added { path }) => writeln!(
+                    output,
+                    "<li class=\"added\"><code>{}</code></li>",
+                    escape_html(path)
+                ),
+                ReportItem::Entry(DiffEntry::Value { path, a, b }) => {
+                    let (a, b, show_type) = value_texts(a, b);
+                    let spans = highlight(&a, &b, !show_type);
+                    let render = |tag| {
+                        move |text: &str, changed| match changed {
+                            true => format!("<{0}>{1}</{0}>", tag, escape_html(text)),
+                            false => escape_html(text),
+                        }
+                    };
+                    writeln!(
+                        output,
+                        "<li class=\"value\"><code>{}</code>: {} &rarr; {}</li>",
+                        escape_html(path),
+                        render_side(&spans, false, render("del")),
+                        render_side(&spans, true, render("ins"))
+                    )
+                },
+                ReportItem::Entry(DiffEntry::Attribute { path, name, a, b }) => writeln!(
+                    output,
+                    "<li class=\"attribute\"><code>{}/@{}</code>: <del>{}</del> &rarr; <ins>{}</ins></li>",
+                    escape_html(path),
+                    escape_html(name),
+                    escape_html(&attribute_text(a)),
+                    escape_html(&attribute_text(b))
+                ),
+            };
+        }
+        output.push_str("</ul>\n");
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::node::Node;
+    use crate::value::Value;
+
+    use super::{diff_streaming, DiffEntry, DiffReport};
+
+    #[test]
+    fn test_diff_streaming() {
+        let a = Node::with_nodes("root", vec![
+            Node::with_value("version", Value::U32(1)),
+            Node::with_attrs("entry", &[("id", "1"), ("old", "x")]),
+            Node::new("music"),
+            Node::with_value("music", Value::U32(1)),
+            Node::with_nodes("gone", vec![Node::new("child")]),
+        ]);
+        let b = Node::with_nodes("root", vec![
+            Node::with_value("version", Value::U32(2)),
+            Node::with_attrs("entry", &[("id", "1"), ("new", "y")]),
+            Node::new("music"),
+            Node::with_value("music", Value::U32(2)),
+            Node::new("added"),
+            Node::new("tail"),
+        ]);
+        let a = crate::to_binary(&a).expect("Failed to encode");
+        let b = crate::to_binary(&b).expect("Failed to encode");
+
+        let mut entries = Vec::new();
+        diff_streaming(&a, &b, |entry| entries.push(entry)).expect("Failed to diff");
+
+        let path = |path: &str| path.to_owned();
+        let attribute = |name: &str, a: Option<&str>, b: Option<&str>| DiffEntry::Attribute {
+            path: path("/root/entry"),
+            name: name.to_owned(),
+            a: a.map(str::to_owned),
+            b: b.map(str::to_owned),
+        };
+        assert_eq!(entries, [
+            DiffEntry::Value {
+                path: path("/root/version"),
+                a: Some(Value::U32(1)),
+                b: Some(Value::U32(2)),
+            },
+            attribute("old", Some("x"), None),
+            attribute("new", None, Some("y")),
+            DiffEntry::Value {
+                path: path("/root/music[1]"),
+                a: Some(Value::U32(1)),
+                b: Some(Value::U32(2)),
+            },
+            DiffEntry::Removed {
+                path: path("/root/gone"),
+            },
+            DiffEntry::Added {
+                path: path("/root/added"),
+            },
+            DiffEntry::Added {
+                path: path("/root/tail"),
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_diff_report() {
+        let a = Node::with_nodes("root", vec![
+            Node::new("a"),
+            Node::new("b"),
+            Node::with_value("pos", Value::S32_3([1, 2, 3])),
+            Node::with_attrs("entry", &[("id", "1")]),
+            Node::with_value("name", Value::String("<old>".into())),
+            Node::new("c"),
+        ]);
+        let b = Node::with_nodes("root", vec![
+            Node::new("a"),
+            Node::new("b"),
+            Node::with_value("pos", Value::S32_3([1, 5, 3])),
+            Node::with_attrs("entry", &[("id", "2")]),
+            Node::with_value("name", Value::U8(1)),
+        ]);
+        let a = crate::to_binary(&a).expect("Failed to encode");
+        let b = crate::to_binary(&b).expect("Failed to encode");
+
+        let report = DiffReport::new(&a, &b).expect("Failed to diff");
+        assert_eq!(report.entries().count(), 4);
+        assert_eq!(
+            report.to_text(),
+            concat!(
+                "  ... 3 unchanged node(s)\n",
+                "~ /root/pos: 1 [2] 3 -> 1 [5] 3\n",
+                "~ /root/entry/@id: \"1\" -> \"2\"\n",
+                "~ /root/name: [(str) <old>] -> [(u8) 1]\n",
+                "- /root/c\n",
+            )
+        );
+
+        let html = report.to_html();
+        assert!(html.contains("1 <del>2</del> 3 &rarr; 1 <ins>5</ins> 3"));
+        assert!(html.contains("<del>(str) &lt;old&gt;</del>"));
+        assert!(html.contains("<li class=\"removed\"><code>/root/c</code></li>"));
+
+        let report = DiffReport::new(&a, &a).expect("Failed to diff");
+        assert!(report.is_empty());
+        assert_eq!(report.to_text(), "  ... 7 unchanged node(s)\n");
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+
+use bytes::Bytes;
+
+use crate::compression_type::CompressionType;
+use crate::encoding_type::EncodingType;
+use crate::error::Result;
+use crate::node::{Node, NodeCollection};
+use crate::options::Options;
+use crate::query::{Query, Selected};
+use crate::rename::rename_nodes;
+
+/// A binary document together with the settings it was read with, so it
+/// can be written back the same way.
+///
+/// ```
+/// # fn main() -> Result<(), kbinxml::KbinError> {
+/// # let input = kbinxml::to_binary(&kbinxml::Node::new("root"))?;
+/// let mut document = kbinxml::Document::parse(&input)?;
+/// document.edit(|root| root.append_child(kbinxml::Node::new("entry")))?;
+/// let output = document.to_bytes()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Document {
+    pub collection: NodeCollection,
+    pub encoding: EncodingType,
+    pub compression: CompressionType,
+
+    /// The options used for reading, converting and writing, apart from
+    /// the compression and encoding.
+    pub options: Options,
+}
+
+impl Document {
+    #[inline]
+    pub fn parse(input: &[u8]) -> Result<Self> {
+        Self::parse_with_options(Options::default(), input)
+    }
+
+    pub fn parse_with_options(options: Options, input: &[u8]) -> Result<Self> {
+        let (collection, encoding) =
+            crate::from_binary_with_options(options.clone(), Bytes::from(input.to_vec()))?;
+        let compression = collection.compression().unwrap_or(options.compression);
+
+        Ok(Self {
+            collection,
+            encoding,
+            compression,
+            options,
+        })
+    }
+
+    /// Create a document from a `Node` tree, taking the compression and
+    /// encoding from `options`.
+    pub fn from_node(node: &Node, options: Options) -> Result<Self> {
+        let input = crate::to_binary_with_options(options.clone(), node)?;
+
+        Self::parse_with_options(options, &input)
+    }
+
+    /// The options for writing this document.
+    fn write_options(&self) -> Options {
+        let mut options = self.options.clone();
+        options.compression = self.compression;
+        options.encoding = self.encoding;
+
+        options
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        crate::to_binary_with_options(self.write_options(), &self.collection)
+    }
+
+    pub fn to_node(&self) -> Result<Node> {
+        self.collection.as_node_with_options(&self.options)
+    }
+
+    /// Replace the tree of this document with `node`, keeping the settings.
+    pub fn set_node(&mut self, node: &Node) -> Result<()> {
+        let input = crate::to_binary_with_options(self.write_options(), node)?;
+        let (collection, _) =
+            crate::from_binary_with_options(self.write_options(), Bytes::from(input))?;
+        self.collection = collection;
+
+        Ok(())
+    }
+
+    /// Convert the tree to a `Node`, let `f` change it and store the result.
+    pub fn edit<F>(&mut self, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut Node),
+    {
+        let mut node = self.to_node()?;
+        f(&mut node);
+
+        self.set_node(&node)
+    }
+
+    /// Select the nodes or attributes matching a path query, see `Query`.
+    pub fn select(&self, query: &str) -> Result<Vec<Selected<'_>>> {
+        Ok(Query::parse(query)?.select_collection(&self.collection))
+    }
+
+    /// Rename nodes in place, see `rename_nodes`.
+    pub fn rename_nodes(&mut self, map: &HashMap<&str, &str>, attributes: bool) -> Result<usize> {
+        rename_nodes(&mut self.collection, map, attributes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compression_type::CompressionType;
+    use crate::encoding_type::EncodingType;
+    use crate::node::Node;
+    use crate::options::Options;
+    use crate::value::Value;
+
+    use super::Document;
+
+    #[test]
+    fn test_document_round_trip() {
+        let node = Node::with_nodes("root", vec![Node::with_value("id", Value::U32(1))]);
+        let options = Options::new(CompressionType::Uncompressed, EncodingType::SHIFT_JIS);
+        let input = crate::to_binary_with_options(options, &node).expect("Failed to encode");
+
+        let mut document = Document::parse(&input).expect("Failed to parse");
+        assert_eq!(document.compression, CompressionType::Uncompressed);
+        assert_eq!(document.encoding, EncodingType::SHIFT_JIS);
+        assert_eq!(document.to_bytes().expect("Failed to encode"), input);
+
+        document
+            .edit(|root| root.append_child(Node::with_value("name", Value::String("名前".into()))))
+            .expect("Failed to edit");
+        let output = document.to_bytes().expect("Failed to encode");
+        let edited = Document::parse(&output).expect("Failed to parse");
+        assert_eq!(edited.encoding, EncodingType::SHIFT_JIS);
+        assert_eq!(edited.compression, CompressionType::Uncompressed);
+        assert_eq!(
+            edited.select("name").expect("Failed to select")[0]
+                .value()
+                .expect("Failed to decode"),
+            Value::String("名前".into())
+        );
+    }
+}
@@ -1,13 +1,25 @@
+use std::borrow::Cow;
 use std::fmt;
+use std::str;
 use std::string::FromUtf8Error;
 
 /// The `encoding_rs` crate uses the following to describe their counterparts:
 ///
 /// `SHIFT_JIS`    => `WINDOWS_31J`
 /// `WINDOWS_1252` => `ISO-8859-1`
-use encoding_rs::{Encoding, EUC_JP, SHIFT_JIS, UTF_8, WINDOWS_1252};
+use encoding_rs::{EncoderResult, Encoding, EUC_JP, SHIFT_JIS, UTF_8, WINDOWS_1252};
 use snafu::{ResultExt, Snafu};
 
+use crate::options::UnmappableCharacterPolicy;
+
+/// The characters the user defined area of CP932 maps to, which games use
+/// for gaiji (custom glyphs).
+const GAIJI_START: u32 = 0xE000;
+const GAIJI_END: u32 = 0xE757;
+
+/// The bytes used in place of characters that cannot be encoded.
+const REPLACEMENT: u8 = b'?';
+
 #[derive(Debug, Snafu)]
 pub enum EncodingError {
     #[snafu(display("Unknown encoding"))]
@@ -125,14 +137,45 @@ impl EncodingType {
         }
     }
 
-    fn encode_ascii(input: &str) -> Result<Vec<u8>, EncodingError> {
+    fn encode_ascii(
+        input: &str,
+        unmappable: UnmappableCharacterPolicy,
+    ) -> Result<Vec<u8>, EncodingError> {
         // ASCII only goes up to 0x7F
         match input.as_bytes().iter().position(|&ch| ch >= 0x80) {
-            Some(index) => Err(EncodingError::InvalidAscii { index }),
+            Some(index) if unmappable == UnmappableCharacterPolicy::Error => {
+                Err(EncodingError::InvalidAscii { index })
+            },
+            Some(_) => Ok(input
+                .chars()
+                .map(|ch| if ch.is_ascii() { ch as u8 } else { REPLACEMENT })
+                .collect()),
             None => Ok(input.as_bytes().to_vec()),
         }
     }
 
+    /// Encode a character of the user defined area of CP932, which
+    /// `encoding_rs` decodes but does not encode.
+    ///
+    /// The area spans lead bytes 0xF0 to 0xF9, with the same 188 trail bytes
+    /// as every other lead byte.
+    fn encode_gaiji(ch: char) -> Option<[u8; 2]> {
+        let index = (ch as u32).checked_sub(GAIJI_START)?;
+        if index > GAIJI_END - GAIJI_START {
+            return None;
+        }
+
+        let lead = 0xF0 + (index / 188) as u8;
+        let trail = (index % 188) as u8;
+        let trail = if trail < 0x3F {
+            trail + 0x40
+        } else {
+            trail + 0x41
+        };
+
+        Some([lead, trail])
+    }
+
     fn decode_with_encoding(
         encoding: &'static Encoding,
         input: &[u8],
@@ -159,16 +202,46 @@ impl EncodingType {
     fn encode_with_encoding(
         encoding: &'static Encoding,
         input: &str,
+        unmappable: UnmappableCharacterPolicy,
     ) -> Result<Vec<u8>, EncodingError> {
-        let (output, actual, had_unmappable_characters) = encoding.encode(input);
-
+        let actual = encoding.output_encoding();
         if encoding != actual {
-            Err(EncodingError::MismatchedEncode { actual })
-        } else if had_unmappable_characters {
-            Err(EncodingError::UnmappableCharacters)
-        } else {
-            Ok(output.into_owned())
+            return Err(EncodingError::MismatchedEncode { actual });
         }
+
+        let mut encoder = encoding.new_encoder();
+        let mut output = Vec::with_capacity(input.len() + 1);
+        let mut rest = input;
+        loop {
+            if let Some(len) = encoder.max_buffer_length_from_utf8_without_replacement(rest.len()) {
+                output.reserve(len);
+            }
+
+            let (result, read) =
+                encoder.encode_from_utf8_to_vec_without_replacement(rest, &mut output, true);
+            rest = &rest[read..];
+
+            match result {
+                EncoderResult::InputEmpty => break,
+                EncoderResult::OutputFull => {},
+                EncoderResult::Unmappable(ch) => {
+                    let gaiji = if encoding == SHIFT_JIS {
+                        Self::encode_gaiji(ch)
+                    } else {
+                        None
+                    };
+                    match gaiji {
+                        Some(bytes) => output.extend_from_slice(&bytes),
+                        None if unmappable == UnmappableCharacterPolicy::Replace => {
+                            output.push(REPLACEMENT)
+                        },
+                        None => return Err(EncodingError::UnmappableCharacters),
+                    }
+                },
+            }
+        }
+
+        Ok(output)
     }
 
     /// Decode bytes using the encoding definition from the `encoding` crate.
@@ -191,18 +264,49 @@ impl EncodingType {
         }
     }
 
+    /// Like `decode_bytes`, borrowing `input` if it reads the same as UTF-8,
+    /// which is any ASCII input.
+    pub fn decode_borrowed<'a>(&self, input: &'a [u8]) -> Result<Cow<'a, str>, EncodingError> {
+        let is_utf8 = matches!(*self, EncodingType::None | EncodingType::UTF_8);
+        if is_utf8 || input.is_ascii() {
+            if let Ok(text) = str::from_utf8(input) {
+                return Ok(Cow::Borrowed(text));
+            }
+        }
+
+        self.decode_bytes(input).map(Cow::Owned)
+    }
+
     /// Encode bytes using the encoding definition from the `encoding` crate.
     ///
     /// A `Some` value indicates the encoding should be used from the `encoding`
     /// crate. A `None` value indicates Rust's own UTF-8 handling should be used.
+    #[inline]
     pub fn encode_bytes(&self, input: &str) -> Result<Vec<u8>, EncodingError> {
+        self.encode_bytes_with(input, UnmappableCharacterPolicy::Error)
+    }
+
+    /// Like `encode_bytes`, handling characters the encoding cannot represent
+    /// according to `unmappable`.
+    ///
+    /// `EncodingType::SHIFT_JIS` is CP932, so gaiji decoded to the private use
+    /// area are encoded back to the user defined area. Characters CP932 has
+    /// two codes for, like the NEC selected IBM extensions, are encoded to the
+    /// code Windows uses.
+    pub fn encode_bytes_with(
+        &self,
+        input: &str,
+        unmappable: UnmappableCharacterPolicy,
+    ) -> Result<Vec<u8>, EncodingError> {
         let mut result = match *self {
             EncodingType::None | EncodingType::UTF_8 => input.as_bytes().to_vec(),
 
-            EncodingType::ASCII => Self::encode_ascii(input)?,
-            EncodingType::ISO_8859_1 => Self::encode_with_encoding(WINDOWS_1252, input)?,
-            EncodingType::EUC_JP => Self::encode_with_encoding(EUC_JP, input)?,
-            EncodingType::SHIFT_JIS => Self::encode_with_encoding(SHIFT_JIS, input)?,
+            EncodingType::ASCII => Self::encode_ascii(input, unmappable)?,
+            EncodingType::ISO_8859_1 => {
+                Self::encode_with_encoding(WINDOWS_1252, input, unmappable)?
+            },
+            EncodingType::EUC_JP => Self::encode_with_encoding(EUC_JP, input, unmappable)?,
+            EncodingType::SHIFT_JIS => Self::encode_with_encoding(SHIFT_JIS, input, unmappable)?,
         };
 
         // Add trailing null byte
@@ -212,3 +316,47 @@ impl EncodingType {
         Ok(result)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::options::UnmappableCharacterPolicy;
+
+    use super::{EncodingError, EncodingType};
+
+    #[test]
+    fn test_shift_jis_gaiji() {
+        // Gaiji at both ends of the user defined area, kana and NEC special
+        // characters
+        let input = [0xF0, 0x40, 0xF9, 0xFC, 0x82, 0xA0, 0x87, 0x40, 0x00];
+        let text = EncodingType::SHIFT_JIS
+            .decode_bytes(&input[..input.len() - 1])
+            .expect("Failed to decode");
+        assert_eq!(text, "\u{e000}\u{e757}あ①");
+        assert_eq!(
+            EncodingType::SHIFT_JIS
+                .encode_bytes(&text)
+                .expect("Failed to encode"),
+            input
+        );
+    }
+
+    #[test]
+    fn test_unmappable_characters() {
+        assert!(matches!(
+            EncodingType::SHIFT_JIS.encode_bytes("a😀b"),
+            Err(EncodingError::UnmappableCharacters)
+        ));
+        for &encoding in &[
+            EncodingType::ASCII,
+            EncodingType::ISO_8859_1,
+            EncodingType::SHIFT_JIS,
+        ] {
+            assert_eq!(
+                encoding
+                    .encode_bytes_with("a😀b", UnmappableCharacterPolicy::Replace)
+                    .expect("Failed to encode"),
+                b"a?b\0"
+            );
+        }
+    }
+}
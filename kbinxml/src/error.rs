@@ -2,17 +2,27 @@ use std::error::Error;
 use std::io;
 use std::num::{ParseFloatError, ParseIntError};
 use std::result::Result as StdResult;
+use std::time::SystemTime;
 
 use quick_xml::Error as QuickXmlError;
 use rustc_hex::FromHexError;
 use snafu::Snafu;
 
 use crate::byte_buffer::ByteBufferError;
+use crate::de::DeError;
 use crate::encoding_type::EncodingError;
 use crate::node_types::StandardType;
+use crate::options::{describe_conflicts, OptionsConflict};
+use crate::patch::PatchError;
+use crate::query::QueryError;
 use crate::reader::ReaderError;
+use crate::rename::RenameError;
+use crate::schema::SchemaError;
+use crate::ser::SerError;
+use crate::shard::ShardError;
 use crate::sixbit::SixbitError;
 use crate::text_reader::TextReaderError;
+use crate::tokens::TokenError;
 use crate::value::Value;
 use crate::writer::WriterError;
 
@@ -72,15 +82,63 @@ pub enum KbinError {
         value: Value,
     },
 
+    #[snafu(display("Time {:?} does not fit in a time value", time))]
+    TimeOutOfRange { time: SystemTime },
+
     #[snafu(display("Value mismatch, expected an array, but found {:?}", value))]
     ExpectedValueArray { value: Value },
 
     #[snafu(display("Invalid input for boolean: {}", input))]
     InvalidBooleanInput { input: u8 },
 
+    #[snafu(display("Invalid input for IPv4 address: {:?}", input))]
+    InvalidIp4 { input: String },
+
     #[snafu(display("Invalid node type for operation: {:?}", node_type))]
     InvalidNodeType { node_type: StandardType },
 
+    #[snafu(display("Invalid attribute name {:?} at {}", name, path))]
+    InvalidAttributeName { path: String, name: String },
+
+    #[snafu(display(
+        "Memory budget of {} bytes exceeded at {} (needed {} bytes)",
+        budget,
+        path,
+        needed
+    ))]
+    MemoryBudget {
+        budget: usize,
+        needed: usize,
+        path: String,
+    },
+
+    #[snafu(display("String at {} holds a null byte or control character", path))]
+    ControlCharacter { path: String },
+
+    #[snafu(display("Node {:?} cannot be written to text XML exactly: {}", name, reason))]
+    LossyTextXml { name: String, reason: String },
+
+    #[snafu(display("Value filter failed at {}", path))]
+    Filter {
+        path: String,
+        source: Box<dyn Error + Send + Sync>,
+    },
+
+    #[snafu(display(
+        "Derived node at {} holds {:?} instead of {:?}",
+        path,
+        actual,
+        expected
+    ))]
+    DerivedMismatch {
+        path: String,
+        expected: Value,
+        actual: Option<Value>,
+    },
+
+    #[snafu(display("Invalid options: {}", describe_conflicts(conflicts)))]
+    InvalidOptions { conflicts: Vec<OptionsConflict> },
+
     #[snafu(display("Invalid state"))]
     InvalidState,
 
@@ -120,6 +178,54 @@ pub enum KbinError {
         source: TextReaderError,
     },
 
+    #[snafu(display("Failed to handle token dump"))]
+    Tokens {
+        #[snafu(backtrace)]
+        source: TokenError,
+    },
+
+    #[snafu(display("Failed to serialize to node"))]
+    Serialize {
+        #[snafu(backtrace)]
+        source: SerError,
+    },
+
+    #[snafu(display("Failed to deserialize from node"))]
+    Deserialize {
+        #[snafu(backtrace)]
+        source: DeError,
+    },
+
+    #[snafu(display("Failed to split or join documents"))]
+    Shard {
+        #[snafu(backtrace)]
+        source: ShardError,
+    },
+
+    #[snafu(display("Failed to patch value"))]
+    Patch {
+        #[snafu(backtrace)]
+        source: PatchError,
+    },
+
+    #[snafu(display("Failed to parse query"))]
+    Query {
+        #[snafu(backtrace)]
+        source: QueryError,
+    },
+
+    #[snafu(display("Failed to rename nodes"))]
+    Rename {
+        #[snafu(backtrace)]
+        source: RenameError,
+    },
+
+    #[snafu(display("Document does not match the schema"))]
+    Schema {
+        #[snafu(backtrace)]
+        source: SchemaError,
+    },
+
     #[snafu(display("Error handling XML"))]
     XmlError { source: QuickXmlError },
 }
@@ -166,6 +272,62 @@ impl From<TextReaderError> for KbinError {
     }
 }
 
+impl From<TokenError> for KbinError {
+    #[inline]
+    fn from(source: TokenError) -> Self {
+        KbinError::Tokens { source }
+    }
+}
+
+impl From<SerError> for KbinError {
+    #[inline]
+    fn from(source: SerError) -> Self {
+        KbinError::Serialize { source }
+    }
+}
+
+impl From<DeError> for KbinError {
+    #[inline]
+    fn from(source: DeError) -> Self {
+        KbinError::Deserialize { source }
+    }
+}
+
+impl From<ShardError> for KbinError {
+    #[inline]
+    fn from(source: ShardError) -> Self {
+        KbinError::Shard { source }
+    }
+}
+
+impl From<PatchError> for KbinError {
+    #[inline]
+    fn from(source: PatchError) -> Self {
+        KbinError::Patch { source }
+    }
+}
+
+impl From<QueryError> for KbinError {
+    #[inline]
+    fn from(source: QueryError) -> Self {
+        KbinError::Query { source }
+    }
+}
+
+impl From<RenameError> for KbinError {
+    #[inline]
+    fn from(source: RenameError) -> Self {
+        KbinError::Rename { source }
+    }
+}
+
+impl From<SchemaError> for KbinError {
+    #[inline]
+    fn from(source: SchemaError) -> Self {
+        KbinError::Schema { source }
+    }
+}
+
 impl From<QuickXmlError> for KbinError {
     #[inline]
     fn from(source: QuickXmlError) -> Self {
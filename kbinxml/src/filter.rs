@@ -0,0 +1,399 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::node::Node;
+use crate::node_types::StandardType;
+use crate::value::Value;
+
+const WILDCARD: &str = "*";
+const RECURSIVE_WILDCARD: &str = "**";
+
+/// The error type returned by value filters.
+pub type FilterError = Box<dyn Error + Send + Sync>;
+
+type FilterFn = dyn Fn(&str, Value) -> Result<Value, FilterError> + Send + Sync;
+type DeriveFn = dyn Fn(&Node) -> Result<Value, FilterError> + Send + Sync;
+
+/// The values a filter is applied to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FilterTarget {
+    /// Every value of a node type, including arrays of it. Attributes are
+    /// `StandardType::Attribute`.
+    Type(StandardType),
+
+    /// The values at a path like `/root/entry/data` or `/root/entry/@id`.
+    ///
+    /// Segments without an index match every node of that name, `*`
+    /// matches any name and `**` matches any number of names. Indexes follow
+    /// the paths of errors, so only names used more than once by the same
+    /// parent have one.
+    Path(String),
+}
+
+impl From<StandardType> for FilterTarget {
+    #[inline]
+    fn from(node_type: StandardType) -> Self {
+        FilterTarget::Type(node_type)
+    }
+}
+
+impl From<&str> for FilterTarget {
+    #[inline]
+    fn from(path: &str) -> Self {
+        FilterTarget::Path(path.to_owned())
+    }
+}
+
+impl From<String> for FilterTarget {
+    #[inline]
+    fn from(path: String) -> Self {
+        FilterTarget::Path(path)
+    }
+}
+
+/// Does a segment of a filter path match a segment of a value path?
+fn segment_matches(pattern: &str, segment: &str) -> bool {
+    if pattern == segment || (pattern == WILDCARD && !segment.starts_with('@')) {
+        return true;
+    }
+
+    match segment.find('[') {
+        Some(start) if segment.ends_with(']') => {
+            let name = &segment[..start];
+            pattern == name || pattern == WILDCARD
+        },
+        _ => false,
+    }
+}
+
+fn segments_match(patterns: &[&str], segments: &[&str]) -> bool {
+    match (patterns.split_first(), segments.split_first()) {
+        (Some((&RECURSIVE_WILDCARD, rest)), segment) => {
+            segments_match(rest, segments) ||
+                matches!(segment, Some((segment, remaining))
+                    if !segment.starts_with('@') && segments_match(patterns, remaining))
+        },
+        (Some((pattern, patterns)), Some((segment, segments))) => {
+            segment_matches(pattern, segment) && segments_match(patterns, segments)
+        },
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn path_matches(pattern: &str, path: &str) -> bool {
+    let patterns: Vec<_> = pattern.trim_start_matches('/').split('/').collect();
+    let segments: Vec<_> = path.trim_start_matches('/').split('/').collect();
+
+    segments_match(&patterns, &segments)
+}
+
+/// The type forced on the node at `path` by the last matching override.
+pub(crate) fn type_override(
+    overrides: &[(String, StandardType)],
+    path: &str,
+) -> Option<StandardType> {
+    overrides
+        .iter()
+        .rev()
+        .find(|(pattern, _)| path_matches(pattern, path))
+        .map(|&(_, node_type)| node_type)
+}
+
+impl FilterTarget {
+    fn matches(&self, path: &str, node_type: StandardType) -> bool {
+        match self {
+            FilterTarget::Type(target) => *target == node_type,
+            FilterTarget::Path(pattern) => path_matches(pattern, path),
+        }
+    }
+}
+
+/// A function changing values at a target, see
+/// `OptionsBuilder::decode_filter` and `OptionsBuilder::encode_filter`.
+#[derive(Clone)]
+pub struct ValueFilter {
+    target: FilterTarget,
+    filter: Arc<FilterFn>,
+}
+
+impl fmt::Debug for ValueFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ValueFilter")
+            .field("target", &self.target)
+            .finish()
+    }
+}
+
+impl ValueFilter {
+    pub fn new<T, F>(target: T, filter: F) -> Self
+    where
+        T: Into<FilterTarget>,
+        F: Fn(&str, Value) -> Result<Value, FilterError> + Send + Sync + 'static,
+    {
+        Self {
+            target: target.into(),
+            filter: Arc::new(filter),
+        }
+    }
+
+    #[inline]
+    pub fn target(&self) -> &FilterTarget {
+        &self.target
+    }
+}
+
+/// A node whose value is computed from its parent, like a checksum or a
+/// count of its siblings, see `OptionsBuilder::derived_node`.
+#[derive(Clone)]
+pub struct DerivedNode {
+    parent: String,
+    name: String,
+    compute: Arc<DeriveFn>,
+}
+
+impl fmt::Debug for DerivedNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DerivedNode")
+            .field("parent", &self.parent)
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+impl DerivedNode {
+    /// Declare the node at `path`, which is matched like
+    /// `FilterTarget::Path` and cannot be the root node.
+    pub fn new<F>(path: &str, compute: F) -> Self
+    where
+        F: Fn(&Node) -> Result<Value, FilterError> + Send + Sync + 'static,
+    {
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+
+        Self {
+            parent: parent.to_owned(),
+            name: name.to_owned(),
+            compute: Arc::new(compute),
+        }
+    }
+
+    /// The name of the derived node.
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Does the node at `path` hold this derived node?
+    pub(crate) fn is_parent(&self, path: &str) -> bool {
+        !self.parent.trim_start_matches('/').is_empty() && path_matches(&self.parent, path)
+    }
+
+    /// Compute the value of the derived node of `parent`.
+    #[inline]
+    pub(crate) fn compute(&self, parent: &Node) -> Result<Value, FilterError> {
+        (self.compute)(parent)
+    }
+}
+
+/// Pass the value at `path` through every filter targeting it, in order.
+pub(crate) fn apply_filters(
+    filters: &[ValueFilter],
+    path: &str,
+    mut value: Value,
+) -> Result<Value, FilterError> {
+    for filter in filters {
+        if filter.target.matches(path, value.standard_type()) {
+            value = (filter.filter)(path, value)?;
+        }
+    }
+
+    Ok(value)
+}
+
+/// Pass the attribute at `path` through every filter targeting it, which
+/// have to return a string.
+pub(crate) fn apply_attribute_filters(
+    filters: &[ValueFilter],
+    path: &str,
+    value: String,
+) -> Result<String, FilterError> {
+    match apply_filters(filters, path, Value::Attribute(value))? {
+        Value::Attribute(value) | Value::String(value) => Ok(value),
+        _ => Err("Attribute filters must return a string".into()),
+    }
+}
+
+/// The paths of the children of the node at `path`, which have an index if
+/// their name is used more than once.
+pub(crate) fn child_paths<'a, I>(path: &str, keys: I) -> Vec<String>
+where
+    I: Iterator<Item = &'a str> + Clone,
+{
+    let mut totals: HashMap<&str, usize> = HashMap::new();
+    for key in keys.clone() {
+        *totals.entry(key).or_insert(0) += 1;
+    }
+
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    keys.map(|key| {
+        let index = seen.entry(key).or_insert(0);
+        let path = if totals[key] > 1 {
+            format!("{}/{}[{}]", path, key, index)
+        } else {
+            format!("{}/{}", path, key)
+        };
+        *index += 1;
+
+        path
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::KbinError;
+    use crate::node::Node;
+    use crate::node_types::StandardType;
+    use crate::options::Options;
+    use crate::value::Value;
+
+    use super::FilterTarget;
+
+    #[test]
+    fn test_filter_target() {
+        let target = FilterTarget::from("/root/entry/data");
+        assert!(target.matches("/root/entry/data", StandardType::Binary));
+        assert!(target.matches("/root/entry[2]/data", StandardType::Binary));
+        assert!(!target.matches("/root/entry/data/value", StandardType::Binary));
+        assert!(!target.matches("/root/entry", StandardType::Binary));
+
+        let target = FilterTarget::from("/root/*/@id");
+        assert!(target.matches("/root/entry[0]/@id", StandardType::Attribute));
+        assert!(!target.matches("/root/@id/@id", StandardType::Attribute));
+        assert!(!target.matches("/root/entry/id", StandardType::U32));
+
+        let target = FilterTarget::from("/root/**/id");
+        assert!(target.matches("/root/id", StandardType::U32));
+        assert!(target.matches("/root/entry[1]/data/id", StandardType::U32));
+        assert!(!target.matches("/root/entry/@id", StandardType::Attribute));
+
+        assert!(FilterTarget::from(StandardType::U32).matches("/root/id", StandardType::U32));
+    }
+
+    #[test]
+    fn test_decode_filters() {
+        let node = Node::with_nodes("root", vec![
+            Node::with_value("data", Value::Binary(vec![3, 2, 1])),
+            Node::with_attrs("entry", &[("name", "a")]),
+            Node::with_value("id", Value::U32(1)),
+        ]);
+        let data = crate::to_binary(&node).expect("Failed to encode");
+        let (collection, _) = crate::from_slice(&data).expect("Failed to decode");
+
+        let options = Options::builder()
+            .decode_filter(StandardType::Binary, |_, value| match value {
+                Value::Binary(mut data) => {
+                    data.reverse();
+                    Ok(Value::Binary(data))
+                },
+                value => Ok(value),
+            })
+            .decode_filter("/root/entry/@name", |_, value| {
+                Ok(Value::Attribute(value.to_string().to_uppercase()))
+            })
+            .build();
+        let decoded = collection
+            .as_node_with_options(&options)
+            .expect("Failed to convert");
+        assert_eq!(
+            decoded.children()[0].value(),
+            Some(&Value::Binary(vec![1, 2, 3]))
+        );
+        assert_eq!(decoded.children()[1].attributes()["name"], "A");
+        assert_eq!(decoded.children()[2].value(), Some(&Value::U32(1)));
+
+        let options = Options::builder()
+            .decode_filter("/root/id", |_, _| Err("Invalid id".into()))
+            .build();
+        match collection.as_node_with_options(&options) {
+            Err(KbinError::Filter { path, .. }) => assert_eq!(path, "/root/id"),
+            result => panic!("Unexpected result: {:?}", result),
+        };
+    }
+
+    #[test]
+    fn test_encode_filters() {
+        let node = Node::with_nodes("root", vec![
+            Node::with_attrs("entry", &[("name", "a")]),
+            Node::with_attrs("entry", &[("name", "b")]),
+        ]);
+        let options = Options::builder()
+            .encode_filter("/root/entry[1]/@name", |_, value| {
+                Ok(Value::Attribute(value.to_string().to_uppercase()))
+            })
+            .build();
+        let data = crate::to_binary_with_options(options.clone(), &node).expect("Failed to encode");
+        let (collection, _) = crate::from_slice(&data).expect("Failed to decode");
+        let decoded = collection.as_node().expect("Failed to convert");
+        assert_eq!(decoded.children()[0].attributes()["name"], "a");
+        assert_eq!(decoded.children()[1].attributes()["name"], "B");
+
+        let rewritten =
+            crate::to_binary_with_options(options, &collection).expect("Failed to encode");
+        assert_eq!(rewritten, data);
+
+        let options = Options::builder()
+            .encode_filter(StandardType::Attribute, |_, _| Ok(Value::U8(1)))
+            .build();
+        match crate::to_binary_with_options(options, &node) {
+            Err(KbinError::Writer { source }) => {
+                assert!(source.to_string().contains("/root/entry[0]/@name"))
+            },
+            result => panic!("Unexpected result: {:?}", result),
+        };
+    }
+
+    #[test]
+    fn test_derived_nodes() {
+        let mut root = Node::new("root");
+        for i in 0..3 {
+            root.append_child(Node::with_value("entry", Value::U32(i)));
+        }
+        let options = Options::builder()
+            .derived_node("/root/count", |parent| {
+                let count = parent
+                    .children()
+                    .iter()
+                    .filter(|child| child.key() == "entry")
+                    .count();
+                Ok(Value::U32(count as u32))
+            })
+            .build();
+
+        let data = crate::to_binary_with_options(options.clone(), &root).expect("Failed to encode");
+        let (collection, _) = crate::from_slice(&data).expect("Failed to decode");
+        let decoded = collection
+            .as_node_with_options(&options)
+            .expect("Failed to convert");
+        assert_eq!(decoded.children()[3].key(), "count");
+        assert_eq!(decoded.children()[3].value(), Some(&Value::U32(3)));
+
+        root.append_child(Node::with_value("count", Value::U32(2)));
+        let data = crate::to_binary(&root).expect("Failed to encode");
+        let (collection, _) = crate::from_slice(&data).expect("Failed to decode");
+        match collection.as_node_with_options(&options) {
+            Err(KbinError::DerivedMismatch {
+                path,
+                expected,
+                actual,
+            }) => {
+                assert_eq!(path, "/root/count");
+                assert_eq!(expected, Value::U32(3));
+                assert_eq!(actual, Some(Value::U32(2)));
+            },
+            result => panic!("Unexpected result: {:?}", result),
+        };
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use bytes::Bytes;
+
+use crate::compression_type::CompressionType;
+use crate::encoding_type::EncodingType;
+use crate::error::KbinError;
+use crate::node_types::StandardType;
+use crate::reader::Reader;
+use crate::ARRAY_MASK;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// A 64-bit FNV-1a hash of `bytes`.
+pub(crate) fn fnv1a<I>(bytes: I) -> u64
+where
+    I: IntoIterator<Item = u8>,
+{
+    bytes.into_iter().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// A summary of the shape of a binary document, used to group documents that
+/// were likely produced by the same game or version.
+///
+/// Two documents have the same fingerprint if they share header flags, use
+/// every node type the same number of times and contain the same set of node
+/// names. Values are not taken into account.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fingerprint {
+    compression: CompressionType,
+    encoding: EncodingType,
+    type_usage: BTreeMap<u8, usize>,
+    names: BTreeSet<String>,
+}
+
+impl Fingerprint {
+    #[inline]
+    pub fn compression(&self) -> CompressionType {
+        self.compression
+    }
+
+    #[inline]
+    pub fn encoding(&self) -> EncodingType {
+        self.encoding
+    }
+
+    /// The number of definitions of each raw node type byte, including the
+    /// array flag, excluding end markers.
+    #[inline]
+    pub fn type_usage(&self) -> &BTreeMap<u8, usize> {
+        &self.type_usage
+    }
+
+    /// The distinct node names, not including attribute names.
+    #[inline]
+    pub fn names(&self) -> &BTreeSet<String> {
+        &self.names
+    }
+
+    /// A 64-bit FNV-1a hash of the sorted node names.
+    pub fn names_hash(&self) -> u64 {
+        fnv1a(
+            self.names
+                .iter()
+                .flat_map(|name| name.bytes().chain(Some(0))),
+        )
+    }
+}
+
+/// Formats the fingerprint as a compact string of the form
+/// `kbin:<compression>:<encoding>:<type>*<count>,...:<name count>:<name hash>`,
+/// with bytes and the hash in hex.
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "kbin:{:02x}:{:02x}:",
+            self.compression.to_byte(),
+            self.encoding.to_byte()
+        )?;
+        for (i, (node_type, count)) in self.type_usage.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{:02x}*{}", node_type, count)?;
+        }
+        write!(f, ":{}:{:016x}", self.names.len(), self.names_hash())
+    }
+}
+
+/// Read the node definitions of a binary document and summarize them.
+pub fn fingerprint(input: &[u8]) -> Result<Fingerprint, KbinError> {
+    let mut reader = Reader::new(Bytes::from(input.to_vec()))?;
+    let mut type_usage = BTreeMap::new();
+    let mut names = BTreeSet::new();
+
+    loop {
+        let def = reader.read_node_definition()?;
+        match def.node_type {
+            StandardType::FileEnd => break,
+            StandardType::NodeEnd => continue,
+            StandardType::Attribute => {},
+            _ => {
+                if let Some(name) = def.key()? {
+                    names.insert(name);
+                }
+            },
+        };
+
+        let raw_type = if def.is_array {
+            def.node_type.id | ARRAY_MASK
+        } else {
+            def.node_type.id
+        };
+        *type_usage.entry(raw_type).or_insert(0) += 1;
+    }
+
+    Ok(Fingerprint {
+        compression: reader.compression(),
+        encoding: reader.encoding(),
+        type_usage,
+        names,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding_type::EncodingType;
+    use crate::node::Node;
+    use crate::node_types::StandardType;
+    use crate::options::Options;
+    use crate::value::{Value, ValueArray};
+    use crate::ARRAY_MASK;
+
+    use super::{fingerprint, Fingerprint};
+
+    fn document(name: &str, values: [Value; 2], options: Options) -> Fingerprint {
+        let mut root = Node::new("root");
+        for value in values {
+            root.append_child(Node::with_value(name, value));
+        }
+        root.children_mut()[0].set_attr("id", "1");
+        let data = crate::to_binary_with_options(options, &root).expect("Failed to encode");
+
+        fingerprint(&data).expect("Failed to fingerprint")
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let options = Options::default();
+        let base = document("entry", [Value::U32(1), Value::U32(2)], options.clone());
+        assert!(base.to_string().starts_with("kbin:42:80:01*1,07*2,2e*1:2:"));
+
+        // Values are ignored
+        let values = document("entry", [Value::U32(3), Value::U32(4)], options.clone());
+        assert_eq!(values, base);
+        assert_eq!(values.to_string(), base.to_string());
+
+        let types = document("entry", [Value::U32(1), Value::U16(2)], options.clone());
+        assert_ne!(types, base);
+
+        let array = Value::Array(ValueArray::U32(vec![2]));
+        let arrays = document("entry", [Value::U32(1), array], options.clone());
+        let array_type = StandardType::U32.id | ARRAY_MASK;
+        assert_eq!(arrays.type_usage().get(&array_type), Some(&1));
+
+        let names = document("item", [Value::U32(1), Value::U32(2)], options);
+        assert_ne!(names, base);
+        assert_ne!(names.names_hash(), base.names_hash());
+
+        let encoding = document(
+            "entry",
+            [Value::U32(1), Value::U32(2)],
+            Options::with_encoding(EncodingType::UTF_8),
+        );
+        assert_ne!(encoding, base);
+        assert_ne!(encoding.to_string(), base.to_string());
+    }
+}
@@ -0,0 +1,172 @@
+//! Generated documents covering the structural patterns found in real
+//! files, for tests that cannot ship game data.
+//!
+//! Only available with the `fixtures` feature. Every fixture is
+//! deterministic, so encoded fixtures can be compared between runs.
+
+use crate::node::Node;
+use crate::node_types::StandardType;
+use crate::value::Value;
+
+/// Every node type that holds a value.
+fn value_types() -> impl Iterator<Item = StandardType> {
+    (0..=u8::MAX)
+        .filter_map(|id| StandardType::from_u8(id).ok())
+        .filter(|node_type| {
+            !matches!(
+                node_type,
+                StandardType::NodeStart |
+                    StandardType::Attribute |
+                    StandardType::NodeEnd |
+                    StandardType::FileEnd
+            )
+        })
+}
+
+/// The text form of `count` elements of `node_type`, varying by element.
+fn element_text(node_type: StandardType, count: usize) -> String {
+    (0..count)
+        .map(|i| match node_type {
+            StandardType::Ip4 => format!("127.0.0.{}", i + 1),
+            StandardType::Float |
+            StandardType::Float2 |
+            StandardType::Float3 |
+            StandardType::Float4 |
+            StandardType::Double |
+            StandardType::Double2 |
+            StandardType::Double3 |
+            StandardType::Double4 => format!("{}.5", i),
+            StandardType::Boolean |
+            StandardType::Boolean2 |
+            StandardType::Boolean3 |
+            StandardType::Boolean4 |
+            StandardType::Vb => (i % 2).to_string(),
+            _ => (i + 1).to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A value of `node_type`, or `None` if it cannot form an array of
+/// `array_len` elements.
+fn fixture_value(node_type: StandardType, array_len: Option<usize>) -> Option<Value> {
+    let text = match node_type {
+        StandardType::String => String::from("fixture"),
+        StandardType::Binary => String::from("00ff10"),
+        _ => element_text(node_type, node_type.count * array_len.unwrap_or(1)),
+    };
+
+    Value::from_string(
+        node_type,
+        &text,
+        array_len.is_some(),
+        array_len.unwrap_or(0),
+    )
+    .ok()
+}
+
+/// A chain of `depth` nested nodes, each with a counter value beside the
+/// next level.
+pub fn deep_nesting(depth: usize) -> Node {
+    let mut node = Node::with_value("leaf", Value::U32(depth as u32));
+
+    for level in (0..depth).rev() {
+        let mut parent = Node::new(format!("level{}", level));
+        parent.append_child(Node::with_value("depth", Value::U32(level as u32)));
+        parent.append_child(node);
+        node = parent;
+    }
+
+    node
+}
+
+/// A node with `count` children of the same name, like the entries of a
+/// music or item list.
+pub fn wide_siblings(count: usize) -> Node {
+    let entries = (0..count)
+        .map(|i| {
+            let mut entry = Node::with_attrs("entry", &[("id", &i.to_string())]);
+            entry.append_child(Node::with_value("index", Value::U32(i as u32)));
+            entry.append_child(Node::with_value(
+                "name",
+                Value::String(format!("entry {}", i)),
+            ));
+            entry.append_child(Node::with_value("enabled", Value::Boolean(i % 2 == 0)));
+            entry
+        })
+        .collect::<Vec<_>>();
+
+    Node::with_nodes("list", entries)
+}
+
+/// A node with one child for every value type, named after the type, and
+/// one array child for every type that can form an array.
+pub fn every_value_type() -> Node {
+    let mut root = Node::new("types");
+
+    for node_type in value_types() {
+        let value = fixture_value(node_type, None).expect("Fixture values must parse");
+        root.append_child(Node::with_value(format!("t_{}", node_type.name), value));
+    }
+    for node_type in value_types() {
+        if matches!(node_type, StandardType::String | StandardType::Binary) {
+            continue;
+        }
+        if let Some(value) = fixture_value(node_type, Some(3)) {
+            root.append_child(Node::with_value(format!("a_{}", node_type.name), value));
+        }
+    }
+
+    root
+}
+
+/// A node with `count` attributes on itself and on each of its children,
+/// including empty and escaped values.
+pub fn attribute_heavy(count: usize) -> Node {
+    let attrs = (0..count)
+        .map(|i| {
+            let value = match i % 3 {
+                0 => String::new(),
+                1 => format!("<value & {}>", i),
+                _ => i.to_string(),
+            };
+            (format!("attr{}", i), value)
+        })
+        .collect::<Vec<_>>();
+    let attrs = attrs
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect::<Vec<_>>();
+
+    let children = (0..count.min(8))
+        .map(|_| Node::with_attrs("child", &attrs))
+        .collect::<Vec<_>>();
+
+    Node::with("attrs", &attrs, children)
+}
+
+/// Every fixture with its name, at sizes suited to regular test runs.
+pub fn all() -> Vec<(&'static str, Node)> {
+    vec![
+        ("deep_nesting", deep_nesting(64)),
+        ("wide_siblings", wide_siblings(256)),
+        ("every_value_type", every_value_type()),
+        ("attribute_heavy", attribute_heavy(32)),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_fixtures_round_trip() {
+        for (name, node) in super::all() {
+            let data = crate::to_binary(&node).expect("Failed to encode fixture");
+            let (collection, _) = crate::from_slice(&data).expect("Failed to decode fixture");
+            let decoded = collection.as_node().expect("Failed to convert fixture");
+            assert_eq!(decoded, node, "fixture {} changed in a round trip", name);
+
+            let text = crate::to_text_xml(&node).expect("Failed to write fixture");
+            crate::from_text_xml(&text).expect("Failed to read fixture");
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use crate::compression_type::CompressionType;
+use crate::encoding_type::EncodingType;
+use crate::SIGNATURE;
+
+/// The length of the fixed-size prefix of a binary document: the signature,
+/// compression, encoding and inverted encoding bytes followed by the length
+/// of the node buffer.
+pub const HEADER_LEN: usize = 8;
+
+/// The fixed-size prefix of a binary document.
+///
+/// The length of the data buffer follows the node buffer, so it is not part
+/// of the header.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Header {
+    pub compression: CompressionType,
+    pub encoding: EncodingType,
+    pub node_buffer_len: u32,
+}
+
+impl Header {
+    pub fn new(compression: CompressionType, encoding: EncodingType) -> Self {
+        Self {
+            compression,
+            encoding,
+            node_buffer_len: 0,
+        }
+    }
+
+    /// Write the header into `output` without allocating, so a writer can
+    /// reserve the header region and fill it in once the node buffer length
+    /// is known.
+    pub fn write_into(&self, output: &mut [u8; HEADER_LEN]) {
+        let encoding = self.encoding.to_byte();
+
+        output[0] = SIGNATURE;
+        output[1] = self.compression.to_byte();
+        output[2] = encoding;
+        output[3] = !encoding;
+        output[4..].copy_from_slice(&self.node_buffer_len.to_be_bytes());
+    }
+
+    #[inline]
+    pub fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut output = [0; HEADER_LEN];
+        self.write_into(&mut output);
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compression_type::CompressionType;
+    use crate::encoding_type::EncodingType;
+    use crate::node::Node;
+    use crate::options::Options;
+
+    use super::{Header, HEADER_LEN};
+
+    #[test]
+    fn test_header() {
+        let header = Header::new(CompressionType::Compressed, EncodingType::SHIFT_JIS);
+        assert_eq!(header.to_bytes(), [0xa0, 0x42, 0x80, 0x7f, 0, 0, 0, 0]);
+
+        let node = Node::new("root");
+        for &compression in &[CompressionType::Compressed, CompressionType::Uncompressed] {
+            let options = Options::new(compression, EncodingType::UTF_8);
+            let data = crate::to_binary_with_options(options, &node).expect("Failed to encode");
+
+            // The data buffer of a node without values is empty.
+            let header = Header {
+                compression,
+                encoding: EncodingType::UTF_8,
+                node_buffer_len: (data.len() - HEADER_LEN - 4) as u32,
+            };
+            assert_eq!(header.to_bytes(), data[..HEADER_LEN]);
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+
+use crate::encoding_type::EncodingType;
+use crate::error::Result;
+use crate::node::{NodeCollection, NodeCursor};
+use crate::options::Options;
+use crate::query::{Query, Selected};
+
+/// A binary document read once and shared by cursors, which can be sent to
+/// other threads to answer queries concurrently without copying the tree.
+///
+/// ```
+/// # fn main() -> Result<(), kbinxml::KbinError> {
+/// # use kbinxml::Node;
+/// # let input = kbinxml::to_binary(&Node::with_nodes("root", vec![Node::new("entry")]))?;
+/// let index = kbinxml::DocumentIndex::parse(&input)?;
+/// let cursor = index.cursor();
+/// let handle = std::thread::spawn(move || cursor.first_child().map(|child| child.path()));
+/// # assert_eq!(handle.join().unwrap().transpose()?.as_deref(), Some("/root/entry"));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct DocumentIndex {
+    collection: Arc<NodeCollection>,
+    encoding: EncodingType,
+}
+
+impl DocumentIndex {
+    #[inline]
+    pub fn parse(input: &[u8]) -> Result<Self> {
+        Self::parse_with_options(Options::default(), input)
+    }
+
+    pub fn parse_with_options(options: Options, input: &[u8]) -> Result<Self> {
+        let (collection, encoding) =
+            crate::from_binary_with_options(options, Bytes::from(input.to_vec()))?;
+
+        Ok(Self {
+            collection: Arc::new(collection),
+            encoding,
+        })
+    }
+
+    #[inline]
+    pub fn collection(&self) -> &NodeCollection {
+        &self.collection
+    }
+
+    #[inline]
+    pub fn encoding(&self) -> EncodingType {
+        self.encoding
+    }
+
+    /// A cursor at the root node.
+    pub fn cursor(&self) -> IndexCursor {
+        IndexCursor {
+            collection: self.collection.clone(),
+            indices: Vec::new(),
+        }
+    }
+
+    /// Select the nodes or attributes matching a path query, see `Query`.
+    pub fn select(&self, query: &str) -> Result<Vec<Selected<'_>>> {
+        Ok(Query::parse(query)?.select_collection(&self.collection))
+    }
+}
+
+impl From<(NodeCollection, EncodingType)> for DocumentIndex {
+    fn from((collection, encoding): (NodeCollection, EncodingType)) -> Self {
+        Self {
+            collection: Arc::new(collection),
+            encoding,
+        }
+    }
+}
+
+/// A position within a `DocumentIndex`, like a `NodeCursor` that shares
+/// ownership of the document instead of borrowing it.
+#[derive(Clone, Debug)]
+pub struct IndexCursor {
+    collection: Arc<NodeCollection>,
+    indices: Vec<usize>,
+}
+
+impl IndexCursor {
+    /// Borrow the cursor as a `NodeCursor`.
+    pub fn as_cursor(&self) -> NodeCursor<'_> {
+        NodeCursor::with_indices(&self.collection, self.indices.clone())
+    }
+
+    fn moved<'a, F>(&'a self, f: F) -> Option<Self>
+    where
+        F: FnOnce(&NodeCursor<'a>) -> Option<NodeCursor<'a>>,
+    {
+        let cursor = f(&self.as_cursor())?;
+
+        Some(Self {
+            collection: self.collection.clone(),
+            indices: cursor.indices().to_vec(),
+        })
+    }
+
+    #[inline]
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+
+    pub fn node(&self) -> &NodeCollection {
+        self.as_cursor().node()
+    }
+
+    pub fn parent(&self) -> Option<Self> {
+        self.moved(NodeCursor::parent)
+    }
+
+    pub fn child(&self, index: usize) -> Option<Self> {
+        self.moved(|cursor| cursor.child(index))
+    }
+
+    pub fn first_child(&self) -> Option<Self> {
+        self.moved(NodeCursor::first_child)
+    }
+
+    pub fn next_sibling(&self) -> Option<Self> {
+        self.moved(NodeCursor::next_sibling)
+    }
+
+    pub fn prev_sibling(&self) -> Option<Self> {
+        self.moved(NodeCursor::prev_sibling)
+    }
+
+    /// The path of the current node, see `NodeCursor::path`.
+    pub fn path(&self) -> Result<String> {
+        self.as_cursor().path()
+    }
+
+    /// Run a path query on the current node, see `Query`.
+    pub fn select(&self, query: &str) -> Result<Vec<Selected<'_>>> {
+        Ok(Query::parse(query)?.select_collection(self.node()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use crate::node::Node;
+    use crate::value::Value;
+
+    use super::DocumentIndex;
+
+    #[test]
+    fn test_concurrent_cursors() {
+        let mut root = Node::new("root");
+        for i in 0..4u32 {
+            root.append_child(Node::with_value("entry", Value::U32(i)));
+        }
+        let input = crate::to_binary(&root).expect("Failed to encode");
+        let index = DocumentIndex::parse(&input).expect("Failed to parse");
+
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let cursor = index.cursor();
+                thread::spawn(move || {
+                    let child = cursor.child(i).expect("Missing child");
+                    let value = child.node().base().value().expect("Failed to decode");
+                    (child.path().expect("Failed to build path"), value)
+                })
+            })
+            .collect();
+        for (i, handle) in handles.into_iter().enumerate() {
+            let (path, value) = handle.join().expect("Thread panicked");
+            assert_eq!(path, format!("/root/entry[{}]", i));
+            assert_eq!(value, Value::U32(i as u32));
+        }
+
+        let cursor = index.cursor().child(2).expect("Missing child");
+        assert_eq!(
+            cursor
+                .prev_sibling()
+                .and_then(|c| c.next_sibling())
+                .map(|c| c.indices().to_vec()),
+            Some(vec![2])
+        );
+        assert_eq!(cursor.parent().map(|c| c.indices().len()), Some(0));
+        assert_eq!(index.select("entry").expect("Failed to select").len(), 4);
+        assert_eq!(
+            index
+                .cursor()
+                .select("entry[1]")
+                .expect("Failed to select")
+                .len(),
+            1
+        );
+    }
+}
@@ -5,21 +5,57 @@ extern crate lazy_static;
 #[macro_use]
 extern crate log;
 
+use std::io;
+use std::iter;
+
 use bytes::Bytes;
 
+mod accounting;
 mod byte_buffer;
+mod capabilities;
+mod compare;
 mod compression_type;
+mod de;
+mod diff;
+mod document;
 mod encoding_type;
 mod error;
+mod filter;
+mod fingerprint;
+#[cfg(feature = "fixtures")]
+pub mod fixtures;
+mod header;
+mod index;
+mod nested;
 mod node;
 mod node_types;
 mod options;
+mod patch;
 mod printer;
+#[cfg(feature = "profile")]
+pub mod profile;
+mod query;
 mod reader;
+mod recovery;
+mod redact;
+mod rename;
+mod repeated;
+mod scan;
+mod schema;
+mod search;
+mod ser;
+mod shard;
 mod sixbit;
+mod size_hint;
+mod symmetry;
+#[cfg(feature = "test_support")]
+pub mod test_support;
 mod text_reader;
 mod to_text_xml;
+mod tokens;
+mod traits;
 mod types;
+mod validate;
 mod value;
 mod writer;
 
@@ -28,17 +64,59 @@ use crate::text_reader::TextXmlReader;
 use crate::to_text_xml::TextXmlWriter;
 
 // Public exports
+pub use crate::accounting::Accountant;
+pub use crate::capabilities::{capabilities, Capabilities};
+pub use crate::compare::{
+    compare_binaries, CompareReport, Divergence, DivergenceKind, TokenLocation,
+};
 pub use crate::compression_type::CompressionType;
+pub use crate::de::{from_node, from_node_with_options, DeError};
+pub use crate::diff::{diff, diff_streaming, DiffEntry, DiffReport};
+pub use crate::document::Document;
 pub use crate::encoding_type::EncodingType;
 pub use crate::error::KbinError;
-pub use crate::node::{Node, NodeCollection};
+pub use crate::filter::{DerivedNode, FilterError, FilterTarget, ValueFilter};
+pub use crate::fingerprint::{fingerprint, Fingerprint};
+pub use crate::header::{Header, HEADER_LEN};
+pub use crate::index::{DocumentIndex, IndexCursor};
+pub use crate::node::{
+    merge, HashedNode, Markup, MarkupKind, NameMatch, Node, NodeBuilder, NodeChange,
+    NodeCollection, NodeCursor, NodePatch, Origin, Provenance, SanitizedName,
+};
 pub use crate::node_types::StandardType;
-pub use crate::options::{Options, OptionsBuilder};
+pub use crate::options::{
+    AttributeNamePolicy, BinarySizePolicy, ControlCharacterPolicy, EmptyArrayPolicy, Endianness,
+    FloatFormat, NameConflictPolicy, Options, OptionsBuilder, OptionsConflict, ParseMode,
+    TypeNamePolicy, UnknownTypeNamePolicy, UnmappableCharacterPolicy,
+};
+pub use crate::patch::{patch_value, PatchError};
 pub use crate::printer::Printer;
-pub use crate::reader::Reader;
+pub use crate::query::{
+    select, CompareOp, Filter, Operand, Predicate, Query, QueryError, Selected, Step,
+};
+pub use crate::reader::{Reader, UnknownNode};
+pub use crate::recovery::{from_binary_partial, PartialDecode, ResumeHandle};
+pub use crate::redact::{redact, Redaction};
+pub use crate::rename::{rename_nodes, RenameError};
+pub use crate::repeated::{find_repeated_subtrees, RepeatedSubtree};
+pub use crate::scan::{scan_strings, FoundString};
+pub use crate::schema::{
+    decode_with_schema, generate_accessors, InferredNode, InferredSchema, SchemaError,
+};
+pub use crate::search::{find_integer, find_string, find_u32, find_values, ValueMatch};
+pub use crate::ser::{to_node, to_node_with_options, SerError};
+pub use crate::shard::{encode_chunked, join_documents, split_by_child, ShardError};
+pub use crate::size_hint::SizeEstimate;
+pub use crate::symmetry::{verify_symmetry, AsymmetryReason, SymmetryReport};
 pub use crate::to_text_xml::ToTextXml;
-pub use crate::value::{Value, ValueArray};
-pub use crate::writer::{Writeable, Writer};
+pub use crate::tokens::{assemble_tokens, dump_tokens};
+pub use crate::traits::{KbinRead, KbinWrite};
+pub use crate::types::{
+    KbinIp4, KbinS16, KbinS32, KbinS64, KbinS8, KbinTime, KbinU16, KbinU32, KbinU64, KbinU8,
+};
+pub use crate::validate::{validate, Problem, ProblemKind, ValidationReport};
+pub use crate::value::{compare, Value, ValueArray, ValueRef};
+pub use crate::writer::{WriteObserver, Writeable, Writer, WriterPool};
 
 const SIGNATURE: u8 = 0xA0;
 
@@ -53,16 +131,59 @@ pub fn is_binary_xml(input: &[u8]) -> bool {
         (input[1] == SIG_COMPRESSED || input[1] == SIG_UNCOMPRESSED)
 }
 
+#[inline]
 pub fn from_binary(input: Bytes) -> Result<(NodeCollection, EncodingType)> {
-    let reader = Reader::new(input)?;
+    from_binary_with_options(Options::default(), input)
+}
+
+pub fn from_binary_with_options(
+    options: Options,
+    input: Bytes,
+) -> Result<(NodeCollection, EncodingType)> {
+    let nested_options = if options.decode_nested_documents {
+        Some(options.clone())
+    } else {
+        None
+    };
+    let mut reader = Reader::with_options(input, options)?;
     let encoding = reader.encoding();
-    let collection = reader.collect::<Option<_>>().ok_or(KbinError::NoNodeCollection)?;
+
+    // The `Iterator` implementation of `Reader` swallows errors, so keep the
+    // first one around to report it instead of returning a partial document.
+    let mut error = None;
+    let collection = iter::from_fn(|| match reader.read_node_definition() {
+        Ok(def) => Some(def),
+        Err(e) => {
+            error = Some(e);
+            None
+        },
+    })
+    .collect::<Option<_>>();
+
+    if let Some(e) = error {
+        return Err(e.into());
+    }
+    let mut collection = collection.ok_or(KbinError::NoNodeCollection)?;
+    reader.read_file_end()?;
+    if let Some(options) = nested_options {
+        nested::expand_nested(&mut collection, &options)?;
+    }
 
     Ok((collection, encoding))
 }
 
+#[inline]
 pub fn from_text_xml(input: &[u8]) -> Result<(NodeCollection, EncodingType)> {
-    let mut reader = TextXmlReader::new(input);
+    from_text_xml_with_options(Options::default(), input)
+}
+
+pub fn from_text_xml_with_options(
+    options: Options,
+    input: &[u8],
+) -> Result<(NodeCollection, EncodingType)> {
+    options.validate_read()?;
+
+    let mut reader = TextXmlReader::with_options(input, &options);
     let collection = reader
         .as_node_collection()?
         .ok_or(KbinError::NoNodeCollection)?;
@@ -86,7 +207,7 @@ pub fn from_slice(input: &[u8]) -> Result<(NodeCollection, EncodingType)> {
 
 pub fn to_binary<T>(input: &T) -> Result<Vec<u8>>
 where
-    T: Writeable,
+    T: Writeable + ?Sized,
 {
     let mut writer = Writer::new();
     writer.to_binary(input).map_err(Into::into)
@@ -94,12 +215,43 @@ where
 
 pub fn to_binary_with_options<T>(options: Options, input: &T) -> Result<Vec<u8>>
 where
-    T: Writeable,
+    T: Writeable + ?Sized,
 {
     let mut writer = Writer::with_options(options);
     writer.to_binary(input).map_err(Into::into)
 }
 
+/// Encode a node tree read with `from_binary`, using the compression and
+/// encoding of the document it was read from.
+///
+/// Nodes keep the types they were read with, so unlike converting through
+/// text XML no types are inferred again.
+pub fn node_collection_to_binary(input: &NodeCollection) -> Result<Vec<u8>> {
+    let compression = input.compression().unwrap_or_default();
+    let options = Options::new(compression, input.base().encoding());
+
+    to_binary_with_options(options, input)
+}
+
+/// Encode `input` directly to `output`, see `Writer::to_writer`.
+pub fn to_writer<T, W>(input: &T, output: W) -> Result<()>
+where
+    T: Writeable + ?Sized,
+    W: io::Write,
+{
+    let mut writer = Writer::new();
+    writer.to_writer(input, output).map_err(Into::into)
+}
+
+pub fn to_writer_with_options<T, W>(options: Options, input: &T, output: W) -> Result<()>
+where
+    T: Writeable + ?Sized,
+    W: io::Write,
+{
+    let mut writer = Writer::with_options(options);
+    writer.to_writer(input, output).map_err(Into::into)
+}
+
 pub fn to_text_xml<T>(input: &T) -> Result<Vec<u8>>
 where
     T: ToTextXml,
@@ -108,3 +260,43 @@ where
 
     writer.into_text_xml(input)
 }
+
+pub fn to_text_xml_with_options<T>(options: Options, input: &T) -> Result<Vec<u8>>
+where
+    T: ToTextXml,
+{
+    let writer = TextXmlWriter::with_options(options);
+
+    writer.into_text_xml(input)
+}
+
+/// Decode a binary document and write it as indented UTF-8 text XML, with
+/// `__type`, `__count` and `__size` attributes like other kbinxml
+/// implementations.
+#[inline]
+pub fn binary_to_text_xml(input: &[u8]) -> Result<Vec<u8>> {
+    binary_to_text_xml_with_options(Options::default(), input)
+}
+
+pub fn binary_to_text_xml_with_options(options: Options, input: &[u8]) -> Result<Vec<u8>> {
+    let (collection, _) = from_binary_with_options(options.clone(), Bytes::from(input.to_vec()))?;
+
+    to_text_xml_with_options(options, &collection)
+}
+
+/// Read text XML, using `__type`, `__count` and `__size` attributes, and
+/// encode it as a binary document in the encoding named by its XML
+/// declaration.
+pub fn text_xml_to_binary(input: &[u8]) -> Result<Vec<u8>> {
+    let (collection, encoding) = from_text_xml(input)?;
+
+    to_binary_with_options(Options::with_encoding(encoding), &collection)
+}
+
+/// Like `text_xml_to_binary`, encoding with the compression and encoding of
+/// `options`.
+pub fn text_xml_to_binary_with_options(options: Options, input: &[u8]) -> Result<Vec<u8>> {
+    let (collection, _) = from_text_xml_with_options(options.clone(), input)?;
+
+    to_binary_with_options(options, &collection)
+}
@@ -0,0 +1,187 @@
+use bytes::Bytes;
+
+use crate::compression_type::CompressionType;
+use crate::encoding_type::EncodingType;
+use crate::error::KbinError;
+use crate::node::{Key, Node, NodeCollection, NodeData, NodeDefinition};
+use crate::node_types::StandardType;
+use crate::options::Options;
+use crate::value::Value;
+use crate::writer::{Writer, WriterError};
+
+/// The attribute that marks a node as holding an embedded document.
+///
+/// Its value is the compression and encoding bytes of the embedded header in
+/// hex, e.g. `4280`, so the document can be embedded again as it was.
+pub(crate) const NESTED_ATTRIBUTE: &str = "__nested";
+
+/// Check the header of `data` without reading the rest of it.
+pub(crate) fn is_document_header(data: &[u8]) -> bool {
+    crate::is_binary_xml(data) &&
+        data.len() >= 8 &&
+        data[3] == !data[2] &&
+        EncodingType::from_byte(data[2]).is_ok()
+}
+
+fn format_header(data: &[u8]) -> String {
+    format!("{:02x}{:02x}", data[1], data[2])
+}
+
+pub(crate) fn parse_header(header: &str) -> Option<(CompressionType, EncodingType)> {
+    if header.len() != 4 {
+        return None;
+    }
+    let compression = u8::from_str_radix(header.get(..2)?, 16).ok()?;
+    let encoding = u8::from_str_radix(header.get(2..)?, 16).ok()?;
+
+    Some((
+        CompressionType::from_byte(compression).ok()?,
+        EncodingType::from_byte(encoding).ok()?,
+    ))
+}
+
+/// Replace every `Binary` node below `collection` that holds a kbin document
+/// with a node that has the decoded document as its only child.
+///
+/// Values that only look like a document are left as they are.
+pub(crate) fn expand_nested(
+    collection: &mut NodeCollection,
+    options: &Options,
+) -> Result<(), KbinError> {
+    for child in collection.children_mut() {
+        let data = match child.base().node_type {
+            StandardType::Binary if !child.base().is_array => child.base().value()?,
+            _ => {
+                expand_nested(child, options)?;
+                continue;
+            },
+        };
+        let data = match data.nested_document() {
+            Some(data) => data,
+            None => continue,
+        };
+        let key = match child.base().data() {
+            NodeData::Some { key, .. } => key.clone(),
+            NodeData::None => continue,
+        };
+
+        let nested =
+            match crate::from_binary_with_options(options.clone(), Bytes::copy_from_slice(data)) {
+                Ok((nested, _)) => nested,
+                Err(e) => {
+                    warn!(
+                        "failed to decode nested document, keeping binary value: {}",
+                        e
+                    );
+                    continue;
+                },
+            };
+
+        let encoding = child.base().encoding();
+        let base =
+            NodeDefinition::with_data(encoding, StandardType::NodeStart, false, NodeData::Some {
+                key,
+                value_data: Bytes::new(),
+            });
+        let marker =
+            NodeDefinition::with_data(encoding, StandardType::Attribute, false, NodeData::Some {
+                key: Key::Uncompressed {
+                    encoding,
+                    data: Bytes::from_static(NESTED_ATTRIBUTE.as_bytes()),
+                },
+                value_data: Bytes::from(format!("{}\0", format_header(data))),
+            });
+
+        let mut attributes = child.attributes().clone();
+        attributes.push_back(marker);
+        let mut expanded = NodeCollection::with_attributes(base, attributes);
+        expanded.children_mut().push_back(nested);
+
+        *child = expanded;
+    }
+
+    Ok(())
+}
+
+/// Encode the child of a node marked with `__nested` and return a `Binary`
+/// node holding it, or `None` if the node is not marked.
+pub(crate) fn embed_nested(node: &Node, options: &Options) -> Result<Option<Node>, WriterError> {
+    let header = match node.attributes().get(NESTED_ATTRIBUTE) {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+    let invalid = || WriterError::InvalidNestedDocument {
+        key: node.key().to_owned(),
+    };
+
+    let (compression, encoding) = parse_header(header).ok_or_else(invalid)?;
+    let child = match node.children() {
+        [child] if node.value().is_none() => child,
+        _ => return Err(invalid()),
+    };
+
+    let mut nested_options = options.clone();
+    nested_options.compression = compression;
+    nested_options.encoding = encoding;
+    let data = Writer::with_options(nested_options).to_binary(child)?;
+
+    let mut embedded = Node::with_value(node.key(), Value::Binary(data));
+    for (key, value) in node.attributes() {
+        if key != NESTED_ATTRIBUTE {
+            embedded.set_attr(key.as_str(), value.as_str());
+        }
+    }
+
+    Ok(Some(embedded))
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use crate::compression_type::CompressionType;
+    use crate::encoding_type::EncodingType;
+    use crate::node::Node;
+    use crate::options::Options;
+    use crate::value::Value;
+
+    use super::NESTED_ATTRIBUTE;
+
+    #[test]
+    fn test_nested_documents() {
+        let inner = Node::with_nodes("inner", [Node::with_value("value", Value::U32(5))]);
+        let utf8 = Options::new(CompressionType::Compressed, EncodingType::UTF_8);
+        let data = crate::to_binary_with_options(utf8, &inner).expect("Failed to encode");
+
+        // Only the header of `broken` is valid, so it stays a binary value
+        let mut broken = data[..8].to_vec();
+        broken.extend_from_slice(&[0xff; 8]);
+        let mut blob = Node::with_value("blob", Value::Binary(data.clone()));
+        blob.set_attr("id", "1");
+        let root = Node::with_nodes("root", [
+            blob.clone(),
+            Node::with_value("broken", Value::Binary(broken.clone())),
+        ]);
+        let input = Bytes::from(crate::to_binary(&root).expect("Failed to encode"));
+
+        let (collection, _) = crate::from_binary(input.clone()).expect("Failed to decode");
+        assert_eq!(collection.as_node().expect("Failed to convert"), root);
+
+        let options = Options::builder().decode_nested_documents(true).build();
+        let (collection, _) =
+            crate::from_binary_with_options(options, input.clone()).expect("Failed to decode");
+        let node = collection.as_node().expect("Failed to convert");
+        let expanded = &node.children()[0];
+        assert_eq!(expanded.value(), None);
+        assert_eq!(expanded.attributes()["id"], "1");
+        assert_eq!(expanded.attributes()[NESTED_ATTRIBUTE], "42a0");
+        assert_eq!(expanded.children(), [inner]);
+        assert_eq!(node.children()[1].value(), Some(&Value::Binary(broken)));
+
+        // Marked nodes are embedded again with their original header
+        let output = crate::to_binary(&collection).expect("Failed to encode");
+        assert_eq!(output, input);
+        let output = crate::to_binary(&node).expect("Failed to encode");
+        assert_eq!(output, input);
+    }
+}
@@ -0,0 +1,92 @@
+use indexmap::IndexMap;
+
+use crate::node::{Meta, Node};
+use crate::value::Value;
+
+/// Builds a `Node` one attribute, child or value at a time.
+#[derive(Clone, Debug, Default)]
+pub struct NodeBuilder {
+    key: String,
+    attributes: IndexMap<String, String>,
+    children: Vec<Node>,
+    value: Option<Value>,
+}
+
+impl NodeBuilder {
+    pub fn new<K>(key: K) -> Self
+    where
+        K: Into<String>,
+    {
+        Self {
+            key: key.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Set an attribute, replacing any previous value with the same name
+    /// but keeping its position.
+    pub fn attr<K, V>(&mut self, key: K, value: V) -> &mut Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.attributes.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn child(&mut self, node: Node) -> &mut Self {
+        self.children.push(node);
+        self
+    }
+
+    pub fn children<I>(&mut self, nodes: I) -> &mut Self
+    where
+        I: IntoIterator<Item = Node>,
+    {
+        self.children.extend(nodes);
+        self
+    }
+
+    pub fn value(&mut self, value: Value) -> &mut Self {
+        self.value = Some(value);
+        self
+    }
+
+    pub fn build(&self) -> Node {
+        Node {
+            key: self.key.clone(),
+            attributes: self.attributes.clone(),
+            children: self.children.clone(),
+            value: self.value.clone(),
+            meta: Meta::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::node::Node;
+    use crate::value::Value;
+
+    use super::NodeBuilder;
+
+    #[test]
+    fn test_builder() {
+        let node = NodeBuilder::new("music")
+            .attr("id", "1")
+            .attr("type", "a")
+            .attr("id", "2")
+            .child(Node::with_value("bpm", Value::U16(200)))
+            .children(vec![Node::new("info"), Node::new("chart")])
+            .build();
+
+        let mut expected = Node::with_attrs("music", &[("id", "2"), ("type", "a")]);
+        expected.append_child(Node::with_value("bpm", Value::U16(200)));
+        expected.append_child(Node::new("info"));
+        expected.append_child(Node::new("chart"));
+        assert_eq!(node, expected);
+
+        let node = Node::builder("id").value(Value::U32(5)).build();
+        assert_eq!(node, Node::with_value("id", Value::U32(5)));
+    }
+}
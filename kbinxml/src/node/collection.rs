@@ -1,10 +1,18 @@
+use std::borrow::Cow;
 use std::collections::VecDeque;
 use std::fmt;
 use std::iter::{FromIterator, Iterator};
+use std::mem;
 
+use crate::accounting::Accountant;
+use crate::compression_type::CompressionType;
 use crate::error::KbinError;
-use crate::node::{Node, NodeDefinition};
+use crate::filter::{apply_attribute_filters, apply_filters, child_paths, DerivedNode};
+use crate::node::{Key, Markup, NameMatch, Node, NodeCursor, NodeData, NodeDefinition};
 use crate::node_types::StandardType;
+use crate::options::{AttributeNamePolicy, Options};
+use crate::query::{Query, Selected};
+use crate::size_hint::{estimate, SizeEstimate};
 use crate::value::Value;
 
 fn parse_index(s: &str) -> Option<usize> {
@@ -14,12 +22,106 @@ fn parse_index(s: &str) -> Option<usize> {
     s.parse().ok()
 }
 
+/// Charge the `len` bytes allocated for the node at `path` to `accountant`.
+fn charge(accountant: &mut Accountant, len: usize, path: &str) -> Result<(), KbinError> {
+    accountant
+        .allocate(len)
+        .map_err(|needed| KbinError::MemoryBudget {
+            budget: accountant.budget().unwrap_or_default(),
+            needed,
+            path: path.to_owned(),
+        })
+}
+
+/// The bytes allocated for a node converted from `def`, its name and value.
+fn node_size(node: &Node, def: &NodeDefinition) -> usize {
+    let value_len = def.value_bytes().map_or(0, <[u8]>::len);
+    mem::size_of::<Node>() + node.key().len() + value_len
+}
+
+/// Checks a name against the XML name rules, allowing any alphabetic or
+/// alphanumeric character where the rules allow letters or digits.
+pub(crate) fn is_xml_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' || c == ':' => {},
+        _ => return false,
+    };
+
+    chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | ':' | '-' | '.'))
+}
+
+fn sanitize_xml_name(name: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '_' | ':' | '-' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    if !sanitized.starts_with(|c: char| c.is_alphabetic() || c == '_' || c == ':') {
+        sanitized.insert(0, '_');
+    }
+
+    sanitized
+}
+
+/// Apply the control character policy of `options` to a decoded string.
+fn check_string<F>(options: &Options, text: String, path: F) -> Result<String, KbinError>
+where
+    F: FnOnce() -> String,
+{
+    match options.control_characters.apply(&text) {
+        Some(Cow::Borrowed(_)) => Ok(text),
+        Some(Cow::Owned(replaced)) => Ok(replaced),
+        None => Err(KbinError::ControlCharacter { path: path() }),
+    }
+}
+
+/// Compare the value of a derived node of `node` with the computed one.
+fn check_derived(derived: &DerivedNode, node: &Node, path: &str) -> Result<(), KbinError> {
+    let path = || format!("{}/{}", path, derived.name());
+    let expected = derived.compute(node).map_err(|source| KbinError::Filter {
+        path: path(),
+        source,
+    })?;
+    let actual = node
+        .children()
+        .iter()
+        .find(|child| child.key() == derived.name())
+        .and_then(Node::value);
+
+    if actual == Some(&expected) {
+        Ok(())
+    } else {
+        Err(KbinError::DerivedMismatch {
+            path: path(),
+            expected,
+            actual: actual.cloned(),
+        })
+    }
+}
+
+/// An attribute renamed by `AttributeNamePolicy::Sanitize`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SanitizedName {
+    /// The path of the node the attribute belongs to.
+    pub path: String,
+    pub original: String,
+    pub sanitized: String,
+}
+
 /// A collection of node definitions (`NodeDefinition`)
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct NodeCollection {
     base: NodeDefinition,
     attributes: VecDeque<NodeDefinition>,
     children: VecDeque<NodeCollection>,
+    markup: Vec<Markup>,
 }
 
 impl NodeCollection {
@@ -28,6 +130,7 @@ impl NodeCollection {
             base,
             attributes: VecDeque::with_capacity(0),
             children: VecDeque::with_capacity(0),
+            markup: Vec::new(),
         }
     }
 
@@ -36,6 +139,7 @@ impl NodeCollection {
             base,
             attributes,
             children: VecDeque::with_capacity(0),
+            markup: Vec::new(),
         }
     }
 
@@ -61,6 +165,7 @@ impl NodeCollection {
             base,
             attributes,
             children,
+            markup: Vec::new(),
         })
     }
 
@@ -94,24 +199,179 @@ impl NodeCollection {
         &mut self.children
     }
 
+    /// The comments and processing instructions read from text XML inside
+    /// this collection, see `Node::markup`.
+    #[inline]
+    pub fn markup(&self) -> &[Markup] {
+        &self.markup
+    }
+
+    #[inline]
+    pub fn markup_mut(&mut self) -> &mut Vec<Markup> {
+        &mut self.markup
+    }
+
+    /// The compression of the names in this collection, or `None` if the
+    /// base node has no name. Collections read from text XML have
+    /// uncompressed names.
+    pub fn compression(&self) -> Option<CompressionType> {
+        match self.base.data() {
+            NodeData::Some {
+                key: Key::Compressed { .. },
+                ..
+            } => Some(CompressionType::Compressed),
+            NodeData::Some {
+                key: Key::Uncompressed { .. },
+                ..
+            } => Some(CompressionType::Uncompressed),
+            NodeData::None => None,
+        }
+    }
+
+    /// Compute the node and data buffer sizes of this collection when
+    /// encoded with the default options, without encoding it.
+    #[inline]
+    pub fn encoded_size_hint(&self) -> SizeEstimate {
+        estimate(self, &Options::default())
+    }
+
+    /// Compute the node and data buffer sizes of this collection when
+    /// encoded with `options`, without encoding it.
+    #[inline]
+    pub fn encoded_size_hint_with_options(&self, options: &Options) -> SizeEstimate {
+        estimate(self, options)
+    }
+
+    /// Create a cursor positioned at this collection.
+    #[inline]
+    pub fn cursor(&self) -> NodeCursor<'_> {
+        NodeCursor::new(self)
+    }
+
+    #[inline]
     pub fn as_node(&self) -> Result<Node, KbinError> {
+        self.as_node_with_options(&Options::default())
+    }
+
+    #[inline]
+    pub fn as_node_with_options(&self, options: &Options) -> Result<Node, KbinError> {
+        self.as_node_with_report(options).map(|(node, _)| node)
+    }
+
+    /// Convert to a `Node` tree, also returning the attributes renamed by
+    /// `AttributeNamePolicy::Sanitize`.
+    ///
+    /// The names and values of the tree are counted against
+    /// `OptionsBuilder::memory_budget`.
+    pub fn as_node_with_report(
+        &self,
+        options: &Options,
+    ) -> Result<(Node, Vec<SanitizedName>), KbinError> {
+        let mut report = Vec::new();
+        let mut accountant = Accountant::new(options.memory_budget);
         let mut node = self.base.as_node()?;
+        let key = options.decode_name(node.key()).into_owned();
+        let path = format!("/{}", key);
+        node.set_key(key);
+        charge(&mut accountant, node_size(&node, &self.base), &path)?;
+
+        self.fill_node(&mut node, &path, options, &mut report, &mut accountant)?;
 
+        Ok((node, report))
+    }
+
+    fn fill_node(
+        &self,
+        node: &mut Node,
+        path: &str,
+        options: &Options,
+        report: &mut Vec<SanitizedName>,
+        accountant: &mut Accountant,
+    ) -> Result<(), KbinError> {
         for attr in &self.attributes {
             let key = attr.key()?.ok_or(KbinError::InvalidState)?;
+            let mut key = options.decode_name(&key).into_owned();
+
+            if !is_xml_name(&key) {
+                match options.attribute_names {
+                    AttributeNamePolicy::Accept => {},
+                    AttributeNamePolicy::Error => {
+                        return Err(KbinError::InvalidAttributeName {
+                            path: path.to_owned(),
+                            name: key,
+                        });
+                    },
+                    AttributeNamePolicy::Sanitize => {
+                        let sanitized = sanitize_xml_name(&key);
+                        report.push(SanitizedName {
+                            path: path.to_owned(),
+                            original: key,
+                            sanitized: sanitized.clone(),
+                        });
+                        key = sanitized;
+                    },
+                };
+            }
 
             if let Value::Attribute(value) = attr.value()? {
+                let attr_path = || format!("{}/@{}", path, key);
+                let mut value = check_string(options, value, attr_path)?;
+                if !options.decode_filters.is_empty() {
+                    let path = attr_path();
+                    value = apply_attribute_filters(&options.decode_filters, &path, value)
+                        .map_err(|source| KbinError::Filter { path, source })?;
+                }
+                let len = 2 * mem::size_of::<String>() + key.len() + value.len();
+                charge(accountant, len, path)?;
                 node.set_attr(key, value);
             } else {
                 return Err(KbinError::InvalidState);
             }
         }
+        if let Some(Value::String(text)) = node.value_mut() {
+            *text = check_string(options, mem::take(text), || path.to_owned())?;
+        }
+        if !options.decode_filters.is_empty() {
+            if let Some(value) = node.value_mut() {
+                let taken = mem::replace(value, Value::Boolean(false));
+                *value = apply_filters(&options.decode_filters, path, taken).map_err(|source| {
+                    KbinError::Filter {
+                        path: path.to_owned(),
+                        source,
+                    }
+                })?;
+            }
+        }
 
+        let mut children = Vec::with_capacity(self.children.len());
         for child in &self.children {
-            node.append_child(child.as_node()?);
+            let mut child_node = child.base.as_node()?;
+            let key = options.decode_name(child_node.key()).into_owned();
+            child_node.set_key(key);
+            charge(accountant, node_size(&child_node, &child.base), path)?;
+            children.push(child_node);
+        }
+
+        let paths = child_paths(path, children.iter().map(Node::key));
+        for ((child, child_node), path) in self.children.iter().zip(children.iter_mut()).zip(paths)
+        {
+            child.fill_node(child_node, &path, options, report, accountant)?;
         }
 
-        Ok(node)
+        for child in children {
+            node.append_child(child);
+        }
+        for markup in &self.markup {
+            node.push_markup(markup.clone());
+        }
+
+        for derived in &options.derived_nodes {
+            if derived.is_parent(path) {
+                check_derived(derived, node, path)?;
+            }
+        }
+
+        Ok(())
     }
 
     pub fn pointer<'a>(&'a self, pointer: &[&str]) -> Option<&'a NodeCollection> {
@@ -143,6 +403,36 @@ impl NodeCollection {
         }
         Some(target)
     }
+
+    /// Like `pointer`, comparing names with `name_match`.
+    ///
+    /// Children with names that fail to decode never match.
+    pub fn pointer_matching<'a>(
+        &'a self,
+        pointer: &[&str],
+        name_match: NameMatch,
+    ) -> Option<&'a NodeCollection> {
+        let mut target = self;
+
+        for token in pointer {
+            let target_opt = if let Some(index) = parse_index(token) {
+                target.children().get(index)
+            } else {
+                target.children().iter().find(|child| {
+                    matches!(child.base().key(), Ok(Some(key)) if name_match.matches(&key, token))
+                })
+            };
+            target = target_opt?;
+        }
+
+        Some(target)
+    }
+
+    /// Select the nodes or attributes matching a path query, e.g.
+    /// `music/info/@title` or `music[0]/*`, see `Query`.
+    pub fn select(&self, query: &str) -> Result<Vec<Selected<'_>>, KbinError> {
+        Ok(Query::parse(query)?.select_collection(self))
+    }
 }
 
 impl FromIterator<NodeDefinition> for Option<NodeCollection> {
@@ -201,3 +491,73 @@ impl fmt::Display for NodeCollection {
         d.finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::mem;
+
+    use bytes::Bytes;
+
+    use crate::error::KbinError;
+    use crate::node::Node;
+    use crate::options::{AttributeNamePolicy, Options};
+    use crate::value::Value;
+
+    use super::{NodeCollection, SanitizedName};
+
+    #[test]
+    fn test_attribute_names() {
+        // Sixbit names can start with a digit, XML names cannot
+        let node = Node::with_nodes("root", [Node::with_attrs("entry", &[
+            ("id", "1"),
+            ("1st_id", "2"),
+        ])]);
+        let data = crate::to_binary(&node).expect("Failed to encode");
+        let (collection, _) = crate::from_slice(&data).expect("Failed to decode");
+        let convert = |policy| {
+            let options = Options::builder().attribute_names(policy).build();
+            collection.as_node_with_report(&options)
+        };
+
+        let (accepted, report) = convert(AttributeNamePolicy::Accept).expect("Failed to convert");
+        assert_eq!(accepted, node);
+        assert!(report.is_empty());
+
+        match convert(AttributeNamePolicy::Error) {
+            Err(KbinError::InvalidAttributeName { path, name }) => {
+                assert_eq!((path.as_str(), name.as_str()), ("/root/entry", "1st_id"));
+            },
+            result => panic!("Unexpected result: {:?}", result),
+        };
+
+        let (sanitized, report) =
+            convert(AttributeNamePolicy::Sanitize).expect("Failed to convert");
+        let attributes = sanitized.children()[0].attributes();
+        assert_eq!(attributes.keys().collect::<Vec<_>>(), ["id", "_1st_id"]);
+        assert_eq!(report, [SanitizedName {
+            path: "/root/entry".into(),
+            original: "1st_id".into(),
+            sanitized: "_1st_id".into(),
+        }]);
+    }
+
+    #[test]
+    fn test_memory_budget() {
+        let text = "kbin".repeat(256);
+        let node = Node::with_nodes("root", [Node::with_value("text", Value::String(text))]);
+        let data = Bytes::from(crate::to_binary(&node).expect("Failed to encode"));
+
+        // The value stays in the input while reading, but is copied out when
+        // converting to a `Node`
+        let budget = 2 * mem::size_of::<NodeCollection>();
+        let options = Options::builder().memory_budget(budget).build();
+        let (collection, _) =
+            crate::from_binary_with_options(options.clone(), data).expect("Failed to read");
+        assert_eq!(collection.as_node().expect("Failed to convert"), node);
+
+        match collection.as_node_with_options(&options) {
+            Err(KbinError::MemoryBudget { path, .. }) => assert_eq!(path, "/root"),
+            result => panic!("Unexpected result: {:?}", result),
+        };
+    }
+}
@@ -0,0 +1,224 @@
+use crate::error::KbinError;
+use crate::node::NodeCollection;
+
+/// A position within a `NodeCollection` tree.
+///
+/// A cursor only borrows the root collection and records the child indices
+/// leading to the current node, so navigation methods return new cursors
+/// instead of mutating borrowed state. Cursors are cheap to clone and can be
+/// kept around to remember a selection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeCursor<'a> {
+    root: &'a NodeCollection,
+    indices: Vec<usize>,
+}
+
+impl<'a> NodeCursor<'a> {
+    pub fn new(root: &'a NodeCollection) -> Self {
+        Self {
+            root,
+            indices: Vec::new(),
+        }
+    }
+
+    /// A cursor at the node reached by following `indices` from `root`,
+    /// which must be valid.
+    pub(crate) fn with_indices(root: &'a NodeCollection, indices: Vec<usize>) -> Self {
+        Self { root, indices }
+    }
+
+    #[inline]
+    pub fn root(&self) -> &'a NodeCollection {
+        self.root
+    }
+
+    /// The child indices leading from the root to the current node.
+    #[inline]
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+
+    #[inline]
+    pub fn depth(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn node(&self) -> &'a NodeCollection {
+        self.indices.iter().fold(self.root, |node, &index| {
+            // Cursors are only constructed from valid indices.
+            &node.children()[index]
+        })
+    }
+
+    fn with_last(&self, index: usize) -> Self {
+        let mut indices = self.indices.clone();
+        if let Some(last) = indices.last_mut() {
+            *last = index;
+        }
+
+        Self {
+            root: self.root,
+            indices,
+        }
+    }
+
+    pub fn parent(&self) -> Option<Self> {
+        let (_, indices) = self.indices.split_last()?;
+
+        Some(Self {
+            root: self.root,
+            indices: indices.to_vec(),
+        })
+    }
+
+    pub fn child(&self, index: usize) -> Option<Self> {
+        if index >= self.node().children().len() {
+            return None;
+        }
+
+        let mut indices = self.indices.clone();
+        indices.push(index);
+
+        Some(Self {
+            root: self.root,
+            indices,
+        })
+    }
+
+    #[inline]
+    pub fn first_child(&self) -> Option<Self> {
+        self.child(0)
+    }
+
+    pub fn last_child(&self) -> Option<Self> {
+        let len = self.node().children().len();
+        self.child(len.checked_sub(1)?)
+    }
+
+    pub fn next_sibling(&self) -> Option<Self> {
+        let parent = self.parent()?;
+        let index = self.indices.last()? + 1;
+
+        if index < parent.node().children().len() {
+            Some(self.with_last(index))
+        } else {
+            None
+        }
+    }
+
+    pub fn prev_sibling(&self) -> Option<Self> {
+        let index = self.indices.last()?.checked_sub(1)?;
+
+        Some(self.with_last(index))
+    }
+
+    /// The location of the current node as a slash separated path of node
+    /// names, e.g. `/root/music[3]/title`.
+    ///
+    /// A zero-based index is appended to a name when its parent has more
+    /// than one child with that name.
+    ///
+    /// Paths built while reading a document, such as those of `DiffEntry`,
+    /// leave out `[0]` as later siblings are not known yet, e.g.
+    /// `/root/music/title` for the path above with index 0. Both formats name
+    /// the same node.
+    pub fn path(&self) -> Result<String, KbinError> {
+        let mut path = String::new();
+        let mut node = self.root;
+
+        path.push('/');
+        path.push_str(&node_name(node)?);
+
+        for &index in &self.indices {
+            let child = &node.children()[index];
+            let name = node_name(child)?;
+
+            let mut position = 0;
+            let mut total = 0;
+            for (i, sibling) in node.children().iter().enumerate() {
+                if node_name(sibling)? == name {
+                    if i < index {
+                        position += 1;
+                    }
+                    total += 1;
+                }
+            }
+
+            path.push('/');
+            path.push_str(&name);
+            if total > 1 {
+                path.push_str(&format!("[{}]", position));
+            }
+
+            node = child;
+        }
+
+        Ok(path)
+    }
+}
+
+/// Whether two paths name the same node, comparing them segment by segment
+/// with `name[0]` equal to `name`, see `NodeCursor::path`.
+pub(crate) fn same_path(a: &str, b: &str) -> bool {
+    fn segment(segment: &str) -> &str {
+        segment.strip_suffix("[0]").unwrap_or(segment)
+    }
+
+    a.split('/').map(segment).eq(b.split('/').map(segment))
+}
+
+fn node_name(node: &NodeCollection) -> Result<String, KbinError> {
+    Ok(node.base().key()?.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::node::Node;
+    use crate::value::Value;
+
+    use super::{same_path, NodeCursor};
+
+    #[test]
+    fn test_navigation() {
+        let root = Node::with_nodes("root", [
+            Node::with_value("version", Value::U32(1)),
+            Node::with_nodes("music", [Node::new("title")]),
+            Node::new("music"),
+        ]);
+        let input = crate::to_binary(&root).expect("Failed to encode");
+        let (collection, _) = crate::from_slice(&input).expect("Failed to decode");
+        let path = |cursor: &NodeCursor| cursor.path().expect("Failed to build path");
+
+        let root = collection.cursor();
+        assert_eq!(path(&root), "/root");
+        assert_eq!(root.parent(), None);
+        assert_eq!(root.next_sibling(), None);
+        assert_eq!(root.prev_sibling(), None);
+        assert_eq!(root.child(3), None);
+
+        let version = root.first_child().expect("Missing first child");
+        assert_eq!(path(&version), "/root/version");
+        assert_eq!(version.prev_sibling(), None);
+        assert_eq!(version.parent().as_ref(), Some(&root));
+
+        let first = version.next_sibling().expect("Missing sibling");
+        assert_eq!(path(&first), "/root/music[0]");
+        let title = first.first_child().expect("Missing title");
+        assert_eq!(path(&title), "/root/music[0]/title");
+        assert_eq!(title.indices(), &[1, 0]);
+        assert_eq!(title.next_sibling(), None);
+        assert_eq!(title.parent().as_ref(), Some(&first));
+
+        let last = first.next_sibling().expect("Missing sibling");
+        assert_eq!(path(&last), "/root/music[1]");
+        assert_eq!(last.next_sibling(), None);
+        assert_eq!(last.first_child(), None);
+        assert_eq!(root.last_child().as_ref(), Some(&last));
+        assert_eq!(last.prev_sibling().as_ref(), Some(&first));
+
+        // Paths built while reading leave out `[0]`
+        assert!(same_path(&path(&title), "/root/music/title"));
+        assert!(!same_path(&path(&last), "/root/music"));
+        assert!(!same_path(&path(&title), "/root/music"));
+    }
+}
@@ -8,7 +8,7 @@ use crate::error::KbinError;
 use crate::node::Node;
 use crate::node_types::StandardType;
 use crate::sixbit::{Sixbit, SixbitSize};
-use crate::value::Value;
+use crate::value::{Value, ValueRef};
 
 #[derive(Clone, Eq)]
 pub enum Key {
@@ -29,10 +29,13 @@ pub struct NodeDefinition {
     pub is_array: bool,
 
     data: NodeData,
+
+    /// The raw type byte of a node of unknown type, see `Value::Unknown`.
+    unknown_type: Option<u8>,
 }
 
 impl Key {
-    fn to_string(&self) -> Result<String, KbinError> {
+    pub(crate) fn to_string(&self) -> Result<String, KbinError> {
         match self {
             Key::Compressed { ref size, ref data } => {
                 Sixbit::unpack(data, *size).map_err(Into::into)
@@ -51,6 +54,7 @@ impl NodeDefinition {
             node_type,
             is_array,
             data: NodeData::None,
+            unknown_type: None,
         }
     }
 
@@ -65,9 +69,34 @@ impl NodeDefinition {
             node_type,
             is_array,
             data,
+            unknown_type: None,
+        }
+    }
+
+    /// A node of unknown type holding the raw `value_data` read for it. It
+    /// acts as a `Binary` node except for its value.
+    pub(crate) fn unknown(
+        encoding: EncodingType,
+        type_byte: u8,
+        key: Key,
+        value_data: Bytes,
+    ) -> Self {
+        Self {
+            encoding,
+            node_type: StandardType::Binary,
+            is_array: false,
+            data: NodeData::Some { key, value_data },
+            unknown_type: Some(type_byte),
         }
     }
 
+    /// The raw type byte of a node read with an unknown type, including the
+    /// array flag.
+    #[inline]
+    pub fn unknown_type(&self) -> Option<u8> {
+        self.unknown_type
+    }
+
     #[inline]
     pub fn encoding(&self) -> EncodingType {
         self.encoding
@@ -97,6 +126,12 @@ impl NodeDefinition {
 
     pub fn value(&self) -> Result<Value, KbinError> {
         match (self.node_type, &self.data) {
+            (_, NodeData::Some { ref value_data, .. }) if self.unknown_type.is_some() => {
+                Ok(Value::Unknown {
+                    id: self.unknown_type.unwrap_or_default(),
+                    bytes: value_data.to_vec(),
+                })
+            },
             (StandardType::Attribute, NodeData::Some { ref value_data, .. }) => {
                 let data = strip_trailing_null_bytes(value_data);
                 let value = self.encoding.decode_bytes(data)?;
@@ -118,6 +153,40 @@ impl NodeDefinition {
         }
     }
 
+    /// Like `value`, borrowing text and binary data where possible instead of
+    /// copying it.
+    pub fn value_ref(&self) -> Result<ValueRef<'_>, KbinError> {
+        let value_data = match &self.data {
+            NodeData::Some { value_data, .. } => value_data,
+            NodeData::None => {
+                return Err(KbinError::InvalidNodeType {
+                    node_type: self.node_type,
+                })
+            },
+        };
+
+        if self.unknown_type.is_some() {
+            return self.value().map(ValueRef::Scalar);
+        }
+
+        match self.node_type {
+            StandardType::Attribute => {
+                let data = strip_trailing_null_bytes(value_data);
+                Ok(ValueRef::Attribute(self.encoding.decode_borrowed(data)?))
+            },
+            StandardType::String => {
+                let data = strip_trailing_null_bytes(value_data);
+                Ok(ValueRef::String(self.encoding.decode_borrowed(data)?))
+            },
+            StandardType::Binary if !self.is_array => Ok(ValueRef::Binary(value_data)),
+            node_type if self.is_array => Ok(ValueRef::Array {
+                node_type,
+                data: value_data,
+            }),
+            _ => self.value().map(ValueRef::Scalar),
+        }
+    }
+
     pub fn value_bytes(&self) -> Option<&[u8]> {
         match self.data {
             NodeData::Some { ref value_data, .. } => Some(value_data),
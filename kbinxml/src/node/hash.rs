@@ -0,0 +1,185 @@
+use crate::fingerprint::fnv1a;
+use crate::node::Node;
+use crate::value::Value;
+
+/// The bytes a value is compared and hashed by, its type followed by its
+/// encoded data.
+fn value_bytes(value: &Value) -> Vec<u8> {
+    // Unknown type bytes never match the id of a known type.
+    let id = match value {
+        Value::Unknown { id, .. } => *id,
+        value => value.standard_type().id,
+    };
+    let mut output = vec![id];
+    match value {
+        Value::String(text) | Value::Attribute(text) => output.extend_from_slice(text.as_bytes()),
+        // Every other value encodes without error.
+        value => value.to_bytes_into(&mut output).unwrap_or_default(),
+    };
+
+    output
+}
+
+fn values_eq(a: Option<&Value>, b: Option<&Value>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => value_bytes(a) == value_bytes(b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Compare everything but the children of two nodes.
+fn shallow_eq(a: &Node, b: &Node) -> bool {
+    a.key == b.key &&
+        a.children.len() == b.children.len() &&
+        a.attributes == b.attributes &&
+        values_eq(a.value.as_ref(), b.value.as_ref())
+}
+
+/// The hash of everything but the children of a node. Attributes are
+/// combined so their order does not matter.
+fn shallow_hash(node: &Node) -> u64 {
+    let value = node.value.as_ref().map(value_bytes);
+    let own = fnv1a(
+        node.key.bytes().chain(Some(0)).chain(
+            value
+                .iter()
+                .flat_map(|value| Some(1).into_iter().chain(value.iter().copied())),
+        ),
+    );
+    let attributes = node
+        .attributes
+        .iter()
+        .map(|(key, value)| fnv1a(key.bytes().chain(Some(0)).chain(value.bytes())))
+        .fold(0u64, u64::wrapping_add);
+
+    fnv1a(
+        own.to_be_bytes()
+            .iter()
+            .chain(&attributes.to_be_bytes())
+            .copied(),
+    )
+}
+
+fn combine<I>(hash: u64, children: I) -> u64
+where
+    I: IntoIterator<Item = u64>,
+{
+    children.into_iter().fold(hash, |hash, child| {
+        fnv1a(
+            hash.to_be_bytes()
+                .iter()
+                .chain(&child.to_be_bytes())
+                .copied(),
+        )
+    })
+}
+
+impl Node {
+    /// A hash of the key, attributes, value and children of this node, which
+    /// is the same for nodes that are `structural_eq`.
+    pub fn content_hash(&self) -> u64 {
+        combine(
+            shallow_hash(self),
+            self.children.iter().map(Node::content_hash),
+        )
+    }
+
+    /// Do both trees have the same keys, attributes, values and children?
+    ///
+    /// Unlike `==`, values are compared by their type and encoded bytes, so
+    /// `NaN` floats with the same bits are equal. Attribute order and
+    /// metadata are ignored.
+    pub fn structural_eq(&self, other: &Node) -> bool {
+        std::ptr::eq(self, other) ||
+            (shallow_eq(self, other) &&
+                self.children
+                    .iter()
+                    .zip(&other.children)
+                    .all(|(a, b)| a.structural_eq(b)))
+    }
+}
+
+/// A `Node` tree with the content hash of every subtree computed once, so
+/// repeated comparisons can skip subtrees whose hashes differ.
+#[derive(Clone, Debug)]
+pub struct HashedNode<'a> {
+    node: &'a Node,
+    hash: u64,
+    children: Vec<HashedNode<'a>>,
+}
+
+impl<'a> HashedNode<'a> {
+    pub fn new(node: &'a Node) -> Self {
+        let children: Vec<_> = node.children.iter().map(HashedNode::new).collect();
+        let hash = combine(shallow_hash(node), children.iter().map(|child| child.hash));
+
+        Self {
+            node,
+            hash,
+            children,
+        }
+    }
+
+    #[inline]
+    pub fn node(&self) -> &'a Node {
+        self.node
+    }
+
+    /// The same hash as `Node::content_hash`.
+    #[inline]
+    pub fn content_hash(&self) -> u64 {
+        self.hash
+    }
+
+    #[inline]
+    pub fn children(&self) -> &[HashedNode<'a>] {
+        &self.children
+    }
+
+    /// Like `Node::structural_eq`, returning early when the hashes of a pair
+    /// of subtrees differ.
+    pub fn structural_eq(&self, other: &HashedNode) -> bool {
+        self.hash == other.hash &&
+            (std::ptr::eq(self.node, other.node) ||
+                (shallow_eq(self.node, other.node) &&
+                    self.children
+                        .iter()
+                        .zip(&other.children)
+                        .all(|(a, b)| a.structural_eq(b))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::node::Node;
+    use crate::value::Value;
+
+    use super::HashedNode;
+
+    #[test]
+    fn test_structural_eq() {
+        let a = Node::with_nodes("root", vec![
+            Node::with_attrs("entry", &[("id", "1"), ("name", "a")]),
+            Node::with_value("rate", Value::Float(f32::NAN)),
+        ]);
+        let mut b = Node::with_nodes("root", vec![
+            Node::with_attrs("entry", &[("name", "a"), ("id", "1")]),
+            Node::with_value("rate", Value::Float(f32::NAN)),
+        ]);
+        assert_ne!(a, b);
+        assert!(a.structural_eq(&b));
+        assert_eq!(a.content_hash(), b.content_hash());
+        assert!(HashedNode::new(&a).structural_eq(&HashedNode::new(&b)));
+
+        b.children_mut()[0].set_attr("id", "2");
+        assert!(!a.structural_eq(&b));
+        assert_ne!(a.content_hash(), b.content_hash());
+
+        let hashed = HashedNode::new(&b);
+        assert_eq!(hashed.content_hash(), b.content_hash());
+        assert!(!HashedNode::new(&a).structural_eq(&hashed));
+        assert!(hashed.children()[1].structural_eq(&HashedNode::new(&a).children()[1]));
+        assert!(!a.structural_eq(&Node::with_value("root", Value::U8(0))));
+    }
+}
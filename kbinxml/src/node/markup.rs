@@ -0,0 +1,47 @@
+use crate::node::Node;
+
+/// The kind of a `Markup` item.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MarkupKind {
+    Comment,
+    ProcessingInstruction,
+}
+
+/// A comment or processing instruction read from text XML, kept so it can be
+/// written back to text XML. Binary XML has no place for it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Markup {
+    pub kind: MarkupKind,
+
+    /// The text between the markup delimiters, as escaped in the document.
+    pub text: String,
+
+    /// The number of children of the node before the markup.
+    pub position: usize,
+}
+
+/// The `meta` slot holding the markup of a node.
+#[derive(Clone, Default)]
+struct MarkupList(Vec<Markup>);
+
+impl Node {
+    /// The comments and processing instructions inside this node, in
+    /// document order.
+    ///
+    /// Positions are not updated when children are added or removed, markup
+    /// past the last child is written after it.
+    pub fn markup(&self) -> &[Markup] {
+        self.meta::<MarkupList>().map_or(&[], |list| &list.0)
+    }
+
+    /// Add markup after the markup already in this node. It is kept in a
+    /// `meta` slot, so it is ignored when comparing nodes.
+    pub fn push_markup(&mut self, markup: Markup) {
+        match self.meta_mut::<MarkupList>() {
+            Some(list) => list.0.push(markup),
+            None => {
+                self.set_meta(MarkupList(vec![markup]));
+            },
+        };
+    }
+}
@@ -0,0 +1,372 @@
+use crate::error::KbinError;
+use crate::node::Node;
+use crate::patch::PatchError;
+use crate::value::Value;
+
+/// A change to a `Node` tree, see `NodePatch`.
+///
+/// Paths start with the name of the root node. A node that follows a
+/// sibling with the same name gets its zero-based index among those siblings
+/// appended, e.g. `/root/music[1]`, like the paths of `DiffEntry`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NodeChange {
+    /// Remove a node and its children.
+    Remove { path: String },
+
+    /// Insert a node after the last sibling with the same name, or after
+    /// the last child if there is none. The index in `path` must be the
+    /// number of those siblings.
+    Add { path: String, node: Node },
+
+    /// Replace the value of a node. `None` removes it.
+    SetValue { path: String, value: Option<Value> },
+
+    /// Replace the value of an attribute. `None` removes it.
+    SetAttribute {
+        path: String,
+        name: String,
+        value: Option<String>,
+    },
+}
+
+/// A list of changes applied in order by `Node::apply_patch`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NodePatch {
+    changes: Vec<NodeChange>,
+}
+
+/// Split a path segment into the node name and its index among the siblings
+/// with that name.
+fn split_segment(segment: &str) -> Option<(&str, usize)> {
+    match segment.strip_suffix(']') {
+        Some(rest) => {
+            let (name, index) = rest.split_once('[')?;
+            Some((name, index.parse().ok()?))
+        },
+        None => Some((segment, 0)),
+    }
+}
+
+/// The position of the `index`-th child named `name`.
+fn position(node: &Node, name: &str, index: usize) -> Option<usize> {
+    node.children
+        .iter()
+        .enumerate()
+        .filter(|(_, child)| child.key == name)
+        .nth(index)
+        .map(|(position, _)| position)
+}
+
+/// The path of the `index`-th child named `name` of the node at `parent`.
+pub(super) fn child_path(parent: &str, name: &str, index: usize) -> String {
+    match index {
+        0 => format!("{}/{}", parent, name),
+        i => format!("{}/{}[{}]", parent, name, i),
+    }
+}
+
+/// The children of `node` with their index among the siblings with the same
+/// name.
+pub(super) fn indexed_children(node: &Node) -> Vec<(usize, &Node)> {
+    let mut seen = Vec::<&str>::new();
+
+    node.children
+        .iter()
+        .map(|child| {
+            let index = seen.iter().filter(|&&name| name == child.key).count();
+            seen.push(&child.key);
+            (index, child)
+        })
+        .collect()
+}
+
+fn diff_nodes(path: &str, a: &Node, b: &Node, changes: &mut Vec<NodeChange>) {
+    if a.value != b.value {
+        changes.push(NodeChange::SetValue {
+            path: path.to_owned(),
+            value: b.value.clone(),
+        });
+    }
+    for (name, value) in &a.attributes {
+        if b.attributes.get(name) != Some(value) {
+            changes.push(NodeChange::SetAttribute {
+                path: path.to_owned(),
+                name: name.clone(),
+                value: b.attributes.get(name).cloned(),
+            });
+        }
+    }
+    for (name, value) in &b.attributes {
+        if !a.attributes.contains_key(name) {
+            changes.push(NodeChange::SetAttribute {
+                path: path.to_owned(),
+                name: name.clone(),
+                value: Some(value.clone()),
+            });
+        }
+    }
+
+    // Children only in `a` always come after the ones with the same name in
+    // both trees, remove them last first so the indices of the others stay
+    // the same.
+    let mut removed = Vec::new();
+    for (index, child) in indexed_children(a) {
+        let child_path = child_path(path, &child.key, index);
+        match position(b, &child.key, index) {
+            Some(other) => diff_nodes(&child_path, child, &b.children[other], changes),
+            None => removed.push(NodeChange::Remove { path: child_path }),
+        };
+    }
+    changes.extend(removed.into_iter().rev());
+
+    for (index, child) in indexed_children(b) {
+        if position(a, &child.key, index).is_none() {
+            changes.push(NodeChange::Add {
+                path: child_path(path, &child.key, index),
+                node: child.clone(),
+            });
+        }
+    }
+}
+
+impl NodePatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The changes that turn `a` into `b`, matching children by their name
+    /// and their index among the siblings with that name.
+    ///
+    /// The names of the root nodes are not compared, paths use the name of
+    /// the root of `a`.
+    pub fn diff(a: &Node, b: &Node) -> Self {
+        let mut changes = Vec::new();
+        diff_nodes(&format!("/{}", a.key), a, b, &mut changes);
+
+        Self { changes }
+    }
+
+    pub fn push(&mut self, change: NodeChange) -> &mut Self {
+        self.changes.push(change);
+        self
+    }
+
+    #[inline]
+    pub fn changes(&self) -> &[NodeChange] {
+        &self.changes
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl From<Vec<NodeChange>> for NodePatch {
+    fn from(changes: Vec<NodeChange>) -> Self {
+        Self { changes }
+    }
+}
+
+impl Node {
+    /// Find the node at `path`, see `NodeChange`.
+    fn resolve_mut(&mut self, path: &str) -> Result<&mut Node, PatchError> {
+        let not_found = || PatchError::NotFound {
+            path: path.to_owned(),
+        };
+        let mut segments = path.strip_prefix('/').ok_or_else(not_found)?.split('/');
+
+        match segments.next().and_then(split_segment) {
+            Some((name, 0)) if name == self.key => {},
+            _ => return Err(not_found()),
+        };
+        let mut target = self;
+        for segment in segments {
+            let (name, index) = split_segment(segment).ok_or_else(not_found)?;
+            let position = position(target, name, index).ok_or_else(not_found)?;
+            target = &mut target.children[position];
+        }
+
+        Ok(target)
+    }
+
+    /// Find the parent of the node at `path` and the name and index of the
+    /// node among its siblings.
+    fn resolve_parent_mut<'a>(
+        &mut self,
+        path: &'a str,
+    ) -> Result<(&mut Node, &'a str, usize), PatchError> {
+        let (parent, segment) = path
+            .rsplit_once('/')
+            .filter(|(parent, _)| !parent.is_empty())
+            .ok_or_else(|| PatchError::InvalidPath {
+                path: path.to_owned(),
+            })?;
+        let (name, index) = split_segment(segment).ok_or_else(|| PatchError::InvalidPath {
+            path: path.to_owned(),
+        })?;
+
+        Ok((self.resolve_mut(parent)?, name, index))
+    }
+
+    fn apply_change(&mut self, change: &NodeChange) -> Result<(), PatchError> {
+        match change {
+            NodeChange::Remove { path } => {
+                let (parent, name, index) = self.resolve_parent_mut(path)?;
+                let position = position(parent, name, index).ok_or(PatchError::NotFound {
+                    path: path.to_owned(),
+                })?;
+                parent.children.remove(position);
+            },
+            NodeChange::Add { path, node } => {
+                let (parent, name, index) = self.resolve_parent_mut(path)?;
+                let count = parent.children.iter().filter(|c| c.key == name).count();
+                if name != node.key || index > count {
+                    return Err(PatchError::InvalidPath {
+                        path: path.to_owned(),
+                    });
+                }
+                if index < count {
+                    return Err(PatchError::Exists {
+                        path: path.to_owned(),
+                    });
+                }
+
+                let position = parent
+                    .children
+                    .iter()
+                    .rposition(|child| child.key == name)
+                    .map_or(parent.children.len(), |position| position + 1);
+                parent.children.insert(position, node.clone());
+            },
+            NodeChange::SetValue { path, value } => {
+                self.resolve_mut(path)?.value = value.clone();
+            },
+            NodeChange::SetAttribute { path, name, value } => {
+                let attributes = &mut self.resolve_mut(path)?.attributes;
+                match value {
+                    Some(value) => {
+                        attributes.insert(name.clone(), value.clone());
+                    },
+                    None => {
+                        attributes.shift_remove(name);
+                    },
+                };
+            },
+        };
+
+        Ok(())
+    }
+
+    /// Apply the changes of `patch` in order.
+    ///
+    /// Fails on the first change whose path does not resolve, leaving the
+    /// changes before it applied.
+    pub fn apply_patch(&mut self, patch: &NodePatch) -> Result<(), KbinError> {
+        for change in &patch.changes {
+            self.apply_change(change)?;
+        }
+
+        Ok(())
+    }
+
+    /// Merge `overlay` into this node, see `merge`.
+    pub fn merge(&mut self, overlay: &Node) {
+        self.merge_provenance(overlay);
+        if overlay.value.is_some() {
+            self.value = overlay.value.clone();
+        }
+        for (name, value) in &overlay.attributes {
+            self.attributes.insert(name.clone(), value.clone());
+        }
+
+        for (index, child) in indexed_children(overlay) {
+            match position(self, &child.key, index) {
+                Some(position) => self.children[position].merge(child),
+                None => {
+                    let position = self
+                        .children
+                        .iter()
+                        .rposition(|other| other.key == child.key)
+                        .map_or(self.children.len(), |position| position + 1);
+                    self.children.insert(position, child.clone());
+                },
+            };
+        }
+    }
+}
+
+/// Layer `overlay` on top of `base`.
+///
+/// The value and attributes of `overlay` win, a node without a value in
+/// `overlay` keeps the value of `base`. Children are matched by their name
+/// and their index among the siblings with that name, children only in
+/// `overlay` are added after their last sibling with the same name. The
+/// root keeps the name of `base`.
+pub fn merge(base: &Node, overlay: &Node) -> Node {
+    let mut merged = base.clone();
+    merged.merge(overlay);
+
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::node::Node;
+    use crate::patch::PatchError;
+    use crate::value::Value;
+
+    use super::{merge, NodeChange, NodePatch};
+
+    #[test]
+    fn test_patch_and_merge() {
+        let a = Node::with_nodes("root", vec![
+            Node::with_attrs("entry", &[("id", "1")]),
+            Node::with_value("rate", Value::U8(1)),
+            Node::with_attrs("entry", &[("id", "2")]),
+            Node::with_attrs("entry", &[("id", "3")]),
+            Node::new("old"),
+        ]);
+        let b = Node::with_nodes("root", vec![
+            Node::with_attrs("entry", &[("id", "1"), ("name", "a")]),
+            Node::with_value("rate", Value::U8(2)),
+            Node::new("entry"),
+            Node::new("new"),
+        ]);
+
+        let patch = NodePatch::diff(&a, &b);
+        assert_eq!(patch.changes().len(), 6);
+        let mut patched = a.clone();
+        patched.apply_patch(&patch).expect("Failed to apply patch");
+        assert_eq!(patched, b);
+        assert!(NodePatch::diff(&b, &patched).is_empty());
+
+        let mut patch = NodePatch::new();
+        patch.push(NodeChange::Add {
+            path: "/root/entry[1]".into(),
+            node: Node::new("entry"),
+        });
+        match a.clone().apply_patch(&patch) {
+            Err(crate::KbinError::Patch {
+                source: PatchError::Exists { path },
+            }) => assert_eq!(path, "/root/entry[1]"),
+            result => panic!("Unexpected result: {:?}", result),
+        };
+
+        let overlay = Node::with_nodes("config", vec![
+            Node::with_attrs("entry", &[("name", "a")]),
+            Node::with_value("rate", Value::U8(5)),
+            Node::new("entry"),
+            Node::new("entry"),
+            Node::with_attrs("entry", &[("id", "4")]),
+        ]);
+        let merged = merge(&a, &overlay);
+        assert_eq!(merged.key(), "root");
+        assert_eq!(merged.children()[0].attr("name"), Some("a"));
+        assert_eq!(merged.children()[0].attr("id"), Some("1"));
+        assert_eq!(merged.children()[1].value(), Some(&Value::U8(5)));
+        assert_eq!(merged.children()[3].attr("id"), Some("3"));
+        assert_eq!(merged.children()[4].attr("id"), Some("4"));
+        assert_eq!(merged.children()[5].key(), "old");
+    }
+}
@@ -0,0 +1,130 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+/// A value stored in a `Meta` slot.
+trait MetaValue: Any + Send + Sync {
+    fn clone_box(&self) -> Box<dyn MetaValue>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<T> MetaValue for T
+where
+    T: Any + Clone + Send + Sync,
+{
+    fn clone_box(&self) -> Box<dyn MetaValue> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+/// Application data attached to a node, with one slot per type.
+///
+/// Slots are cloned along with their node, and are ignored when comparing
+/// nodes.
+#[derive(Default)]
+pub(crate) struct Meta {
+    slots: HashMap<TypeId, Box<dyn MetaValue>>,
+}
+
+impl Meta {
+    pub(crate) fn get<T>(&self) -> Option<&T>
+    where
+        T: Any,
+    {
+        let value = self.slots.get(&TypeId::of::<T>())?;
+        (**value).as_any().downcast_ref()
+    }
+
+    pub(crate) fn get_mut<T>(&mut self) -> Option<&mut T>
+    where
+        T: Any,
+    {
+        let value = self.slots.get_mut(&TypeId::of::<T>())?;
+        (**value).as_any_mut().downcast_mut()
+    }
+
+    pub(crate) fn insert<T>(&mut self, value: T) -> Option<T>
+    where
+        T: Any + Clone + Send + Sync,
+    {
+        let previous = self.slots.insert(TypeId::of::<T>(), Box::new(value))?;
+
+        previous.into_any().downcast().ok().map(|value| *value)
+    }
+
+    pub(crate) fn remove<T>(&mut self) -> Option<T>
+    where
+        T: Any,
+    {
+        let value = self.slots.remove(&TypeId::of::<T>())?;
+
+        value.into_any().downcast().ok().map(|value| *value)
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.slots.clear();
+    }
+}
+
+impl Clone for Meta {
+    fn clone(&self) -> Self {
+        let slots = self
+            .slots
+            .iter()
+            .map(|(&type_id, value)| (type_id, (**value).clone_box()))
+            .collect();
+
+        Self { slots }
+    }
+}
+
+impl PartialEq for Meta {
+    #[inline]
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::node::Node;
+    use crate::value::Value;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Status(&'static str);
+
+    #[test]
+    fn test_meta_slots() {
+        let mut node = Node::with_value("id", Value::U32(1));
+        assert_eq!(node.meta::<Status>(), None);
+
+        assert_eq!(node.set_meta(Status("added")), None);
+        assert_eq!(node.set_meta(7u32), None);
+        assert_eq!(node.set_meta(Status("changed")), Some(Status("added")));
+        *node.meta_mut::<u32>().unwrap() += 1;
+
+        let copy = node.clone();
+        assert_eq!(copy.meta::<Status>(), Some(&Status("changed")));
+        assert_eq!(copy.meta::<u32>(), Some(&8));
+        assert_eq!(copy, Node::with_value("id", Value::U32(1)));
+
+        assert_eq!(node.remove_meta::<u32>(), Some(8));
+        assert_eq!(node.meta::<u32>(), None);
+        node.clear_meta();
+        assert_eq!(node.meta::<Status>(), None);
+        assert_eq!(copy.meta::<u32>(), Some(&8));
+    }
+}
@@ -1,3 +1,4 @@
+use std::any::Any;
 use std::fmt;
 use std::mem;
 
@@ -5,11 +6,30 @@ use indexmap::IndexMap;
 
 use crate::value::Value;
 
+mod builder;
 mod collection;
+mod cursor;
 mod definition;
-
-pub use self::collection::NodeCollection;
+mod hash;
+mod markup;
+mod merge;
+mod meta;
+mod name_match;
+mod provenance;
+
+pub use self::builder::NodeBuilder;
+pub use self::collection::{NodeCollection, SanitizedName};
+pub use self::cursor::NodeCursor;
 pub use self::definition::{Key, NodeData, NodeDefinition};
+pub use self::hash::HashedNode;
+pub use self::markup::{Markup, MarkupKind};
+pub use self::merge::{merge, NodeChange, NodePatch};
+pub use self::name_match::NameMatch;
+pub use self::provenance::{Origin, Provenance};
+
+pub(crate) use self::collection::is_xml_name;
+pub(crate) use self::cursor::same_path;
+pub(crate) use self::meta::Meta;
 
 // The attributes argument is very hard to generalize
 fn convert_attributes(attrs: &[(&str, &str)]) -> IndexMap<String, String> {
@@ -32,6 +52,7 @@ pub struct Node {
     attributes: IndexMap<String, String>,
     children: Vec<Node>,
     value: Option<Value>,
+    meta: Meta,
 }
 
 impl fmt::Debug for Node {
@@ -60,6 +81,7 @@ impl Node {
             attributes: IndexMap::new(),
             children: Vec::new(),
             value: None,
+            meta: Meta::default(),
         }
     }
 
@@ -72,6 +94,7 @@ impl Node {
             attributes: convert_attributes(attrs),
             children: Vec::new(),
             value: None,
+            meta: Meta::default(),
         }
     }
 
@@ -84,6 +107,7 @@ impl Node {
             attributes: IndexMap::new(),
             children: Vec::new(),
             value: Some(value),
+            meta: Meta::default(),
         }
     }
 
@@ -97,6 +121,7 @@ impl Node {
             attributes: IndexMap::new(),
             children: nodes.into(),
             value: None,
+            meta: Meta::default(),
         }
     }
 
@@ -110,6 +135,7 @@ impl Node {
             attributes: convert_attributes(attrs),
             children: nodes.into(),
             value: None,
+            meta: Meta::default(),
         }
     }
 
@@ -122,9 +148,61 @@ impl Node {
             attributes: convert_attributes(attrs),
             children: Vec::new(),
             value: Some(value),
+            meta: Meta::default(),
         }
     }
 
+    #[inline]
+    pub fn builder<K>(key: K) -> NodeBuilder
+    where
+        K: Into<String>,
+    {
+        NodeBuilder::new(key)
+    }
+
+    /// The application data of type `T` attached to this node.
+    #[inline]
+    pub fn meta<T>(&self) -> Option<&T>
+    where
+        T: Any,
+    {
+        self.meta.get()
+    }
+
+    #[inline]
+    pub fn meta_mut<T>(&mut self) -> Option<&mut T>
+    where
+        T: Any,
+    {
+        self.meta.get_mut()
+    }
+
+    /// Attach application data to this node, such as editor state or
+    /// provenance, replacing and returning any previous data of type `T`.
+    ///
+    /// Attached data is cloned along with the node, is ignored when comparing
+    /// nodes and is not encoded.
+    #[inline]
+    pub fn set_meta<T>(&mut self, value: T) -> Option<T>
+    where
+        T: Any + Clone + Send + Sync,
+    {
+        self.meta.insert(value)
+    }
+
+    #[inline]
+    pub fn remove_meta<T>(&mut self) -> Option<T>
+    where
+        T: Any,
+    {
+        self.meta.remove()
+    }
+
+    #[inline]
+    pub fn clear_meta(&mut self) {
+        self.meta.clear();
+    }
+
     #[inline]
     pub fn key(&self) -> &str {
         &self.key
@@ -191,6 +269,21 @@ impl Node {
         mem::replace(&mut self.value, value)
     }
 
+    /// The value of the attribute `key`, which may share its name with a
+    /// child node.
+    pub fn attr(&self, key: &str) -> Option<&str> {
+        self.attributes.get(key).map(String::as_str)
+    }
+
+    /// The names of attributes that are also the name of a child node.
+    pub fn name_conflicts(&self) -> Vec<&str> {
+        self.attributes
+            .keys()
+            .map(String::as_str)
+            .filter(|&key| self.has(key))
+            .collect()
+    }
+
     pub fn has(&self, key: &str) -> bool {
         self.children.iter().any(|node| node.key == key)
     }
@@ -199,6 +292,13 @@ impl Node {
         self.children.iter().find(|node| node.key == key)
     }
 
+    /// Find the first child whose name matches `key` under `name_match`.
+    pub fn get_child_matching(&self, key: &str, name_match: NameMatch) -> Option<&Node> {
+        self.children
+            .iter()
+            .find(|node| name_match.matches(&node.key, key))
+    }
+
     pub fn get_child_mut(&mut self, key: &str) -> Option<&mut Node> {
         self.children.iter_mut().find(|node| node.key == key)
     }
@@ -212,6 +312,15 @@ impl Node {
     }
 
     pub fn pointer<'a>(&'a self, pointer: &[&str]) -> Option<&'a Node> {
+        self.pointer_matching(pointer, NameMatch::Exact)
+    }
+
+    /// Like `pointer`, comparing names with `name_match`.
+    pub fn pointer_matching<'a>(
+        &'a self,
+        pointer: &[&str],
+        name_match: NameMatch,
+    ) -> Option<&'a Node> {
         if pointer.is_empty() {
             return Some(self);
         }
@@ -222,7 +331,10 @@ impl Node {
             let target_opt = if let Some(index) = parse_index(token) {
                 target.children.get(index)
             } else {
-                target.children.iter().find(|child| child.key == *token)
+                target
+                    .children
+                    .iter()
+                    .find(|child| name_match.matches(&child.key, token))
             };
 
             if let Some(t) = target_opt {
@@ -0,0 +1,57 @@
+use unicode_normalization::UnicodeNormalization;
+
+/// How node names are compared by lookups such as `Node::pointer_matching`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NameMatch {
+    /// Names must be equal.
+    #[default]
+    Exact,
+
+    /// Names must be equal ignoring case.
+    CaseInsensitive,
+
+    /// Names must be equal after NFKC normalization, so full-width and
+    /// half-width forms decoded from Shift-JIS names match.
+    Normalized,
+}
+
+impl NameMatch {
+    pub fn matches(self, name: &str, query: &str) -> bool {
+        match self {
+            NameMatch::Exact => name == query,
+            NameMatch::CaseInsensitive => {
+                name.eq_ignore_ascii_case(query) ||
+                    name.chars()
+                        .flat_map(char::to_lowercase)
+                        .eq(query.chars().flat_map(char::to_lowercase))
+            },
+            NameMatch::Normalized => name == query || name.nfkc().eq(query.nfkc()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::node::Node;
+
+    use super::NameMatch;
+
+    #[test]
+    fn test_name_match() {
+        assert!(NameMatch::CaseInsensitive.matches("Music", "music"));
+        assert!(!NameMatch::Exact.matches("Music", "music"));
+        assert!(NameMatch::Normalized.matches("ｍｕｓｉｃ", "music"));
+        assert!(!NameMatch::Normalized.matches("Music", "music"));
+
+        let mut root = Node::new("root");
+        root.append_child(Node::with_nodes("Info", vec![Node::new("ＩＤ")]));
+        assert!(root.pointer(&["info"]).is_none());
+        assert!(root
+            .pointer_matching(&["info"], NameMatch::CaseInsensitive)
+            .is_some());
+        assert!(root
+            .get_child_matching("Info", NameMatch::Exact)
+            .and_then(|info| info.get_child_matching("ID", NameMatch::Normalized))
+            .is_some());
+    }
+}
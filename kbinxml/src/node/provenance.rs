@@ -0,0 +1,154 @@
+use indexmap::IndexMap;
+
+use crate::node::merge::{child_path, indexed_children};
+use crate::node::Node;
+
+/// A node in a source document.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Origin {
+    /// The id given to `Node::track_provenance`.
+    pub document: String,
+
+    /// The path of the node in that document, like the paths of
+    /// `NodeChange`.
+    pub path: String,
+}
+
+/// Where a node and its contents came from, see `Node::track_provenance`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Provenance {
+    /// The node the node was created from.
+    pub node: Origin,
+
+    /// The node its value was last merged from, if not `node`.
+    pub value: Option<Origin>,
+
+    /// The nodes each attribute was last merged from, if not `node`.
+    pub attributes: IndexMap<String, Origin>,
+}
+
+impl Provenance {
+    /// Where the value of the node came from.
+    pub fn value_origin(&self) -> &Origin {
+        self.value.as_ref().unwrap_or(&self.node)
+    }
+
+    /// Where the attribute `name` of the node came from.
+    pub fn attribute_origin(&self, name: &str) -> &Origin {
+        self.attributes.get(name).unwrap_or(&self.node)
+    }
+}
+
+fn track(node: &mut Node, document: &str, path: String) {
+    let paths: Vec<_> = indexed_children(node)
+        .into_iter()
+        .map(|(index, child)| child_path(&path, &child.key, index))
+        .collect();
+    for (child, path) in node.children.iter_mut().zip(paths) {
+        track(child, document, path);
+    }
+
+    node.set_meta(Provenance {
+        node: Origin {
+            document: document.to_owned(),
+            path,
+        },
+        value: None,
+        attributes: IndexMap::new(),
+    });
+}
+
+fn collect<'a>(node: &'a Node, path: String, origins: &mut Vec<(String, &'a Provenance)>) {
+    let children = indexed_children(node);
+    if let Some(provenance) = node.provenance() {
+        origins.push((path.clone(), provenance));
+    }
+    for (index, child) in children {
+        collect(child, child_path(&path, &child.key, index), origins);
+    }
+}
+
+impl Node {
+    /// Record `document` and the path of every node in this tree as their
+    /// origin, replacing any provenance recorded before.
+    ///
+    /// The provenance is kept in a `meta` slot, so it follows nodes through
+    /// clones, patches and other transforms. `Node::merge` also records
+    /// which overlay node values and attributes came from, as long as both
+    /// nodes are tracked.
+    pub fn track_provenance(&mut self, document: &str) {
+        let path = format!("/{}", self.key);
+        track(self, document, path);
+    }
+
+    #[inline]
+    pub fn provenance(&self) -> Option<&Provenance> {
+        self.meta()
+    }
+
+    /// Every tracked node in this tree with its path in this tree.
+    pub fn origins(&self) -> Vec<(String, &Provenance)> {
+        let mut origins = Vec::new();
+        collect(self, format!("/{}", self.key), &mut origins);
+
+        origins
+    }
+
+    /// Record the origins of the value and attributes `overlay` replaces
+    /// when merged into this node.
+    pub(super) fn merge_provenance(&mut self, overlay: &Node) {
+        let theirs = match overlay.provenance() {
+            Some(theirs) => theirs,
+            None => return,
+        };
+        let ours = match self.meta.get_mut::<Provenance>() {
+            Some(ours) => ours,
+            None => return,
+        };
+
+        if overlay.value.is_some() {
+            ours.value = Some(theirs.value_origin().clone());
+        }
+        for name in overlay.attributes.keys() {
+            ours.attributes
+                .insert(name.clone(), theirs.attribute_origin(name).clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::node::{merge, Node};
+    use crate::value::Value;
+
+    #[test]
+    fn test_provenance() {
+        let mut base = Node::with_nodes("root", vec![
+            Node::with_attrs_value("rate", &[("unit", "s")], Value::U8(1)),
+            Node::new("entry"),
+        ]);
+        let mut overlay = Node::with_nodes("root", vec![
+            Node::with_value("rate", Value::U8(2)),
+            Node::new("entry"),
+            Node::with_attrs("entry", &[("id", "2")]),
+        ]);
+        base.track_provenance("base.xml");
+        overlay.track_provenance("patch.xml");
+        assert!(merge(&Node::new("root"), &overlay).provenance().is_none());
+
+        let merged = merge(&base, &overlay);
+        let rate = merged.children()[0]
+            .provenance()
+            .expect("Missing provenance");
+        assert_eq!(rate.node.document, "base.xml");
+        assert_eq!(rate.value_origin().document, "patch.xml");
+        assert_eq!(rate.attribute_origin("unit").document, "base.xml");
+
+        let origins = merged.origins();
+        assert_eq!(origins.len(), 4);
+        let (path, entry) = &origins[3];
+        assert_eq!(path, "/root/entry[1]");
+        assert_eq!(entry.node.document, "patch.xml");
+        assert_eq!(entry.node.path, "/root/entry[1]");
+    }
+}
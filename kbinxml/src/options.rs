@@ -1,16 +1,491 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use indexmap::IndexMap;
+
 use crate::compression_type::CompressionType;
 use crate::encoding_type::EncodingType;
+use crate::error::KbinError;
+use crate::filter::{type_override, DerivedNode, FilterError, FilterTarget, ValueFilter};
+use crate::node::Node;
+use crate::node_types::StandardType;
+use crate::sixbit::Sixbit;
+use crate::value::integer_bounds;
+use crate::value::Value;
+
+/// How attribute names that are not valid XML names are handled when
+/// converting to a `Node` tree.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "profile",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum AttributeNamePolicy {
+    /// Keep the name as decoded.
+    #[default]
+    Accept,
+
+    /// Fail with an error.
+    Error,
+
+    /// Replace invalid characters with `_` and report the rename.
+    Sanitize,
+}
+
+/// How arrays without any elements are handled when encoding.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "profile",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum EmptyArrayPolicy {
+    /// Write a zero-length array.
+    #[default]
+    Emit,
+
+    /// Fail with an error.
+    Error,
+}
+
+/// How the hex text of a `bin` node that decodes to more bytes than its
+/// `__size` attribute is handled when reading text XML.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "profile",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum BinarySizePolicy {
+    /// Fail with an error.
+    #[default]
+    Error,
+
+    /// Drop the bytes past `__size`.
+    Truncate,
+
+    /// Keep every byte, replacing `__size` with the decoded length.
+    UpdateSize,
+}
+
+/// How `__type` attribute values that do not name a known node type are
+/// handled when reading text XML.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "profile",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum UnknownTypeNamePolicy {
+    /// Fail with an error.
+    #[default]
+    Error,
+
+    /// Read the node as a `str` node and log a warning.
+    String,
+
+    /// Read the node as a `bin` node and log a warning.
+    Binary,
+}
+
+/// How strings holding null bytes or other control characters are handled
+/// when encoding and when converting to a `Node` tree.
+///
+/// Strings are null terminated in the data buffer, so other tools read such
+/// strings truncated. Tab, line feed and carriage return are allowed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "profile",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum ControlCharacterPolicy {
+    /// Fail with the path of the string.
+    #[default]
+    Error,
+
+    /// Remove the characters.
+    Strip,
+
+    /// Replace each character with an escape like `\u{0}`.
+    Escape,
+}
+
+/// How characters the encoding of a document cannot represent are handled
+/// when encoding string values and attributes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "profile",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum UnmappableCharacterPolicy {
+    /// Fail to encode the string.
+    #[default]
+    Error,
+
+    /// Replace each character with `?`.
+    Replace,
+}
+
+/// How an attribute that shares its name with a child node is handled by
+/// conversions that put both in one map, like deserializing a node into a
+/// map with serde.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "profile",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum NameConflictPolicy {
+    /// Fail with the path of the attribute.
+    #[default]
+    Error,
+
+    /// Prefix the name of the attribute with `@`, like struct fields read
+    /// from attributes.
+    PrefixAttribute,
+
+    /// Prefix the name of every attribute with `@`, whether or not a child
+    /// has the same name. Structs with `#[serde(flatten)]` fields are read
+    /// as maps, so their attribute fields need this.
+    PrefixAllAttributes,
+}
+
+fn is_disallowed_control(byte: u8) -> bool {
+    byte.is_ascii_control() && !matches!(byte, b'\t' | b'\n' | b'\r')
+}
+
+impl ControlCharacterPolicy {
+    /// Apply the policy to encoded string data without its null terminator,
+    /// returning `None` if it is `Error` and `data` holds a disallowed
+    /// character.
+    ///
+    /// Every supported encoding stores ASCII control characters as single
+    /// bytes that never appear inside multibyte characters, so this works on
+    /// the data of any of them.
+    pub(crate) fn apply_bytes(self, data: &[u8]) -> Option<Cow<'_, [u8]>> {
+        if !data.iter().any(|&byte| is_disallowed_control(byte)) {
+            return Some(Cow::Borrowed(data));
+        }
+
+        match self {
+            ControlCharacterPolicy::Error => None,
+            ControlCharacterPolicy::Strip => Some(Cow::Owned(
+                data.iter()
+                    .copied()
+                    .filter(|&byte| !is_disallowed_control(byte))
+                    .collect(),
+            )),
+            ControlCharacterPolicy::Escape => {
+                let mut escaped = Vec::with_capacity(data.len() + 8);
+                for &byte in data {
+                    if is_disallowed_control(byte) {
+                        escaped.extend(char::from(byte).escape_unicode().map(|c| c as u8));
+                    } else {
+                        escaped.push(byte);
+                    }
+                }
+
+                Some(Cow::Owned(escaped))
+            },
+        }
+    }
+
+    /// Like `apply_bytes`, for decoded strings.
+    pub(crate) fn apply(self, text: &str) -> Option<Cow<'_, str>> {
+        let replaced = match self.apply_bytes(text.as_bytes())? {
+            Cow::Borrowed(_) => Cow::Borrowed(text),
+            Cow::Owned(data) => Cow::Owned(
+                String::from_utf8(data).expect("Only ASCII bytes are removed or inserted"),
+            ),
+        };
+
+        Some(replaced)
+    }
+}
+
+/// The byte order of numeric values in the data buffer. The header, buffer
+/// lengths and `ip4` values are big endian either way.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "profile",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Endianness {
+    #[default]
+    Big,
+    Little,
+}
+
+impl Endianness {
+    /// Whether values of `node_type` are stored differently from the big
+    /// endian data of `NodeDefinition`s.
+    pub(crate) fn swaps(self, node_type: StandardType) -> bool {
+        self == Endianness::Little && node_type.size > 1 && node_type != StandardType::Ip4
+    }
+
+    /// Reverse the bytes of every element of `node_type` in `data`,
+    /// converting between big and little endian.
+    pub(crate) fn swap(node_type: StandardType, data: &mut [u8]) {
+        for element in data.chunks_exact_mut(node_type.size) {
+            element.reverse();
+        }
+    }
+}
+
+/// Which name of a node type is written to the `__type` attribute of text
+/// XML.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "profile",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum TypeNamePolicy {
+    /// Write the primary names, e.g. `str`, `bin` and `4u32`.
+    #[default]
+    Primary,
+
+    /// Write the alternate names where a type has one, e.g. `string`,
+    /// `binary` and `vu32`.
+    Alternate,
+}
+
+impl TypeNamePolicy {
+    /// Find the names used by the `__type` attributes of a text XML
+    /// document, so they can be written back out after a round trip through
+    /// the binary format.
+    ///
+    /// Only types that have an alternate name count. Documents that mix
+    /// both kinds of names use the kind that appears most.
+    pub fn detect(input: &[u8]) -> Self {
+        crate::text_reader::detect_type_names(input)
+    }
+
+    /// The name of `node_type` under this policy.
+    pub fn name(self, node_type: StandardType) -> &'static str {
+        match self {
+            TypeNamePolicy::Primary => node_type.name,
+            TypeNamePolicy::Alternate => node_type.alt_name.unwrap_or(node_type.name),
+        }
+    }
+}
+
+/// How the reader handles malformed binary documents, see
+/// `OptionsBuilder::parse_mode`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "profile",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum ParseMode {
+    /// Only check array lengths when their values are read, and ignore
+    /// padding and anything after the root node.
+    #[default]
+    Unchecked,
+
+    /// Fail on array lengths that are not a whole number of elements,
+    /// non-zero padding and data after the end of the document.
+    Strict,
+
+    /// Recover from those problems, recording each as a warning, see
+    /// `Reader::warnings`. Arrays are truncated to their whole elements.
+    ///
+    /// Also implies `OptionsBuilder::trust_encoding_byte` and
+    /// `OptionsBuilder::lenient_unknown_types`, recording a warning for each
+    /// mismatched encoding byte and unknown node type.
+    Lenient,
+}
+
+/// How `float` and `double` values are written to text XML.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "profile",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum FloatFormat {
+    /// Write six digits after the decimal point, like the reference
+    /// implementation. Digits beyond those are lost.
+    #[default]
+    Fixed,
+
+    /// Write the shortest text that reads back as the same value, so
+    /// documents survive a round trip through text XML unchanged.
+    Shortest,
+}
+
+/// A combination of options that cannot work.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OptionsConflict {
+    /// A renamed document name cannot be written with the configured
+    /// compression and encoding.
+    UnencodableRename {
+        name: String,
+        compression: CompressionType,
+        encoding: EncodingType,
+    },
+
+    /// More than one document name is renamed to `name`, so it cannot be
+    /// renamed back when encoding.
+    AmbiguousRename { name: String },
+
+    /// A memory budget of zero rejects every document.
+    ZeroMemoryBudget,
+
+    /// A maximum of zero nodes rejects every document.
+    ZeroMaxNodes,
+
+    /// Values can only be widened to integer types.
+    NonIntegerSerializeType {
+        path: String,
+        node_type: StandardType,
+    },
+
+    /// Values can only be forced to value types.
+    InvalidTypeOverride {
+        pattern: String,
+        node_type: StandardType,
+    },
+}
+
+impl fmt::Display for OptionsConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OptionsConflict::UnencodableRename {
+                name,
+                compression,
+                encoding,
+            } => write!(
+                f,
+                "renamed name {:?} cannot be written with {:?} compression and {} encoding",
+                name, compression, encoding
+            ),
+            OptionsConflict::AmbiguousRename { name } => {
+                write!(f, "more than one name is renamed to {:?}", name)
+            },
+            OptionsConflict::ZeroMemoryBudget => write!(f, "memory budget is zero"),
+            OptionsConflict::ZeroMaxNodes => write!(f, "maximum node count is zero"),
+            OptionsConflict::NonIntegerSerializeType { path, node_type } => write!(
+                f,
+                "{} cannot be serialized as non-integer type {}",
+                path, node_type
+            ),
+            OptionsConflict::InvalidTypeOverride { pattern, node_type } => write!(
+                f,
+                "nodes matching {} cannot be forced to type {}",
+                pattern, node_type
+            ),
+        }
+    }
+}
+
+impl OptionsConflict {
+    /// Whether the conflict affects reading documents. The others only stop
+    /// documents from being written.
+    pub fn affects_reading(&self) -> bool {
+        match self {
+            OptionsConflict::UnencodableRename { .. } |
+            OptionsConflict::AmbiguousRename { .. } |
+            OptionsConflict::NonIntegerSerializeType { .. } => false,
+            OptionsConflict::ZeroMemoryBudget |
+            OptionsConflict::ZeroMaxNodes |
+            OptionsConflict::InvalidTypeOverride { .. } => true,
+        }
+    }
+}
+
+/// Join conflicts into a single message for error displays.
+pub(crate) fn describe_conflicts(conflicts: &[OptionsConflict]) -> String {
+    conflicts
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Map a document name through `renames`, see `OptionsBuilder::rename`.
+pub(crate) fn decode_name<'a>(renames: &IndexMap<String, String>, name: &'a str) -> Cow<'a, str> {
+    match renames.get(name) {
+        Some(renamed) => Cow::Owned(renamed.clone()),
+        None => Cow::Borrowed(name),
+    }
+}
+
+/// Map a renamed name back to the document name it was renamed from.
+pub(crate) fn encode_name<'a>(renames: &IndexMap<String, String>, name: &'a str) -> Cow<'a, str> {
+    match renames.iter().find(|(_, renamed)| *renamed == name) {
+        Some((original, _)) => Cow::Owned(original.clone()),
+        None => Cow::Borrowed(name),
+    }
+}
 
 #[derive(Clone, Debug, Default)]
 pub struct Options {
     pub(crate) compression: CompressionType,
     pub(crate) encoding: EncodingType,
+    pub(crate) renames: IndexMap<String, String>,
+    pub(crate) variables: IndexMap<String, String>,
+    pub(crate) empty_arrays: EmptyArrayPolicy,
+    pub(crate) strict_names: bool,
+    pub(crate) strict_text_xml: bool,
+    pub(crate) trust_encoding_byte: bool,
+    pub(crate) lenient_unknown_types: bool,
+    pub(crate) parse_mode: ParseMode,
+    pub(crate) decode_nested_documents: bool,
+    pub(crate) attribute_names: AttributeNamePolicy,
+    pub(crate) memory_budget: Option<usize>,
+    pub(crate) max_nodes: Option<usize>,
+    pub(crate) allow_lossless_narrowing: bool,
+    pub(crate) serialize_types: IndexMap<String, StandardType>,
+    pub(crate) type_overrides: Vec<(String, StandardType)>,
+    pub(crate) type_names: TypeNamePolicy,
+    pub(crate) float_format: FloatFormat,
+    pub(crate) binary_size: BinarySizePolicy,
+    pub(crate) unknown_type_names: UnknownTypeNamePolicy,
+    pub(crate) control_characters: ControlCharacterPolicy,
+    pub(crate) unmappable_characters: UnmappableCharacterPolicy,
+    pub(crate) name_conflicts: NameConflictPolicy,
+    pub(crate) endianness: Endianness,
+    pub(crate) decode_filters: Vec<ValueFilter>,
+    pub(crate) encode_filters: Vec<ValueFilter>,
+    pub(crate) derived_nodes: Vec<DerivedNode>,
 }
 
 #[derive(Default)]
 pub struct OptionsBuilder {
     compression: CompressionType,
     encoding: EncodingType,
+    renames: IndexMap<String, String>,
+    variables: IndexMap<String, String>,
+    empty_arrays: EmptyArrayPolicy,
+    strict_names: bool,
+    strict_text_xml: bool,
+    trust_encoding_byte: bool,
+    lenient_unknown_types: bool,
+    parse_mode: ParseMode,
+    decode_nested_documents: bool,
+    attribute_names: AttributeNamePolicy,
+    memory_budget: Option<usize>,
+    max_nodes: Option<usize>,
+    allow_lossless_narrowing: bool,
+    serialize_types: IndexMap<String, StandardType>,
+    type_overrides: Vec<(String, StandardType)>,
+    type_names: TypeNamePolicy,
+    float_format: FloatFormat,
+    binary_size: BinarySizePolicy,
+    unknown_type_names: UnknownTypeNamePolicy,
+    control_characters: ControlCharacterPolicy,
+    unmappable_characters: UnmappableCharacterPolicy,
+    name_conflicts: NameConflictPolicy,
+    endianness: Endianness,
+    decode_filters: Vec<ValueFilter>,
+    encode_filters: Vec<ValueFilter>,
+    derived_nodes: Vec<DerivedNode>,
 }
 
 impl Options {
@@ -18,6 +493,7 @@ impl Options {
         Self {
             compression,
             encoding,
+            ..Default::default()
         }
     }
 
@@ -31,6 +507,131 @@ impl Options {
             ..Default::default()
         }
     }
+
+    /// Find every combination of options that cannot work.
+    pub fn conflicts(&self) -> Vec<OptionsConflict> {
+        let mut conflicts = Vec::new();
+
+        for (i, (from, to)) in self.renames.iter().enumerate() {
+            let encodable = match self.compression {
+                CompressionType::Compressed => Sixbit::is_valid(from),
+                CompressionType::Uncompressed => self.encoding.encode_bytes(from).is_ok(),
+            };
+            if !encodable {
+                conflicts.push(OptionsConflict::UnencodableRename {
+                    name: from.clone(),
+                    compression: self.compression,
+                    encoding: self.encoding,
+                });
+            }
+
+            let first = self.renames.values().position(|other| other == to);
+            if first == Some(i) && self.renames.values().filter(|other| *other == to).count() > 1 {
+                conflicts.push(OptionsConflict::AmbiguousRename { name: to.clone() });
+            }
+        }
+
+        if self.memory_budget == Some(0) {
+            conflicts.push(OptionsConflict::ZeroMemoryBudget);
+        }
+        if self.max_nodes == Some(0) {
+            conflicts.push(OptionsConflict::ZeroMaxNodes);
+        }
+
+        for (path, &node_type) in &self.serialize_types {
+            if integer_bounds(node_type).is_none() {
+                conflicts.push(OptionsConflict::NonIntegerSerializeType {
+                    path: path.clone(),
+                    node_type,
+                });
+            }
+        }
+        for (pattern, node_type) in &self.type_overrides {
+            if let StandardType::NodeStart |
+            StandardType::NodeEnd |
+            StandardType::FileEnd |
+            StandardType::Attribute = node_type
+            {
+                conflicts.push(OptionsConflict::InvalidTypeOverride {
+                    pattern: pattern.clone(),
+                    node_type: *node_type,
+                });
+            }
+        }
+
+        conflicts
+    }
+
+    /// Find every combination of options that cannot work when reading,
+    /// see `OptionsConflict::affects_reading`.
+    pub fn read_conflicts(&self) -> Vec<OptionsConflict> {
+        self.conflicts()
+            .into_iter()
+            .filter(OptionsConflict::affects_reading)
+            .collect()
+    }
+
+    /// Check the options for conflicts, failing with all of them at once.
+    ///
+    /// The writer and serializer entry points call this before doing any
+    /// work.
+    pub fn validate(&self) -> Result<(), KbinError> {
+        let conflicts = self.conflicts();
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(KbinError::InvalidOptions { conflicts })
+        }
+    }
+
+    /// Check the options for conflicts that affect reading, see
+    /// `Options::read_conflicts`.
+    ///
+    /// The reader and deserializer entry points call this before doing any
+    /// work.
+    pub fn validate_read(&self) -> Result<(), KbinError> {
+        let conflicts = self.read_conflicts();
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(KbinError::InvalidOptions { conflicts })
+        }
+    }
+
+    /// Map a node or attribute name read from a document to the name used in
+    /// a `Node` tree or text XML.
+    #[inline]
+    pub(crate) fn decode_name<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        decode_name(&self.renames, name)
+    }
+
+    /// Map a name used in a `Node` tree or text XML back to the name written
+    /// to a document.
+    #[inline]
+    pub(crate) fn encode_name<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        encode_name(&self.renames, name)
+    }
+
+    /// Whether a mismatched encoding byte is used, see
+    /// `OptionsBuilder::trust_encoding_byte`.
+    #[inline]
+    pub(crate) fn trusts_encoding_byte(&self) -> bool {
+        self.trust_encoding_byte || self.parse_mode == ParseMode::Lenient
+    }
+
+    /// Whether nodes of unknown type are read, see
+    /// `OptionsBuilder::lenient_unknown_types`.
+    #[inline]
+    pub(crate) fn reads_unknown_types(&self) -> bool {
+        self.lenient_unknown_types || self.parse_mode == ParseMode::Lenient
+    }
+
+    /// The type forced on the node at `path`, see
+    /// `OptionsBuilder::type_overrides`.
+    #[inline]
+    pub(crate) fn type_override(&self, path: &str) -> Option<StandardType> {
+        type_override(&self.type_overrides, path)
+    }
 }
 
 impl OptionsBuilder {
@@ -39,15 +640,297 @@ impl OptionsBuilder {
         self
     }
 
+    /// Set the encoding of written strings, attributes and uncompressed
+    /// names, which is also recorded in the header. Documents read in another
+    /// encoding are converted.
     pub fn encoding(&mut self, encoding: EncodingType) -> &mut Self {
         self.encoding = encoding;
         self
     }
 
+    /// Rename a node or attribute name when converting to a `Node` tree or
+    /// text XML.
+    ///
+    /// The rename is applied in reverse when encoding a `Node` tree or
+    /// reading text XML, so `"illegal-name" -> "legal_name"` reads
+    /// `legal_name` from documents containing `illegal-name` and writes
+    /// `illegal-name` back out. A `NodeCollection` keeps the names of the
+    /// document, so it is written to binary as is.
+    pub fn rename<F, T>(&mut self, from: F, to: T) -> &mut Self
+    where
+        F: Into<String>,
+        T: Into<String>,
+    {
+        self.renames.insert(from.into(), to.into());
+        self
+    }
+
+    /// Replace `${name}` placeholders in node text and attributes with
+    /// `value` when reading text XML, before the text is parsed as the type
+    /// of its node.
+    ///
+    /// Placeholders without a variable fail reading, `$${` reads as a
+    /// literal `${`. Nothing is replaced unless a variable is set.
+    pub fn variable<N, V>(&mut self, name: N, value: V) -> &mut Self
+    where
+        N: Into<String>,
+        V: Into<String>,
+    {
+        self.variables.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn empty_arrays(&mut self, empty_arrays: EmptyArrayPolicy) -> &mut Self {
+        self.empty_arrays = empty_arrays;
+        self
+    }
+
+    /// Require node names in uncompressed documents to be valid UTF-8
+    /// without control characters when decoding.
+    ///
+    /// By default, names are decoded with the document encoding and any
+    /// byte sequence is accepted.
+    pub fn strict_names(&mut self, strict_names: bool) -> &mut Self {
+        self.strict_names = strict_names;
+        self
+    }
+
+    /// Fail writing text XML instead of writing something that does not read
+    /// back as the same document, e.g. names that are not XML names, strings
+    /// with surrounding whitespace or floats rounded by `FloatFormat::Fixed`.
+    ///
+    /// Use the `Node` API or the binary format for such documents.
+    pub fn strict_text_xml(&mut self, strict_text_xml: bool) -> &mut Self {
+        self.strict_text_xml = strict_text_xml;
+        self
+    }
+
+    /// Use the encoding byte of the header even when the inverted copy after
+    /// it does not match. Implied by `ParseMode::Lenient`.
+    pub fn trust_encoding_byte(&mut self, trust_encoding_byte: bool) -> &mut Self {
+        self.trust_encoding_byte = trust_encoding_byte;
+        self
+    }
+
+    /// Read nodes with a reserved or unsupported type id instead of failing.
+    /// Implied by `ParseMode::Lenient`.
+    ///
+    /// Arrays of unknown type hold a `Value::Unknown` with their data, as
+    /// arrays of every type store their length. Other nodes are read as empty
+    /// nodes with an `__unknown_type` attribute holding the id, so values
+    /// read after them may be wrong if their type stores data.
+    pub fn lenient_unknown_types(&mut self, lenient_unknown_types: bool) -> &mut Self {
+        self.lenient_unknown_types = lenient_unknown_types;
+        self
+    }
+
+    /// Check binary documents for problems the reader otherwise ignores, or
+    /// recover from them, see `ParseMode`.
+    pub fn parse_mode(&mut self, parse_mode: ParseMode) -> &mut Self {
+        self.parse_mode = parse_mode;
+        self
+    }
+
+    /// Decode `Binary` values that hold a whole kbin document into a child
+    /// node of their node, which is marked with a `__nested` attribute.
+    ///
+    /// Marked nodes are embedded as `Binary` values again when encoding.
+    pub fn decode_nested_documents(&mut self, decode_nested_documents: bool) -> &mut Self {
+        self.decode_nested_documents = decode_nested_documents;
+        self
+    }
+
+    /// Check that decoded attribute names are valid XML names.
+    pub fn attribute_names(&mut self, attribute_names: AttributeNamePolicy) -> &mut Self {
+        self.attribute_names = attribute_names;
+        self
+    }
+
+    /// Fail reading once the estimated memory used by the `NodeCollection`
+    /// being built exceeds `memory_budget` bytes, and converting a collection
+    /// to a `Node` once the names and values it allocates exceed it.
+    ///
+    /// Names and values of a collection are slices of the input, so only
+    /// the space for each node and attribute is counted while reading.
+    pub fn memory_budget(&mut self, memory_budget: usize) -> &mut Self {
+        self.memory_budget = Some(memory_budget);
+        self
+    }
+
+    /// Fail reading once a document has more than `max_nodes` nodes, not
+    /// counting attributes.
+    pub fn max_nodes(&mut self, max_nodes: usize) -> &mut Self {
+        self.max_nodes = Some(max_nodes);
+        self
+    }
+
+    /// Allow deserializing an integer into a narrower integer type when the
+    /// stored value fits, e.g. a `u32` node into a `u8` field.
+    pub fn allow_lossless_narrowing(&mut self, allow_lossless_narrowing: bool) -> &mut Self {
+        self.allow_lossless_narrowing = allow_lossless_narrowing;
+        self
+    }
+
+    /// Widen the integer value of the node at `path` to `node_type` when
+    /// converting to a `Node` tree with `to_node_with_options`.
+    ///
+    /// Paths start at the root node and leave out indices, e.g.
+    /// `/root/entry/id` applies to `id` in every `entry` node.
+    pub fn serialize_as<P>(&mut self, path: P, node_type: StandardType) -> &mut Self
+    where
+        P: Into<String>,
+    {
+        self.serialize_types.insert(path.into(), node_type);
+        self
+    }
+
+    /// Force the values of the nodes matching a path pattern to a node type
+    /// when reading text XML or converting with `to_node_with_options`,
+    /// regardless of their `__type` attribute or serialized type, e.g.
+    /// `("/**/flag", StandardType::U8)`.
+    ///
+    /// Patterns are matched like `FilterTarget::Path`. The last added
+    /// pattern matching a node wins.
+    pub fn type_overrides(&mut self, overrides: &[(&str, StandardType)]) -> &mut Self {
+        self.type_overrides.extend(
+            overrides
+                .iter()
+                .map(|&(pattern, node_type)| (pattern.to_owned(), node_type)),
+        );
+        self
+    }
+
+    /// Choose between the primary and alternate type names when writing
+    /// text XML.
+    pub fn type_names(&mut self, type_names: TypeNamePolicy) -> &mut Self {
+        self.type_names = type_names;
+        self
+    }
+
+    /// Choose how float values are formatted when writing text XML.
+    pub fn float_format(&mut self, float_format: FloatFormat) -> &mut Self {
+        self.float_format = float_format;
+        self
+    }
+
+    /// Choose how oversized `bin` node text is handled when reading text
+    /// XML.
+    pub fn binary_size(&mut self, binary_size: BinarySizePolicy) -> &mut Self {
+        self.binary_size = binary_size;
+        self
+    }
+
+    /// Choose how unknown `__type` names are handled when reading text XML,
+    /// e.g. aliases invented by other tools.
+    pub fn unknown_type_names(&mut self, unknown_type_names: UnknownTypeNamePolicy) -> &mut Self {
+        self.unknown_type_names = unknown_type_names;
+        self
+    }
+
+    /// Choose how strings with null bytes or other control characters are
+    /// handled.
+    pub fn control_characters(&mut self, control_characters: ControlCharacterPolicy) -> &mut Self {
+        self.control_characters = control_characters;
+        self
+    }
+
+    /// Choose how characters the encoding cannot represent are handled when
+    /// writing string values and attributes. Node names always fail.
+    pub fn unmappable_characters(
+        &mut self,
+        unmappable_characters: UnmappableCharacterPolicy,
+    ) -> &mut Self {
+        self.unmappable_characters = unmappable_characters;
+        self
+    }
+
+    /// Choose how attributes sharing a name with a child node are handled
+    /// when deserializing a node into a map, or whether every attribute is
+    /// prefixed.
+    pub fn name_conflicts(&mut self, name_conflicts: NameConflictPolicy) -> &mut Self {
+        self.name_conflicts = name_conflicts;
+        self
+    }
+
+    /// Set the byte order of numeric values, for variants of the format
+    /// that store them little endian. The header does not record it.
+    pub fn endianness(&mut self, endianness: Endianness) -> &mut Self {
+        self.endianness = endianness;
+        self
+    }
+
+    /// Pass the values at `target` through `filter` when converting to a
+    /// `Node` tree, e.g. to decompress a `bin` node. Filters run in the order
+    /// they are added, and get the path of the value and the value.
+    ///
+    /// Attributes are passed as `Value::Attribute` and have to stay strings.
+    pub fn decode_filter<T, F>(&mut self, target: T, filter: F) -> &mut Self
+    where
+        T: Into<FilterTarget>,
+        F: Fn(&str, Value) -> Result<Value, FilterError> + Send + Sync + 'static,
+    {
+        self.decode_filters.push(ValueFilter::new(target, filter));
+        self
+    }
+
+    /// Pass the values at `target` through `filter` before they are
+    /// written, e.g. to compress a `bin` node, see `decode_filter`.
+    ///
+    /// The filters run on a copy of the input, so a document read with
+    /// `from_binary` is written like its `Node` tree when any are set.
+    pub fn encode_filter<T, F>(&mut self, target: T, filter: F) -> &mut Self
+    where
+        T: Into<FilterTarget>,
+        F: Fn(&str, Value) -> Result<Value, FilterError> + Send + Sync + 'static,
+    {
+        self.encode_filters.push(ValueFilter::new(target, filter));
+        self
+    }
+
+    /// Declare the node at `path` as derived from its parent, e.g. a
+    /// checksum or count of its siblings. `compute` gets the parent node.
+    ///
+    /// The writer sets the value, adding the node if it is missing, after
+    /// the encode filters have run. Converting to a `Node` tree fails if the
+    /// value differs from the computed one.
+    pub fn derived_node<F>(&mut self, path: &str, compute: F) -> &mut Self
+    where
+        F: Fn(&Node) -> Result<Value, FilterError> + Send + Sync + 'static,
+    {
+        self.derived_nodes.push(DerivedNode::new(path, compute));
+        self
+    }
+
     pub fn build(&self) -> Options {
         Options {
             compression: self.compression,
             encoding: self.encoding,
+            renames: self.renames.clone(),
+            variables: self.variables.clone(),
+            empty_arrays: self.empty_arrays,
+            strict_names: self.strict_names,
+            strict_text_xml: self.strict_text_xml,
+            trust_encoding_byte: self.trust_encoding_byte,
+            lenient_unknown_types: self.lenient_unknown_types,
+            parse_mode: self.parse_mode,
+            decode_nested_documents: self.decode_nested_documents,
+            attribute_names: self.attribute_names,
+            memory_budget: self.memory_budget,
+            max_nodes: self.max_nodes,
+            allow_lossless_narrowing: self.allow_lossless_narrowing,
+            serialize_types: self.serialize_types.clone(),
+            type_overrides: self.type_overrides.clone(),
+            type_names: self.type_names,
+            float_format: self.float_format,
+            binary_size: self.binary_size,
+            unknown_type_names: self.unknown_type_names,
+            control_characters: self.control_characters,
+            unmappable_characters: self.unmappable_characters,
+            name_conflicts: self.name_conflicts,
+            endianness: self.endianness,
+            decode_filters: self.decode_filters.clone(),
+            encode_filters: self.encode_filters.clone(),
+            derived_nodes: self.derived_nodes.clone(),
         }
     }
 }
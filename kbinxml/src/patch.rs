@@ -0,0 +1,180 @@
+use std::mem;
+
+use bytes::Bytes;
+use snafu::Snafu;
+
+use crate::diff::Paths;
+use crate::error::KbinError;
+use crate::node::same_path;
+use crate::node_types::StandardType;
+use crate::reader::Reader;
+use crate::value::Value;
+
+#[derive(Debug, Snafu)]
+pub enum PatchError {
+    #[snafu(display("No node found at {}", path))]
+    NotFound { path: String },
+
+    #[snafu(display("A node already exists at {}", path))]
+    Exists { path: String },
+
+    #[snafu(display("Invalid path {} for this change", path))]
+    InvalidPath { path: String },
+
+    #[snafu(display("Node at {} has variable size type {}", path, node_type))]
+    NotFixedSize {
+        path: String,
+        node_type: StandardType,
+    },
+
+    #[snafu(display(
+        "New value for {} is {} byte(s) long, expected {}",
+        path,
+        actual,
+        expected
+    ))]
+    LengthChanged {
+        path: String,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+/// Overwrite the value of the node at `path` in a binary document without
+/// re-encoding it.
+///
+/// Only fixed-size values can be patched. The new value must have the same
+/// type as the old one and, for arrays, the same number of elements. Paths
+/// use the format of `NodeCursor::path`, `[0]` may be left out.
+pub fn patch_value(input: &mut Vec<u8>, path: &str, new_value: &Value) -> Result<(), KbinError> {
+    // The reader shares the document, so lend it the buffer and take it back
+    // once the reader is dropped instead of copying the document.
+    let shared = Bytes::from(mem::take(input));
+    let located = locate(shared.clone(), path, new_value);
+    *input = Vec::from(shared);
+
+    let (offset, data) = located?;
+    input[offset..offset + data.len()].copy_from_slice(&data);
+
+    Ok(())
+}
+
+/// Find the data buffer offset of the value at `path` and encode `new_value`
+/// to replace it.
+fn locate(input: Bytes, path: &str, new_value: &Value) -> Result<(usize, Vec<u8>), KbinError> {
+    let mut reader = Reader::new(input)?;
+    let mut paths = Paths::new();
+
+    let (def, offset) = loop {
+        let def = reader.read_node_definition()?;
+        match def.node_type {
+            StandardType::FileEnd => {
+                return Err(PatchError::NotFound {
+                    path: path.to_owned(),
+                }
+                .into());
+            },
+            StandardType::NodeEnd => paths.leave(),
+            StandardType::Attribute => {},
+            _ => {
+                let name = def.key()?.ok_or(KbinError::InvalidState)?;
+                if same_path(&paths.enter(&name), path) {
+                    break (def, reader.last_data_offset() as usize);
+                }
+            },
+        };
+    };
+
+    match def.node_type {
+        StandardType::NodeStart | StandardType::String | StandardType::Binary => {
+            return Err(PatchError::NotFixedSize {
+                path: path.to_owned(),
+                node_type: def.node_type,
+            }
+            .into());
+        },
+        _ => {},
+    };
+
+    let found = match new_value {
+        Value::Array(values) => (values.standard_type(), true),
+        value => (value.standard_type(), false),
+    };
+    if found != def.node_type_tuple() {
+        return Err(KbinError::TypeMismatch {
+            expected: def.node_type,
+            found: found.0,
+        });
+    }
+
+    let data = new_value.to_bytes()?;
+    let expected = def.value_bytes().map_or(0, <[u8]>::len);
+    if data.len() != expected {
+        return Err(PatchError::LengthChanged {
+            path: path.to_owned(),
+            expected,
+            actual: data.len(),
+        }
+        .into());
+    }
+
+    Ok((offset, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::KbinError;
+    use crate::node::Node;
+    use crate::node_types::StandardType;
+    use crate::value::{Value, ValueArray};
+
+    use super::{patch_value, PatchError};
+
+    fn read(data: &[u8], index: usize) -> Value {
+        let (collection, _) = crate::from_slice(data).expect("Failed to decode");
+        collection.children()[index]
+            .base()
+            .value()
+            .expect("Failed to read value")
+    }
+
+    #[test]
+    fn test_patch_value() {
+        let node = Node::with_nodes("root", [
+            Node::with_value("count", Value::U32(1)),
+            Node::with_value("list", Value::Array(ValueArray::U16(vec![1, 2, 3]))),
+            Node::with_value("name", Value::String("a".into())),
+        ]);
+        let mut data = crate::to_binary(&node).expect("Failed to encode");
+        let ptr = data.as_ptr();
+
+        patch_value(&mut data, "/root/count", &Value::U32(5)).expect("Failed to patch");
+        assert_eq!(read(&data, 0), Value::U32(5));
+
+        let list = Value::Array(ValueArray::U16(vec![4, 5, 6]));
+        patch_value(&mut data, "/root/list[0]", &list).expect("Failed to patch");
+        assert_eq!(read(&data, 1), list);
+
+        let short = Value::Array(ValueArray::U16(vec![1, 2]));
+        match patch_value(&mut data, "/root/list", &short) {
+            Err(KbinError::Patch {
+                source:
+                    PatchError::LengthChanged {
+                        expected, actual, ..
+                    },
+            }) => assert_eq!((expected, actual), (6, 4)),
+            result => panic!("Unexpected result: {:?}", result),
+        };
+
+        let name = Value::String("b".into());
+        match patch_value(&mut data, "/root/name", &name) {
+            Err(KbinError::Patch {
+                source: PatchError::NotFixedSize { node_type, .. },
+            }) => assert_eq!(node_type, StandardType::String),
+            result => panic!("Unexpected result: {:?}", result),
+        };
+
+        // The document is patched in place, errors included.
+        assert_eq!(data.as_ptr(), ptr);
+    }
+}
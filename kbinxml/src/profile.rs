@@ -0,0 +1,260 @@
+//! Mapping profiles: the options for converting the files of one game,
+//! stored as JSON or TOML so tools can load them without recompiling.
+//!
+//! Only available with the `profile` feature.
+//!
+//! A TOML profile looks like this:
+//!
+//! ```toml
+//! compression = "compressed"
+//! encoding = "shift_jis"
+//! type_names = "alternate"
+//!
+//! [renames]
+//! "illegal-name" = "legal_name"
+//!
+//! [types]
+//! "/root/entry/id" = "u32"
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
+
+use crate::compression_type::CompressionType;
+use crate::encoding_type::{EncodingError, EncodingType};
+use crate::node_types::{StandardType, UnknownKbinType};
+use crate::options::{
+    describe_conflicts, AttributeNamePolicy, BinarySizePolicy, ControlCharacterPolicy,
+    EmptyArrayPolicy, Endianness, FloatFormat, NameConflictPolicy, Options, OptionsBuilder,
+    OptionsConflict, ParseMode, TypeNamePolicy, UnknownTypeNamePolicy, UnmappableCharacterPolicy,
+};
+
+#[derive(Debug, Snafu)]
+pub enum ProfileError {
+    #[snafu(display("Failed to read profile {}", path.display()))]
+    Read { path: PathBuf, source: io::Error },
+
+    #[snafu(display(
+        "Unknown profile format for {}, expected a .json or .toml file",
+        path.display()
+    ))]
+    UnknownFormat { path: PathBuf },
+
+    #[snafu(display("Failed to parse JSON profile"))]
+    Json { source: serde_json::Error },
+
+    #[snafu(display("Failed to parse TOML profile"))]
+    Toml { source: toml::de::Error },
+
+    #[snafu(display("Unknown encoding {:?} in profile", label))]
+    UnknownEncoding {
+        label: String,
+        source: EncodingError,
+    },
+
+    #[snafu(display("Unknown node type for {} in profile", path))]
+    UnknownType {
+        path: String,
+        source: UnknownKbinType,
+    },
+
+    #[snafu(display("Invalid options in profile: {}", describe_conflicts(conflicts)))]
+    InvalidOptions { conflicts: Vec<OptionsConflict> },
+}
+
+/// The options for converting the files of one game.
+///
+/// Every field is optional in the file and falls back to the default of
+/// `Options`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    pub compression: Option<CompressionType>,
+
+    /// An encoding label, e.g. `shift_jis` or `utf-8`.
+    pub encoding: Option<String>,
+
+    /// Names in documents mapped to the names used in `Node` trees.
+    pub renames: IndexMap<String, String>,
+
+    /// Values for `${name}` placeholders in text XML, see
+    /// `OptionsBuilder::variable`.
+    pub variables: IndexMap<String, String>,
+
+    /// Node paths mapped to the node type their values are serialized as,
+    /// see `OptionsBuilder::serialize_as`.
+    pub types: IndexMap<String, String>,
+
+    /// Node path patterns mapped to the node type their values are forced
+    /// to, see `OptionsBuilder::type_overrides`.
+    pub type_overrides: IndexMap<String, String>,
+
+    pub empty_arrays: EmptyArrayPolicy,
+    pub attribute_names: AttributeNamePolicy,
+    pub type_names: TypeNamePolicy,
+    pub float_format: FloatFormat,
+    pub binary_size: BinarySizePolicy,
+    pub unknown_type_names: UnknownTypeNamePolicy,
+    pub control_characters: ControlCharacterPolicy,
+    pub unmappable_characters: UnmappableCharacterPolicy,
+    pub name_conflicts: NameConflictPolicy,
+    pub endianness: Endianness,
+    pub parse_mode: ParseMode,
+    pub strict_names: bool,
+    pub strict_text_xml: bool,
+    pub lenient_unknown_types: bool,
+    pub decode_nested_documents: bool,
+}
+
+impl Profile {
+    pub fn from_json(input: &str) -> Result<Self, ProfileError> {
+        serde_json::from_str(input).context(JsonSnafu)
+    }
+
+    pub fn from_toml(input: &str) -> Result<Self, ProfileError> {
+        toml::from_str(input).context(TomlSnafu)
+    }
+
+    /// Load a profile, choosing the format by the `.json` or `.toml`
+    /// extension of `path`.
+    pub fn load<P>(path: P) -> Result<Self, ProfileError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        let parse = match extension.as_deref() {
+            Some("json") => Self::from_json,
+            Some("toml") => Self::from_toml,
+            _ => return Err(ProfileError::UnknownFormat { path: path.into() }),
+        };
+
+        let input = fs::read_to_string(path).context(ReadSnafu { path })?;
+        parse(&input)
+    }
+
+    /// Create a builder with the options of this profile, so callers can
+    /// override some of them.
+    pub fn to_builder(&self) -> Result<OptionsBuilder, ProfileError> {
+        let mut builder = Options::builder();
+
+        if let Some(compression) = self.compression {
+            builder.compression(compression);
+        }
+        if let Some(label) = &self.encoding {
+            let encoding =
+                EncodingType::from_label(label.as_bytes()).context(UnknownEncodingSnafu {
+                    label: label.as_str(),
+                })?;
+            builder.encoding(encoding);
+        }
+        for (from, to) in &self.renames {
+            builder.rename(from.as_str(), to.as_str());
+        }
+        for (name, value) in &self.variables {
+            builder.variable(name.as_str(), value.as_str());
+        }
+        for (path, name) in &self.types {
+            let node_type = StandardType::from_name(name).context(UnknownTypeSnafu {
+                path: path.as_str(),
+            })?;
+            builder.serialize_as(path.as_str(), node_type);
+        }
+        let mut overrides = Vec::with_capacity(self.type_overrides.len());
+        for (pattern, name) in &self.type_overrides {
+            let node_type = StandardType::from_name(name).context(UnknownTypeSnafu {
+                path: pattern.as_str(),
+            })?;
+            overrides.push((pattern.as_str(), node_type));
+        }
+        builder.type_overrides(&overrides);
+        builder
+            .empty_arrays(self.empty_arrays)
+            .attribute_names(self.attribute_names)
+            .type_names(self.type_names)
+            .float_format(self.float_format)
+            .binary_size(self.binary_size)
+            .unknown_type_names(self.unknown_type_names)
+            .control_characters(self.control_characters)
+            .unmappable_characters(self.unmappable_characters)
+            .name_conflicts(self.name_conflicts)
+            .endianness(self.endianness)
+            .parse_mode(self.parse_mode)
+            .strict_names(self.strict_names)
+            .strict_text_xml(self.strict_text_xml)
+            .lenient_unknown_types(self.lenient_unknown_types)
+            .decode_nested_documents(self.decode_nested_documents);
+
+        Ok(builder)
+    }
+
+    /// Build the options of this profile, failing if they conflict.
+    pub fn to_options(&self) -> Result<Options, ProfileError> {
+        let options = self.to_builder()?.build();
+
+        let conflicts = options.conflicts();
+        if conflicts.is_empty() {
+            Ok(options)
+        } else {
+            Err(ProfileError::InvalidOptions { conflicts })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compression_type::CompressionType;
+    use crate::encoding_type::EncodingType;
+    use crate::options::TypeNamePolicy;
+
+    use super::{Profile, ProfileError};
+
+    #[test]
+    fn test_profile_formats() {
+        let toml = Profile::from_toml(
+            r#"
+            compression = "uncompressed"
+            encoding = "shift_jis"
+            type_names = "alternate"
+
+            [renames]
+            "illegal-name" = "legal_name"
+
+            [types]
+            "/root/entry/id" = "u32"
+            "#,
+        )
+        .expect("Failed to parse TOML");
+        let json = Profile::from_json(
+            r#"{
+                "compression": "uncompressed",
+                "encoding": "shift_jis",
+                "type_names": "alternate",
+                "renames": { "illegal-name": "legal_name" },
+                "types": { "/root/entry/id": "u32" }
+            }"#,
+        )
+        .expect("Failed to parse JSON");
+        assert_eq!(toml, json);
+
+        let options = toml.to_options().expect("Failed to build options");
+        assert_eq!(options.compression, CompressionType::Uncompressed);
+        assert_eq!(options.encoding, EncodingType::SHIFT_JIS);
+        assert_eq!(options.type_names, TypeNamePolicy::Alternate);
+        assert_eq!(options.decode_name("illegal-name"), "legal_name");
+
+        let err = Profile::from_toml("[types]\n\"/root\" = \"u33\"")
+            .expect("Failed to parse TOML")
+            .to_options()
+            .expect_err("Accepted an unknown type");
+        assert!(matches!(err, ProfileError::UnknownType { .. }));
+    }
+}
@@ -0,0 +1,826 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use snafu::Snafu;
+
+use crate::error::KbinError;
+use crate::node::{NameMatch, Node, NodeCollection, NodeDefinition};
+use crate::value::{compare, Value};
+
+/// The step or attribute name selecting any name.
+const WILDCARD: &str = "*";
+
+#[derive(Debug, Snafu)]
+pub enum QueryError {
+    #[snafu(display("Query is empty"))]
+    Empty,
+
+    #[snafu(display("Unexpected end of query, expected {}", expected))]
+    UnexpectedEnd { expected: &'static str },
+
+    #[snafu(display(
+        "Unexpected character {:?} at position {}, expected {}",
+        found,
+        position,
+        expected
+    ))]
+    UnexpectedCharacter {
+        found: char,
+        position: usize,
+        expected: &'static str,
+    },
+
+    #[snafu(display("Invalid number {:?} at position {}", literal, position))]
+    InvalidNumber { literal: String, position: usize },
+}
+
+/// A comparison operator in a predicate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CompareOp {
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            CompareOp::Eq => ordering == Ordering::Equal,
+            CompareOp::Ne => ordering != Ordering::Equal,
+            CompareOp::Lt => ordering == Ordering::Less,
+            CompareOp::Le => ordering != Ordering::Greater,
+            CompareOp::Gt => ordering == Ordering::Greater,
+            CompareOp::Ge => ordering != Ordering::Less,
+        }
+    }
+}
+
+impl fmt::Display for CompareOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            CompareOp::Eq => "=",
+            CompareOp::Ne => "!=",
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+        })
+    }
+}
+
+/// What a predicate looks at, relative to the node being filtered.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Operand {
+    /// The value of the node itself, written `.`.
+    Value,
+
+    /// The descendants reached by following `path`, or one of their
+    /// attributes, e.g. `info/bpm` or `info/@id`. An empty path with an
+    /// attribute is an attribute of the node itself, e.g. `@id`.
+    Path {
+        path: Vec<String>,
+        attribute: Option<String>,
+    },
+}
+
+/// A condition on a node, written in brackets after a step name.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Predicate {
+    /// The operand resolves to at least one node, attribute or value.
+    Exists(Operand),
+
+    /// Any value the operand resolves to compares to `value` with `op`,
+    /// following the conversions of `compare`.
+    Compare {
+        operand: Operand,
+        op: CompareOp,
+        value: Value,
+    },
+
+    And(Box<Predicate>, Box<Predicate>),
+    Or(Box<Predicate>, Box<Predicate>),
+    Not(Box<Predicate>),
+}
+
+/// A filter on the nodes selected by a step, written in brackets after the
+/// step name. Filters apply in order to the nodes selected under each
+/// parent.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Filter {
+    /// Keep the nodes matching the predicate.
+    Predicate(Predicate),
+
+    /// Keep only the node at this index, counting from 0 like the indices
+    /// of `Node::pointer`, e.g. `music[0]`.
+    Index(usize),
+}
+
+/// A node name to select, followed by the filters the nodes must pass. The
+/// name `*` selects nodes with any name.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    pub name: String,
+    pub filters: Vec<Filter>,
+}
+
+/// A compiled path query, e.g. `music[info/bpm > 200]/info`,
+/// `/root/music[@id="403"]` or `music[0]/*/@title`.
+///
+/// Each step selects children of the nodes selected by the previous step.
+/// A query starting with `/` matches its first step against the node it is
+/// run on instead of that node's children. A query ending with `@name` or
+/// `@*` selects attributes of the nodes selected by the last step.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Query {
+    pub absolute: bool,
+    pub steps: Vec<Step>,
+    pub attribute: Option<String>,
+}
+
+/// A node or attribute selected by running a query on a `NodeCollection`.
+#[derive(Clone, Copy, Debug)]
+pub enum Selected<'a> {
+    Node(&'a NodeCollection),
+    Attribute(&'a NodeDefinition),
+}
+
+impl<'a> Selected<'a> {
+    /// Decode the value of the selected node or attribute.
+    pub fn value(&self) -> Result<Value, KbinError> {
+        match self {
+            Selected::Node(node) => node.base().value(),
+            Selected::Attribute(attribute) => attribute.value(),
+        }
+    }
+}
+
+/// The tree operations queries need, so they run on both `Node` trees and
+/// the `NodeCollection` trees of binary documents.
+trait Queryable {
+    type Attribute;
+
+    fn query_name(&self) -> Option<Cow<'_, str>>;
+    fn query_value(&self) -> Option<Value>;
+    fn query_children(&self) -> Vec<&Self>;
+    fn query_attributes(&self) -> Vec<(Cow<'_, str>, &Self::Attribute)>;
+    fn attribute_value(attribute: &Self::Attribute) -> Option<Value>;
+}
+
+impl Queryable for Node {
+    type Attribute = String;
+
+    fn query_name(&self) -> Option<Cow<'_, str>> {
+        Some(Cow::Borrowed(self.key()))
+    }
+
+    fn query_value(&self) -> Option<Value> {
+        self.value().cloned()
+    }
+
+    fn query_children(&self) -> Vec<&Self> {
+        self.children().iter().collect()
+    }
+
+    fn query_attributes(&self) -> Vec<(Cow<'_, str>, &String)> {
+        self.attributes()
+            .iter()
+            .map(|(key, value)| (Cow::Borrowed(key.as_str()), value))
+            .collect()
+    }
+
+    fn attribute_value(attribute: &String) -> Option<Value> {
+        Some(Value::Attribute(attribute.clone()))
+    }
+}
+
+/// Nodes and attributes with names that fail to decode never match.
+impl Queryable for NodeCollection {
+    type Attribute = NodeDefinition;
+
+    fn query_name(&self) -> Option<Cow<'_, str>> {
+        self.base().key().ok().flatten().map(Cow::Owned)
+    }
+
+    fn query_value(&self) -> Option<Value> {
+        self.base().value().ok()
+    }
+
+    fn query_children(&self) -> Vec<&Self> {
+        self.children().iter().collect()
+    }
+
+    fn query_attributes(&self) -> Vec<(Cow<'_, str>, &NodeDefinition)> {
+        self.attributes()
+            .iter()
+            .filter_map(|attribute| Some((Cow::Owned(attribute.key().ok()??), attribute)))
+            .collect()
+    }
+
+    fn attribute_value(attribute: &NodeDefinition) -> Option<Value> {
+        attribute.value().ok()
+    }
+}
+
+fn name_matches(name_match: NameMatch, name: &str, query: &str) -> bool {
+    query == WILDCARD || name_match.matches(name, query)
+}
+
+fn has_name<N: Queryable>(node: &N, query: &str, name_match: NameMatch) -> bool {
+    node.query_name()
+        .is_some_and(|name| name_matches(name_match, &name, query))
+}
+
+fn matching_attributes<'a, N: Queryable>(
+    node: &'a N,
+    attribute: &str,
+    name_match: NameMatch,
+) -> Vec<&'a N::Attribute> {
+    node.query_attributes()
+        .into_iter()
+        .filter(|(key, _)| name_matches(name_match, key, attribute))
+        .map(|(_, value)| value)
+        .collect()
+}
+
+fn resolve_nodes<'a, N: Queryable>(
+    node: &'a N,
+    path: &[String],
+    name_match: NameMatch,
+) -> Vec<&'a N> {
+    let mut nodes = vec![node];
+
+    for name in path {
+        nodes = nodes
+            .into_iter()
+            .flat_map(|node| node.query_children())
+            .filter(|child| has_name(*child, name, name_match))
+            .collect();
+    }
+
+    nodes
+}
+
+impl Operand {
+    fn resolve<N: Queryable>(&self, node: &N, name_match: NameMatch) -> Vec<Value> {
+        match self {
+            Operand::Value => node.query_value().into_iter().collect(),
+            Operand::Path { path, attribute } => {
+                let nodes = resolve_nodes(node, path, name_match);
+                match attribute {
+                    Some(attribute) => nodes
+                        .into_iter()
+                        .flat_map(|node| matching_attributes(node, attribute, name_match))
+                        .filter_map(N::attribute_value)
+                        .collect(),
+                    None => nodes
+                        .into_iter()
+                        .filter_map(|node| node.query_value())
+                        .collect(),
+                }
+            },
+        }
+    }
+
+    fn exists<N: Queryable>(&self, node: &N, name_match: NameMatch) -> bool {
+        match self {
+            Operand::Path {
+                path,
+                attribute: None,
+            } => !resolve_nodes(node, path, name_match).is_empty(),
+            operand => !operand.resolve(node, name_match).is_empty(),
+        }
+    }
+}
+
+impl Predicate {
+    /// Check `node` against this predicate, comparing names with
+    /// `name_match`.
+    #[inline]
+    pub fn matches(&self, node: &Node, name_match: NameMatch) -> bool {
+        self.evaluate(node, name_match)
+    }
+
+    fn evaluate<N: Queryable>(&self, node: &N, name_match: NameMatch) -> bool {
+        match self {
+            Predicate::Exists(operand) => operand.exists(node, name_match),
+            Predicate::Compare { operand, op, value } => operand
+                .resolve(node, name_match)
+                .iter()
+                .any(|found| compare(found, value).is_some_and(|ordering| op.holds(ordering))),
+            Predicate::And(a, b) => a.evaluate(node, name_match) && b.evaluate(node, name_match),
+            Predicate::Or(a, b) => a.evaluate(node, name_match) || b.evaluate(node, name_match),
+            Predicate::Not(predicate) => !predicate.evaluate(node, name_match),
+        }
+    }
+}
+
+impl Step {
+    fn apply<'a, N: Queryable>(&self, candidates: Vec<&'a N>, name_match: NameMatch) -> Vec<&'a N> {
+        let mut nodes: Vec<&N> = candidates
+            .into_iter()
+            .filter(|node| has_name(*node, &self.name, name_match))
+            .collect();
+
+        for filter in &self.filters {
+            nodes = match filter {
+                Filter::Predicate(predicate) => nodes
+                    .into_iter()
+                    .filter(|node| predicate.evaluate(*node, name_match))
+                    .collect(),
+                Filter::Index(index) => nodes.get(*index).copied().into_iter().collect(),
+            };
+        }
+
+        nodes
+    }
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Self, QueryError> {
+        Parser::new(input).query()
+    }
+
+    /// Run the query on `node`, returning the selected nodes in document
+    /// order.
+    ///
+    /// If the query ends with an attribute, this returns the nodes having
+    /// that attribute, see `select_attributes` for the attributes
+    /// themselves.
+    #[inline]
+    pub fn select<'a>(&self, node: &'a Node) -> Vec<&'a Node> {
+        self.select_matching(node, NameMatch::Exact)
+    }
+
+    /// Like `select`, comparing names with `name_match`.
+    pub fn select_matching<'a>(&self, node: &'a Node, name_match: NameMatch) -> Vec<&'a Node> {
+        let mut nodes = self.select_nodes(node, name_match);
+        if let Some(attribute) = &self.attribute {
+            nodes.retain(|node| !matching_attributes(*node, attribute, name_match).is_empty());
+        }
+
+        nodes
+    }
+
+    /// Run the query on `node`, returning the names and values of the
+    /// selected attributes. Returns nothing if the query does not end with
+    /// an attribute.
+    pub fn select_attributes<'a>(&self, node: &'a Node) -> Vec<(&'a str, &'a str)> {
+        let attribute = match &self.attribute {
+            Some(attribute) => attribute,
+            None => return Vec::new(),
+        };
+
+        self.select_nodes(node, NameMatch::Exact)
+            .into_iter()
+            .flat_map(|node| node.attributes())
+            .filter(|(key, _)| name_matches(NameMatch::Exact, key, attribute))
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect()
+    }
+
+    /// Run the query on a node tree read from a binary document, returning
+    /// the selected nodes, or attributes if the query ends with one.
+    #[inline]
+    pub fn select_collection<'a>(&self, collection: &'a NodeCollection) -> Vec<Selected<'a>> {
+        self.select_collection_matching(collection, NameMatch::Exact)
+    }
+
+    /// Like `select_collection`, comparing names with `name_match`.
+    pub fn select_collection_matching<'a>(
+        &self,
+        collection: &'a NodeCollection,
+        name_match: NameMatch,
+    ) -> Vec<Selected<'a>> {
+        let nodes = self.select_nodes(collection, name_match);
+
+        match &self.attribute {
+            Some(attribute) => nodes
+                .into_iter()
+                .flat_map(|node| matching_attributes(node, attribute, name_match))
+                .map(Selected::Attribute)
+                .collect(),
+            None => nodes.into_iter().map(Selected::Node).collect(),
+        }
+    }
+
+    fn select_nodes<'a, N: Queryable>(&self, node: &'a N, name_match: NameMatch) -> Vec<&'a N> {
+        let mut steps = self.steps.iter();
+        let mut selected = match steps.next() {
+            Some(step) if self.absolute => step.apply(vec![node], name_match),
+            Some(step) => step.apply(node.query_children(), name_match),
+            None => return vec![node],
+        };
+
+        for step in steps {
+            selected = selected
+                .into_iter()
+                .flat_map(|node| step.apply(node.query_children(), name_match))
+                .collect();
+        }
+
+        selected
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    #[inline]
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        Query::parse(input)
+    }
+}
+
+/// Parse `query` and run it on `node`.
+pub fn select<'a>(node: &'a Node, query: &str) -> Result<Vec<&'a Node>, QueryError> {
+    Ok(Query::parse(query)?.select(node))
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+fn is_name_char(c: char) -> bool {
+    !c.is_whitespace() && !"/[]()@=!<>\"'.".contains(c)
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self { input, position: 0 }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.position..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if self.rest().starts_with(token) {
+            self.position += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Consume a keyword that is not the start of a longer name.
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let rest = self.rest();
+        let is_keyword = rest.starts_with(keyword) &&
+            !rest[keyword.len()..].starts_with(|c: char| is_name_char(c) || c == '.');
+        if is_keyword {
+            self.position += keyword.len();
+        }
+
+        is_keyword
+    }
+
+    fn unexpected(&self, expected: &'static str) -> QueryError {
+        match self.peek() {
+            Some(found) => QueryError::UnexpectedCharacter {
+                found,
+                position: self.position,
+                expected,
+            },
+            None => QueryError::UnexpectedEnd { expected },
+        }
+    }
+
+    fn expect(&mut self, token: &'static str) -> Result<(), QueryError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.unexpected(token))
+        }
+    }
+
+    fn name(&mut self) -> Result<String, QueryError> {
+        let rest = self.rest();
+        let len = rest.find(|c: char| !is_name_char(c)).unwrap_or(rest.len());
+        if len == 0 {
+            return Err(self.unexpected("a node name"));
+        }
+        self.position += len;
+
+        Ok(rest[..len].to_owned())
+    }
+
+    fn query(mut self) -> Result<Query, QueryError> {
+        self.skip_whitespace();
+        if self.rest().is_empty() {
+            return Err(QueryError::Empty);
+        }
+
+        let absolute = self.eat("/");
+        let mut steps = Vec::new();
+        let mut attribute = None;
+        loop {
+            if self.eat("@") {
+                attribute = Some(self.name()?);
+                break;
+            }
+            steps.push(self.step()?);
+            if !self.eat("/") {
+                break;
+            }
+        }
+
+        self.skip_whitespace();
+        if !self.rest().is_empty() {
+            return Err(self.unexpected("`/` or the end of the query"));
+        }
+
+        Ok(Query {
+            absolute,
+            steps,
+            attribute,
+        })
+    }
+
+    fn step(&mut self) -> Result<Step, QueryError> {
+        let name = self.name()?;
+        let mut filters = Vec::new();
+
+        while self.eat("[") {
+            self.skip_whitespace();
+            let filter = match self.index() {
+                Some(index) => Filter::Index(index),
+                None => Filter::Predicate(self.or()?),
+            };
+            filters.push(filter);
+            self.skip_whitespace();
+            self.expect("]")?;
+        }
+
+        Ok(Step { name, filters })
+    }
+
+    /// Consume an index filter, a bracket holding only digits.
+    fn index(&mut self) -> Option<usize> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if len == 0 || !rest[len..].trim_start().starts_with(']') {
+            return None;
+        }
+        let index = rest[..len].parse().ok()?;
+        self.position += len;
+
+        Some(index)
+    }
+
+    fn or(&mut self) -> Result<Predicate, QueryError> {
+        let mut predicate = self.and()?;
+
+        loop {
+            self.skip_whitespace();
+            if !self.eat_keyword("or") {
+                return Ok(predicate);
+            }
+            predicate = Predicate::Or(Box::new(predicate), Box::new(self.and()?));
+        }
+    }
+
+    fn and(&mut self) -> Result<Predicate, QueryError> {
+        let mut predicate = self.unary()?;
+
+        loop {
+            self.skip_whitespace();
+            if !self.eat_keyword("and") {
+                return Ok(predicate);
+            }
+            predicate = Predicate::And(Box::new(predicate), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Predicate, QueryError> {
+        self.skip_whitespace();
+
+        if self.eat_keyword("not") {
+            self.skip_whitespace();
+            self.expect("(")?;
+            let predicate = self.or()?;
+            self.skip_whitespace();
+            self.expect(")")?;
+
+            return Ok(Predicate::Not(Box::new(predicate)));
+        }
+        if self.eat("(") {
+            let predicate = self.or()?;
+            self.skip_whitespace();
+            self.expect(")")?;
+
+            return Ok(predicate);
+        }
+
+        let operand = self.operand()?;
+        self.skip_whitespace();
+
+        let op = if self.eat("!=") {
+            CompareOp::Ne
+        } else if self.eat("<=") {
+            CompareOp::Le
+        } else if self.eat(">=") {
+            CompareOp::Ge
+        } else if self.eat("=") {
+            CompareOp::Eq
+        } else if self.eat("<") {
+            CompareOp::Lt
+        } else if self.eat(">") {
+            CompareOp::Gt
+        } else {
+            return Ok(Predicate::Exists(operand));
+        };
+
+        self.skip_whitespace();
+        let value = self.literal()?;
+
+        Ok(Predicate::Compare { operand, op, value })
+    }
+
+    fn operand(&mut self) -> Result<Operand, QueryError> {
+        if self.eat(".") {
+            return Ok(Operand::Value);
+        }
+
+        let mut path = Vec::new();
+        loop {
+            if self.eat("@") {
+                let attribute = Some(self.name()?);
+                return Ok(Operand::Path { path, attribute });
+            }
+            path.push(self.name()?);
+            if !self.eat("/") {
+                return Ok(Operand::Path {
+                    path,
+                    attribute: None,
+                });
+            }
+        }
+    }
+
+    fn literal(&mut self) -> Result<Value, QueryError> {
+        let quote = match self.peek() {
+            Some(quote @ '"') | Some(quote @ '\'') => quote,
+            Some(_) => return self.number(),
+            None => return Err(self.unexpected("a string or number")),
+        };
+
+        self.position += 1;
+        let rest = self.rest();
+        let end = rest.find(quote).ok_or(QueryError::UnexpectedEnd {
+            expected: "a closing quote",
+        })?;
+        self.position += end + 1;
+
+        Ok(Value::String(rest[..end].to_owned()))
+    }
+
+    fn number(&mut self) -> Result<Value, QueryError> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || "+-.".contains(c)))
+            .unwrap_or(rest.len());
+        let literal = &rest[..len];
+        if literal.is_empty() {
+            return Err(self.unexpected("a string or number"));
+        }
+
+        let value = if let Ok(integer) = literal.parse::<i64>() {
+            Value::S64(integer)
+        } else if let Ok(float) = literal.parse::<f64>() {
+            Value::Double(float)
+        } else {
+            return Err(QueryError::InvalidNumber {
+                literal: literal.to_owned(),
+                position: self.position,
+            });
+        };
+        self.position += len;
+
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::node::{NameMatch, Node};
+    use crate::value::Value;
+
+    use super::{select, Query, QueryError, Selected};
+
+    fn music(id: &str, bpm: u16, title: &str) -> Node {
+        let mut info = Node::new("info");
+        info.append_child(Node::with_value("bpm", Value::U16(bpm)));
+        info.append_child(Node::with_value("title", Value::String(title.into())));
+
+        Node::with("music", &[("id", id)], vec![info])
+    }
+
+    #[test]
+    fn test_query_predicates() {
+        let root = Node::with_nodes("root", vec![
+            music("401", 150, "first"),
+            music("403", 210, "second"),
+            music("405", 240, "third"),
+        ]);
+        let ids = |query: &str| -> Vec<String> {
+            select(&root, query)
+                .expect("Failed to parse query")
+                .iter()
+                .map(|node| node.attributes()["id"].clone())
+                .collect()
+        };
+
+        assert_eq!(ids("music[info/bpm > 200]"), ["403", "405"]);
+        assert_eq!(ids(r#"/root/music[@id="403"]"#), ["403"]);
+        assert_eq!(ids("music[@id >= 403 and not(info/bpm = 240)]"), ["403"]);
+        assert_eq!(ids("music[info/title = 'first' or info/bpm >= 240]"), [
+            "401", "405"
+        ]);
+        assert_eq!(ids("music[info/missing]").len(), 0);
+
+        let titles = select(&root, "music[@id != '401']/info/title[. < 'third']")
+            .expect("Failed to parse query");
+        assert_eq!(titles.len(), 1);
+        assert_eq!(titles[0].value(), Some(&Value::String("second".into())));
+
+        let query: Query = "MUSIC[INFO/BPM < 200]"
+            .parse()
+            .expect("Failed to parse query");
+        assert!(query.select(&root).is_empty());
+        assert_eq!(
+            query
+                .select_matching(&root, NameMatch::CaseInsensitive)
+                .len(),
+            1
+        );
+
+        assert!(matches!(
+            Query::parse("music[@id = ]"),
+            Err(QueryError::UnexpectedCharacter { found: ']', .. })
+        ));
+        assert!(matches!(
+            Query::parse("music[@id"),
+            Err(QueryError::UnexpectedEnd { .. })
+        ));
+    }
+
+    #[test]
+    fn test_query_wildcards() {
+        let root = Node::with_nodes("root", vec![
+            music("401", 150, "first"),
+            music("403", 210, "second"),
+        ]);
+
+        let query = Query::parse("music[1]/*/@*").expect("Failed to parse query");
+        assert!(query.select_attributes(&root).is_empty());
+        let query = Query::parse("music[1]/@id").expect("Failed to parse query");
+        assert_eq!(query.select_attributes(&root), [("id", "403")]);
+        assert_eq!(query.select(&root).len(), 1);
+
+        let titles =
+            select(&root, "*[info/bpm > 100][0]/info/*[1]").expect("Failed to parse query");
+        assert_eq!(titles.len(), 1);
+        assert_eq!(titles[0].value(), Some(&Value::String("first".into())));
+
+        let bytes = crate::to_binary(&root).expect("Failed to encode");
+        let (collection, _) = crate::from_binary(bytes.into()).expect("Failed to decode");
+        let values = |query: &str| -> Vec<Value> {
+            collection
+                .select(query)
+                .expect("Failed to parse query")
+                .iter()
+                .map(|selected| selected.value().expect("Failed to decode value"))
+                .collect()
+        };
+        assert_eq!(values("music/@id"), [
+            Value::Attribute("401".into()),
+            Value::Attribute("403".into())
+        ]);
+        assert_eq!(values("music[@id = 403]/info/title"), [Value::String(
+            "second".into()
+        )]);
+        assert!(matches!(
+            collection
+                .select("music[0]")
+                .expect("Failed to parse query")[..],
+            [Selected::Node(_)]
+        ));
+    }
+}
@@ -1,15 +1,20 @@
 use std::io::{self, Cursor, Seek, SeekFrom};
+use std::mem;
 
 use byteorder::{BigEndian, ReadBytesExt};
 use bytes::Bytes;
 use snafu::{ResultExt, Snafu};
 
-use crate::byte_buffer::{ByteBufferError, ByteBufferRead};
+use crate::accounting::Accountant;
+use crate::byte_buffer::{strip_trailing_null_bytes, ByteBufferError, ByteBufferRead};
 use crate::compression_type::{CompressionType, UnknownCompression};
+use crate::diff::Paths;
 use crate::encoding_type::{EncodingError, EncodingType};
-use crate::node::{Key, NodeData, NodeDefinition};
+use crate::node::{Key, NodeCollection, NodeData, NodeDefinition};
 use crate::node_types::{StandardType, UnknownKbinType};
+use crate::options::{describe_conflicts, Endianness, Options, OptionsConflict, ParseMode};
 use crate::sixbit::{Sixbit, SixbitError};
+use crate::validate::{is_whole_array, Problem, ProblemKind};
 use crate::{ARRAY_MASK, SIGNATURE};
 
 #[derive(Debug, Snafu)]
@@ -35,8 +40,12 @@ pub enum ReaderError {
     #[snafu(display("Invalid encoding type read from header"))]
     InvalidEncoding { source: EncodingError },
 
-    #[snafu(display("Mismatched encoding type and encoding type inverted values from header"))]
-    MismatchedEncoding,
+    #[snafu(display(
+        "Mismatched encoding type and encoding type inverted values from header (encoding: 0x{:02x}, inverted: 0x{:02x})",
+        encoding,
+        negation
+    ))]
+    MismatchedEncoding { encoding: u8, negation: u8 },
 
     #[snafu(display("Failed to read node buffer length"))]
     NodeBufferLength { source: io::Error },
@@ -44,6 +53,13 @@ pub enum ReaderError {
     #[snafu(display("Failed to read data buffer length"))]
     DataBufferLength { source: io::Error },
 
+    #[snafu(display(
+        "Node buffer length of {} bytes exceeds the {} bytes after the header",
+        declared,
+        remaining
+    ))]
+    NodeBufferLengthExceedsInput { declared: usize, remaining: usize },
+
     #[snafu(display(
         "Failed to seek forward {} bytes in input buffer for data buffer length",
         len_node
@@ -59,15 +75,54 @@ pub enum ReaderError {
     #[snafu(display("Invalid node type read"))]
     InvalidNodeType { source: UnknownKbinType },
 
+    #[snafu(display(
+        "Unknown node type {} at offset 0x{:x} (surrounding bytes from 0x{:x}: {:02x?})",
+        node.type_id,
+        node.offset,
+        node.context_offset,
+        node.context
+    ))]
+    UnknownNodeType { node: UnknownNode },
+
+    #[snafu(display("Malformed document: {}", problem))]
+    Malformed { problem: Problem },
+
     #[snafu(display("Failed to read sixbit node name"))]
     NodeSixbitName { source: SixbitError },
 
+    // Array lengths are checked against the data buffer, so failures are
+    // reported as `DataBuffer` instead.
     #[snafu(display("Failed to read array node length"))]
     ArrayLength { source: io::Error },
 
     #[snafu(display("Failed to read node name length"))]
     NameLength { source: io::Error },
 
+    #[snafu(display("Invalid node name at offset 0x{:x}", offset))]
+    InvalidNodeName { offset: u64 },
+
+    #[snafu(display(
+        "Memory budget of {} bytes exceeded at offset 0x{:x} (needed {} bytes)",
+        budget,
+        offset,
+        needed
+    ))]
+    MemoryBudget {
+        budget: usize,
+        needed: usize,
+        offset: u64,
+    },
+
+    #[snafu(display(
+        "Document has more than the maximum of {} nodes at offset {}",
+        max_nodes,
+        offset
+    ))]
+    TooManyNodes { max_nodes: usize, offset: u64 },
+
+    #[snafu(display("Invalid options: {}", describe_conflicts(conflicts)))]
+    InvalidOptions { conflicts: Vec<OptionsConflict> },
+
     #[snafu(display("Failed to read {} bytes from data buffer", size))]
     DataRead { size: usize, source: io::Error },
 
@@ -82,6 +137,61 @@ pub enum ReaderError {
         node_type: StandardType,
         source: ByteBufferError,
     },
+
+    #[snafu(display(
+        "Failed to read node {} at offset 0x{:x} (data offset 0x{:x})",
+        path,
+        node_offset,
+        data_offset
+    ))]
+    Node {
+        path: String,
+        node_offset: u64,
+        data_offset: u64,
+        source: Box<ReaderError>,
+    },
+}
+
+impl ReaderError {
+    /// The error without the location added by `ReaderError::Node`.
+    pub fn inner(&self) -> &ReaderError {
+        match self {
+            ReaderError::Node { source, .. } => source.inner(),
+            error => error,
+        }
+    }
+}
+
+/// The name of the attribute added to nodes of unknown type when reading with
+/// `OptionsBuilder::lenient_unknown_types`.
+pub(crate) const UNKNOWN_TYPE_ATTRIBUTE: &str = "__unknown_type";
+
+/// The number of node buffer bytes captured on each side of an unknown type.
+const UNKNOWN_CONTEXT_LEN: usize = 8;
+
+/// A node with a type id that is reserved or not supported.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownNode {
+    /// The type id, without the array flag.
+    pub type_id: u8,
+    pub is_array: bool,
+
+    /// The absolute offset of the type id in the input.
+    pub offset: u64,
+
+    /// The node buffer bytes around the type id.
+    pub context: Vec<u8>,
+
+    /// The absolute offset of the first byte of `context` in the input.
+    pub context_offset: u64,
+}
+
+/// Checks that a raw node name is valid UTF-8 without control characters.
+fn is_strict_name(data: &[u8]) -> bool {
+    match std::str::from_utf8(data) {
+        Ok(name) => !name.is_empty() && !name.chars().any(char::is_control),
+        Err(_) => false,
+    }
 }
 
 pub struct Reader {
@@ -92,10 +202,28 @@ pub struct Reader {
     pub(crate) data_buf: ByteBufferRead,
 
     data_buf_start: u64,
+
+    options: Options,
+    accountant: Accountant,
+
+    pending: Option<NodeDefinition>,
+    unknown_nodes: Vec<UnknownNode>,
+    warnings: Vec<Problem>,
+    paths: Paths,
 }
 
 impl Reader {
+    #[inline]
     pub fn new(input: Bytes) -> Result<Self, ReaderError> {
+        Self::with_options(input, Options::default())
+    }
+
+    pub fn with_options(input: Bytes, options: Options) -> Result<Self, ReaderError> {
+        let conflicts = options.read_conflicts();
+        if !conflicts.is_empty() {
+            return Err(ReaderError::InvalidOptions { conflicts });
+        }
+
         let mut header = Cursor::new(&input);
 
         let signature = header.read_u8().context(SignatureSnafu)?;
@@ -109,10 +237,30 @@ impl Reader {
 
         let encoding_byte = header.read_u8().context(EncodingSnafu)?;
         let encoding_negation = header.read_u8().context(EncodingNegateSnafu)?;
-        let encoding = EncodingType::from_byte(encoding_byte).context(InvalidEncodingSnafu)?;
+        let mut warnings = Vec::new();
         if encoding_negation != !encoding_byte {
-            return Err(ReaderError::MismatchedEncoding);
+            let lenient = options.parse_mode == ParseMode::Lenient;
+            if !options.trusts_encoding_byte() {
+                return Err(ReaderError::MismatchedEncoding {
+                    encoding: encoding_byte,
+                    negation: encoding_negation,
+                });
+            }
+            warn!(
+                "encoding: 0x{:02X} does not match inverted value 0x{:02X}, trusting encoding",
+                encoding_byte, encoding_negation
+            );
+            if lenient {
+                warnings.push(Problem {
+                    offset: 3,
+                    kind: ProblemKind::MismatchedEncoding {
+                        encoding: encoding_byte,
+                        negation: encoding_negation,
+                    },
+                });
+            }
         }
+        let encoding = EncodingType::from_byte(encoding_byte).context(InvalidEncodingSnafu)?;
 
         info!(
             "signature: 0x{:X}, compression: 0x{:X} ({:?}), encoding: 0x{:X} ({:?})",
@@ -124,6 +272,15 @@ impl Reader {
             .context(NodeBufferLengthSnafu)?;
         info!("len_node: {0} (0x{0:x})", len_node);
 
+        // The data buffer length follows the node buffer.
+        let remaining = input.len().saturating_sub(8);
+        if len_node as usize + 4 > remaining {
+            return Err(ReaderError::NodeBufferLengthExceedsInput {
+                declared: len_node as usize,
+                remaining,
+            });
+        }
+
         // The length of the data buffer is the 4 bytes right after the node buffer.
         header
             .seek(SeekFrom::Current(len_node as i64))
@@ -150,6 +307,14 @@ impl Reader {
             data_buf,
 
             data_buf_start: data_buffer_start as u64,
+
+            accountant: Accountant::new(options.memory_budget).with_max_nodes(options.max_nodes),
+            options,
+
+            pending: None,
+            unknown_nodes: Vec::new(),
+            warnings,
+            paths: Paths::new(),
         })
     }
 
@@ -171,6 +336,161 @@ impl Reader {
         self.encoding
     }
 
+    #[inline]
+    pub fn compression(&self) -> CompressionType {
+        self.compression
+    }
+
+    /// The absolute offset in the input of the next node buffer read.
+    #[inline]
+    pub(crate) fn node_offset(&self) -> u64 {
+        8 + self.node_buf.position()
+    }
+
+    /// The absolute offset in the input of the most recent data buffer read.
+    #[inline]
+    pub(crate) fn last_data_offset(&self) -> u64 {
+        self.data_buf_start + self.data_buf.last_read_offset() as u64
+    }
+
+    /// The nodes of unknown type read so far with
+    /// `OptionsBuilder::lenient_unknown_types`.
+    #[inline]
+    pub fn unknown_nodes(&self) -> &[UnknownNode] {
+        &self.unknown_nodes
+    }
+
+    /// The problems recovered from so far in `ParseMode::Lenient`.
+    #[inline]
+    pub fn warnings(&self) -> &[Problem] {
+        &self.warnings
+    }
+
+    /// Memory and node count statistics for the definitions read so far.
+    #[inline]
+    pub fn accountant(&self) -> &Accountant {
+        &self.accountant
+    }
+
+    /// Charge the space `def` takes in a `NodeCollection`. Names and values
+    /// are slices of the input, so only the definition itself is counted.
+    fn account(&mut self, offset: u64, def: &NodeDefinition) -> Result<(), ReaderError> {
+        let len = match def.node_type {
+            StandardType::Attribute => mem::size_of::<NodeDefinition>(),
+            StandardType::NodeEnd | StandardType::FileEnd => return Ok(()),
+            _ => mem::size_of::<NodeCollection>(),
+        };
+        self.accountant
+            .allocate(len)
+            .map_err(|needed| ReaderError::MemoryBudget {
+                budget: self.accountant.budget().unwrap_or_default(),
+                needed,
+                offset,
+            })?;
+
+        if def.node_type != StandardType::Attribute {
+            self.accountant
+                .count_node()
+                .map_err(|_| ReaderError::TooManyNodes {
+                    max_nodes: self.accountant.max_nodes().unwrap_or_default(),
+                    offset,
+                })?;
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    fn is_lenient(&self) -> bool {
+        self.options.parse_mode == ParseMode::Lenient
+    }
+
+    /// Fail with the problem in `ParseMode::Strict` or record it in
+    /// `ParseMode::Lenient`.
+    fn report(&mut self, offset: u64, kind: ProblemKind) -> Result<(), ReaderError> {
+        let problem = Problem { offset, kind };
+        match self.options.parse_mode {
+            ParseMode::Strict => return Err(ReaderError::Malformed { problem }),
+            ParseMode::Lenient => {
+                warn!("recovered from malformed document: {}", problem);
+                self.warnings.push(problem);
+            },
+            ParseMode::Unchecked => {},
+        };
+
+        Ok(())
+    }
+
+    /// Check the alignment padding between the end of the `len` bytes just
+    /// read from the data buffer and the next read.
+    fn check_padding(&mut self, len: usize) -> Result<(), ReaderError> {
+        let buf = self.data_buf.get_ref();
+        let end = (self.data_buf.last_read_offset() + len).min(buf.len());
+        let next = (self.data_buf.position() as usize).clamp(end, buf.len());
+
+        match buf[end..next].iter().position(|&b| b != 0) {
+            Some(i) if self.options.parse_mode != ParseMode::Unchecked => {
+                let offset = self.data_buf_start + (end + i) as u64;
+                self.report(offset, ProblemKind::NonZeroPadding)
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that an array holds whole elements, truncating it to them in
+    /// `ParseMode::Lenient`.
+    fn check_array(&mut self, node_type: StandardType, data: Bytes) -> Result<Bytes, ReaderError> {
+        if is_whole_array(node_type, data.len()) {
+            return Ok(data);
+        }
+
+        let offset = self.last_data_offset();
+        self.report(offset, ProblemKind::ArrayLength {
+            node_type,
+            length: data.len(),
+        })?;
+        if self.is_lenient() {
+            let size = node_type.size * node_type.count;
+            Ok(data.slice(..data.len() / size * size))
+        } else {
+            Ok(data)
+        }
+    }
+
+    /// Read the file end after the root node and check that nothing but
+    /// padding follows it in either buffer, see `OptionsBuilder::parse_mode`.
+    ///
+    /// Does nothing in `ParseMode::Unchecked`.
+    pub fn read_file_end(&mut self) -> Result<(), ReaderError> {
+        if self.options.parse_mode == ParseMode::Unchecked {
+            return Ok(());
+        }
+
+        let offset = self.node_offset();
+        match self.read_node_type() {
+            Ok((StandardType::FileEnd, _)) => {
+                let position = self.node_buf.position() as usize;
+                let rest = &self.node_buf.get_ref()[position..];
+                if let Some(i) = rest.iter().position(|&b| b != 0) {
+                    self.report(self.node_offset() + i as u64, ProblemKind::TrailingNodeData)?;
+                }
+            },
+            Ok(_) | Err(ReaderError::UnknownNodeType { .. }) => {
+                self.report(offset, ProblemKind::TrailingNodeData)?
+            },
+            Err(_) => self.report(offset, ProblemKind::MissingFileEnd)?,
+        };
+
+        let buf = self.data_buf.get_ref();
+        let position = (self.data_buf.position() as usize).min(buf.len());
+        if let Some(i) = buf[position..].iter().position(|&b| b != 0) {
+            let offset = self.data_buf_start + (position + i) as u64;
+            self.report(offset, ProblemKind::TrailingValueData)?;
+        }
+
+        Ok(())
+    }
+
     pub fn check_if_node_buffer_end(&self) -> Result<(), ReaderError> {
         if self.node_buf.position() >= self.data_buf_start {
             Err(ReaderError::EndOfNodeBuffer)
@@ -182,12 +502,138 @@ impl Reader {
     pub fn read_node_type(&mut self) -> Result<(StandardType, bool), ReaderError> {
         self.check_if_node_buffer_end()?;
 
+        let offset = self.node_offset();
         let raw_node_type = self.node_buf.read_u8().context(NodeTypeSnafu)?;
-        let value = Self::parse_node_type(raw_node_type)?;
+        let value =
+            Self::parse_node_type(raw_node_type).map_err(|_| ReaderError::UnknownNodeType {
+                node: self.unknown_node(raw_node_type, offset),
+            })?;
 
         Ok(value)
     }
 
+    fn unknown_node(&self, raw_node_type: u8, offset: u64) -> UnknownNode {
+        let buf = self.node_buf.get_ref();
+        let position = (offset - 8) as usize;
+        let start = position.saturating_sub(UNKNOWN_CONTEXT_LEN);
+        let end = (position + UNKNOWN_CONTEXT_LEN + 1).min(buf.len());
+
+        UnknownNode {
+            type_id: raw_node_type & !ARRAY_MASK,
+            is_array: raw_node_type & ARRAY_MASK == ARRAY_MASK,
+            offset,
+            context: buf[start..end].to_vec(),
+            context_offset: 8 + start as u64,
+        }
+    }
+
+    fn read_key(&mut self, node_type: StandardType) -> Result<Key, ReaderError> {
+        let key = match self.compression {
+            CompressionType::Compressed => {
+                let offset = self.node_offset();
+                let size = Sixbit::size(&mut *self.node_buf).context(NodeSixbitNameSnafu)?;
+                let data = self
+                    .node_buf
+                    .get(size.real_len as u32)
+                    .context(NodeBufferSnafu { node_type })?;
+
+                let padding = size.real_len * 8 - usize::from(size.sixbit_len) * 6;
+                if data.last().is_some_and(|&b| b & ((1 << padding) - 1) != 0) {
+                    self.report(offset, ProblemKind::InvalidName {
+                        reason: "sixbit padding bits are set",
+                    })?;
+                }
+
+                Key::Compressed { size, data }
+            },
+            CompressionType::Uncompressed => {
+                let encoding = self.encoding;
+                let offset = self.node_offset();
+                let length = (self.node_buf.read_u8().context(NameLengthSnafu)? & !ARRAY_MASK) + 1;
+                let data = self
+                    .node_buf
+                    .get(length as u32)
+                    .context(NodeBufferSnafu { node_type })?;
+
+                // Earlier versions of this crate ended names with a null
+                // byte, counted in the length
+                let data = data.slice(..strip_trailing_null_bytes(&data).len());
+
+                if self.options.strict_names && !is_strict_name(&data) {
+                    return Err(ReaderError::InvalidNodeName { offset });
+                }
+
+                Key::Uncompressed { encoding, data }
+            },
+        };
+
+        Ok(key)
+    }
+
+    /// Read the name of a node of unknown type and return it as an empty node.
+    ///
+    /// The value of the node cannot be read because its size is unknown, so
+    /// values read after it may be misaligned if the type stores data.
+    fn read_unknown_node(&mut self, node: UnknownNode) -> Result<NodeDefinition, ReaderError> {
+        let key = self.read_key(StandardType::NodeStart)?;
+        let attribute = NodeDefinition::with_data(
+            self.encoding,
+            StandardType::Attribute,
+            false,
+            NodeData::Some {
+                key: Key::Uncompressed {
+                    encoding: self.encoding,
+                    data: Bytes::from_static(UNKNOWN_TYPE_ATTRIBUTE.as_bytes()),
+                },
+                value_data: Bytes::from(node.type_id.to_string()),
+            },
+        );
+        warn!(
+            "unknown node type {} at offset 0x{:x}, reading as an empty node",
+            node.type_id, node.offset
+        );
+
+        self.pending = Some(attribute);
+        self.unknown_nodes.push(node);
+
+        Ok(NodeDefinition::with_data(
+            self.encoding,
+            StandardType::NodeStart,
+            false,
+            NodeData::Some {
+                key,
+                value_data: Bytes::new(),
+            },
+        ))
+    }
+
+    /// Read an array of unknown type as a `Value::Unknown` holding its raw
+    /// data. Arrays of every type store their length, so the data is read
+    /// whole.
+    fn read_unknown_value(&mut self, node: UnknownNode) -> Result<NodeDefinition, ReaderError> {
+        let key = self.read_key(StandardType::NodeStart)?;
+        let value_data = self.data_buf.buf_read().context(DataBufferSnafu {
+            node_type: StandardType::Binary,
+        })?;
+        self.check_padding(value_data.len())?;
+        warn!(
+            "unknown node type {} at offset 0x{:x}, reading {} bytes of raw data",
+            node.type_id,
+            node.offset,
+            value_data.len()
+        );
+
+        let type_byte = node.type_id | ARRAY_MASK;
+        self.unknown_nodes.push(node);
+
+        Ok(NodeDefinition::unknown(
+            self.encoding,
+            type_byte,
+            key,
+            value_data,
+        ))
+    }
+
     pub fn read_node_data(
         &mut self,
         node_type: StandardType,
@@ -200,31 +646,55 @@ impl Reader {
         );
 
         let value = match node_type {
-            StandardType::Attribute | StandardType::String => self
-                .data_buf
-                .buf_read()
-                .context(DataBufferSnafu { node_type })?,
-            StandardType::Binary => self.read_bytes().context(DataBufferSnafu { node_type })?,
+            StandardType::Attribute | StandardType::String | StandardType::Binary => {
+                let data = self
+                    .data_buf
+                    .buf_read()
+                    .context(DataBufferSnafu { node_type })?;
+                self.check_padding(data.len())?;
+
+                data
+            },
             StandardType::NodeStart | StandardType::NodeEnd | StandardType::FileEnd => Bytes::new(),
             node_type if is_array => {
                 let arr_size = self
                     .data_buf
-                    .read_u32::<BigEndian>()
-                    .context(ArrayLengthSnafu)?;
+                    .read_length()
+                    .context(DataBufferSnafu { node_type })?;
                 let data = self
                     .data_buf
-                    .get(arr_size)
+                    .get(arr_size as u32)
                     .context(DataBufferSnafu { node_type })?;
                 self.data_buf
                     .realign_reads(None)
                     .context(DataBufferSnafu { node_type })?;
+                let len = data.len();
+                let data = self.check_array(node_type, data)?;
+                self.check_padding(len)?;
 
                 data
             },
-            node_type => self
-                .data_buf
-                .get_aligned(node_type)
-                .context(DataBufferSnafu { node_type })?,
+            node_type => {
+                let data = self
+                    .data_buf
+                    .get_aligned(node_type)
+                    .context(DataBufferSnafu { node_type })?;
+
+                // One and two byte values share their padding with the
+                // values packed after them.
+                if data.len() > 2 {
+                    self.check_padding(data.len())?;
+                }
+
+                data
+            },
+        };
+        let value = if self.options.endianness.swaps(node_type) {
+            let mut data = value.to_vec();
+            Endianness::swap(node_type, &mut data);
+            Bytes::from(data)
+        } else {
+            value
         };
         debug!(
             "Reader::read_node_data(node_type: {:?}, is_array: {}) => value: 0x{:02x?}",
@@ -236,47 +706,96 @@ impl Reader {
         Ok(value)
     }
 
+    /// Read the next node definition.
+    ///
+    /// Errors are wrapped in `ReaderError::Node` with the path of the node
+    /// and the offsets the read started at, see `ReaderError::inner`.
     pub fn read_node_definition(&mut self) -> Result<NodeDefinition, ReaderError> {
-        let (node_type, is_array) = self.read_node_type()?;
+        let node_offset = self.node_offset();
+        let data_offset = self.data_buf_start + self.data_buf.position();
+        let mut key = None;
+
+        match self.read_definition(&mut key) {
+            Ok(def) => {
+                match def.node_type {
+                    StandardType::Attribute | StandardType::FileEnd => {},
+                    StandardType::NodeEnd => self.paths.leave(),
+                    _ => {
+                        let name = def.key().ok().flatten().unwrap_or_default();
+                        self.paths.enter(&name);
+                    },
+                };
+
+                Ok(def)
+            },
+            Err(source) => {
+                let mut path = self.paths.current();
+                if let Some((node_type, key)) = key {
+                    let name = key.to_string().unwrap_or_default();
+                    match node_type {
+                        StandardType::Attribute => path = format!("{}/@{}", path, name),
+                        _ => path = format!("{}/{}", path, name),
+                    };
+                }
+
+                Err(ReaderError::Node {
+                    path,
+                    node_offset,
+                    data_offset,
+                    source: Box::new(source),
+                })
+            },
+        }
+    }
+
+    /// Read the next node definition, keeping its name in `key` once read.
+    fn read_definition(
+        &mut self,
+        key: &mut Option<(StandardType, Key)>,
+    ) -> Result<NodeDefinition, ReaderError> {
+        if let Some(def) = self.pending.take() {
+            return Ok(def);
+        }
+
+        let start = self.node_offset();
+        let (node_type, is_array) = match self.read_node_type() {
+            Ok(node_type) => node_type,
+            Err(ReaderError::UnknownNodeType { node }) if self.options.reads_unknown_types() => {
+                if self.is_lenient() {
+                    self.report(node.offset, ProblemKind::UnknownType {
+                        type_id: node.type_id,
+                    })?;
+                }
+                let def = if node.is_array {
+                    self.read_unknown_value(node)?
+                } else {
+                    self.read_unknown_node(node)?
+                };
+                self.account(start, &def)?;
+
+                return Ok(def);
+            },
+            Err(e) => return Err(e),
+        };
 
-        match node_type {
+        let def = match node_type {
             StandardType::NodeEnd | StandardType::FileEnd => {
-                Ok(NodeDefinition::new(self.encoding, node_type, is_array))
+                NodeDefinition::new(self.encoding, node_type, is_array)
             },
             _ => {
-                let key = match self.compression {
-                    CompressionType::Compressed => {
-                        let size =
-                            Sixbit::size(&mut *self.node_buf).context(NodeSixbitNameSnafu)?;
-                        let data = self
-                            .node_buf
-                            .get(size.real_len as u32)
-                            .context(NodeBufferSnafu { node_type })?;
-
-                        Key::Compressed { size, data }
-                    },
-                    CompressionType::Uncompressed => {
-                        let encoding = self.encoding;
-                        let length =
-                            (self.node_buf.read_u8().context(NameLengthSnafu)? & !ARRAY_MASK) + 1;
-                        let data = self
-                            .node_buf
-                            .get(length as u32)
-                            .context(NodeBufferSnafu { node_type })?;
-
-                        Key::Uncompressed { encoding, data }
-                    },
-                };
+                let name = self.read_key(node_type)?;
+                *key = Some((node_type, name.clone()));
                 let value_data = self.read_node_data(node_type, is_array)?;
 
-                Ok(NodeDefinition::with_data(
-                    self.encoding,
-                    node_type,
-                    is_array,
-                    NodeData::Some { key, value_data },
-                ))
+                NodeDefinition::with_data(self.encoding, node_type, is_array, NodeData::Some {
+                    key: name,
+                    value_data,
+                })
             },
-        }
+        };
+        self.account(start, &def)?;
+
+        Ok(def)
     }
 
     pub fn read_u32(&mut self) -> Result<u32, ReaderError> {
@@ -308,3 +827,451 @@ impl Iterator for Reader {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use byteorder::{BigEndian, ByteOrder};
+    use bytes::Bytes;
+
+    use crate::byte_buffer::ByteBufferError;
+    use crate::compression_type::CompressionType;
+    use crate::encoding_type::EncodingType;
+    use crate::error::KbinError;
+    use crate::node::{Node, NodeCollection};
+    use crate::node_types::StandardType;
+    use crate::options::{Endianness, Options, ParseMode};
+    use crate::validate::ProblemKind;
+    use crate::value::{Value, ValueArray};
+    use crate::writer::WriterError;
+
+    use super::{Reader, ReaderError};
+
+    #[test]
+    fn test_length_exceeds_buffer() {
+        let node = Node::with_value("name", Value::String("kbin".into()));
+        let mut data = crate::to_binary(&node).expect("Failed to encode");
+
+        let len_node = BigEndian::read_u32(&data[4..8]) as usize;
+        let data_start = 8 + len_node + 4;
+        BigEndian::write_u32(&mut data[data_start..], 0xFFFF_FFF0);
+
+        let mut reader = Reader::new(Bytes::from(data.clone())).expect("Failed to read header");
+        let err = reader
+            .read_node_definition()
+            .expect_err("Read past the end of the data buffer");
+        match err.inner() {
+            ReaderError::DataBuffer {
+                source:
+                    ByteBufferError::LengthExceedsBuffer {
+                        offset: 0,
+                        declared: 0xFFFF_FFF0,
+                        remaining: 8,
+                    },
+                ..
+            } => {},
+            err => panic!("Unexpected error: {}", err),
+        };
+
+        BigEndian::write_u32(&mut data[4..8], 0xFFFF_FFF0);
+        assert!(matches!(
+            Reader::new(Bytes::from(data)),
+            Err(ReaderError::NodeBufferLengthExceedsInput { .. })
+        ));
+    }
+
+    #[test]
+    fn test_memory_budget() {
+        let node = Node::with_nodes("root", [
+            Node::with_value("a", Value::U32(1)),
+            Node::with_value("b", Value::String("kbin".into())),
+        ]);
+        let data = Bytes::from(crate::to_binary(&node).expect("Failed to encode"));
+        let read = |options| -> Result<Reader, ReaderError> {
+            let mut reader = Reader::with_options(data.clone(), options)?;
+            while reader.read_node_definition()?.node_type != StandardType::FileEnd {}
+            Ok(reader)
+        };
+
+        let reader = read(Options::default()).expect("Failed to read");
+        let peak = reader.accountant().peak_bytes();
+        assert_eq!(reader.accountant().nodes(), 3);
+
+        let options = Options::builder().memory_budget(peak).build();
+        let reader = read(options).expect("Failed to read within budget");
+        assert_eq!(reader.accountant().peak_bytes(), peak);
+
+        // Reading fails at the last node
+        let options = Options::builder().memory_budget(peak - 1).build();
+        let err = read(options).map(|_| ()).expect_err("Read over budget");
+        match err.inner() {
+            ReaderError::MemoryBudget { budget, needed, .. } => {
+                assert_eq!((*budget, *needed), (peak - 1, peak));
+            },
+            err => panic!("Unexpected error: {}", err),
+        };
+    }
+
+    #[test]
+    fn test_max_nodes() {
+        let node = Node::with_nodes("root", [
+            Node::with_attrs("a", &[("id", "1")]),
+            Node::with_value("b", Value::U32(2)),
+        ]);
+        let data = Bytes::from(crate::to_binary(&node).expect("Failed to encode"));
+        let read = |max_nodes| {
+            let options = Options::builder().max_nodes(max_nodes).build();
+            crate::from_binary_with_options(options, data.clone())
+        };
+
+        // Attributes and end markers are not counted
+        let (collection, _) = read(3).expect("Failed to read");
+        assert_eq!(collection.as_node().expect("Failed to convert"), node);
+
+        // `b` starts after the root, `a`, its attribute and its end marker
+        match read(2).expect_err("Read too many nodes") {
+            KbinError::Reader { source } => match source.inner() {
+                ReaderError::TooManyNodes { max_nodes, offset } => {
+                    assert_eq!((*max_nodes, *offset), (2, 21));
+                },
+                err => panic!("Unexpected error: {}", err),
+            },
+            err => panic!("Unexpected error: {}", err),
+        };
+    }
+
+    #[test]
+    fn test_mismatched_encoding() {
+        let options = Options::with_encoding(EncodingType::SHIFT_JIS);
+        let node = Node::with_value("name", Value::String("名前".into()));
+        let mut data = crate::to_binary_with_options(options, &node).expect("Failed to encode");
+        data[3] ^= 0x01;
+        let read = |trust_encoding_byte| {
+            let options = Options::builder()
+                .trust_encoding_byte(trust_encoding_byte)
+                .build();
+            crate::from_binary_with_options(options, Bytes::from(data.clone()))
+        };
+
+        match read(false).expect_err("Read mismatched encoding") {
+            KbinError::Reader {
+                source: ReaderError::MismatchedEncoding { encoding, negation },
+            } => assert_eq!((encoding, negation), (data[2], !data[2] ^ 0x01)),
+            err => panic!("Unexpected error: {}", err),
+        };
+
+        let (collection, encoding) = read(true).expect("Failed to read");
+        assert_eq!(encoding, EncodingType::SHIFT_JIS);
+        assert_eq!(collection.as_node().expect("Failed to convert"), node);
+    }
+
+    #[test]
+    fn test_unknown_node_type() {
+        let node = Node::with_nodes("root", [
+            Node::with_value("a", Value::U32(1)),
+            Node::with_value("b", Value::U32(2)),
+        ]);
+        let mut data = crate::to_binary(&node).expect("Failed to encode");
+
+        // `b` starts after the root, `a` and its end marker
+        assert_eq!(data[17], StandardType::U32.id);
+        data[17] = 60;
+        let expected = super::UnknownNode {
+            type_id: 60,
+            is_array: false,
+            offset: 17,
+            context: data[9..24].to_vec(),
+            context_offset: 9,
+        };
+
+        let err = crate::from_slice(&data).expect_err("Read unknown type");
+        match err {
+            KbinError::Reader { source } => match source.inner() {
+                ReaderError::UnknownNodeType { node } => assert_eq!(node, &expected),
+                err => panic!("Unexpected error: {}", err),
+            },
+            err => panic!("Unexpected error: {}", err),
+        };
+
+        let options = Options::builder().lenient_unknown_types(true).build();
+        let mut reader =
+            Reader::with_options(Bytes::from(data), options).expect("Failed to read header");
+        let mut next = || reader.read_node_definition().expect("Failed to read");
+        while next().node_type != StandardType::FileEnd {}
+        assert_eq!(reader.unknown_nodes(), [expected]);
+    }
+
+    #[test]
+    fn test_strict_names() {
+        let uncompressed = Options::new(CompressionType::Uncompressed, EncodingType::UTF_8);
+        let node = Node::with_nodes("root", [Node::new("name")]);
+        let mut data =
+            crate::to_binary_with_options(uncompressed, &node).expect("Failed to encode");
+        let read = |data: &[u8], strict_names| {
+            let options = Options::builder().strict_names(strict_names).build();
+            crate::from_binary_with_options(options, Bytes::from(data.to_vec()))
+        };
+
+        let (collection, _) = read(&data, true).expect("Failed to read");
+        assert_eq!(collection.as_node().expect("Failed to convert"), node);
+
+        // The child name length is at 15, after the root name
+        assert_eq!(&data[16..20], b"name");
+        data[17] = 0x01;
+        let (collection, _) = read(&data, false).expect("Failed to read");
+        let name = collection.children()[0]
+            .base()
+            .key()
+            .expect("Failed to read name");
+        assert_eq!(name.as_deref(), Some("n\u{1}me"));
+
+        match read(&data, true).expect_err("Read control character") {
+            KbinError::Reader { source } => match source.inner() {
+                ReaderError::InvalidNodeName { offset } => assert_eq!(*offset, 15),
+                err => panic!("Unexpected error: {}", err),
+            },
+            err => panic!("Unexpected error: {}", err),
+        };
+    }
+
+    #[test]
+    fn test_error_location() {
+        let node = Node::with_nodes("root", vec![
+            Node::with_value("title", Value::String("a".into())),
+            Node::with_nodes("music", vec![Node::with_value(
+                "title",
+                Value::String("b".into()),
+            )]),
+            Node::with_nodes("music", vec![Node::with_value(
+                "title",
+                Value::String("c".into()),
+            )]),
+        ]);
+        let mut data = crate::to_binary(&node).expect("Failed to encode");
+        let len_node = BigEndian::read_u32(&data[4..8]) as usize;
+        let data_start = 8 + len_node + 4;
+
+        // Break the length of the last string.
+        let offset = data.len() - 8;
+        BigEndian::write_u32(&mut data[offset..], 0xFF);
+
+        let err = crate::from_slice(&data).expect_err("Read past the end of the data buffer");
+        match err {
+            KbinError::Reader {
+                source:
+                    ReaderError::Node {
+                        ref path,
+                        node_offset,
+                        data_offset,
+                        ..
+                    },
+            } => {
+                assert_eq!(path, "/root/music[1]/title");
+                assert!(node_offset > 8 && node_offset < data_start as u64);
+                assert_eq!(data_offset, offset as u64);
+            },
+            ref err => panic!("Unexpected error: {}", err),
+        };
+    }
+
+    #[test]
+    fn test_malformed_values() {
+        let node = Node::with_nodes("root", vec![
+            Node::with_value("flag", Value::Boolean(true)),
+            Node::with_value("addr", Value::Ip4(Ipv4Addr::new(127, 0, 0, 1))),
+        ]);
+        let mut data = crate::to_binary(&node).expect("Failed to encode");
+        let len_node = BigEndian::read_u32(&data[4..8]) as usize;
+        data[8 + len_node + 4] = 2;
+
+        let (collection, _) = crate::from_slice(&data).expect("Failed to read");
+        assert!(matches!(
+            collection.as_node(),
+            Err(KbinError::InvalidBooleanInput { input: 2 })
+        ));
+        assert!(crate::to_text_xml(&collection).is_err());
+        assert_eq!(
+            collection.children()[1].base().value().ok(),
+            Some(Value::Ip4(Ipv4Addr::new(127, 0, 0, 1)))
+        );
+    }
+
+    #[test]
+    fn test_endianness() {
+        let node = Node::with_nodes("root", vec![
+            Node::with_value("id", Value::U16(0x1234)),
+            Node::with_value("pos", Value::Array(ValueArray::S32(vec![1, 2]))),
+            Node::with_value("addr", Value::Ip4(Ipv4Addr::new(127, 0, 0, 1))),
+        ]);
+        let little = Options::builder().endianness(Endianness::Little).build();
+        let data = crate::to_binary_with_options(little.clone(), &node).expect("Failed to encode");
+        let decode = |options: Options| {
+            crate::from_binary_with_options(options, Bytes::from(data.clone()))
+                .and_then(|(collection, _)| collection.as_node())
+                .expect("Failed to decode")
+        };
+
+        assert_eq!(decode(little), node);
+        let swapped = decode(Options::default());
+        assert_eq!(swapped.children()[0].value(), Some(&Value::U16(0x3412)));
+        assert_eq!(
+            swapped.children()[1].value(),
+            Some(&Value::Array(ValueArray::S32(vec![
+                0x0100_0000,
+                0x0200_0000
+            ])))
+        );
+        assert_eq!(swapped.children()[2], node.children()[2]);
+    }
+
+    #[test]
+    fn test_read_conflicts() {
+        let uncompressed = Options::new(CompressionType::Uncompressed, EncodingType::UTF_8);
+        let data = crate::to_binary_with_options(uncompressed, &Node::new("illegal-name"))
+            .expect("Failed to encode");
+
+        // The name cannot be written with sixbit compression, but reading
+        // takes the compression from the header.
+        let options = Options::builder()
+            .rename("illegal-name", "legal_name")
+            .build();
+        assert!(options.read_conflicts().is_empty());
+        let (collection, _) = crate::from_binary_with_options(options.clone(), Bytes::from(data))
+            .expect("Failed to read");
+        let node = collection
+            .as_node_with_options(&options)
+            .expect("Failed to convert");
+        assert_eq!(node.key(), "legal_name");
+
+        match crate::to_binary_with_options(options, &node) {
+            Err(KbinError::Writer {
+                source: WriterError::InvalidOptions { conflicts },
+            }) => assert_eq!(conflicts.len(), 1),
+            result => panic!("Unexpected result: {:?}", result),
+        };
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        let node = Node::with_nodes("root", vec![
+            Node::with_value("name", Value::String("ab".into())),
+            Node::with_value("list", Value::Array(ValueArray::U16(vec![1, 2, 3]))),
+        ]);
+        let data = crate::to_binary(&node).expect("Failed to encode");
+        let len_node = BigEndian::read_u32(&data[4..8]) as usize;
+        let data_start = 8 + len_node + 4;
+
+        let strict = |data: &[u8]| {
+            let options = Options::builder().parse_mode(ParseMode::Strict).build();
+            match crate::from_binary_with_options(options, Bytes::from(data.to_vec())) {
+                Ok(_) => None,
+                Err(KbinError::Reader { source }) => match source.inner() {
+                    ReaderError::Malformed { problem } => Some(problem.kind.clone()),
+                    err => panic!("Unexpected error: {}", err),
+                },
+                Err(err) => panic!("Unexpected error: {}", err),
+            }
+        };
+        let lenient = |data: &[u8]| {
+            let options = Options::builder().parse_mode(ParseMode::Lenient).build();
+            let mut reader = Reader::with_options(Bytes::from(data.to_vec()), options)
+                .expect("Failed to read header");
+            let collection: Option<NodeCollection> = (&mut reader).collect();
+            reader.read_file_end().expect("Failed to read file end");
+            let node = collection
+                .expect("Missing node collection")
+                .as_node()
+                .expect("Failed to convert");
+            let kinds: Vec<_> = reader.warnings().iter().map(|w| w.kind.clone()).collect();
+
+            (node, kinds)
+        };
+
+        assert_eq!(strict(&data), None);
+        assert_eq!(lenient(&data), (node.clone(), Vec::new()));
+
+        let mut padding = data.clone();
+        padding[data_start + 7] = 0xff;
+        assert_eq!(strict(&padding), Some(ProblemKind::NonZeroPadding));
+        let (recovered, kinds) = lenient(&padding);
+        assert_eq!(recovered, node);
+        assert_eq!(kinds, vec![ProblemKind::NonZeroPadding]);
+
+        let mut array = data.clone();
+        BigEndian::write_u32(&mut array[data_start + 8..], 5);
+        let array_length = ProblemKind::ArrayLength {
+            node_type: StandardType::U16,
+            length: 5,
+        };
+        assert_eq!(strict(&array), Some(array_length.clone()));
+        let (recovered, kinds) = lenient(&array);
+        assert_eq!(
+            recovered.children()[1].value(),
+            Some(&Value::Array(ValueArray::U16(vec![1, 2])))
+        );
+        assert_eq!(kinds, vec![array_length, ProblemKind::NonZeroPadding]);
+
+        let mut trailing = data.clone();
+        trailing.extend_from_slice(&[0, 0, 0, 1]);
+        assert_eq!(strict(&trailing), Some(ProblemKind::TrailingValueData));
+
+        let mut encoding = data;
+        encoding[3] ^= 0xff;
+        let (_, kinds) = lenient(&encoding);
+        assert_eq!(kinds.len(), 1);
+        assert!(matches!(kinds[0], ProblemKind::MismatchedEncoding { .. }));
+    }
+
+    #[test]
+    fn test_unknown_value() {
+        let node = Node::with_nodes("root", vec![Node::with_value(
+            "data",
+            Value::Binary(vec![1, 2, 3]),
+        )]);
+        let mut data = crate::to_binary(&node).expect("Failed to encode");
+
+        // The type byte of `data`, after the one of `root` and its name.
+        let offset = 8 + 5;
+        assert_eq!(data[offset], StandardType::Binary.id);
+        data[offset] = 60 | super::ARRAY_MASK;
+        let data = Bytes::from(data);
+
+        let read = |options| crate::from_binary_with_options(options, data.clone());
+        for options in [
+            Options::default(),
+            Options::builder().parse_mode(ParseMode::Strict).build(),
+        ] {
+            let err = read(options).expect_err("Read unknown type");
+            assert!(matches!(err, KbinError::Reader { .. }));
+        }
+
+        let options = Options::builder().parse_mode(ParseMode::Lenient).build();
+        let (collection, _) = read(options).expect("Failed to read");
+        let encoded = crate::to_binary(&collection).expect("Failed to encode");
+        assert_eq!(encoded, data);
+
+        let node = collection.as_node().expect("Failed to convert");
+        let unknown = Value::Unknown {
+            id: 60 | super::ARRAY_MASK,
+            bytes: vec![1, 2, 3],
+        };
+        assert_eq!(node.children()[0].value(), Some(&unknown));
+        assert_eq!(crate::to_binary(&node).expect("Failed to encode"), data);
+
+        // Values that are not arrays have no known size, so their nodes are
+        // read without one, as with `lenient_unknown_types`.
+        let mut value = data.to_vec();
+        value[offset] = 60;
+        let options = Options::builder().parse_mode(ParseMode::Lenient).build();
+        let (collection, _) = crate::from_binary_with_options(options, Bytes::from(value))
+            .expect("Read unknown value");
+        let node = collection.as_node().expect("Failed to convert");
+        let data = &node.children()[0];
+        assert_eq!(data.value(), None);
+        assert_eq!(
+            data.attributes().get(super::UNKNOWN_TYPE_ATTRIBUTE),
+            Some(&String::from("60"))
+        );
+    }
+}
@@ -0,0 +1,294 @@
+use bytes::Bytes;
+
+use crate::byte_buffer::ByteBufferRead;
+use crate::encoding_type::EncodingType;
+use crate::error::KbinError;
+use crate::node::{NodeCollection, NodeDefinition};
+use crate::node_types::StandardType;
+use crate::options::Options;
+use crate::reader::Reader;
+
+/// The nodes read from a document that failed to decode, with a handle to
+/// continue reading after the failure.
+#[derive(Debug)]
+pub struct PartialDecode {
+    /// The nodes read before the failure, with any open nodes closed.
+    ///
+    /// The first decode of a document holds at most the root node, while a
+    /// resumed decode holds every subtree read after the resume point.
+    pub nodes: Vec<NodeCollection>,
+
+    /// The encoding from the header, if the header could be read.
+    pub encoding: Option<EncodingType>,
+
+    /// The absolute offset in the input of the node definition that failed
+    /// to read.
+    pub offset: u64,
+
+    pub error: Box<KbinError>,
+
+    resume: Option<Box<ResumeHandle>>,
+}
+
+impl PartialDecode {
+    /// Take the handle to continue decoding after the failure, if the
+    /// failure was in the node buffer.
+    #[inline]
+    pub fn resume_handle(&mut self) -> Option<ResumeHandle> {
+        self.resume.take().map(|handle| *handle)
+    }
+
+    /// Resume decoding until the end of the document, collecting every
+    /// node read along the way and the offset and error of each failure.
+    pub fn recover_all(mut self) -> (Vec<NodeCollection>, Vec<(u64, KbinError)>) {
+        let mut nodes = Vec::new();
+        let mut errors = Vec::new();
+        let mut resume = self.resume.take();
+        nodes.append(&mut self.nodes);
+        errors.push((self.offset, *self.error));
+
+        while let Some(handle) = resume {
+            match handle.resume() {
+                Ok(mut recovered) => {
+                    nodes.append(&mut recovered);
+                    break;
+                },
+                Err(mut partial) => {
+                    resume = partial.resume.take();
+                    nodes.append(&mut partial.nodes);
+                    errors.push((partial.offset, *partial.error));
+                },
+            };
+        }
+
+        (nodes, errors)
+    }
+}
+
+/// The reader state at a failed node definition.
+pub struct ResumeHandle {
+    reader: Reader,
+
+    /// The node buffer position of the failed node definition.
+    failed_at: u64,
+
+    /// The data buffer before the failed node definition was read.
+    data_buf: ByteBufferRead,
+}
+
+impl std::fmt::Debug for ResumeHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ResumeHandle")
+            .field("offset", &self.offset())
+            .finish()
+    }
+}
+
+/// Checks that a name read at a guessed node boundary looks like a real
+/// node name.
+fn is_plausible_name(name: &str) -> bool {
+    !name.is_empty() &&
+        name.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | ':' | '-' | '.'))
+}
+
+/// Checks that a definition read at a guessed node boundary can start a
+/// subtree.
+fn is_plausible_start(def: &NodeDefinition) -> bool {
+    match def.node_type {
+        StandardType::Attribute | StandardType::NodeEnd | StandardType::FileEnd => false,
+        _ => matches!(def.key(), Ok(Some(name)) if is_plausible_name(&name)),
+    }
+}
+
+impl ResumeHandle {
+    /// The absolute offset in the input of the failed node definition.
+    #[inline]
+    pub fn offset(&self) -> u64 {
+        8 + self.failed_at
+    }
+
+    /// Search forward from the failed node definition for the next offset
+    /// where a node with a plausible name can be read, then continue
+    /// decoding from there.
+    ///
+    /// Data buffer reads restart from where the failed node left off, so
+    /// values after the resume point are only correct if the skipped nodes
+    /// did not store any data. Returns no nodes if no boundary was found.
+    pub fn resume(self) -> Result<Vec<NodeCollection>, PartialDecode> {
+        let ResumeHandle {
+            mut reader,
+            failed_at,
+            data_buf,
+        } = self;
+        let end = reader.node_buf.get_ref().len() as u64;
+
+        for position in (failed_at + 1)..end {
+            reader.node_buf.set_position(position);
+            reader.data_buf = data_buf.clone();
+
+            match reader.read_node_definition() {
+                Ok(def) if is_plausible_start(&def) => {
+                    debug!("resuming decode at offset 0x{:x}", 8 + position);
+                    return decode_fragments(reader, Some(def));
+                },
+                _ => {},
+            };
+        }
+
+        Ok(Vec::new())
+    }
+}
+
+/// Read node definitions until the end of the document, building a subtree
+/// for each node that is not inside another.
+fn decode_fragments(
+    mut reader: Reader,
+    first: Option<NodeDefinition>,
+) -> Result<Vec<NodeCollection>, PartialDecode> {
+    let mut fragments = Vec::new();
+    let mut open: Vec<NodeCollection> = Vec::new();
+    let mut next = first;
+
+    // Closing a node adds it to its parent, or to the fragments if it has
+    // none.
+    let close = |open: &mut Vec<NodeCollection>, fragments: &mut Vec<NodeCollection>| {
+        if let Some(node) = open.pop() {
+            match open.last_mut() {
+                Some(parent) => parent.children_mut().push_back(node),
+                None => fragments.push(node),
+            };
+        }
+    };
+
+    loop {
+        let failed_at = reader.node_buf.position();
+        let data_buf = reader.data_buf.clone();
+        let def = match next.take() {
+            Some(def) => def,
+            None => match reader.read_node_definition() {
+                Ok(def) => def,
+                Err(error) => {
+                    while !open.is_empty() {
+                        close(&mut open, &mut fragments);
+                    }
+
+                    return Err(PartialDecode {
+                        nodes: fragments,
+                        encoding: Some(reader.encoding()),
+                        offset: 8 + failed_at,
+                        error: Box::new(error.into()),
+                        resume: Some(Box::new(ResumeHandle {
+                            reader,
+                            failed_at,
+                            data_buf,
+                        })),
+                    });
+                },
+            },
+        };
+
+        match def.node_type {
+            StandardType::Attribute => {
+                if let Some(node) = open.last_mut() {
+                    node.attributes_mut().push_back(def);
+                }
+            },
+            StandardType::NodeEnd => close(&mut open, &mut fragments),
+            StandardType::FileEnd => break,
+            _ => open.push(NodeCollection::new(def)),
+        };
+    }
+
+    while !open.is_empty() {
+        close(&mut open, &mut fragments);
+    }
+
+    Ok(fragments)
+}
+
+/// Decode a binary document, keeping the nodes read before a failure.
+///
+/// Nested documents are not expanded.
+pub fn from_binary_partial(
+    options: Options,
+    input: Bytes,
+) -> Result<(NodeCollection, EncodingType), PartialDecode> {
+    let reader = Reader::with_options(input, options).map_err(|error| PartialDecode {
+        nodes: Vec::new(),
+        encoding: None,
+        offset: 0,
+        error: Box::new(error.into()),
+        resume: None,
+    })?;
+    let encoding = reader.encoding();
+
+    let mut fragments = decode_fragments(reader, None)?;
+    match fragments.len() {
+        1 => Ok((fragments.remove(0), encoding)),
+        _ => Err(PartialDecode {
+            nodes: fragments,
+            encoding: Some(encoding),
+            offset: 8,
+            error: Box::new(KbinError::NoNodeCollection),
+            resume: None,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use crate::node::Node;
+    use crate::options::Options;
+    use crate::value::Value;
+
+    use super::from_binary_partial;
+
+    #[test]
+    fn test_partial_decode() {
+        let node = Node::with_nodes("root", vec![
+            Node::with_nodes("first", vec![Node::with_value("id", Value::U32(1))]),
+            Node::with_nodes("second", vec![Node::new("inner")]),
+            Node::with_nodes("third", vec![Node::new("last")]),
+        ]);
+        let data = crate::to_binary(&node).expect("Failed to encode");
+        let (collection, _) =
+            from_binary_partial(Options::default(), Bytes::from(data.clone())).unwrap();
+        assert_eq!(collection.as_node().unwrap(), node);
+
+        // Corrupt the type of the `second` node, which follows the type and
+        // sixbit name of `root`, `first` and `id`, then two node ends.
+        let mut damaged = data;
+        let second = 8 + (1 + 4) + (1 + 5) + (1 + 3) + 2;
+        damaged[second] = 0x7f;
+
+        let mut partial = from_binary_partial(Options::default(), Bytes::from(damaged.clone()))
+            .expect_err("Damaged document decoded");
+        assert_eq!(partial.offset, second as u64);
+        assert_eq!(partial.nodes.len(), 1);
+        let root = partial.nodes[0].as_node().unwrap();
+        assert_eq!(root.children().len(), 1);
+        assert_eq!(
+            root.pointer(&["first", "id"]).and_then(Node::value),
+            Some(&Value::U32(1))
+        );
+
+        let recovered = partial
+            .resume_handle()
+            .expect("Missing resume handle")
+            .resume()
+            .expect("Failed to resume");
+        let names: Vec<_> = recovered
+            .iter()
+            .map(|fragment| fragment.as_node().unwrap().key().to_owned())
+            .collect();
+        assert_eq!(names.last().map(String::as_str), Some("third"));
+
+        let partial = from_binary_partial(Options::default(), Bytes::from(damaged)).unwrap_err();
+        let (nodes, errors) = partial.recover_all();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(nodes.len(), 1 + recovered.len());
+    }
+}
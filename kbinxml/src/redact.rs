@@ -0,0 +1,256 @@
+use crate::diff::Paths;
+use crate::error::KbinError;
+use crate::fingerprint::fnv1a;
+use crate::node::{same_path, NodeCollection, NodeData, NodeDefinition};
+use crate::node_types::StandardType;
+
+/// How `redact` changes the values it finds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Redaction {
+    /// Replace numbers and binary data with zero bytes and the characters of
+    /// strings with `0`.
+    Zero,
+
+    /// Replace values with bytes derived from a hash of the original value,
+    /// so equal values stay equal after redaction. Strings are replaced with
+    /// hex digits.
+    Hash,
+
+    /// Remove the node, including its children, or the attribute.
+    Remove,
+}
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// The bytes that replace `data` when hashing, repeating the hash chain as
+/// needed to fill the original length.
+fn hash_bytes(data: &[u8]) -> impl Iterator<Item = u8> {
+    let mut hash = fnv1a(data.iter().copied());
+
+    std::iter::from_fn(move || {
+        let bytes = hash.to_be_bytes();
+        hash = fnv1a(bytes.iter().copied());
+        Some(bytes)
+    })
+    .flatten()
+}
+
+fn redact_definition(def: &mut NodeDefinition, policy: Redaction) {
+    let node_type = def.node_type;
+    let value_data = match def.data_mut() {
+        NodeData::Some { value_data, .. } => value_data,
+        NodeData::None => return,
+    };
+    let mut data = value_data.to_vec();
+
+    match node_type {
+        // Keep the trailing null bytes so the string length is unchanged.
+        StandardType::String | StandardType::Attribute => {
+            let len = data.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+            let hash = hash_bytes(&data[..len]);
+            let text = &mut data[..len];
+
+            match policy {
+                Redaction::Hash => {
+                    for (byte, hash) in text.iter_mut().zip(hash) {
+                        *byte = HEX_DIGITS[(hash & 0xF) as usize];
+                    }
+                },
+                _ => text.iter_mut().for_each(|byte| *byte = b'0'),
+            };
+        },
+        node_type => {
+            let hash = hash_bytes(&data);
+            let is_boolean = matches!(
+                node_type,
+                StandardType::Boolean |
+                    StandardType::Boolean2 |
+                    StandardType::Boolean3 |
+                    StandardType::Boolean4 |
+                    StandardType::Vb
+            );
+
+            match policy {
+                // Booleans must stay 0 or 1 to decode.
+                Redaction::Hash if is_boolean => {
+                    for (byte, hash) in data.iter_mut().zip(hash) {
+                        *byte = hash & 1;
+                    }
+                },
+                Redaction::Hash => {
+                    for (byte, hash) in data.iter_mut().zip(hash) {
+                        *byte = hash;
+                    }
+                },
+                _ => data.iter_mut().for_each(|byte| *byte = 0),
+            };
+        },
+    };
+
+    *value_data = data.into();
+}
+
+fn is_target(targets: &[&str], path: &str) -> bool {
+    targets.iter().any(|target| {
+        if target.contains('[') {
+            same_path(target, path)
+        } else {
+            strip_indices(path) == *target
+        }
+    })
+}
+
+fn strip_indices(path: &str) -> String {
+    let mut stripped = String::with_capacity(path.len());
+    let mut in_index = false;
+
+    for c in path.chars() {
+        match c {
+            '[' => in_index = true,
+            ']' => in_index = false,
+            c if !in_index => stripped.push(c),
+            _ => {},
+        };
+    }
+
+    stripped
+}
+
+fn redact_collection(
+    collection: &mut NodeCollection,
+    paths: &mut Paths,
+    path: &str,
+    targets: &[&str],
+    policy: Redaction,
+) -> Result<usize, KbinError> {
+    let mut redacted = 0;
+
+    let mut keep = Vec::with_capacity(collection.attributes().len());
+    for attr in collection.attributes_mut() {
+        let name = attr.key()?.unwrap_or_default();
+        let is_match = is_target(targets, &format!("{}/@{}", path, name));
+        if is_match {
+            redacted += 1;
+            if policy != Redaction::Remove {
+                redact_definition(attr, policy);
+            }
+        }
+        keep.push(!is_match || policy != Redaction::Remove);
+    }
+    let mut keep = keep.into_iter();
+    collection
+        .attributes_mut()
+        .retain(|_| keep.next().unwrap_or(true));
+
+    let mut keep = Vec::with_capacity(collection.children().len());
+    for child in collection.children_mut() {
+        let name = child.base().key()?.unwrap_or_default();
+        let child_path = paths.enter(&name);
+        let is_match = is_target(targets, &child_path);
+
+        if is_match {
+            redacted += 1;
+            if policy != Redaction::Remove {
+                redact_definition(child.base_mut(), policy);
+            }
+        }
+        if !is_match || policy != Redaction::Remove {
+            redacted += redact_collection(child, paths, &child_path, targets, policy)?;
+        }
+        keep.push(!is_match || policy != Redaction::Remove);
+
+        paths.leave();
+    }
+    let mut keep = keep.into_iter();
+    collection
+        .children_mut()
+        .retain(|_| keep.next().unwrap_or(true));
+
+    Ok(redacted)
+}
+
+/// Zero, hash or remove the values of nodes and attributes at `paths`,
+/// returning the number of nodes and attributes that matched.
+///
+/// Paths use the format of `NodeCursor::path` or `diff`, with `/@name`
+/// appended for attributes. A path without any indices matches every
+/// instance of a repeated node. Zeroed and hashed values keep their
+/// type and size, so the document can be encoded as before. The root node
+/// cannot be removed.
+pub fn redact(
+    collection: &mut NodeCollection,
+    paths: &[&str],
+    policy: Redaction,
+) -> Result<usize, KbinError> {
+    let mut tracker = Paths::new();
+    let name = collection.base().key()?.unwrap_or_default();
+    let root_path = tracker.enter(&name);
+
+    let mut redacted = 0;
+    if is_target(paths, &root_path) && policy != Redaction::Remove {
+        redact_definition(collection.base_mut(), policy);
+        redacted += 1;
+    }
+
+    redacted += redact_collection(collection, &mut tracker, &root_path, paths, policy)?;
+
+    Ok(redacted)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::node::Node;
+    use crate::value::Value;
+
+    use super::{redact, Redaction};
+
+    #[test]
+    fn test_redact() {
+        let mut root = Node::new("root");
+        for id in &["E004010000000001", "E004010000000002"] {
+            let mut card = Node::with_attrs("card", &[("pcbid", "0120000000000000")]);
+            card.append_child(Node::with_value("id", Value::String(id.to_string())));
+            card.append_child(Node::with_value("count", Value::U32(5)));
+            root.append_child(card);
+        }
+        let data = crate::to_binary(&root).expect("Failed to encode");
+
+        let (mut collection, _) = crate::from_slice(&data).expect("Failed to decode");
+        let redacted =
+            redact(&mut collection, &["/root/card/id"], Redaction::Hash).expect("Failed to redact");
+        assert_eq!(redacted, 2);
+        let redacted = redact(&mut collection, &["/root/card[1]/count"], Redaction::Zero)
+            .expect("Failed to redact");
+        assert_eq!(redacted, 1);
+        let redacted = redact(&mut collection, &["/root/card[0]/@pcbid"], Redaction::Zero)
+            .expect("Failed to redact");
+        assert_eq!(redacted, 1);
+        let redacted = redact(&mut collection, &["/root/card/@pcbid"], Redaction::Remove)
+            .expect("Failed to redact");
+        assert_eq!(redacted, 2);
+
+        let node = collection.as_node().expect("Failed to convert");
+        let cards = node.children();
+        assert!(cards.iter().all(|card| card.attributes().is_empty()));
+        assert_eq!(
+            cards[0].get_child("count").and_then(Node::value),
+            Some(&Value::U32(5))
+        );
+        assert_eq!(
+            cards[1].get_child("count").and_then(Node::value),
+            Some(&Value::U32(0))
+        );
+
+        let ids: Vec<_> = cards
+            .iter()
+            .filter_map(|card| card.get_child("id").and_then(Node::value))
+            .collect();
+        assert!(ids.iter().all(|id| match id {
+            Value::String(id) => id.len() == 16 && !id.starts_with("E004"),
+            _ => false,
+        }));
+        assert_ne!(ids[0], ids[1]);
+
+        crate::to_binary(&collection).expect("Failed to encode redacted document");
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+
+use bytes::Bytes;
+use snafu::Snafu;
+
+use crate::encoding_type::EncodingType;
+use crate::error::KbinError;
+use crate::node::{Key, NodeCollection, NodeData, NodeDefinition};
+use crate::sixbit::{Sixbit, SixbitSize};
+
+/// The longest name the length byte of an uncompressed name can describe.
+const MAX_UNCOMPRESSED_NAME_LEN: usize = 64;
+
+#[derive(Debug, Snafu)]
+pub enum RenameError {
+    #[snafu(display("Cannot rename nodes to an empty name"))]
+    EmptyName,
+
+    #[snafu(display("Name {:?} is not a valid compressed node name", name))]
+    InvalidCompressedName { name: String },
+
+    #[snafu(display("Name {:?} cannot be encoded as a {:?} node name", name, encoding))]
+    InvalidUncompressedName {
+        name: String,
+        encoding: EncodingType,
+    },
+}
+
+/// Encode `name` with the same compression and encoding as `key`.
+fn encode_key(key: &Key, name: &str) -> Result<Key, RenameError> {
+    if name.is_empty() {
+        return Err(RenameError::EmptyName);
+    }
+
+    match key {
+        Key::Compressed { .. } => {
+            if !Sixbit::is_valid(name) {
+                return Err(RenameError::InvalidCompressedName {
+                    name: name.to_owned(),
+                });
+            }
+
+            let mut data = Vec::new();
+            Sixbit::pack(&mut data, name).expect("Writing to a Vec cannot fail");
+            let size = SixbitSize {
+                sixbit_len: name.len() as u8,
+                real_len: data.len() - 1,
+            };
+
+            Ok(Key::Compressed {
+                size,
+                data: Bytes::from(data).slice(1..),
+            })
+        },
+        Key::Uncompressed { encoding, .. } => {
+            let invalid = || RenameError::InvalidUncompressedName {
+                name: name.to_owned(),
+                encoding: *encoding,
+            };
+            let mut data = encoding.encode_bytes(name).map_err(|_| invalid())?;
+
+            // Names in documents are not null terminated
+            data.pop();
+            if data.len() > MAX_UNCOMPRESSED_NAME_LEN {
+                return Err(invalid());
+            }
+
+            Ok(Key::Uncompressed {
+                encoding: *encoding,
+                data: Bytes::from(data),
+            })
+        },
+    }
+}
+
+fn rename_definition(
+    def: &mut NodeDefinition,
+    keys: &HashMap<&str, Key>,
+) -> Result<bool, KbinError> {
+    let name = match def.key()? {
+        Some(name) => name,
+        None => return Ok(false),
+    };
+    let new_key = match keys.get(name.as_str()) {
+        Some(key) => key,
+        None => return Ok(false),
+    };
+
+    if let NodeData::Some { key, .. } = def.data_mut() {
+        *key = new_key.clone();
+    }
+
+    Ok(true)
+}
+
+fn rename_collection(
+    collection: &mut NodeCollection,
+    keys: &HashMap<&str, Key>,
+    attributes: bool,
+) -> Result<usize, KbinError> {
+    let mut renamed = usize::from(rename_definition(collection.base_mut(), keys)?);
+
+    if attributes {
+        for attr in collection.attributes_mut() {
+            renamed += usize::from(rename_definition(attr, keys)?);
+        }
+    }
+    for child in collection.children_mut() {
+        renamed += rename_collection(child, keys, attributes)?;
+    }
+
+    Ok(renamed)
+}
+
+/// Rename every node named like a key of `map` to the matching value,
+/// including the root node, returning the number of renamed nodes and
+/// attributes. Attributes are renamed too if `attributes` is set.
+///
+/// The new names are checked against the compression and encoding of the
+/// document before anything is renamed, so a failed rename leaves the
+/// document unchanged.
+pub fn rename_nodes(
+    collection: &mut NodeCollection,
+    map: &HashMap<&str, &str>,
+    attributes: bool,
+) -> Result<usize, KbinError> {
+    let template = match collection.base().data() {
+        NodeData::Some { key, .. } => key.clone(),
+        NodeData::None => return Ok(0),
+    };
+
+    let mut keys = HashMap::with_capacity(map.len());
+    for (&from, &to) in map {
+        keys.insert(from, encode_key(&template, to)?);
+    }
+
+    rename_collection(collection, &keys, attributes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::compression_type::CompressionType;
+    use crate::node::{Node, NodeDefinition};
+    use crate::options::Options;
+    use crate::value::Value;
+
+    use super::rename_nodes;
+
+    fn name(def: &NodeDefinition) -> String {
+        def.key().expect("Failed to decode").unwrap_or_default()
+    }
+
+    #[test]
+    fn test_rename_nodes() {
+        let mut root = Node::new("root");
+        for _ in 0..2 {
+            let mut entry = Node::with_attrs("entry", &[("id", "1")]);
+            entry.append_child(Node::with_value("id", Value::U32(1)));
+            root.append_child(entry);
+        }
+        let map: HashMap<&str, &str> = [("entry", "item"), ("id", "key")].iter().copied().collect();
+
+        for &compression in &[CompressionType::Compressed, CompressionType::Uncompressed] {
+            let options = Options::builder().compression(compression).build();
+            let data = crate::to_binary_with_options(options, &root).expect("Failed to encode");
+            let (mut collection, _) = crate::from_slice(&data).expect("Failed to decode");
+
+            assert_eq!(
+                rename_nodes(&mut collection, &map, false).expect("Failed to rename"),
+                4
+            );
+            assert_eq!(
+                rename_nodes(&mut collection, &map, true).expect("Failed to rename"),
+                2
+            );
+            let entry = &collection.children()[1];
+            assert_eq!(name(entry.base()), "item");
+            assert_eq!(name(entry.children()[0].base()), "key");
+            assert_eq!(name(&entry.attributes()[0]), "key");
+
+            let invalid: HashMap<&str, &str> = [("item", "")].iter().copied().collect();
+            assert!(rename_nodes(&mut collection, &invalid, false).is_err());
+        }
+
+        let data = crate::to_binary(&root).expect("Failed to encode");
+        let (mut collection, _) = crate::from_slice(&data).expect("Failed to decode");
+        let invalid: HashMap<&str, &str> =
+            [("id", "k-y"), ("entry", "item")].iter().copied().collect();
+        assert!(rename_nodes(&mut collection, &invalid, true).is_err());
+        assert_eq!(name(collection.children()[0].base()), "entry");
+    }
+}
@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+
+use crate::diff::Paths;
+use crate::error::KbinError;
+use crate::fingerprint::fnv1a;
+use crate::node::{NodeCollection, NodeDefinition};
+use crate::node_types::StandardType;
+use crate::size_hint::{padded, sixbit_name_size};
+
+/// A subtree that appears more than once in a document.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RepeatedSubtree {
+    /// The name of the root node of the subtree.
+    pub name: String,
+
+    /// The paths of every instance, in document order.
+    pub paths: Vec<String>,
+
+    /// The approximate encoded size of one instance in bytes.
+    pub size: usize,
+
+    /// The approximate number of bytes saved if only one instance was stored.
+    pub savings: usize,
+}
+
+struct Instance {
+    order: usize,
+    key: (u64, usize),
+    parent: Option<(u64, usize)>,
+    name: String,
+    path: String,
+}
+
+/// The hash and approximate size of a definition, without children.
+fn definition_key(def: &NodeDefinition, name: &str) -> (u64, usize) {
+    let value = def.value_bytes().unwrap_or_default();
+    let hash = fnv1a(
+        name.bytes()
+            .chain(Some(0))
+            .chain(Some(def.node_type as u8))
+            .chain(Some(def.is_array as u8))
+            .chain(value.iter().copied()),
+    );
+
+    let data_size = match def.node_type {
+        StandardType::NodeStart => 0,
+        StandardType::String | StandardType::Binary | StandardType::Attribute => {
+            4 + padded(value.len())
+        },
+        _ if def.is_array => 4 + padded(value.len()),
+        _ => value.len(),
+    };
+
+    (hash, 1 + sixbit_name_size(name) + data_size)
+}
+
+fn collect(
+    collection: &NodeCollection,
+    paths: &mut Paths,
+    instances: &mut Vec<Instance>,
+    next_order: &mut usize,
+) -> Result<(u64, usize), KbinError> {
+    let name = collection.base().key()?.unwrap_or_default();
+    let path = paths.enter(&name);
+    let order = *next_order;
+    *next_order += 1;
+    let (mut hash, mut size) = definition_key(collection.base(), &name);
+
+    for attr in collection.attributes() {
+        let name = attr.key()?.unwrap_or_default();
+        let (attr_hash, attr_size) = definition_key(attr, &name);
+        hash = fnv1a(
+            hash.to_be_bytes()
+                .iter()
+                .chain(&attr_hash.to_be_bytes())
+                .copied(),
+        );
+        size += attr_size;
+    }
+
+    // Instances of children are recorded before the parent key is known, so
+    // fix up their parent afterwards.
+    let mut child_indices = Vec::with_capacity(collection.children().len());
+    for child in collection.children() {
+        let (child_hash, child_size) = collect(child, paths, instances, next_order)?;
+        child_indices.push(instances.len() - 1);
+        hash = fnv1a(
+            hash.to_be_bytes()
+                .iter()
+                .chain(&child_hash.to_be_bytes())
+                .copied(),
+        );
+        size += child_size;
+    }
+    paths.leave();
+
+    // The node end marker.
+    size += 1;
+
+    let key = (hash, size);
+    for index in child_indices {
+        instances[index].parent = Some(key);
+    }
+    instances.push(Instance {
+        order,
+        key,
+        parent: None,
+        name,
+        path,
+    });
+
+    Ok(key)
+}
+
+/// Find subtrees that appear more than once in `collection` with an
+/// approximate encoded size of at least `min_size` bytes.
+///
+/// Subtrees are identical when their names, types, values, attributes and
+/// children match. Repeated subtrees that only occur inside instances of a
+/// larger repeated subtree are not reported separately. Sizes assume
+/// compressed names and ignore alignment shared between values. Results are
+/// sorted by savings, largest first.
+pub fn find_repeated_subtrees(
+    collection: &NodeCollection,
+    min_size: usize,
+) -> Result<Vec<RepeatedSubtree>, KbinError> {
+    let mut instances = Vec::new();
+    collect(collection, &mut Paths::new(), &mut instances, &mut 0)?;
+
+    // Children are pushed before their parents, so restore document order.
+    instances.sort_by_key(|instance| instance.order);
+
+    let mut groups: HashMap<(u64, usize), Vec<&Instance>> = HashMap::new();
+    for instance in &instances {
+        groups.entry(instance.key).or_default().push(instance);
+    }
+    let is_repeated = |key: &(u64, usize)| {
+        key.1 >= min_size && groups.get(key).is_some_and(|group| group.len() > 1)
+    };
+
+    let mut repeated: Vec<RepeatedSubtree> = groups
+        .iter()
+        .filter(|(key, _)| is_repeated(key))
+        .filter(|(_, group)| {
+            !group
+                .iter()
+                .all(|instance| instance.parent.as_ref().is_some_and(is_repeated))
+        })
+        .map(|(&(_, size), group)| RepeatedSubtree {
+            name: group[0].name.clone(),
+            paths: group.iter().map(|instance| instance.path.clone()).collect(),
+            size,
+            savings: size * (group.len() - 1),
+        })
+        .collect();
+    repeated.sort_by(|a, b| {
+        b.savings
+            .cmp(&a.savings)
+            .then_with(|| a.paths.cmp(&b.paths))
+    });
+
+    Ok(repeated)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::node::Node;
+    use crate::value::Value;
+
+    use super::{find_repeated_subtrees, RepeatedSubtree};
+
+    fn entry(id: &str) -> Node {
+        let mut entry = Node::with_attrs("entry", &[("id", id)]);
+        entry.append_child(Node::with_value("name", Value::String("abc".into())));
+        entry.append_child(Node::with_value("score", Value::U32(5)));
+
+        entry
+    }
+
+    fn repeated<const N: usize>(name: &str, paths: [&str; N], size: usize) -> RepeatedSubtree {
+        RepeatedSubtree {
+            name: name.to_owned(),
+            paths: paths.iter().map(|&path| path.to_owned()).collect(),
+            size,
+            savings: size * (N - 1),
+        }
+    }
+
+    #[test]
+    fn test_find_repeated_subtrees() {
+        let root = Node::with_nodes("root", [
+            entry("1"),
+            Node::with_value("other", Value::U8(1)),
+            entry("1"),
+            entry("2"),
+        ]);
+        let input = crate::to_binary(&root).expect("Failed to encode");
+        let (collection, _) = crate::from_slice(&input).expect("Failed to decode");
+
+        // The children of the first two entries are part of a larger repeated
+        // subtree, but the third entry has a different attribute
+        let found = find_repeated_subtrees(&collection, 0).expect("Failed to search");
+        assert_eq!(found, [
+            repeated("entry", ["/root/entry", "/root/entry[1]"], 44),
+            repeated(
+                "name",
+                [
+                    "/root/entry/name",
+                    "/root/entry[1]/name",
+                    "/root/entry[2]/name"
+                ],
+                14,
+            ),
+            repeated(
+                "score",
+                [
+                    "/root/entry/score",
+                    "/root/entry[1]/score",
+                    "/root/entry[2]/score"
+                ],
+                11,
+            ),
+        ]);
+
+        let found = find_repeated_subtrees(&collection, 12).expect("Failed to search");
+        let names: Vec<&str> = found.iter().map(|found| found.name.as_str()).collect();
+        assert_eq!(names, ["entry", "name"]);
+    }
+}
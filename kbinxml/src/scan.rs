@@ -0,0 +1,142 @@
+use bytes::Bytes;
+
+use crate::byte_buffer::strip_trailing_null_bytes;
+use crate::diff::Paths;
+use crate::encoding_type::EncodingType;
+use crate::error::KbinError;
+use crate::node_types::StandardType;
+use crate::reader::Reader;
+
+/// Encodings tried, in order, when a string cannot be decoded with the
+/// encoding of its document.
+const FALLBACK_ENCODINGS: &[EncodingType] = &[
+    EncodingType::UTF_8,
+    EncodingType::SHIFT_JIS,
+    EncodingType::EUC_JP,
+    EncodingType::ISO_8859_1,
+];
+
+/// A string value found by `scan_strings`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FoundString {
+    /// The path of the node, followed by `/@name` for attributes.
+    pub path: String,
+    pub value: String,
+
+    /// The encoding the value was decoded with, or `None` if no encoding
+    /// could decode it and invalid sequences were replaced.
+    pub encoding: Option<EncodingType>,
+    pub is_attribute: bool,
+
+    /// The absolute offset of the definition in the node buffer.
+    pub node_offset: u64,
+
+    /// The absolute offset of the value in the data buffer.
+    pub data_offset: u64,
+}
+
+fn decode(document: EncodingType, data: &[u8]) -> (String, Option<EncodingType>) {
+    let data = strip_trailing_null_bytes(data);
+    let encodings = Some(document).into_iter().chain(
+        FALLBACK_ENCODINGS
+            .iter()
+            .copied()
+            .filter(|&e| e != document),
+    );
+
+    for encoding in encodings {
+        if let Ok(value) = encoding.decode_bytes(data) {
+            return (value, Some(encoding));
+        }
+    }
+
+    (String::from_utf8_lossy(data).into_owned(), None)
+}
+
+/// Extract every string and attribute value from a binary document without
+/// building a node tree.
+pub fn scan_strings(input: &[u8]) -> Result<Vec<FoundString>, KbinError> {
+    let mut reader = Reader::new(Bytes::from(input.to_vec()))?;
+    let document = reader.encoding();
+    let mut paths = Paths::new();
+    let mut strings = Vec::new();
+
+    loop {
+        let node_offset = reader.node_offset();
+        let def = reader.read_node_definition()?;
+        let name = def.key()?;
+
+        let path = match (def.node_type, name) {
+            (StandardType::FileEnd, _) => break,
+            (StandardType::NodeEnd, _) => {
+                paths.leave();
+                continue;
+            },
+            (StandardType::Attribute, Some(name)) => format!("{}/@{}", paths.current(), name),
+            (_, Some(name)) => paths.enter(&name),
+            (_, None) => continue,
+        };
+
+        match def.node_type {
+            StandardType::String | StandardType::Attribute => {},
+            _ => continue,
+        };
+
+        let (value, encoding) = decode(document, def.value_bytes().unwrap_or_default());
+        strings.push(FoundString {
+            path,
+            value,
+            encoding,
+            is_attribute: def.node_type == StandardType::Attribute,
+            node_offset,
+            data_offset: reader.last_data_offset(),
+        });
+    }
+
+    Ok(strings)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding_type::EncodingType;
+    use crate::node::Node;
+    use crate::options::Options;
+    use crate::value::Value;
+
+    use super::{scan_strings, FoundString};
+
+    fn found(path: &str, value: &str, encoding: EncodingType, offsets: (u64, u64)) -> FoundString {
+        FoundString {
+            path: path.to_owned(),
+            value: value.to_owned(),
+            encoding: Some(encoding),
+            is_attribute: path.contains('@'),
+            node_offset: offsets.0,
+            data_offset: offsets.1,
+        }
+    }
+
+    #[test]
+    fn test_scan_strings() {
+        let mut root = Node::with_attrs("root", &[("id", "1")]);
+        root.append_child(Node::with_value("title", Value::String("名前".into())));
+        root.append_child(Node::with_value("count", Value::U32(1)));
+        root.append_child(Node::with_value("title", Value::String("ab".into())));
+        let options = Options::with_encoding(EncodingType::UTF_8);
+        let mut input = crate::to_binary_with_options(options, &root).expect("Failed to encode");
+
+        // Replace "ab" with "あ" in Shift-JIS, which is not valid UTF-8
+        let offset = input.windows(3).position(|w| w == b"ab\0");
+        let offset = offset.expect("Missing string");
+        input[offset..offset + 2].copy_from_slice(&[0x82, 0xa0]);
+
+        // The data buffer starts at 44, data offsets point past the length
+        // prefix of each string
+        let strings = scan_strings(&input).expect("Failed to scan");
+        assert_eq!(strings, [
+            found("/root/@id", "1", EncodingType::UTF_8, (13, 48)),
+            found("/root/title", "名前", EncodingType::UTF_8, (17, 56)),
+            found("/root/title[1]", "あ", EncodingType::SHIFT_JIS, (31, 72)),
+        ]);
+    }
+}
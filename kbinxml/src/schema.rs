@@ -0,0 +1,545 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::iter;
+
+use bytes::Bytes;
+use indexmap::IndexMap;
+use snafu::Snafu;
+
+use crate::error::KbinError;
+use crate::node::{Node, NodeCollection, NodeDefinition};
+use crate::node_types::StandardType;
+use crate::reader::Reader;
+use crate::value::Value;
+
+#[derive(Debug, Snafu)]
+pub enum SchemaError {
+    #[snafu(display("Node {} is not in the schema", path))]
+    UnknownNode { path: String },
+
+    #[snafu(display("Attribute {} is not in the schema", path))]
+    UnknownAttribute { path: String },
+
+    #[snafu(display(
+        "Node {} is {} (array: {}) instead of {} (array: {})",
+        path,
+        actual,
+        actual_array,
+        expected,
+        expected_array
+    ))]
+    TypeMismatch {
+        path: String,
+        expected: StandardType,
+        expected_array: bool,
+        actual: StandardType,
+        actual_array: bool,
+    },
+}
+
+/// What was seen at one node path across the inferred documents.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InferredNode {
+    /// The type of the values seen at this path, or `None` if no node here
+    /// had a value.
+    pub node_type: Option<StandardType>,
+
+    /// The values seen at this path are arrays.
+    pub is_array: bool,
+
+    /// Nodes at this path had values of different types, or both array and
+    /// non-array values.
+    pub mixed_types: bool,
+
+    /// A node at this path had a sibling with the same name.
+    pub repeated: bool,
+
+    /// The attribute names seen at this path, in the order first seen.
+    pub attributes: Vec<String>,
+}
+
+impl InferredNode {
+    fn observe(&mut self, node: &Node) {
+        if let Some(value) = node.value() {
+            let node_type = value.standard_type();
+            let is_array = matches!(value, Value::Array(_));
+
+            match self.node_type {
+                None => {
+                    self.node_type = Some(node_type);
+                    self.is_array = is_array;
+                },
+                Some(seen) if seen != node_type || self.is_array != is_array => {
+                    self.mixed_types = true;
+                },
+                Some(_) => {},
+            };
+        }
+
+        for key in node.attributes().keys() {
+            if !self.attributes.contains(key) {
+                self.attributes.push(key.clone());
+            }
+        }
+    }
+
+    /// The Rust type returned by a generated accessor for this node's value,
+    /// and the expression converting `value` to it.
+    fn accessor_type(&self) -> (&'static str, &'static str) {
+        const VALUE: (&str, &str) = ("&kbinxml::Value", "Some(value)");
+
+        if self.mixed_types || self.is_array {
+            return VALUE;
+        }
+
+        match self.node_type {
+            Some(StandardType::S8) => ("i8", "value.as_i8().ok()"),
+            Some(StandardType::U8) => ("u8", "value.as_u8().ok()"),
+            Some(StandardType::S16) => ("i16", "value.as_i16().ok()"),
+            Some(StandardType::U16) => ("u16", "value.as_u16().ok()"),
+            Some(StandardType::S32) => ("i32", "value.as_i32().ok()"),
+            Some(StandardType::U32) => ("u32", "value.as_u32().ok()"),
+            Some(StandardType::S64) => ("i64", "value.as_i64().ok()"),
+            Some(StandardType::U64) => ("u64", "value.as_u64().ok()"),
+            Some(StandardType::String) => ("&str", "value.as_str().ok()"),
+            Some(StandardType::Binary) => ("&[u8]", "value.as_slice().ok()"),
+            _ => VALUE,
+        }
+    }
+}
+
+/// The node paths of one or more documents with the types found at each.
+///
+/// Paths start at the root node and leave out indices, e.g.
+/// `/root/entry/id`, the same as `OptionsBuilder::serialize_as`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InferredSchema {
+    nodes: IndexMap<String, InferredNode>,
+}
+
+impl InferredSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_node(root: &Node) -> Self {
+        let mut schema = Self::new();
+        schema.add_node(root);
+
+        schema
+    }
+
+    pub fn from_collection(collection: &NodeCollection) -> Result<Self, KbinError> {
+        Ok(Self::from_node(&collection.as_node()?))
+    }
+
+    /// Merge the paths of another document into the schema.
+    pub fn add_node(&mut self, root: &Node) {
+        let path = format!("/{}", root.key());
+        self.observe(path, root, false);
+    }
+
+    fn observe(&mut self, path: String, node: &Node, repeated: bool) {
+        let mut counts = HashMap::new();
+        for child in node.children() {
+            *counts.entry(child.key()).or_insert(0usize) += 1;
+        }
+
+        for child in node.children() {
+            let path = format!("{}/{}", path, child.key());
+            self.observe(path, child, counts[child.key()] > 1);
+        }
+
+        let entry = self.nodes.entry(path).or_default();
+        entry.repeated |= repeated;
+        entry.observe(node);
+    }
+
+    #[inline]
+    pub fn get(&self, path: &str) -> Option<&InferredNode> {
+        self.nodes.get(path)
+    }
+
+    /// The paths in the order first seen, parents before their children.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &InferredNode)> {
+        let mut nodes: Vec<_> = self
+            .nodes
+            .iter()
+            .map(|(path, node)| (path.as_str(), node))
+            .collect();
+
+        // Children are inserted before their parent, so order by the first
+        // occurrence of each parent prefix instead.
+        let order: HashMap<&str, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, (path, _))| (*path, i))
+            .collect();
+        nodes.sort_by_cached_key(|(path, _)| {
+            let mut key = Vec::new();
+            let mut end = 0;
+            while let Some(next) = path[end + 1..].find('/') {
+                end += next + 1;
+                key.push(order[&path[..end]]);
+            }
+            key.push(order[path]);
+            key
+        });
+
+        nodes.into_iter()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+/// Checks node definitions against a schema as they are read.
+struct SchemaValidator<'a> {
+    schema: &'a InferredSchema,
+
+    /// The paths of the open nodes.
+    stack: Vec<String>,
+}
+
+impl<'a> SchemaValidator<'a> {
+    fn check(&mut self, def: &NodeDefinition) -> Result<(), KbinError> {
+        let (node_type, is_array) = def.node_type_tuple();
+        match node_type {
+            StandardType::NodeEnd => {
+                self.stack.pop();
+                return Ok(());
+            },
+            StandardType::FileEnd => return Ok(()),
+            _ => {},
+        };
+
+        let key = def.key()?.ok_or(KbinError::InvalidState)?;
+        let parent = self.stack.last().map_or("", String::as_str);
+        let path = format!("{}/{}", parent, key);
+
+        if node_type == StandardType::Attribute {
+            let node = self.schema.get(parent);
+            if !node.is_some_and(|node| node.attributes.contains(&key)) {
+                return Err(SchemaError::UnknownAttribute {
+                    path: format!("{}/@{}", parent, key),
+                }
+                .into());
+            }
+
+            return Ok(());
+        }
+
+        let node = match self.schema.get(&path) {
+            Some(node) => node,
+            None => return Err(SchemaError::UnknownNode { path }.into()),
+        };
+
+        // Nodes without a value are never seen by the schema, so any node
+        // may be empty
+        if let Some(expected) = node.node_type {
+            let matches = node.mixed_types ||
+                node_type == StandardType::NodeStart ||
+                (node_type == expected && is_array == node.is_array);
+            if !matches {
+                return Err(SchemaError::TypeMismatch {
+                    path,
+                    expected,
+                    expected_array: node.is_array,
+                    actual: node_type,
+                    actual_array: is_array,
+                }
+                .into());
+            }
+        }
+        self.stack.push(path);
+
+        Ok(())
+    }
+}
+
+/// Decode a binary document into a `Node` tree, checking every node against
+/// `schema` as it is read so documents that do not match fail before a tree
+/// is built.
+///
+/// Nodes and attributes have to be in the schema, and values need the type
+/// the schema has for their path unless it saw mixed types there.
+pub fn decode_with_schema(input: &[u8], schema: &InferredSchema) -> Result<Node, KbinError> {
+    let mut reader = Reader::new(Bytes::from(input.to_vec()))?;
+    let mut validator = SchemaValidator {
+        schema,
+        stack: Vec::new(),
+    };
+
+    let mut error = None;
+    let collection = iter::from_fn(|| {
+        let result = reader
+            .read_node_definition()
+            .map_err(KbinError::from)
+            .and_then(|def| validator.check(&def).map(|_| def));
+        match result {
+            Ok(def) => Some(def),
+            Err(e) => {
+                error = Some(e);
+                None
+            },
+        }
+    })
+    .collect::<Option<NodeCollection>>();
+
+    if let Some(e) = error {
+        return Err(e);
+    }
+
+    collection.ok_or(KbinError::NoNodeCollection)?.as_node()
+}
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use",
+    "where", "while",
+];
+
+/// Replace the characters of a name that cannot appear in a Rust identifier.
+fn identifier(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Make a joined identifier valid by prefixing a leading digit and
+/// suffixing keywords with an underscore.
+fn escape_identifier(mut ident: String) -> String {
+    if ident.is_empty() || ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.insert(0, '_');
+    }
+    if KEYWORDS.contains(&ident.as_str()) {
+        ident.push('_');
+    }
+
+    ident
+}
+
+/// Pick an identifier not in `used`, adding a numeric suffix if needed.
+fn unique(used: &mut HashSet<String>, ident: String) -> String {
+    let ident = escape_identifier(ident);
+    let mut candidate = ident.clone();
+    let mut suffix = 2;
+    while !used.insert(candidate.clone()) {
+        candidate = format!("{}_{}", ident, suffix);
+        suffix += 1;
+    }
+
+    candidate
+}
+
+/// Generate the source of a Rust module with a path constant for every node
+/// path and attribute in `schema`, and an accessor function for every node
+/// with a value or attribute.
+///
+/// Accessors take the root node of a document and return the value of the
+/// first node along the path.
+pub fn generate_accessors(schema: &InferredSchema, module_name: &str) -> String {
+    let mut output = String::new();
+    let mut used = HashSet::new();
+
+    // Writing to a `String` cannot fail.
+    let _ = writeln!(output, "// Generated by kbinxml from an inferred schema.");
+    let _ = writeln!(output);
+    let _ = writeln!(
+        output,
+        "pub mod {} {{",
+        escape_identifier(identifier(module_name))
+    );
+    let _ = writeln!(output, "    #![allow(dead_code)]");
+
+    for (path, node) in schema.iter() {
+        let segments: Vec<&str> = path[1..].split('/').collect();
+        let pointer = segments[1..]
+            .iter()
+            .map(|segment| format!("{:?}", segment))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let ident = segments
+            .iter()
+            .map(|segment| identifier(segment))
+            .collect::<Vec<_>>()
+            .join("_");
+        let ident = unique(&mut used, ident);
+
+        let description = match node.node_type {
+            Some(_) if node.mixed_types => "mixed types".to_owned(),
+            Some(node_type) if node.is_array => format!("`{}` array", node_type.name),
+            Some(node_type) => format!("`{}`", node_type.name),
+            None => "no value".to_owned(),
+        };
+        let repeated = if node.repeated { ", repeated" } else { "" };
+
+        let _ = writeln!(output);
+        let _ = writeln!(output, "    /// `{}` ({}{})", path, description, repeated);
+        let _ = writeln!(
+            output,
+            "    pub const {}: &str = {:?};",
+            ident.to_ascii_uppercase(),
+            path
+        );
+
+        if node.node_type.is_some() {
+            let (return_type, conversion) = node.accessor_type();
+            let _ = writeln!(output);
+            let _ = writeln!(
+                output,
+                "    pub fn {}(root: &kbinxml::Node) -> Option<{}> {{",
+                ident, return_type
+            );
+            let _ = writeln!(
+                output,
+                "        let value = root.pointer(&[{}])?.value()?;",
+                pointer
+            );
+            let _ = writeln!(output, "        {}", conversion);
+            let _ = writeln!(output, "    }}");
+        }
+
+        for attribute in &node.attributes {
+            let attribute_path = format!("{}/@{}", path, attribute);
+            let attribute_ident = unique(
+                &mut used,
+                format!("{}_attr_{}", ident, identifier(attribute)),
+            );
+
+            let _ = writeln!(output);
+            let _ = writeln!(output, "    /// `{}`", attribute_path);
+            let _ = writeln!(
+                output,
+                "    pub const {}: &str = {:?};",
+                attribute_ident.to_ascii_uppercase(),
+                attribute_path
+            );
+            let _ = writeln!(output);
+            let _ = writeln!(
+                output,
+                "    pub fn {}(root: &kbinxml::Node) -> Option<&str> {{",
+                attribute_ident
+            );
+            let _ = writeln!(output, "        let node = root.pointer(&[{}])?;", pointer);
+            let _ = writeln!(
+                output,
+                "        node.attributes().get({:?}).map(String::as_str)",
+                attribute
+            );
+            let _ = writeln!(output, "    }}");
+        }
+    }
+
+    let _ = writeln!(output, "}}");
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::KbinError;
+    use crate::node::Node;
+    use crate::node_types::StandardType;
+    use crate::value::{Value, ValueArray};
+
+    use super::{decode_with_schema, generate_accessors, InferredSchema, SchemaError};
+
+    #[test]
+    fn test_generate_accessors() {
+        let music = |id: &str, bpm: Value| {
+            Node::with("music", &[("id", id)], vec![
+                Node::with_value("title", Value::String("title".into())),
+                Node::with_value("bpm", bpm),
+            ])
+        };
+        let root = Node::with_nodes("mdb", vec![
+            music("1", Value::U16(150)),
+            music("2", Value::U16(210)),
+            Node::with_value("type", Value::Array(ValueArray::U8(vec![1, 2]))),
+        ]);
+
+        let schema = InferredSchema::from_node(&root);
+        let paths: Vec<_> = schema.iter().map(|(path, _)| path).collect();
+        assert_eq!(paths, [
+            "/mdb",
+            "/mdb/music",
+            "/mdb/music/title",
+            "/mdb/music/bpm",
+            "/mdb/type",
+        ]);
+
+        let node = schema.get("/mdb/music").unwrap();
+        assert!(node.repeated);
+        assert_eq!(node.attributes, ["id"]);
+        let bpm = schema.get("/mdb/music/bpm").unwrap();
+        assert_eq!(bpm.node_type, Some(StandardType::U16));
+        assert!(!bpm.mixed_types);
+
+        let source = generate_accessors(&schema, "music-db");
+        assert!(source.contains("pub mod music_db {"));
+        assert!(source.contains(r#"pub const MDB_MUSIC_BPM: &str = "/mdb/music/bpm";"#));
+        assert!(source.contains("pub fn mdb_music_bpm(root: &kbinxml::Node) -> Option<u16> {"));
+        assert!(source.contains(r#"root.pointer(&["music", "bpm"])?.value()?;"#));
+        assert!(source.contains("pub fn mdb_music_attr_id(root: &kbinxml::Node) -> Option<&str> {"));
+        assert!(
+            source.contains("pub fn mdb_type(root: &kbinxml::Node) -> Option<&kbinxml::Value> {")
+        );
+
+        let mut schema = schema;
+        schema.add_node(&Node::with_nodes("mdb", vec![music(
+            "3",
+            Value::String("fast".into()),
+        )]));
+        assert!(schema.get("/mdb/music/bpm").unwrap().mixed_types);
+    }
+
+    #[test]
+    fn test_decode_with_schema() {
+        let entry =
+            |id: Value| Node::with("entry", &[("kind", "a")], vec![Node::with_value("id", id)]);
+        let root = Node::with_nodes("root", vec![entry(Value::U32(1)), entry(Value::U32(2))]);
+        let schema = InferredSchema::from_node(&root);
+
+        let data = crate::to_binary(&root).expect("Failed to encode");
+        assert_eq!(
+            decode_with_schema(&data, &schema).expect("Failed to decode"),
+            root
+        );
+
+        let invalid = Node::with_nodes("root", vec![entry(Value::U32(1)), entry(Value::S8(2))]);
+        let data = crate::to_binary(&invalid).expect("Failed to encode");
+        match decode_with_schema(&data, &schema) {
+            Err(KbinError::Schema {
+                source: SchemaError::TypeMismatch { path, actual, .. },
+            }) => {
+                assert_eq!(path, "/root/entry/id");
+                assert_eq!(actual, StandardType::S8);
+            },
+            result => panic!("Unexpected result: {:?}", result),
+        };
+
+        let unknown = Node::with_nodes("root", vec![Node::with_attrs("entry", &[("name", "a")])]);
+        let data = crate::to_binary(&unknown).expect("Failed to encode");
+        match decode_with_schema(&data, &schema) {
+            Err(KbinError::Schema {
+                source: SchemaError::UnknownAttribute { path },
+            }) => assert_eq!(path, "/root/entry/@name"),
+            result => panic!("Unexpected result: {:?}", result),
+        };
+    }
+}
@@ -0,0 +1,150 @@
+use crate::error::KbinError;
+use crate::node::{NodeCollection, NodeCursor};
+use crate::node_types::StandardType;
+use crate::value::Value;
+
+/// A node or attribute whose value matched a search.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValueMatch<'a> {
+    /// The matched node, or the node holding the matched attribute.
+    pub cursor: NodeCursor<'a>,
+
+    /// The path of the node, in the format of `NodeCursor::path`, followed by
+    /// `/@name` for an attribute.
+    pub path: String,
+    pub value: Value,
+}
+
+/// Find every node and attribute in `collection` with a value that satisfies
+/// `predicate`, in document order. Attribute values are `Value::Attribute`.
+pub fn find_values<F>(
+    collection: &NodeCollection,
+    predicate: F,
+) -> Result<Vec<ValueMatch<'_>>, KbinError>
+where
+    F: Fn(&Value) -> bool,
+{
+    let mut matches = Vec::new();
+    let mut stack = vec![collection.cursor()];
+
+    while let Some(cursor) = stack.pop() {
+        let node = cursor.node();
+        if node.base().node_type != StandardType::NodeStart {
+            let value = node.base().value()?;
+            if predicate(&value) {
+                matches.push(ValueMatch {
+                    path: cursor.path()?,
+                    cursor: cursor.clone(),
+                    value,
+                });
+            }
+        }
+        for attr in node.attributes() {
+            let value = attr.value()?;
+            if predicate(&value) {
+                let key = attr.key()?.ok_or(KbinError::InvalidState)?;
+                matches.push(ValueMatch {
+                    path: format!("{}/@{}", cursor.path()?, key),
+                    cursor: cursor.clone(),
+                    value,
+                });
+            }
+        }
+
+        // Push in reverse so children are visited in order.
+        stack.extend(
+            (0..node.children().len())
+                .rev()
+                .filter_map(|i| cursor.child(i)),
+        );
+    }
+
+    Ok(matches)
+}
+
+/// Find integer values equal to `needle` regardless of the integer type they
+/// are stored as, including integer arrays containing it.
+pub fn find_integer(
+    collection: &NodeCollection,
+    needle: i128,
+) -> Result<Vec<ValueMatch<'_>>, KbinError> {
+    find_values(collection, |value| match value {
+        Value::Array(values) => values
+            .to_values()
+            .iter()
+            .any(|value| value.as_integer() == Some(needle)),
+        value => value.as_integer() == Some(needle),
+    })
+}
+
+#[inline]
+pub fn find_u32(
+    collection: &NodeCollection,
+    needle: u32,
+) -> Result<Vec<ValueMatch<'_>>, KbinError> {
+    find_integer(collection, needle as i128)
+}
+
+/// Find string and attribute values equal to `needle`.
+pub fn find_string<'a>(
+    collection: &'a NodeCollection,
+    needle: &str,
+) -> Result<Vec<ValueMatch<'a>>, KbinError> {
+    find_values(collection, |value| match value {
+        Value::String(s) | Value::Attribute(s) => s == needle,
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::node::Node;
+    use crate::value::{Value, ValueArray};
+
+    use super::{find_integer, find_string, find_u32, find_values, ValueMatch};
+
+    fn paths(matches: Vec<ValueMatch>) -> Vec<String> {
+        matches.into_iter().map(|found| found.path).collect()
+    }
+
+    #[test]
+    fn test_find_values() {
+        let root = Node::with_nodes("root", [
+            Node::with_value("a", Value::U8(7)),
+            Node::with_value("b", Value::S64(7)),
+            Node::with_nodes("nested", [Node::with_value(
+                "c",
+                Value::Array(ValueArray::U16(vec![1, 7])),
+            )]),
+            Node::with_value("d", Value::U32(8)),
+            Node::with_value("e", Value::String("7".into())),
+            Node::with_attrs("attrs", &[("id", "7"), ("name", "e")]),
+            Node::with_value("f", Value::S32(-1)),
+        ]);
+        let input = crate::to_binary(&root).expect("Failed to encode");
+        let (collection, _) = crate::from_slice(&input).expect("Failed to decode");
+
+        // Integers match across types and inside arrays, strings do not
+        let found = find_u32(&collection, 7).expect("Failed to search");
+        assert_eq!(paths(found), ["/root/a", "/root/b", "/root/nested/c"]);
+
+        // Signed values do not match the unsigned value with the same bits
+        let found = find_integer(&collection, -1).expect("Failed to search");
+        assert_eq!(paths(found), ["/root/f"]);
+        let found = find_u32(&collection, u32::MAX).expect("Failed to search");
+        assert!(found.is_empty());
+
+        let found = find_values(&collection, |value| matches!(value, Value::String(_)))
+            .expect("Failed to search");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].value, Value::String("7".into()));
+        assert_eq!(found[0].cursor.indices(), &[4]);
+        assert_eq!(found[0].cursor.node(), &collection.children()[4]);
+
+        // Attributes are found by their value, with their node's cursor
+        let found = find_string(&collection, "7").expect("Failed to search");
+        assert_eq!(paths(found.clone()), ["/root/e", "/root/attrs/@id"]);
+        assert_eq!(found[1].value, Value::Attribute("7".into()));
+        assert_eq!(found[1].cursor.node(), &collection.children()[5]);
+    }
+}
@@ -0,0 +1,1271 @@
+use std::fmt;
+use std::net::Ipv4Addr;
+
+use serde::ser::{self, Serialize};
+use snafu::{ResultExt, Snafu};
+
+use crate::error::KbinError;
+use crate::filter::child_paths;
+use crate::node::Node;
+use crate::node_types::StandardType;
+use crate::options::Options;
+use crate::types::TYPED_NAME_PREFIX;
+use crate::value::{integer_bounds, Value, ValueArray};
+
+/// Marks a struct field or map key, e.g. `#[serde(rename = "@id")]`, as an
+/// attribute of the node instead of a child node.
+pub(crate) const ATTRIBUTE_PREFIX: &str = "@";
+
+/// Marks a struct field as an attribute without renaming it, e.g. `attr_id`
+/// for the attribute `id`.
+pub(crate) const ATTRIBUTE_FIELD_PREFIX: &str = "attr_";
+
+/// The name of the attribute a struct field is stored in, if it is one.
+pub(crate) fn attribute_field(field: &str) -> Option<&str> {
+    field
+        .strip_prefix(ATTRIBUTE_PREFIX)
+        .or_else(|| field.strip_prefix(ATTRIBUTE_FIELD_PREFIX))
+}
+
+#[derive(Debug, Snafu)]
+pub enum SerError {
+    #[snafu(display("{}", message))]
+    Custom { message: String },
+
+    #[snafu(display("Serializing {} is not supported", kind))]
+    Unsupported { kind: &'static str },
+
+    #[snafu(display("Root value must be a struct, found {}", kind))]
+    InvalidRoot { kind: &'static str },
+
+    #[snafu(display("Map keys must be strings"))]
+    InvalidMapKey,
+
+    #[snafu(display("Failed to build array of {} for field {}", node_type, key))]
+    ArrayValue {
+        key: String,
+        node_type: StandardType,
+        #[snafu(source(from(KbinError, Box::new)))]
+        source: Box<KbinError>,
+    },
+
+    #[snafu(display("Attribute {} must be a single value, found {}", key, kind))]
+    InvalidAttribute { key: String, kind: &'static str },
+
+    #[snafu(display("Cannot force {} to type {}", kind, node_type))]
+    InvalidForcedType {
+        kind: &'static str,
+        node_type: StandardType,
+    },
+
+    #[snafu(display("Value {} does not fit in forced type {}", value, node_type))]
+    ForcedOutOfRange {
+        value: i128,
+        node_type: StandardType,
+    },
+
+    #[snafu(display("Failed to force value at {} to type {}", path, node_type))]
+    TypeOverride {
+        path: String,
+        node_type: StandardType,
+        #[snafu(source(from(KbinError, Box::new)))]
+        source: Box<KbinError>,
+    },
+
+    #[snafu(display("Cannot widen {} to {} at {}", stored, target, path))]
+    InvalidWidening {
+        path: String,
+        stored: StandardType,
+        target: StandardType,
+    },
+}
+
+impl ser::Error for SerError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        SerError::Custom {
+            message: msg.to_string(),
+        }
+    }
+}
+
+/// The intermediate form of a serialized Rust value.
+pub(crate) enum Serialized {
+    Value(Value),
+    Node(Node),
+    Seq(Vec<Serialized>),
+    None,
+}
+
+impl Serialized {
+    fn kind(&self) -> &'static str {
+        match self {
+            Serialized::Value(_) => "a value",
+            Serialized::Node(_) => "a struct",
+            Serialized::Seq(_) => "a sequence",
+            Serialized::None => "nothing",
+        }
+    }
+}
+
+/// Convert a serializable value to a `Node` tree.
+///
+/// The root value must be a struct and its name is used as the key of the
+/// root node. Struct fields and map entries become child nodes, primitive
+/// values become node values, sequences of primitives become arrays and other
+/// sequences become repeated child nodes. `None` values and empty sequences
+/// are omitted.
+///
+/// Tuples and tuple structs of two to four numbers or booleans of the same
+/// type become multi-count values, like `3f`, and are read back from them.
+///
+/// Struct fields named with a leading `attr_`, or renamed to start with `@`,
+/// become attributes holding the text of their value.
+///
+/// Unit enum variants become strings and other variants become a node named
+/// after the variant, holding its value or fields. Internally and adjacently
+/// tagged enums store the tag as a string child node.
+#[inline]
+pub fn to_node<T>(value: &T) -> Result<Node, KbinError>
+where
+    T: Serialize + ?Sized,
+{
+    to_node_with_options(value, &Options::default())
+}
+
+/// Convert a serializable value to a `Node` tree, widening integer values to
+/// the types requested with `OptionsBuilder::serialize_as` and forcing the
+/// types of `OptionsBuilder::type_overrides`.
+pub fn to_node_with_options<T>(value: &T, options: &Options) -> Result<Node, KbinError>
+where
+    T: Serialize + ?Sized,
+{
+    options.validate()?;
+
+    match value.serialize(Serializer)? {
+        Serialized::Node(mut node) if !node.key().is_empty() => {
+            let path = format!("/{}", node.key());
+            if !options.serialize_types.is_empty() {
+                widen_node(&mut node, &path, options)?;
+            }
+            if !options.type_overrides.is_empty() {
+                override_types(&mut node, &path, options)?;
+            }
+
+            Ok(node)
+        },
+        Serialized::Node(_) => Err(SerError::InvalidRoot { kind: "a map" }.into()),
+        serialized => Err(SerError::InvalidRoot {
+            kind: serialized.kind(),
+        }
+        .into()),
+    }
+}
+
+/// Apply the requested types to `node` and its children.
+///
+/// Paths do not include indices, so a type requested for a repeated node
+/// applies to every instance of it.
+fn widen_node(node: &mut Node, path: &str, options: &Options) -> Result<(), SerError> {
+    if let Some(&target) = options.serialize_types.get(path) {
+        if let Some(value) = node.value_mut() {
+            *value = widen_value(value, path, target)?;
+        }
+    }
+
+    for child in node.children_mut() {
+        let path = format!("{}/{}", path, child.key());
+        widen_node(child, &path, options)?;
+    }
+
+    Ok(())
+}
+
+/// Force the values of `node` and its children to the types of the matching
+/// `OptionsBuilder::type_overrides`.
+fn override_types(node: &mut Node, path: &str, options: &Options) -> Result<(), SerError> {
+    if let Some(node_type) = options.type_override(path) {
+        if let Some(value) = node.value_mut() {
+            *value = value
+                .retype(node_type)
+                .context(TypeOverrideSnafu { path, node_type })?;
+        }
+    }
+
+    let paths = child_paths(path, node.children().iter().map(Node::key));
+    for (child, path) in node.children_mut().iter_mut().zip(paths) {
+        override_types(child, &path, options)?;
+    }
+
+    Ok(())
+}
+
+/// Convert an integer or integer array value to `target`, which must be able
+/// to represent every value of the stored type.
+fn widen_value(value: &Value, path: &str, target: StandardType) -> Result<Value, SerError> {
+    let stored = match value {
+        Value::Array(values) => values.standard_type(),
+        value => value.standard_type(),
+    };
+    let widens = match (integer_bounds(stored), integer_bounds(target)) {
+        (Some((stored_min, stored_max)), Some((target_min, target_max))) => {
+            stored_min >= target_min && stored_max <= target_max
+        },
+        _ => false,
+    };
+    let error = || SerError::InvalidWidening {
+        path: path.to_owned(),
+        stored,
+        target,
+    };
+    if !widens {
+        return Err(error());
+    }
+
+    let widen = |value: &Value| {
+        value
+            .as_integer()
+            .and_then(|integer| Value::from_integer(target, integer))
+            .ok_or_else(error)
+    };
+
+    match value {
+        Value::Array(values) => {
+            let values = values
+                .to_values()
+                .iter()
+                .map(widen)
+                .collect::<Result<Vec<_>, _>>()?;
+            let array = ValueArray::from_values(target, values).map_err(|_| error())?;
+
+            Ok(Value::Array(array))
+        },
+        value => widen(value),
+    }
+}
+
+/// Convert a serialized integer, or sequence of integers, to `node_type`.
+fn force_type(serialized: Serialized, node_type: StandardType) -> Result<Serialized, SerError> {
+    let integer_type = match node_type {
+        StandardType::Time => StandardType::U32,
+        node_type => node_type,
+    };
+
+    match serialized {
+        Serialized::Value(value) => {
+            let integer = value.as_integer().ok_or(SerError::InvalidForcedType {
+                kind: "a non-integer value",
+                node_type,
+            })?;
+            let value =
+                Value::from_integer(integer_type, integer).ok_or(SerError::ForcedOutOfRange {
+                    value: integer,
+                    node_type,
+                })?;
+
+            match value {
+                Value::U32(seconds) if node_type == StandardType::Time => {
+                    Ok(Serialized::Value(Value::Time(seconds)))
+                },
+                value => Ok(Serialized::Value(value)),
+            }
+        },
+        Serialized::Seq(items) => items
+            .into_iter()
+            .map(|item| force_type(item, node_type))
+            .collect::<Result<_, _>>()
+            .map(Serialized::Seq),
+        Serialized::None => Ok(Serialized::None),
+        serialized => Err(SerError::InvalidForcedType {
+            kind: serialized.kind(),
+            node_type,
+        }),
+    }
+}
+
+/// Convert serialized octets, or a sequence of them, to `Ip4` values as
+/// `Ipv4Addr` serializes to its octets in a compact format.
+fn force_ip4(serialized: Serialized) -> Result<Serialized, SerError> {
+    match serialized {
+        Serialized::Seq(items) => {
+            let octets = items
+                .iter()
+                .map(|item| match item {
+                    Serialized::Value(Value::U8(octet)) => Some(*octet),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>();
+
+            match octets.as_deref() {
+                Some(&[a, b, c, d]) => Ok(Serialized::Value(Value::Ip4(Ipv4Addr::new(a, b, c, d)))),
+                _ => items
+                    .into_iter()
+                    .map(force_ip4)
+                    .collect::<Result<_, _>>()
+                    .map(Serialized::Seq),
+            }
+        },
+        Serialized::Value(Value::U8_4([a, b, c, d])) => {
+            Ok(Serialized::Value(Value::Ip4(Ipv4Addr::new(a, b, c, d))))
+        },
+        Serialized::Value(value @ Value::Ip4(_)) => Ok(Serialized::Value(value)),
+        Serialized::None => Ok(Serialized::None),
+        serialized => Err(SerError::InvalidForcedType {
+            kind: serialized.kind(),
+            node_type: StandardType::Ip4,
+        }),
+    }
+}
+
+/// Set the attribute `key` of `node` to the text of a serialized value.
+fn set_attribute(node: &mut Node, key: &str, serialized: Serialized) -> Result<(), SerError> {
+    match serialized {
+        Serialized::Value(Value::String(value)) | Serialized::Value(Value::Attribute(value)) => {
+            node.set_attr(key, value);
+        },
+        Serialized::Value(value) => {
+            node.set_attr(key, value.to_string());
+        },
+        Serialized::None => {},
+        serialized => {
+            return Err(SerError::InvalidAttribute {
+                key: key.to_owned(),
+                kind: serialized.kind(),
+            });
+        },
+    };
+
+    Ok(())
+}
+
+/// Serialize a value and add it to `node` as a child named `key`, or as an
+/// attribute if `key` starts with `@`.
+fn append_serialized(node: &mut Node, key: &str, serialized: Serialized) -> Result<(), SerError> {
+    if let Some(key) = key.strip_prefix(ATTRIBUTE_PREFIX) {
+        return set_attribute(node, key, serialized);
+    }
+
+    match serialized {
+        Serialized::Value(value) => node.append_child(Node::with_value(key, value)),
+        Serialized::Node(mut child) => {
+            child.set_key(key);
+            node.append_child(child);
+        },
+        Serialized::Seq(items) => match array_type(&items) {
+            Some(node_type) => {
+                let values = items
+                    .into_iter()
+                    .filter_map(|item| match item {
+                        Serialized::Value(value) => Some(value),
+                        _ => None,
+                    })
+                    .collect();
+                let array = ValueArray::from_values(node_type, values)
+                    .context(ArrayValueSnafu { key, node_type })?;
+                node.append_child(Node::with_value(key, Value::Array(array)));
+            },
+            None => {
+                for item in items {
+                    append_serialized(node, key, item)?;
+                }
+            },
+        },
+        Serialized::None => {},
+    };
+
+    Ok(())
+}
+
+/// Wrap the value of an enum variant in a node with a child named after the
+/// variant.
+fn wrap_variant(variant: &str, serialized: Serialized) -> Result<Serialized, SerError> {
+    let mut node = Node::new("");
+    append_serialized(&mut node, variant, serialized)?;
+
+    Ok(Serialized::Node(node))
+}
+
+/// The array type for a sequence if every item is a value of the same type
+/// that can be stored in an array.
+fn array_type(items: &[Serialized]) -> Option<StandardType> {
+    let mut node_type = None;
+
+    for item in items {
+        let item_type = match item {
+            Serialized::Value(Value::String(_)) |
+            Serialized::Value(Value::Binary(_)) |
+            Serialized::Value(Value::Time(_)) |
+            Serialized::Value(Value::Attribute(_)) |
+            Serialized::Value(Value::Unknown { .. }) |
+            Serialized::Value(Value::Array(_)) => return None,
+            Serialized::Value(value) => value.standard_type(),
+            _ => return None,
+        };
+
+        match node_type {
+            Some(node_type) if node_type != item_type => return None,
+            _ => node_type = Some(item_type),
+        };
+    }
+
+    node_type
+}
+
+/// The multi-count value, like `3f`, for a tuple of two to four values of
+/// the same type.
+fn multi_value(items: &[Serialized]) -> Option<Value> {
+    let element = array_type(items)?;
+    let suffix = element.alt_name.unwrap_or(element.name);
+    let node_type = StandardType::from_name(&format!("{}{}", items.len(), suffix)).ok()?;
+
+    let mut data = Vec::new();
+    for item in items {
+        if let Serialized::Value(value) = item {
+            value.to_bytes_into(&mut data).ok()?;
+        }
+    }
+
+    Value::from_standard_type(node_type, false, &data).ok()?
+}
+
+pub(crate) struct Serializer;
+
+pub(crate) struct SeqSerializer {
+    items: Vec<Serialized>,
+    tuple: bool,
+}
+
+pub(crate) struct StructSerializer {
+    node: Node,
+    key: Option<String>,
+}
+
+/// Serializes the fields of a tuple or struct enum variant.
+pub(crate) struct VariantSerializer<S> {
+    variant: &'static str,
+    inner: S,
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = Serialized;
+    type Error = SerError;
+
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = VariantSerializer<SeqSerializer>;
+    type SerializeMap = StructSerializer;
+    type SerializeStruct = StructSerializer;
+    type SerializeStructVariant = VariantSerializer<StructSerializer>;
+
+    /// Types with a compact form, like `Ipv4Addr`, use it instead of a string.
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn serialize_bool(self, v: bool) -> Result<Serialized, SerError> {
+        Ok(Serialized::Value(Value::Boolean(v)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Serialized, SerError> {
+        Ok(Serialized::Value(Value::S8(v)))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Serialized, SerError> {
+        Ok(Serialized::Value(Value::S16(v)))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Serialized, SerError> {
+        Ok(Serialized::Value(Value::S32(v)))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Serialized, SerError> {
+        Ok(Serialized::Value(Value::S64(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Serialized, SerError> {
+        Ok(Serialized::Value(Value::U8(v)))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Serialized, SerError> {
+        Ok(Serialized::Value(Value::U16(v)))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Serialized, SerError> {
+        Ok(Serialized::Value(Value::U32(v)))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Serialized, SerError> {
+        Ok(Serialized::Value(Value::U64(v)))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Serialized, SerError> {
+        Ok(Serialized::Value(Value::Float(v)))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Serialized, SerError> {
+        Ok(Serialized::Value(Value::Double(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Serialized, SerError> {
+        Ok(Serialized::Value(Value::String(v.to_string())))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Serialized, SerError> {
+        Ok(Serialized::Value(Value::String(v.to_owned())))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Serialized, SerError> {
+        Ok(Serialized::Value(Value::Binary(v.to_vec())))
+    }
+
+    fn serialize_none(self) -> Result<Serialized, SerError> {
+        Ok(Serialized::None)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Serialized, SerError>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Serialized, SerError> {
+        Ok(Serialized::Node(Node::new("")))
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Serialized, SerError> {
+        Ok(Serialized::Node(Node::new(name)))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Serialized, SerError> {
+        Ok(Serialized::Value(Value::String(variant.to_owned())))
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Serialized, SerError>
+    where
+        T: Serialize + ?Sized,
+    {
+        let node_type = name
+            .strip_prefix(TYPED_NAME_PREFIX)
+            .and_then(|name| StandardType::from_name(name).ok());
+
+        match node_type {
+            Some(StandardType::Ip4) => force_ip4(value.serialize(self)?),
+            Some(node_type) => force_type(value.serialize(self)?, node_type),
+            None => value.serialize(self),
+        }
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Serialized, SerError>
+    where
+        T: Serialize + ?Sized,
+    {
+        wrap_variant(variant, value.serialize(self)?)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, SerError> {
+        Ok(SeqSerializer {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+            tuple: false,
+        })
+    }
+
+    /// Tuples of two to four numbers or booleans of the same type become
+    /// multi-count values, like `3f`.
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, SerError> {
+        Ok(SeqSerializer {
+            items: Vec::with_capacity(len),
+            tuple: true,
+        })
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, SerError> {
+        self.serialize_tuple(len)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, SerError> {
+        Ok(VariantSerializer {
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<StructSerializer, SerError> {
+        Ok(StructSerializer {
+            node: Node::new(""),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        _len: usize,
+    ) -> Result<StructSerializer, SerError> {
+        Ok(StructSerializer {
+            node: Node::new(name),
+            key: None,
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, SerError> {
+        Ok(VariantSerializer {
+            variant,
+            inner: StructSerializer {
+                node: Node::new(variant),
+                key: None,
+            },
+        })
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Serialized;
+    type Error = SerError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), SerError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.items.push(value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Serialized, SerError> {
+        match multi_value(&self.items) {
+            Some(value) if self.tuple => Ok(Serialized::Value(value)),
+            _ => Ok(Serialized::Seq(self.items)),
+        }
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Serialized;
+    type Error = SerError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), SerError>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Serialized, SerError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Serialized;
+    type Error = SerError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), SerError>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Serialized, SerError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeMap for StructSerializer {
+    type Ok = Serialized;
+    type Error = SerError;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), SerError>
+    where
+        T: Serialize + ?Sized,
+    {
+        match key.serialize(Serializer)? {
+            Serialized::Value(Value::String(key)) => {
+                self.key = Some(key);
+                Ok(())
+            },
+            _ => Err(SerError::InvalidMapKey),
+        }
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), SerError>
+    where
+        T: Serialize + ?Sized,
+    {
+        let key = self.key.take().ok_or(SerError::InvalidMapKey)?;
+        append_serialized(&mut self.node, &key, value.serialize(Serializer)?)
+    }
+
+    fn end(self) -> Result<Serialized, SerError> {
+        Ok(Serialized::Node(self.node))
+    }
+}
+
+impl ser::SerializeStruct for StructSerializer {
+    type Ok = Serialized;
+    type Error = SerError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), SerError>
+    where
+        T: Serialize + ?Sized,
+    {
+        let serialized = value.serialize(Serializer)?;
+        match key.strip_prefix(ATTRIBUTE_FIELD_PREFIX) {
+            Some(name) => set_attribute(&mut self.node, name, serialized),
+            None => append_serialized(&mut self.node, key, serialized),
+        }
+    }
+
+    fn end(self) -> Result<Serialized, SerError> {
+        Ok(Serialized::Node(self.node))
+    }
+}
+
+impl ser::SerializeTupleVariant for VariantSerializer<SeqSerializer> {
+    type Ok = Serialized;
+    type Error = SerError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), SerError>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeSeq::serialize_element(&mut self.inner, value)
+    }
+
+    fn end(self) -> Result<Serialized, SerError> {
+        wrap_variant(self.variant, ser::SerializeSeq::end(self.inner)?)
+    }
+}
+
+impl ser::SerializeStructVariant for VariantSerializer<StructSerializer> {
+    type Ok = Serialized;
+    type Error = SerError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), SerError>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeStruct::serialize_field(&mut self.inner, key, value)
+    }
+
+    fn end(self) -> Result<Serialized, SerError> {
+        wrap_variant(self.variant, ser::SerializeStruct::end(self.inner)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use serde::{Deserialize, Serialize};
+    use uuid::Uuid;
+
+    use crate::de::{from_node, DeError};
+    use crate::error::KbinError;
+    use crate::node::Node;
+    use crate::node_types::StandardType;
+    use crate::options::Options;
+    use crate::types::{KbinIp4, KbinS8, KbinTime, KbinU16};
+    use crate::value::{Value, ValueArray};
+
+    use super::{to_node, to_node_with_options, SerError};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Entry {
+        id: u32,
+        name: String,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Root {
+        version: u8,
+        scores: Vec<u16>,
+        entry: Vec<Entry>,
+        comment: Option<String>,
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let root = Root {
+            version: 1,
+            scores: vec![10, 20],
+            entry: vec![
+                Entry {
+                    id: 1,
+                    name: String::from("first"),
+                },
+                Entry {
+                    id: 2,
+                    name: String::from("second"),
+                },
+            ],
+            comment: None,
+        };
+
+        let node = to_node(&root).expect("Failed to serialize");
+        assert_eq!(node.key(), "Root");
+        assert_eq!(
+            node.get_child("scores").and_then(Node::value),
+            Some(&Value::Array(ValueArray::U16(vec![10, 20])))
+        );
+        assert_eq!(node.children().len(), 4);
+        assert!(!node.has("comment"));
+
+        let decoded: Root = from_node(&node).expect("Failed to deserialize");
+        assert_eq!(decoded, root);
+    }
+
+    #[test]
+    fn test_widening() {
+        let root = Root {
+            version: 1,
+            scores: vec![10, 20],
+            entry: Vec::new(),
+            comment: None,
+        };
+        let options = Options::builder()
+            .serialize_as("/Root/version", StandardType::U32)
+            .serialize_as("/Root/scores", StandardType::S32)
+            .build();
+
+        let node = to_node_with_options(&root, &options).expect("Failed to serialize");
+        assert_eq!(
+            node.get_child("version").and_then(Node::value),
+            Some(&Value::U32(1))
+        );
+        assert_eq!(
+            node.get_child("scores").and_then(Node::value),
+            Some(&Value::Array(ValueArray::S32(vec![10, 20])))
+        );
+
+        let options = Options::builder()
+            .serialize_as("/Root/scores", StandardType::S16)
+            .build();
+        match to_node_with_options(&root, &options) {
+            Err(KbinError::Serialize {
+                source: SerError::InvalidWidening { path, .. },
+            }) => assert_eq!(path, "/Root/scores"),
+            result => panic!("Unexpected result: {:?}", result),
+        };
+    }
+
+    #[test]
+    fn test_type_overrides() {
+        let root = Root {
+            version: 1,
+            scores: vec![10, 20],
+            entry: Vec::new(),
+            comment: Some("2".into()),
+        };
+        let options = Options::builder()
+            .type_overrides(&[
+                ("/**", StandardType::U32),
+                ("/Root/comment", StandardType::S8),
+            ])
+            .build();
+
+        let node = to_node_with_options(&root, &options).expect("Failed to serialize");
+        assert_eq!(
+            node.get_child("version").and_then(Node::value),
+            Some(&Value::U32(1))
+        );
+        assert_eq!(
+            node.get_child("scores").and_then(Node::value),
+            Some(&Value::Array(ValueArray::U32(vec![10, 20])))
+        );
+        assert_eq!(
+            node.get_child("comment").and_then(Node::value),
+            Some(&Value::S8(2))
+        );
+
+        let options = Options::builder()
+            .type_overrides(&[("/Root/scores", StandardType::NodeStart)])
+            .build();
+        match to_node_with_options(&root, &options) {
+            Err(KbinError::InvalidOptions { conflicts }) => assert_eq!(conflicts.len(), 1),
+            result => panic!("Unexpected result: {:?}", result),
+        };
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Compact {
+        id: Uuid,
+        v4: Ipv4Addr,
+        v6: Ipv6Addr,
+    }
+
+    #[test]
+    fn test_compact_types() {
+        let compact = Compact {
+            id: Uuid::from_u128(0x0123_4567_89ab_cdef_0123_4567_89ab_cdef),
+            v4: Ipv4Addr::new(192, 168, 0, 1),
+            v6: Ipv6Addr::LOCALHOST,
+        };
+
+        let node = to_node(&compact).expect("Failed to serialize");
+        assert_eq!(
+            node.get_child("id").and_then(Node::value),
+            Some(&Value::Binary(compact.id.as_bytes().to_vec()))
+        );
+        assert_eq!(
+            node.get_child("v4").and_then(Node::value),
+            Some(&Value::U8_4([192, 168, 0, 1]))
+        );
+
+        let decoded: Compact = from_node(&node).expect("Failed to deserialize");
+        assert_eq!(decoded, compact);
+
+        let mut node = node;
+        node.get_child_mut("v4")
+            .expect("Missing v4 node")
+            .set_value(Some(Value::Ip4(Ipv4Addr::new(10, 0, 0, 1))));
+        let decoded: Compact = from_node(&node).expect("Failed to deserialize");
+        assert_eq!(decoded.v4, Ipv4Addr::new(10, 0, 0, 1));
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Host {
+        addr: KbinIp4<Ipv4Addr>,
+        peers: KbinIp4<Vec<Ipv4Addr>>,
+    }
+
+    #[test]
+    fn test_ip4_fields() {
+        let host = Host {
+            addr: KbinIp4(Ipv4Addr::new(192, 168, 0, 1)),
+            peers: KbinIp4(vec![Ipv4Addr::LOCALHOST, Ipv4Addr::BROADCAST]),
+        };
+
+        let node = to_node(&host).expect("Failed to serialize");
+        assert_eq!(
+            node.get_child("addr").and_then(Node::value),
+            Some(&Value::Ip4(Ipv4Addr::new(192, 168, 0, 1)))
+        );
+        assert_eq!(
+            node.get_child("peers").and_then(Node::value),
+            Some(&Value::Array(ValueArray::Ip4(vec![
+                Ipv4Addr::LOCALHOST,
+                Ipv4Addr::BROADCAST
+            ])))
+        );
+
+        let decoded: Host = from_node(&node).expect("Failed to deserialize");
+        assert_eq!(decoded, host);
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Song {
+        #[serde(rename = "@id")]
+        id: u32,
+        #[serde(rename = "@hidden")]
+        hidden: bool,
+        #[serde(rename = "@artist")]
+        artist: Option<String>,
+        title: String,
+    }
+
+    #[test]
+    fn test_attribute_fields() {
+        let song = Song {
+            id: 403,
+            hidden: true,
+            artist: None,
+            title: String::from("title"),
+        };
+
+        let node = to_node(&song).expect("Failed to serialize");
+        assert_eq!(node.attributes().get("id").map(String::as_str), Some("403"));
+        assert_eq!(
+            node.attributes().get("hidden").map(String::as_str),
+            Some("1")
+        );
+        assert!(!node.attributes().contains_key("artist"));
+        assert_eq!(node.children().len(), 1);
+
+        let decoded: Song = from_node(&node).expect("Failed to deserialize");
+        assert_eq!(decoded, song);
+
+        let mut node = node;
+        node.set_attr("artist", "artist");
+        let decoded: Song = from_node(&node).expect("Failed to deserialize");
+        assert_eq!(decoded.artist.as_deref(), Some("artist"));
+
+        node.set_attr("id", "x");
+        match from_node::<Song>(&node) {
+            Err(KbinError::Deserialize {
+                source: DeError::InvalidAttribute { path, .. },
+            }) => assert_eq!(path, "/Song/@id"),
+            result => panic!("Unexpected result: {:?}", result),
+        };
+    }
+
+    #[derive(Serialize)]
+    enum Mode {
+        Easy,
+        Level(u8),
+        Range(u8, u8),
+        Custom {
+            #[serde(rename = "@name")]
+            name: String,
+            speed: u32,
+        },
+    }
+
+    #[derive(Serialize)]
+    #[serde(tag = "kind")]
+    enum Reward {
+        Coins { amount: u32 },
+    }
+
+    #[derive(Serialize)]
+    #[serde(tag = "type", content = "value")]
+    enum Unlock {
+        Song(u32),
+        Course,
+    }
+
+    #[derive(Serialize)]
+    struct Config {
+        #[serde(rename = "@mode")]
+        default_mode: Mode,
+        mode: Vec<Mode>,
+        reward: Reward,
+        unlock: Vec<Unlock>,
+    }
+
+    #[test]
+    fn test_enums() {
+        let config = Config {
+            default_mode: Mode::Easy,
+            mode: vec![
+                Mode::Easy,
+                Mode::Level(3),
+                Mode::Range(1, 2),
+                Mode::Custom {
+                    name: String::from("fast"),
+                    speed: 2,
+                },
+            ],
+            reward: Reward::Coins { amount: 100 },
+            unlock: vec![Unlock::Song(403), Unlock::Course],
+        };
+
+        let node = to_node(&config).expect("Failed to serialize");
+        assert_eq!(node.attributes()["mode"], "Easy");
+
+        let modes: Vec<&Node> = node
+            .children()
+            .iter()
+            .filter(|child| child.key() == "mode")
+            .collect();
+        assert_eq!(modes[0].value(), Some(&Value::String("Easy".into())));
+        assert_eq!(
+            modes[1].get_child("Level").and_then(Node::value),
+            Some(&Value::U8(3))
+        );
+        assert_eq!(
+            modes[2].get_child("Range").and_then(Node::value),
+            Some(&Value::Array(ValueArray::U8(vec![1, 2])))
+        );
+        let custom = modes[3].get_child("Custom").expect("Missing variant node");
+        assert_eq!(custom.attributes()["name"], "fast");
+        assert_eq!(
+            custom.get_child("speed").and_then(Node::value),
+            Some(&Value::U32(2))
+        );
+
+        let reward = node.get_child("reward").expect("Missing reward node");
+        assert_eq!(
+            reward.get_child("kind").and_then(Node::value),
+            Some(&Value::String("Coins".into()))
+        );
+        assert_eq!(
+            reward.get_child("amount").and_then(Node::value),
+            Some(&Value::U32(100))
+        );
+
+        let unlocks: Vec<&Node> = node
+            .children()
+            .iter()
+            .filter(|child| child.key() == "unlock")
+            .collect();
+        assert_eq!(
+            unlocks[0].get_child("type").and_then(Node::value),
+            Some(&Value::String("Song".into()))
+        );
+        assert_eq!(
+            unlocks[0].get_child("value").and_then(Node::value),
+            Some(&Value::U32(403))
+        );
+        assert!(!unlocks[1].has("value"));
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Chart {
+        attr_id: u32,
+        attr_difficulty: Option<String>,
+        level: u8,
+    }
+
+    #[test]
+    fn test_attribute_field_prefix() {
+        let chart = Chart {
+            attr_id: 7,
+            attr_difficulty: Some(String::from("hard")),
+            level: 12,
+        };
+
+        let node = to_node(&chart).expect("Failed to serialize");
+        assert_eq!(node.attr("id"), Some("7"));
+        assert_eq!(node.attr("difficulty"), Some("hard"));
+        assert_eq!(node.children().len(), 1);
+
+        let decoded: Chart = from_node(&node).expect("Failed to deserialize");
+        assert_eq!(decoded, chart);
+
+        let mut node = node;
+        node.set_attr("id", "x");
+        match from_node::<Chart>(&node) {
+            Err(KbinError::Deserialize {
+                source: DeError::InvalidAttribute { path, .. },
+            }) => assert_eq!(path, "/Chart/@id"),
+            result => panic!("Unexpected result: {:?}", result),
+        };
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Forced {
+        count: KbinU16<u32>,
+        offsets: KbinS8<Vec<i32>>,
+        created: KbinTime<u64>,
+    }
+
+    #[test]
+    fn test_forced_types() {
+        let forced = Forced {
+            count: KbinU16(500),
+            offsets: KbinS8(vec![-1, 1]),
+            created: KbinTime(1_600_000_000),
+        };
+
+        let node = to_node(&forced).expect("Failed to serialize");
+        assert_eq!(
+            node.get_child("count").and_then(Node::value),
+            Some(&Value::U16(500))
+        );
+        assert_eq!(
+            node.get_child("offsets").and_then(Node::value),
+            Some(&Value::Array(ValueArray::S8(vec![-1, 1])))
+        );
+        assert_eq!(
+            node.get_child("created").and_then(Node::value),
+            Some(&Value::Time(1_600_000_000))
+        );
+
+        let decoded: Forced = from_node(&node).expect("Failed to deserialize");
+        assert_eq!(decoded, forced);
+
+        let forced = Forced {
+            count: KbinU16(70_000),
+            ..forced
+        };
+        match to_node(&forced) {
+            Err(KbinError::Serialize {
+                source: SerError::ForcedOutOfRange { value: 70_000, .. },
+            }) => {},
+            result => panic!("Unexpected result: {:?}", result),
+        };
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Position(f32, f32, f32);
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Geometry {
+        position: Position,
+        scale: (f32, f32),
+        flags: (bool, bool, bool, bool),
+        mixed: (u8, u16),
+    }
+
+    #[test]
+    fn test_tuples() {
+        let geometry = Geometry {
+            position: Position(1.0, 2.0, 3.0),
+            scale: (0.5, 2.0),
+            flags: (true, false, true, true),
+            mixed: (1, 2),
+        };
+
+        let node = to_node(&geometry).expect("Failed to serialize");
+        assert_eq!(
+            node.get_child("position").and_then(Node::value),
+            Some(&Value::Float3([1.0, 2.0, 3.0]))
+        );
+        assert_eq!(
+            node.get_child("scale").and_then(Node::value),
+            Some(&Value::Float2([0.5, 2.0]))
+        );
+        assert_eq!(
+            node.get_child("flags").and_then(Node::value),
+            Some(&Value::Boolean4([true, false, true, true]))
+        );
+        assert_eq!(
+            node.children()
+                .iter()
+                .filter(|child| child.key() == "mixed")
+                .count(),
+            2
+        );
+        let decoded: Geometry = from_node(&node).expect("Failed to deserialize");
+        assert_eq!(decoded, geometry);
+
+        let mut node = node;
+        node.get_child_mut("position")
+            .expect("Missing position node")
+            .set_value(Some(Value::Float4([1.0, 2.0, 3.0, 4.0])));
+        match from_node::<Geometry>(&node) {
+            Err(KbinError::Deserialize {
+                source:
+                    DeError::CountMismatch {
+                        path,
+                        expected: 3,
+                        found: 4,
+                        ..
+                    },
+            }) => assert_eq!(path, "/Geometry/position"),
+            result => panic!("Unexpected result: {:?}", result),
+        };
+    }
+}
@@ -0,0 +1,347 @@
+use std::collections::HashMap;
+use std::mem;
+
+use bytes::Bytes;
+use snafu::Snafu;
+
+use crate::compression_type::CompressionType;
+use crate::encoding_type::EncodingType;
+use crate::error::KbinError;
+use crate::node::Node;
+use crate::options::Options;
+use crate::reader::Reader;
+
+#[derive(Debug, Snafu)]
+pub enum ShardError {
+    #[snafu(display("Chunk size must be greater than zero"))]
+    ZeroChunkSize,
+
+    #[snafu(display("No documents to join"))]
+    NoParts,
+
+    #[snafu(display("Envelope of part {} does not match the first part", part))]
+    EnvelopeMismatch { part: usize },
+
+    #[snafu(display(
+        "Document without repeated children is {} bytes, over the limit of {} bytes",
+        size,
+        max_bytes
+    ))]
+    DocumentTooLarge { size: usize, max_bytes: usize },
+
+    #[snafu(display(
+        "Child {} named {:?} does not fit in {} bytes on its own",
+        index,
+        name,
+        max_bytes
+    ))]
+    ChildTooLarge {
+        name: String,
+        index: usize,
+        max_bytes: usize,
+    },
+
+    #[snafu(display(
+        "Child {} named {:?} is separated from the earlier children with that name",
+        index,
+        name
+    ))]
+    NotContiguous { name: String, index: usize },
+}
+
+/// A document with the repeated children taken out.
+#[derive(PartialEq)]
+struct Envelope {
+    compression: CompressionType,
+    encoding: EncodingType,
+    root: Node,
+
+    /// The position in the root children the repeated children start at.
+    index: usize,
+}
+
+impl Envelope {
+    fn read(input: &[u8], child_name: &str) -> Result<(Self, Vec<Node>), KbinError> {
+        let input = Bytes::from(input.to_vec());
+        let compression = Reader::new(input.clone())?.compression();
+        let (collection, encoding) = crate::from_binary(input)?;
+        let root = collection.as_node()?;
+
+        Self::new(compression, encoding, root, child_name)
+    }
+
+    fn new(
+        compression: CompressionType,
+        encoding: EncodingType,
+        mut root: Node,
+        child_name: &str,
+    ) -> Result<(Self, Vec<Node>), KbinError> {
+        let mut index = None;
+        let mut children = Vec::new();
+        for (i, child) in mem::take(root.children_mut()).into_iter().enumerate() {
+            if child.key() == child_name {
+                // The children are put back as one run, so any other child
+                // between them would end up in the wrong place
+                if *index.get_or_insert(root.children().len()) != root.children().len() {
+                    return Err(ShardError::NotContiguous {
+                        name: child_name.to_owned(),
+                        index: i,
+                    }
+                    .into());
+                }
+                children.push(child);
+            } else {
+                root.append_child(child);
+            }
+        }
+
+        let envelope = Self {
+            compression,
+            encoding,
+            index: index.unwrap_or_else(|| root.children().len()),
+            root,
+        };
+
+        Ok((envelope, children))
+    }
+
+    fn root_with(&self, children: &[Node]) -> Node {
+        let mut root = self.root.clone();
+        root.children_mut()
+            .splice(self.index..self.index, children.iter().cloned());
+
+        root
+    }
+
+    fn write(&self, children: &[Node]) -> Result<Vec<u8>, KbinError> {
+        let options = Options::new(self.compression, self.encoding);
+
+        crate::to_binary_with_options(options, &self.root_with(children))
+    }
+}
+
+/// Split a document into documents that each contain at most `chunk_size`
+/// of the root children named `child_name`.
+///
+/// Every other child of the root is copied to each document, which keep the
+/// compression and encoding of the input. A document without any matching
+/// children is returned as a single part. The matching children must follow
+/// each other, otherwise `ShardError::NotContiguous` is returned.
+pub fn split_by_child(
+    input: &[u8],
+    child_name: &str,
+    chunk_size: usize,
+) -> Result<Vec<Vec<u8>>, KbinError> {
+    if chunk_size == 0 {
+        return Err(ShardError::ZeroChunkSize.into());
+    }
+
+    let (envelope, children) = Envelope::read(input, child_name)?;
+    if children.is_empty() {
+        return Ok(vec![envelope.write(&[])?]);
+    }
+
+    children
+        .chunks(chunk_size)
+        .map(|chunk| envelope.write(chunk))
+        .collect()
+}
+
+/// The most common name among the root children, if any name is used more
+/// than once.
+fn repeated_child_name(root: &Node) -> Option<&str> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for child in root.children() {
+        *counts.entry(child.key()).or_default() += 1;
+    }
+
+    // Go through the children again so ties go to the first name
+    root.children()
+        .iter()
+        .map(|child| (child.key(), counts[child.key()]))
+        .filter(|&(_, count)| count > 1)
+        .fold(None, |best, (name, count)| match best {
+            Some((_, best_count)) if best_count >= count => best,
+            _ => Some((name, count)),
+        })
+        .map(|(name, _)| name)
+}
+
+/// Encode `root` as one or more documents of at most `max_bytes` each.
+///
+/// The root children sharing the most common name are split across the
+/// documents, in order and as few documents as possible. Every other child of
+/// the root is copied to each document, like `split_by_child` does, so the
+/// parts can be put back together with `join_documents`. The repeated
+/// children must follow each other, as with `split_by_child`.
+pub fn encode_chunked(
+    root: &Node,
+    options: &Options,
+    max_bytes: usize,
+) -> Result<Vec<Vec<u8>>, KbinError> {
+    let encode = |root: &Node| crate::to_binary_with_options(options.clone(), root);
+
+    let child_name = match repeated_child_name(root) {
+        Some(name) => name,
+        None => {
+            let data = encode(root)?;
+            if data.len() > max_bytes {
+                return Err(ShardError::DocumentTooLarge {
+                    size: data.len(),
+                    max_bytes,
+                }
+                .into());
+            }
+
+            return Ok(vec![data]);
+        },
+    };
+    let (envelope, children) = Envelope::new(
+        options.compression,
+        options.encoding,
+        root.clone(),
+        child_name,
+    )?;
+
+    // Encode `children[start..end]` if the result fits
+    let fit = |start: usize, end: usize| -> Result<Option<Vec<u8>>, KbinError> {
+        let data = encode(&envelope.root_with(&children[start..end]))?;
+
+        Ok(Some(data).filter(|data| data.len() <= max_bytes))
+    };
+
+    let mut parts = Vec::new();
+    let mut start = 0;
+    while start < children.len() {
+        let mut best = fit(start, start + 1)?.ok_or(ShardError::ChildTooLarge {
+            name: child_name.to_owned(),
+            index: start,
+            max_bytes,
+        })?;
+
+        // Double the chunk until it no longer fits, then narrow down the
+        // largest chunk that does
+        let mut low = start + 1;
+        let mut high = None;
+        while high.is_none() && low < children.len() {
+            let end = (start + (low - start) * 2).min(children.len());
+            match fit(start, end)? {
+                Some(data) => {
+                    best = data;
+                    low = end;
+                },
+                None => high = Some(end),
+            }
+        }
+        if let Some(mut high) = high {
+            while high - low > 1 {
+                let mid = low + (high - low) / 2;
+                match fit(start, mid)? {
+                    Some(data) => {
+                        best = data;
+                        low = mid;
+                    },
+                    None => high = mid,
+                }
+            }
+        }
+
+        parts.push(best);
+        start = low;
+    }
+
+    Ok(parts)
+}
+
+/// Join documents produced by `split_by_child` back into one document.
+///
+/// The root children named `child_name` of every part are concatenated in
+/// order. Everything else, including the compression and encoding, must be
+/// the same in every part.
+pub fn join_documents(parts: &[&[u8]], child_name: &str) -> Result<Vec<u8>, KbinError> {
+    let (first, rest) = parts.split_first().ok_or(ShardError::NoParts)?;
+    let (envelope, mut children) = Envelope::read(first, child_name)?;
+
+    for (i, part) in rest.iter().enumerate() {
+        let (part_envelope, part_children) = Envelope::read(part, child_name)?;
+        if part_envelope != envelope {
+            return Err(ShardError::EnvelopeMismatch { part: i + 1 }.into());
+        }
+        children.extend(part_children);
+    }
+
+    envelope.write(&children)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::KbinError;
+    use crate::node::Node;
+    use crate::value::Value;
+
+    use crate::options::Options;
+
+    use super::{encode_chunked, join_documents, split_by_child, ShardError};
+
+    #[test]
+    fn test_split_join() {
+        let mut root = Node::new("root");
+        root.append_child(Node::with_value("version", Value::U32(1)));
+        for i in 0..5 {
+            root.append_child(Node::with_value("music", Value::U32(i)));
+        }
+        root.append_child(Node::new("footer"));
+        let input = crate::to_binary(&root).expect("Failed to encode");
+
+        let parts = split_by_child(&input, "music", 2).expect("Failed to split");
+        assert_eq!(parts.len(), 3);
+
+        let parts: Vec<&[u8]> = parts.iter().map(Vec::as_slice).collect();
+        let joined = join_documents(&parts, "music").expect("Failed to join");
+        assert_eq!(joined, input);
+    }
+
+    #[test]
+    fn test_encode_chunked() {
+        let mut root = Node::new("root");
+        root.append_child(Node::with_value("version", Value::U32(1)));
+        for i in 0..20 {
+            root.append_child(Node::with_value(
+                "music",
+                Value::String(format!("song {}", i)),
+            ));
+        }
+        let options = Options::default();
+        let input = crate::to_binary(&root).expect("Failed to encode");
+
+        let parts = encode_chunked(&root, &options, 200).expect("Failed to encode");
+        assert!(parts.len() > 1);
+        assert!(parts.iter().all(|part| part.len() <= 200));
+
+        let parts: Vec<&[u8]> = parts.iter().map(Vec::as_slice).collect();
+        let joined = join_documents(&parts, "music").expect("Failed to join");
+        assert_eq!(joined, input);
+
+        let whole = encode_chunked(&root, &options, input.len()).expect("Failed to encode");
+        assert_eq!(whole, vec![input]);
+        assert!(encode_chunked(&root, &options, 40).is_err());
+    }
+
+    #[test]
+    fn test_interleaved_children() {
+        let mut root = Node::new("root");
+        root.append_child(Node::with_value("music", Value::U32(0)));
+        root.append_child(Node::new("separator"));
+        root.append_child(Node::with_value("music", Value::U32(1)));
+        let input = crate::to_binary(&root).expect("Failed to encode");
+
+        match split_by_child(&input, "music", 1) {
+            Err(KbinError::Shard {
+                source: ShardError::NotContiguous { name, index },
+            }) => assert_eq!((name.as_str(), index), ("music", 2)),
+            result => panic!("Unexpected result: {:?}", result),
+        };
+        let result = encode_chunked(&root, &Options::default(), input.len() - 1);
+        assert!(result.is_err());
+    }
+}
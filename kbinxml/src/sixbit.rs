@@ -33,6 +33,9 @@ pub enum SixbitError {
 
     #[snafu(display("Failed to write sixbit string data"))]
     DataWrite { source: io::Error },
+
+    #[snafu(display("Invalid sixbit character {:?} in {:?}", ch, input))]
+    InvalidCharacter { ch: char, input: String },
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -44,13 +47,18 @@ pub struct SixbitSize {
 pub struct Sixbit;
 
 impl Sixbit {
+    /// Whether `input` only contains sixbit characters and fits in the
+    /// length byte.
+    pub fn is_valid(input: &str) -> bool {
+        input.len() <= u8::MAX as usize && input.bytes().all(|ch| BYTE_MAP.contains_key(&ch))
+    }
+
     pub fn size<T>(reader: &mut T) -> Result<SixbitSize, SixbitError>
     where
         T: Read,
     {
         let sixbit_len = reader.read_u8().context(LengthReadSnafu)?;
-        let real_len = (f32::from(sixbit_len * 6) / 8f32).ceil();
-        let real_len = (real_len as u32) as usize;
+        let real_len = (usize::from(sixbit_len) * 6).div_ceil(8);
         debug!("sixbit_len: {}, real_len: {}", sixbit_len, real_len);
 
         Ok(SixbitSize {
@@ -63,11 +71,16 @@ impl Sixbit {
     where
         T: Write,
     {
-        let sixbit_chars = input.bytes().map(|ch| {
-            *BYTE_MAP
-                .get(&ch)
-                .expect("Character must be a valid sixbit character")
-        });
+        let invalid = input
+            .chars()
+            .find(|&ch| !ch.is_ascii() || !BYTE_MAP.contains_key(&(ch as u8)));
+        if let Some(ch) = invalid {
+            return Err(SixbitError::InvalidCharacter {
+                ch,
+                input: input.to_owned(),
+            });
+        }
+        let sixbit_chars = input.bytes().map(|ch| BYTE_MAP[&ch]);
 
         let len = input.len();
         let real_len = (f64::from(len as u32 * 6) / 8f64).ceil() as usize;
@@ -136,6 +149,10 @@ mod tests {
         let mut data: Cursor<Vec<u8>> = Cursor::new(Vec::new());
         Sixbit::pack(&mut data, TEST1_STR).expect("Failed to pack sixbit");
         assert_eq!(data.into_inner(), TEST1_BYTES);
+
+        let mut data = Vec::new();
+        assert!(Sixbit::pack(&mut data, "a-b").is_err());
+        assert!(Sixbit::pack(&mut data, "\u{e9}").is_err());
     }
 
     #[test]
@@ -147,6 +164,19 @@ mod tests {
         assert_eq!(result, TEST1_STR);
     }
 
+    #[test]
+    fn test_long_name_size() {
+        let name = "a".repeat(200);
+        let mut data: Cursor<Vec<u8>> = Cursor::new(Vec::new());
+        Sixbit::pack(&mut data, &name).expect("Failed to pack sixbit");
+        let data = data.into_inner();
+
+        let size = Sixbit::size(&mut Cursor::new(&data)).expect("Failed to get size");
+        assert_eq!(size.real_len, 150);
+        let result = Sixbit::unpack(&data[1..], size).expect("Failed to unpack sixbit string");
+        assert_eq!(result, name);
+    }
+
     #[bench]
     fn bench_pack(b: &mut Bencher) {
         let mut data: Cursor<Vec<u8>> = Cursor::new(Vec::with_capacity(10));
@@ -0,0 +1,250 @@
+use crate::compression_type::CompressionType;
+use crate::nested::{parse_header, NESTED_ATTRIBUTE};
+use crate::node::{NodeCollection, NodeDefinition};
+use crate::node_types::StandardType;
+use crate::options::Options;
+use crate::value::Value;
+use crate::writer::attribute_bytes;
+use crate::ARRAY_MASK;
+
+/// The sizes of the buffers of an encoded document.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SizeEstimate {
+    /// The length of the node buffer, including its final padding.
+    pub node_buffer: usize,
+
+    /// The length of the data buffer.
+    pub data_buffer: usize,
+}
+
+impl SizeEstimate {
+    /// The length of the whole document, including the header and the length
+    /// of the data buffer.
+    #[inline]
+    pub fn total(&self) -> usize {
+        8 + self.node_buffer + 4 + self.data_buffer
+    }
+}
+
+/// `len` rounded up to the 4 byte alignment of both buffers.
+#[inline]
+pub(crate) fn padded(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// The encoded length of a name with sixbit compression, including its
+/// length byte.
+#[inline]
+pub(crate) fn sixbit_name_size(name: &str) -> usize {
+    1 + (name.len() * 6).div_ceil(8)
+}
+
+/// Tracks the length of the data buffer the way `ByteBufferWrite` fills it,
+/// including the packing of 1 and 2 byte values into shared words.
+#[derive(Default)]
+struct DataLayout {
+    len: usize,
+    offset_1: usize,
+    offset_2: usize,
+}
+
+impl DataLayout {
+    fn write_sized(&mut self, len: usize) {
+        self.len = padded(self.len + 4 + len);
+    }
+
+    fn write_raw(&mut self, len: usize) {
+        self.len = padded(self.len + len);
+    }
+
+    fn write_aligned(&mut self, size: usize) {
+        if self.offset_1.is_multiple_of(4) {
+            self.offset_1 = self.len;
+        }
+        if self.offset_2.is_multiple_of(4) {
+            self.offset_2 = self.len;
+        }
+
+        match size {
+            1 => {
+                if self.offset_1.is_multiple_of(4) {
+                    self.len += 4;
+                }
+                self.offset_1 += 1;
+            },
+            2 => {
+                if self.offset_2.is_multiple_of(4) {
+                    self.len += 4;
+                }
+                self.offset_2 += 2;
+            },
+            size => self.len = padded(self.len + size),
+        };
+    }
+}
+
+struct Estimator<'a> {
+    options: &'a Options,
+    node_buffer: usize,
+    data: DataLayout,
+}
+
+impl<'a> Estimator<'a> {
+    fn name(&mut self, name: &str) {
+        let len = match self.options.compression {
+            CompressionType::Compressed => sixbit_name_size(name),
+            CompressionType::Uncompressed => {
+                let encoded = self.options.encoding.encode_bytes(name);
+                1 + encoded.map_or(name.len(), |data| data.len() - 1)
+            },
+        };
+
+        // The node type byte comes first.
+        self.node_buffer += 1 + len;
+    }
+
+    fn value(&mut self, def: &NodeDefinition) {
+        let (node_type, is_array) = def.node_type_tuple();
+
+        // Unknown values are written as they were read, see `Value::Unknown`.
+        if let Some(id) = def.unknown_type() {
+            let len = def.value_bytes().map_or(0, <[u8]>::len);
+            match id & ARRAY_MASK {
+                0 => self.data.write_raw(len),
+                _ => self.data.write_sized(len),
+            };
+            return;
+        }
+        match node_type {
+            StandardType::NodeStart => {},
+            StandardType::String => {
+                let len = match def.value() {
+                    Ok(Value::String(text)) => self
+                        .options
+                        .encoding
+                        .encode_bytes_with(&text, self.options.unmappable_characters)
+                        .map_or(text.len() + 1, |data| data.len()),
+                    _ => def.value_bytes().map_or(0, <[u8]>::len),
+                };
+                self.data.write_sized(len);
+            },
+            StandardType::Binary => {
+                self.data
+                    .write_sized(def.value_bytes().map_or(0, <[u8]>::len));
+            },
+            _ if is_array => {
+                self.data
+                    .write_sized(def.value_bytes().map_or(0, <[u8]>::len));
+            },
+            node_type => self.data.write_aligned(node_type.size * node_type.count),
+        };
+    }
+
+    fn collection(&mut self, collection: &NodeCollection) {
+        let name = collection.base().key().ok().flatten().unwrap_or_default();
+
+        let nested = collection.attributes().iter().find_map(|attr| {
+            match attr.key() {
+                Ok(Some(key)) if key == NESTED_ATTRIBUTE => {},
+                _ => return None,
+            };
+            match attr.value() {
+                Ok(Value::Attribute(header)) => parse_header(&header),
+                _ => None,
+            }
+        });
+
+        self.name(&name);
+        match (nested, collection.children().front()) {
+            (Some((compression, encoding)), Some(child)) => {
+                let mut options = self.options.clone();
+                options.compression = compression;
+                options.encoding = encoding;
+                let len = estimate(child, &options).total();
+                self.data.write_sized(len);
+            },
+            _ => self.value(collection.base()),
+        };
+
+        for attr in collection.attributes() {
+            let key = attr.key().ok().flatten().unwrap_or_default();
+            if nested.is_some() && key == NESTED_ATTRIBUTE {
+                continue;
+            }
+
+            let len = attribute_bytes(self.options, attr).map_or_else(
+                |_| attr.value_bytes().map_or(0, <[u8]>::len),
+                |data| data.len(),
+            );
+            self.data.write_sized(len);
+            self.name(&key);
+        }
+
+        if nested.is_none() {
+            for child in collection.children() {
+                self.collection(child);
+            }
+        }
+
+        // The node end marker.
+        self.node_buffer += 1;
+    }
+}
+
+/// Compute the buffer sizes `collection` would be encoded with, without
+/// encoding any values.
+///
+/// Names that fail to decode are counted as empty, where encoding would fail
+/// instead.
+pub(crate) fn estimate(collection: &NodeCollection, options: &Options) -> SizeEstimate {
+    let mut estimator = Estimator {
+        options,
+        node_buffer: 0,
+        data: DataLayout::default(),
+    };
+    estimator.collection(collection);
+
+    SizeEstimate {
+        // The file end marker and the final padding.
+        node_buffer: padded(estimator.node_buffer + 1),
+        data_buffer: estimator.data.len,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compression_type::CompressionType;
+    use crate::encoding_type::EncodingType;
+    use crate::node::Node;
+    use crate::options::Options;
+    use crate::value::Value;
+
+    #[test]
+    fn test_encoded_size_hint() {
+        let mut root = Node::with_attrs("root", &[("status", "0")]);
+        root.append_child(Node::with_value("a", Value::U8(1)));
+        root.append_child(Node::with_value("b", Value::S16(-2)));
+        root.append_child(Node::with_value("c", Value::U8(3)));
+        root.append_child(Node::with_value("d", Value::U32(4)));
+        root.append_child(Node::with_value("e", Value::U8_2([5, 6])));
+        root.append_child(Node::with_value("f", Value::U8(7)));
+        root.append_child(Node::with_value("name", Value::String("kbinxml".into())));
+        root.append_child(Node::with_value("data", Value::Binary(vec![1, 2, 3])));
+        root.append_child(Node::with_value("pos", Value::Double(1.5)));
+        let mut child = Node::with_attrs("child", &[("id", "12345")]);
+        child.append_child(Node::with_value("flag", Value::Boolean(true)));
+        root.append_child(child);
+
+        let data = crate::to_binary(&root).expect("Failed to encode");
+        let (collection, _) = crate::from_slice(&data).expect("Failed to decode");
+        assert_eq!(collection.encoded_size_hint().total(), data.len());
+
+        let options = Options::new(CompressionType::Uncompressed, EncodingType::UTF_8);
+        let data =
+            crate::to_binary_with_options(options.clone(), &collection).expect("Failed to encode");
+        assert_eq!(
+            collection.encoded_size_hint_with_options(&options).total(),
+            data.len()
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use bytes::Bytes;
+
+use crate::compare::{compare_binaries, CompareReport, Divergence};
+use crate::error::KbinError;
+use crate::fingerprint::fingerprint;
+use crate::options::Options;
+use crate::reader::Reader;
+
+/// Why a re-encoded document differs from the original.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AsymmetryReason {
+    /// The header bytes differ.
+    Header,
+
+    /// Every token matches, so the difference is in padding or unused bytes.
+    Padding,
+
+    /// The same tokens are present in a different order.
+    Ordering(Divergence),
+
+    /// A token was changed, added or removed.
+    Token(Divergence),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SymmetryReport {
+    pub original_len: usize,
+    pub encoded_len: usize,
+
+    /// The offset of the first differing byte, if any.
+    pub first_difference: Option<usize>,
+    pub reasons: Vec<AsymmetryReason>,
+}
+
+impl SymmetryReport {
+    #[inline]
+    pub fn is_identical(&self) -> bool {
+        self.first_difference.is_none()
+    }
+}
+
+/// Decode a binary document to a `Node` tree, encode it again with the same
+/// compression and encoding and report whether the output is byte-identical.
+pub fn verify_symmetry(input: &[u8]) -> Result<SymmetryReport, KbinError> {
+    let bytes = Bytes::from(input.to_vec());
+    let compression = Reader::new(bytes.clone())?.compression();
+    let (collection, encoding) = crate::from_binary(bytes)?;
+    let node = collection.as_node()?;
+    let encoded = crate::to_binary_with_options(Options::new(compression, encoding), &node)?;
+
+    let first_difference = input
+        .iter()
+        .zip(&encoded)
+        .position(|(a, b)| a != b)
+        .or_else(|| {
+            if input.len() == encoded.len() {
+                None
+            } else {
+                Some(input.len().min(encoded.len()))
+            }
+        });
+
+    let mut reasons = Vec::new();
+    if first_difference.is_some() {
+        if input.get(..4) != encoded.get(..4) {
+            reasons.push(AsymmetryReason::Header);
+        }
+
+        match compare_binaries(input, &encoded)? {
+            CompareReport::Identical => reasons.push(AsymmetryReason::Padding),
+            CompareReport::Diverged(divergence) => {
+                if fingerprint(input)? == fingerprint(&encoded)? {
+                    reasons.push(AsymmetryReason::Ordering(divergence));
+                } else {
+                    reasons.push(AsymmetryReason::Token(divergence));
+                }
+            },
+        };
+    }
+
+    Ok(SymmetryReport {
+        original_len: input.len(),
+        encoded_len: encoded.len(),
+        first_difference,
+        reasons,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compare::DivergenceKind;
+    use crate::node::Node;
+    use crate::value::Value;
+
+    use super::{verify_symmetry, AsymmetryReason};
+
+    #[test]
+    fn test_verify_symmetry() {
+        let root = Node::with_nodes("root", [Node::with_value("x", Value::U32(1))]);
+        let mut input = crate::to_binary(&root).expect("Failed to encode");
+
+        let report = verify_symmetry(&input).expect("Failed to verify");
+        assert!(report.is_identical());
+        assert!(report.reasons.is_empty());
+        assert_eq!(report.encoded_len, input.len());
+
+        // The node buffer has 11 bytes of nodes and one byte of padding
+        input[19] = 0xff;
+        let report = verify_symmetry(&input).expect("Failed to verify");
+        assert_eq!(report.first_difference, Some(19));
+        assert_eq!(report.reasons, [AsymmetryReason::Padding]);
+    }
+
+    #[test]
+    fn test_duplicate_attributes() {
+        // The sixbit name of a lone node follows the header and its type
+        let name =
+            |name| crate::to_binary(&Node::new(name)).expect("Failed to encode")[9..12].to_vec();
+        let (id, ie) = (name("id"), name("ie"));
+        let root = Node::with_attrs("root", &[("id", "1"), ("ie", "2")]);
+        let mut input = crate::to_binary(&root).expect("Failed to encode");
+        let offset = input
+            .windows(ie.len())
+            .position(|w| w == ie)
+            .expect("Missing name");
+        input[offset..offset + id.len()].copy_from_slice(&id);
+
+        // Only the last of the duplicate attributes is kept
+        let report = verify_symmetry(&input).expect("Failed to verify");
+        assert!(report.encoded_len < report.original_len);
+        match report.reasons.as_slice() {
+            [AsymmetryReason::Token(divergence)] => {
+                assert_eq!(divergence.kind, DivergenceKind::Value);
+                assert_eq!(divergence.a.path, "/root/@id");
+            },
+            reasons => panic!("Unexpected reasons: {:?}", reasons),
+        };
+    }
+}
@@ -0,0 +1,222 @@
+//! Helpers for checking that values survive encoding and decoding.
+//!
+//! Only available with the `test_support` feature.
+
+use std::net::Ipv4Addr;
+
+use crate::error::KbinError;
+use crate::node::Node;
+use crate::node_types::StandardType;
+use crate::value::{Value, ValueArray};
+
+/// Encode `value` as the value of a node of type `node_type` in a binary
+/// document and decode it again.
+pub fn round_trip_value(node_type: StandardType, value: &Value) -> Result<Value, KbinError> {
+    let found = match value {
+        Value::Array(values) => values.standard_type(),
+        value => value.standard_type(),
+    };
+    if found != node_type {
+        return Err(KbinError::TypeMismatch {
+            expected: node_type,
+            found,
+        });
+    }
+
+    let node = Node::with_value("value", value.clone());
+    let data = crate::to_binary(&node)?;
+    let (collection, _) = crate::from_slice(&data)?;
+
+    collection
+        .as_node()?
+        .value()
+        .cloned()
+        .ok_or(KbinError::InvalidState)
+}
+
+/// Compare values bit for bit, so `NaN` equals itself and `0.0` differs from
+/// `-0.0`.
+pub fn values_equal(a: &Value, b: &Value) -> bool {
+    match (a.to_bytes(), b.to_bytes()) {
+        (Ok(a_bytes), Ok(b_bytes)) => a.standard_type() == b.standard_type() && a_bytes == b_bytes,
+        _ => a == b,
+    }
+}
+
+macro_rules! push_values {
+    ($values:ident, [$($element:expr),* $(,)?], $($konst:ident $(* $count:literal)?),* $(,)?) => {
+        for element in [$($element),*] {
+            $(
+                $values.push(Value::$konst(push_values!(@element element $(, $count)?)));
+            )*
+        }
+    };
+    (@element $element:ident) => {
+        $element
+    };
+    (@element $element:ident, $count:literal) => {
+        [$element; $count]
+    };
+}
+
+/// Values at the boundaries of every type that can be stored in a node,
+/// including arrays of every scalar type.
+pub fn boundary_values() -> Vec<Value> {
+    let mut values = Vec::new();
+
+    push_values!(
+        values,
+        [i8::MIN, 0, i8::MAX],
+        S8,
+        S8_2 * 2,
+        S8_3 * 3,
+        S8_4 * 4,
+        Vs8 * 16
+    );
+    push_values!(
+        values,
+        [u8::MIN, u8::MAX],
+        U8,
+        U8_2 * 2,
+        U8_3 * 3,
+        U8_4 * 4,
+        Vu8 * 16
+    );
+    push_values!(
+        values,
+        [i16::MIN, 0, i16::MAX],
+        S16,
+        S16_2 * 2,
+        S16_3 * 3,
+        S16_4 * 4,
+        Vs16 * 8
+    );
+    push_values!(
+        values,
+        [u16::MIN, u16::MAX],
+        U16,
+        U16_2 * 2,
+        U16_3 * 3,
+        U16_4 * 4,
+        Vu16 * 8
+    );
+    push_values!(
+        values,
+        [i32::MIN, 0, i32::MAX],
+        S32,
+        S32_2 * 2,
+        S32_3 * 3,
+        S32_4 * 4
+    );
+    push_values!(
+        values,
+        [u32::MIN, u32::MAX],
+        U32,
+        U32_2 * 2,
+        U32_3 * 3,
+        U32_4 * 4,
+        Time
+    );
+    push_values!(
+        values,
+        [i64::MIN, 0, i64::MAX],
+        S64,
+        S64_2 * 2,
+        S64_3 * 3,
+        S64_4 * 4
+    );
+    push_values!(
+        values,
+        [u64::MIN, u64::MAX],
+        U64,
+        U64_2 * 2,
+        U64_3 * 3,
+        U64_4 * 4
+    );
+    push_values!(
+        values,
+        [
+            0.0,
+            -0.0,
+            f32::NAN,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::MIN,
+            f32::MAX,
+            f32::MIN_POSITIVE
+        ],
+        Float,
+        Float2 * 2,
+        Float3 * 3,
+        Float4 * 4,
+    );
+    push_values!(
+        values,
+        [
+            0.0,
+            -0.0,
+            f64::NAN,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::MIN,
+            f64::MAX,
+            f64::MIN_POSITIVE
+        ],
+        Double,
+        Double2 * 2,
+        Double3 * 3,
+        Double4 * 4,
+    );
+    push_values!(
+        values,
+        [false, true],
+        Boolean,
+        Boolean2 * 2,
+        Boolean3 * 3,
+        Boolean4 * 4,
+        Vb * 16
+    );
+    push_values!(values, [Ipv4Addr::UNSPECIFIED, Ipv4Addr::BROADCAST], Ip4);
+
+    values.push(Value::String(String::new()));
+    values.push(Value::String(String::from("kbin")));
+    values.push(Value::Binary(Vec::new()));
+    values.push(Value::Binary(vec![0x00, 0xFF]));
+
+    let arrays: Vec<Value> = values
+        .iter()
+        .filter(|value| value.standard_type().count == 1)
+        .filter_map(|value| {
+            let node_type = value.standard_type();
+            let values = vec![value.clone(), value.clone()];
+            ValueArray::from_values(node_type, values)
+                .ok()
+                .map(Value::Array)
+        })
+        .collect();
+    values.extend(arrays);
+
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{boundary_values, round_trip_value, values_equal};
+
+    #[test]
+    fn test_round_trip_matrix() {
+        for value in boundary_values() {
+            let node_type = match value {
+                crate::value::Value::Array(ref values) => values.standard_type(),
+                ref value => value.standard_type(),
+            };
+            let decoded = round_trip_value(node_type, &value).expect("Failed to round trip");
+            assert!(
+                values_equal(&value, &decoded),
+                "{:?} != {:?}",
+                value,
+                decoded
+            );
+        }
+    }
+}
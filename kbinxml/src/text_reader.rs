@@ -1,7 +1,9 @@
+use std::borrow::Cow;
 use std::num::ParseIntError;
 use std::str::{self, Utf8Error};
 
 use bytes::{BufMut, Bytes, BytesMut};
+use indexmap::IndexMap;
 use quick_xml::events::attributes::Attributes;
 use quick_xml::events::{BytesStart, BytesText, Event};
 use quick_xml::Error as QuickXmlError;
@@ -9,12 +11,109 @@ use quick_xml::Reader;
 use snafu::{ResultExt, Snafu};
 
 use crate::encoding_type::{EncodingError, EncodingType};
-use crate::node::{Key, NodeCollection, NodeData, NodeDefinition};
+use crate::filter::{child_paths, type_override};
+use crate::node::{Key, Markup, MarkupKind, NodeCollection, NodeData, NodeDefinition};
 use crate::node_types::{StandardType, UnknownKbinType};
+use crate::options::{
+    decode_name, encode_name, BinarySizePolicy, Options, TypeNamePolicy, UnknownTypeNamePolicy,
+};
 use crate::value::Value;
 
 const EMPTY_STRING_DATA: &[u8] = &[0];
 
+/// The opening of a placeholder, see `OptionsBuilder::variable`.
+const PLACEHOLDER_START: &str = "${";
+
+/// Replace the placeholders in `text` with the values of `variables`.
+fn substitute<'t>(
+    text: &'t str,
+    variables: &IndexMap<String, String>,
+) -> Result<Cow<'t, str>, TextReaderError> {
+    if variables.is_empty() || !text.contains(PLACEHOLDER_START) {
+        return Ok(Cow::Borrowed(text));
+    }
+
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(PLACEHOLDER_START) {
+        // `$${` escapes a placeholder.
+        if rest[..start].ends_with('$') {
+            output.push_str(&rest[..start - 1]);
+            output.push_str(PLACEHOLDER_START);
+            rest = &rest[start + PLACEHOLDER_START.len()..];
+            continue;
+        }
+
+        output.push_str(&rest[..start]);
+        rest = &rest[start + PLACEHOLDER_START.len()..];
+        let end = rest
+            .find('}')
+            .ok_or_else(|| TextReaderError::UnclosedPlaceholder {
+                text: text.to_owned(),
+            })?;
+        let name = &rest[..end];
+        let value = variables
+            .get(name)
+            .ok_or_else(|| TextReaderError::UnknownVariable {
+                name: name.to_owned(),
+            })?;
+        output.push_str(value);
+        rest = &rest[end + 1..];
+    }
+    output.push_str(rest);
+
+    Ok(Cow::Owned(output))
+}
+
+/// Count the primary and alternate names in the `__type` attributes of
+/// `input`, stopping at the first XML error.
+pub(crate) fn detect_type_names(input: &[u8]) -> TypeNamePolicy {
+    let mut reader = Reader::from_reader(input);
+    let mut buf = Vec::new();
+    let mut primary = 0usize;
+    let mut alternate = 0usize;
+
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
+                let name = e
+                    .attributes()
+                    .filter_map(Result::ok)
+                    .find(|attr| attr.key == b"__type")
+                    .and_then(|attr| str::from_utf8(&attr.value).ok().map(str::to_owned));
+                let node_type = name
+                    .as_deref()
+                    .and_then(|name| StandardType::from_name(name).ok().map(|t| (name, t)));
+
+                if let Some((name, node_type)) = node_type {
+                    if node_type.alt_name == Some(name) {
+                        alternate += 1;
+                    } else if node_type.alt_name.is_some() && node_type.name == name {
+                        primary += 1;
+                    }
+                }
+            },
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {},
+        };
+        buf.clear();
+    }
+
+    if alternate > primary {
+        TypeNamePolicy::Alternate
+    } else {
+        TypeNamePolicy::Primary
+    }
+}
+
+/// Node type, `__count`, `__size` and remaining attributes of a start tag.
+type ParsedAttributes = (
+    StandardType,
+    Option<usize>,
+    Option<usize>,
+    Vec<NodeDefinition>,
+);
+
 #[derive(Debug, Snafu)]
 pub enum TextReaderError {
     #[snafu(display("Invalid kbin type found"))]
@@ -23,6 +122,9 @@ pub enum TextReaderError {
     #[snafu(display("Invalid encoding type read from declaration"))]
     InvalidEncoding { source: EncodingError },
 
+    #[snafu(display("Failed to convert renamed node name"))]
+    NameEncoding { source: EncodingError },
+
     #[snafu(display("Failed to parse array count from attribute"))]
     ParseArrayCount { source: ParseIntError },
 
@@ -36,9 +138,22 @@ pub enum TextReaderError {
     ))]
     MismatchedBinaryNodeLength { len: usize, size: usize },
 
+    #[snafu(display(
+        "Binary node value is longer than its size attribute (value length: {}, size attribute: {})",
+        len,
+        size
+    ))]
+    BinaryExceedsSize { len: usize, size: usize },
+
     #[snafu(display("No node data found"))]
     NoNodeData,
 
+    #[snafu(display(
+        "Empty node {:?} has no value for its __count or __size attribute",
+        name
+    ))]
+    MissingValue { name: String },
+
     #[snafu(display("Failed to interpret byte slice as UTF-8"))]
     Utf8 { source: Utf8Error },
 
@@ -56,6 +171,20 @@ pub enum TextReaderError {
         source: Box<crate::KbinError>,
     },
 
+    #[snafu(display("Failed to force value at {} to type {}", path, node_type))]
+    TypeOverride {
+        path: String,
+        node_type: StandardType,
+        #[snafu(source(from(crate::KbinError, Box::new)))]
+        source: Box<crate::KbinError>,
+    },
+
+    #[snafu(display("No value for variable {:?}", name))]
+    UnknownVariable { name: String },
+
+    #[snafu(display("Unclosed placeholder in {:?}", text))]
+    UnclosedPlaceholder { text: String },
+
     #[snafu(display("Failed to handle XML operation"))]
     Xml { source: QuickXmlError },
 }
@@ -77,22 +206,36 @@ impl From<QuickXmlError> for TextReaderError {
 pub struct TextXmlReader<'a> {
     xml_reader: Reader<&'a [u8]>,
     encoding: EncodingType,
+    binary_size: BinarySizePolicy,
+    unknown_type_names: UnknownTypeNamePolicy,
+    type_overrides: Vec<(String, StandardType)>,
+    renames: IndexMap<String, String>,
+    variables: IndexMap<String, String>,
 
     stack: Vec<(NodeCollection, usize, Option<usize>)>,
+
+    // Comments and processing instructions read before the root node.
+    leading_markup: Vec<Markup>,
 }
 
 impl<'a> TextXmlReader<'a> {
-    pub fn new(input: &'a [u8]) -> Self {
+    pub fn with_options(input: &'a [u8], options: &Options) -> Self {
         let mut xml_reader = Reader::from_reader(input);
         xml_reader.trim_text(true);
 
         Self {
             xml_reader,
             encoding: EncodingType::UTF_8,
+            binary_size: options.binary_size,
+            unknown_type_names: options.unknown_type_names,
+            type_overrides: options.type_overrides.clone(),
+            renames: options.renames.clone(),
+            variables: options.variables.clone(),
 
             // Most kbinxml files that I have come across do not have too
             // many inner layers.
             stack: Vec::with_capacity(6),
+            leading_markup: Vec::new(),
         }
     }
 
@@ -101,6 +244,41 @@ impl<'a> TextXmlReader<'a> {
         self.encoding
     }
 
+    /// The name written to the document for a name read from text XML, see
+    /// `OptionsBuilder::rename`.
+    fn document_name(&self, name: &[u8]) -> Result<Bytes, TextReaderError> {
+        if self.renames.is_empty() {
+            return Ok(Bytes::from(name.to_vec()));
+        }
+
+        let decoded = self
+            .encoding
+            .decode_borrowed(name)
+            .context(NameEncodingSnafu)?;
+        let data = match encode_name(&self.renames, &decoded) {
+            Cow::Borrowed(_) => name.to_vec(),
+            Cow::Owned(original) => {
+                let mut data = self
+                    .encoding
+                    .encode_bytes(&original)
+                    .context(NameEncodingSnafu)?;
+
+                // Names have no trailing null byte
+                data.pop();
+                data
+            },
+        };
+
+        Ok(Bytes::from(data))
+    }
+
+    /// The name of `collection` as read from the text XML.
+    fn text_name(&self, collection: &NodeCollection) -> String {
+        let key = collection.base().key().ok().flatten().unwrap_or_default();
+
+        decode_name(&self.renames, &key).into_owned()
+    }
+
     fn parse_attribute(&self, key: &[u8], value: &[u8]) -> Result<NodeDefinition, TextReaderError> {
         let mut value = BytesMut::from(value);
 
@@ -111,7 +289,7 @@ impl<'a> TextXmlReader<'a> {
         let data = NodeData::Some {
             key: Key::Uncompressed {
                 encoding: self.encoding,
-                data: Bytes::from(key.to_vec()),
+                data: self.document_name(key)?,
             },
             value_data: value.freeze(),
         };
@@ -125,12 +303,29 @@ impl<'a> TextXmlReader<'a> {
         ))
     }
 
-    fn parse_attributes(
-        &self,
-        attrs: Attributes<'a>,
-    ) -> Result<(StandardType, usize, Option<usize>, Vec<NodeDefinition>), TextReaderError> {
+    fn parse_type_name(&self, name: &str) -> Result<StandardType, TextReaderError> {
+        let error = match StandardType::from_name(name) {
+            Ok(node_type) => return Ok(node_type),
+            Err(e) => e,
+        };
+        let node_type = match self.unknown_type_names {
+            UnknownTypeNamePolicy::Error => {
+                return Err(TextReaderError::InvalidKbinType { source: error });
+            },
+            UnknownTypeNamePolicy::String => StandardType::String,
+            UnknownTypeNamePolicy::Binary => StandardType::Binary,
+        };
+        warn!(
+            "unknown node type name {:?}, reading as {}",
+            name, node_type.name
+        );
+
+        Ok(node_type)
+    }
+
+    fn parse_attributes(&self, attrs: Attributes<'a>) -> Result<ParsedAttributes, TextReaderError> {
         let mut node_type = None;
-        let mut count = 0;
+        let mut count = None;
         let mut size = None;
         let mut attributes = Vec::new();
 
@@ -148,13 +343,12 @@ impl<'a> TextXmlReader<'a> {
                     if attr.key == b"__type" {
                         let value = str::from_utf8(&*value)?;
 
-                        node_type =
-                            Some(StandardType::from_name(value).context(InvalidKbinTypeSnafu)?);
+                        node_type = Some(self.parse_type_name(value)?);
                     } else if attr.key == b"__count" {
                         let value = str::from_utf8(&*value)?;
                         let num_count = value.parse::<u32>().context(ParseArrayCountSnafu)?;
 
-                        count = num_count as usize;
+                        count = Some(num_count as usize);
                     } else if attr.key == b"__size" {
                         let value = str::from_utf8(&*value)?
                             .parse::<usize>()
@@ -162,7 +356,8 @@ impl<'a> TextXmlReader<'a> {
 
                         size = Some(value);
                     } else {
-                        let definition = self.parse_attribute(attr.key, &value)?;
+                        let value = substitute(str::from_utf8(&value)?, &self.variables)?;
+                        let definition = self.parse_attribute(attr.key, value.as_bytes())?;
                         attributes.push(definition);
                     }
                 },
@@ -189,7 +384,10 @@ impl<'a> TextXmlReader<'a> {
         e: BytesStart,
     ) -> Result<(NodeCollection, usize, Option<usize>), TextReaderError> {
         let (node_type, count, size, attributes) = self.parse_attributes(e.attributes())?;
-        let is_array = count > 0;
+
+        // A `__count` attribute marks an array, even when it is zero.
+        let is_array = count.is_some();
+        let count = count.unwrap_or(0);
 
         // Stub the value for now, handle with `Event::Text`.
         let value_data = match node_type {
@@ -199,7 +397,7 @@ impl<'a> TextXmlReader<'a> {
         let data = NodeData::Some {
             key: Key::Uncompressed {
                 encoding: self.encoding,
-                data: Bytes::from(e.name().to_vec()),
+                data: self.document_name(e.name())?,
             },
             value_data,
         };
@@ -215,11 +413,14 @@ impl<'a> TextXmlReader<'a> {
         definition: &mut NodeDefinition,
         count: usize,
         size: Option<usize>,
+        binary_size: BinarySizePolicy,
+        variables: &IndexMap<String, String>,
     ) -> Result<(), TextReaderError> {
         let data = event.unescaped()?;
+        let data = substitute(str::from_utf8(&*data)?, variables)?;
         let data = match definition.node_type {
             StandardType::String | StandardType::NodeStart => {
-                let mut data = BytesMut::from(&*data);
+                let mut data = BytesMut::from(data.as_bytes());
 
                 // Add the trailing null byte that kbin has at the end of strings
                 data.reserve(1);
@@ -228,18 +429,23 @@ impl<'a> TextXmlReader<'a> {
                 data.freeze()
             },
             node_type => {
-                let text = str::from_utf8(&*data)?;
-                let value = Value::from_string(node_type, text, definition.is_array, count)
+                let mut value = Value::from_string(node_type, &data, definition.is_array, count)
                     .context(ValueDecodeSnafu { node_type })?;
 
                 // The read number of bytes must match the size attribute, if set
-                if let Value::Binary(data) = &value {
+                if let Value::Binary(data) = &mut value {
                     if let Some(size) = size {
-                        if data.len() != size {
-                            return Err(TextReaderError::MismatchedBinaryNodeLength {
-                                len: data.len(),
-                                size,
-                            });
+                        let len = data.len();
+                        if len > size {
+                            match binary_size {
+                                BinarySizePolicy::Error => {
+                                    return Err(TextReaderError::BinaryExceedsSize { len, size });
+                                },
+                                BinarySizePolicy::Truncate => data.truncate(size),
+                                BinarySizePolicy::UpdateSize => {},
+                            };
+                        } else if len < size {
+                            return Err(TextReaderError::MismatchedBinaryNodeLength { len, size });
                         }
                     }
                 }
@@ -267,6 +473,71 @@ impl<'a> TextXmlReader<'a> {
         Ok(())
     }
 
+    /// Force the values of `collection` and its children to the types of the
+    /// matching `OptionsBuilder::type_overrides`.
+    fn override_types(
+        &self,
+        collection: &mut NodeCollection,
+        path: &str,
+    ) -> Result<(), TextReaderError> {
+        let base = collection.base_mut();
+        let node_type = type_override(&self.type_overrides, path).filter(|&node_type| {
+            base.node_type != StandardType::NodeStart && base.node_type != node_type
+        });
+        if let Some(node_type) = node_type {
+            let retyped = base.value().and_then(|value| {
+                let value = value.retype(node_type)?;
+                let data = match &value {
+                    Value::String(text) => base.encoding().encode_bytes(text)?,
+                    value => value.to_bytes()?,
+                };
+
+                Ok((matches!(value, Value::Array(_)), data))
+            });
+            let (is_array, data) = retyped.context(TypeOverrideSnafu { path, node_type })?;
+
+            base.node_type = node_type;
+            base.is_array = is_array;
+            if let NodeData::Some { value_data, .. } = base.data_mut() {
+                *value_data = Bytes::from(data);
+            }
+        }
+
+        // Overrides match the names in the text XML
+        let keys: Vec<_> = collection
+            .children()
+            .iter()
+            .map(|child| self.text_name(child))
+            .collect();
+        let paths = child_paths(path, keys.iter().map(String::as_str));
+        for (child, path) in collection.children_mut().iter_mut().zip(paths) {
+            self.override_types(child, &path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Keep a comment or processing instruction with the node it is in.
+    ///
+    /// Markup before the root node is added to the start of the root node.
+    fn handle_markup(&mut self, kind: MarkupKind, event: BytesText) -> Result<(), TextReaderError> {
+        let mut markup = Markup {
+            kind,
+            text: str::from_utf8(event.escaped())?.to_owned(),
+            position: 0,
+        };
+
+        match self.stack.last_mut() {
+            Some((parent_collection, _count, _size)) => {
+                markup.position = parent_collection.children().len();
+                parent_collection.markup_mut().push(markup);
+            },
+            None => self.leading_markup.push(markup),
+        };
+
+        Ok(())
+    }
+
     pub fn as_node_collection(&mut self) -> Result<Option<NodeCollection>, TextReaderError> {
         // A buffer size for reading a `quick_xml::events::Event` that I pulled
         // out of my head.
@@ -275,32 +546,48 @@ impl<'a> TextXmlReader<'a> {
         loop {
             match self.xml_reader.read_event(&mut buf)? {
                 Event::Start(e) => {
-                    let start = self.handle_start(e)?;
+                    let mut start = self.handle_start(e)?;
+                    if self.stack.is_empty() {
+                        start.0.markup_mut().append(&mut self.leading_markup);
+                    }
                     self.stack.push(start);
                 },
                 Event::Text(e) => {
                     if let Some((ref mut collection, ref count, ref size)) = self.stack.last_mut() {
                         let base = collection.base_mut();
-                        Self::handle_text(e, base, *count, *size)?;
+                        Self::handle_text(
+                            e,
+                            base,
+                            *count,
+                            *size,
+                            self.binary_size,
+                            &self.variables,
+                        )?;
                     }
                 },
                 Event::End(_) => {
-                    if let Some((collection, _count, _size)) = self.stack.pop() {
+                    if let Some((mut collection, _count, _size)) = self.stack.pop() {
                         if let Some((parent_collection, _count, _size)) = self.stack.last_mut() {
                             parent_collection.children_mut().push_back(collection);
                         } else {
                             // The end of the structure has been reached.
+                            if !self.type_overrides.is_empty() {
+                                let path = format!("/{}", self.text_name(&collection));
+                                self.override_types(&mut collection, &path)?;
+                            }
+
                             return Ok(Some(collection));
                         }
                     }
                 },
                 Event::Empty(e) => {
                     let (collection, count, size) = self.handle_start(e)?;
-                    assert!(count == 0, "empty node should not signal an array");
-                    assert!(
-                        size.is_none() || size == Some(0),
-                        "empty node should not signal binary data"
-                    );
+                    if count != 0 || size.unwrap_or(0) != 0 {
+                        let name = collection.base().key().ok().flatten();
+                        return Err(TextReaderError::MissingValue {
+                            name: name.unwrap_or_default(),
+                        });
+                    }
 
                     if let Some((ref mut parent_collection, _count, _size)) = self.stack.last_mut()
                     {
@@ -313,6 +600,8 @@ impl<'a> TextXmlReader<'a> {
                             EncodingType::from_label(&encoding?).context(InvalidEncodingSnafu)?;
                     }
                 },
+                Event::Comment(e) => self.handle_markup(MarkupKind::Comment, e)?,
+                Event::PI(e) => self.handle_markup(MarkupKind::ProcessingInstruction, e)?,
                 Event::Eof => break,
                 _ => {},
             };
@@ -323,3 +612,219 @@ impl<'a> TextXmlReader<'a> {
         Ok(None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::error::KbinError;
+    use crate::node::{Markup, MarkupKind, Node};
+    use crate::node_types::StandardType;
+    use crate::options::{BinarySizePolicy, Options, UnknownTypeNamePolicy};
+    use crate::value::{Value, ValueArray};
+
+    use super::TextReaderError;
+
+    const OVERSIZED: &[u8] = br#"<root><data __type="bin" __size="2">aabbcc</data></root>"#;
+    const UNKNOWN_TYPE: &[u8] = br#"<root><data __type="u24">aabbcc</data></root>"#;
+
+    fn read_data(binary_size: BinarySizePolicy) -> Result<Value, KbinError> {
+        let options = Options::builder().binary_size(binary_size).build();
+        let (collection, _) = crate::from_text_xml_with_options(options, OVERSIZED)?;
+
+        collection.children()[0].base().value()
+    }
+
+    #[test]
+    fn test_oversized_binary() {
+        assert!(matches!(
+            read_data(BinarySizePolicy::Error),
+            Err(KbinError::TextReader {
+                source: TextReaderError::BinaryExceedsSize { len: 3, size: 2 },
+            })
+        ));
+        assert_eq!(
+            read_data(BinarySizePolicy::Truncate).unwrap(),
+            Value::Binary(vec![0xaa, 0xbb])
+        );
+        assert_eq!(
+            read_data(BinarySizePolicy::UpdateSize).unwrap(),
+            Value::Binary(vec![0xaa, 0xbb, 0xcc])
+        );
+    }
+
+    #[test]
+    fn test_empty_array() {
+        let input = br#"<root><data __type="u8" __count="2"/></root>"#;
+        assert!(matches!(
+            crate::from_text_xml(input),
+            Err(KbinError::TextReader {
+                source: TextReaderError::MissingValue { .. },
+            })
+        ));
+        assert!(crate::from_text_xml(br#"<root><data __type="u8" __count="0"/></root>"#).is_ok());
+    }
+
+    #[test]
+    fn test_markup() {
+        let input = br#"<!-- header --><root>
+            <?editor fold?><a __type="u32">7</a>
+            <!-- note --><__pi __type="u32">7</__pi>
+        </root>"#;
+        let markup = |kind, text: &str, position| Markup {
+            kind,
+            text: text.to_owned(),
+            position,
+        };
+        let expected = [
+            markup(MarkupKind::Comment, " header ", 0),
+            markup(MarkupKind::ProcessingInstruction, "editor fold", 0),
+            markup(MarkupKind::Comment, " note ", 1),
+        ];
+
+        let (collection, _) = crate::from_text_xml(input).expect("Failed to read");
+        assert_eq!(collection.children().len(), 2);
+        assert_eq!(collection.markup(), &expected[..]);
+        let node = collection.as_node().expect("Failed to convert");
+        assert_eq!(node.children()[1].key(), "__pi");
+        assert_eq!(node.markup(), &expected[..]);
+
+        for text in [
+            crate::to_text_xml(&collection).expect("Failed to write"),
+            crate::to_text_xml(&node).expect("Failed to write"),
+        ] {
+            let (read, _) = crate::from_text_xml(&text).expect("Failed to read");
+            assert_eq!(read, collection);
+        }
+
+        // Markup has no binary form, while `__pi` is an ordinary node.
+        let data = crate::to_binary(&node).expect("Failed to encode");
+        let (collection, _) = crate::from_binary(data.into()).expect("Failed to decode");
+        let read = collection.as_node().expect("Failed to convert");
+        assert_eq!(read, node);
+        assert_eq!(read.children()[1].value(), Some(&Value::U32(7)));
+        assert!(read.markup().is_empty());
+    }
+
+    #[test]
+    fn test_unknown_type_names() {
+        let read = |policy| -> Result<Value, KbinError> {
+            let options = Options::builder().unknown_type_names(policy).build();
+            let (collection, _) = crate::from_text_xml_with_options(options, UNKNOWN_TYPE)?;
+
+            collection.children()[0].base().value()
+        };
+
+        assert!(matches!(
+            read(UnknownTypeNamePolicy::Error),
+            Err(KbinError::TextReader {
+                source: TextReaderError::InvalidKbinType { .. },
+            })
+        ));
+        assert_eq!(
+            read(UnknownTypeNamePolicy::String).unwrap(),
+            Value::String("aabbcc".into())
+        );
+        assert_eq!(
+            read(UnknownTypeNamePolicy::Binary).unwrap(),
+            Value::Binary(vec![0xaa, 0xbb, 0xcc])
+        );
+    }
+
+    #[test]
+    fn test_type_overrides() {
+        let input = br#"<root>
+            <a><flag __type="s32">1</flag></a>
+            <b><flag>0</flag><name>1</name></b>
+            <list __type="u16" __count="2">1 2</list>
+            <name __type="u8">3</name>
+        </root>"#;
+        let options = Options::builder()
+            .type_overrides(&[
+                ("/**/flag", StandardType::U8),
+                ("/root/list", StandardType::U32),
+                ("/root/name", StandardType::String),
+            ])
+            .build();
+        let (collection, _) =
+            crate::from_text_xml_with_options(options.clone(), input).expect("Failed to read");
+        let node = collection.as_node().expect("Failed to convert");
+
+        let value = |path: &[&str]| {
+            path.iter()
+                .try_fold(&node, |node, name| node.get_child(name))
+                .and_then(Node::value)
+                .cloned()
+        };
+        assert_eq!(value(&["a", "flag"]), Some(Value::U8(1)));
+        assert_eq!(value(&["b", "flag"]), Some(Value::U8(0)));
+        assert_eq!(value(&["b", "name"]), Some(Value::String("1".into())));
+        assert_eq!(
+            value(&["list"]),
+            Some(Value::Array(ValueArray::U32(vec![1, 2])))
+        );
+        assert_eq!(value(&["name"]), Some(Value::String("3".into())));
+
+        let binary = crate::to_binary(&collection).expect("Failed to encode");
+        let (decoded, _) = crate::from_slice(&binary).expect("Failed to decode");
+        assert_eq!(decoded.as_node().expect("Failed to convert"), node);
+
+        let input = br#"<root><flag>yes</flag></root>"#;
+        match crate::from_text_xml_with_options(options, input) {
+            Err(KbinError::TextReader {
+                source: TextReaderError::TypeOverride { path, .. },
+            }) => assert_eq!(path, "/root/flag"),
+            result => panic!("Unexpected result: {:?}", result),
+        };
+    }
+
+    #[test]
+    fn test_variables() {
+        let input = br#"<root version="${version}">
+            <ver __type="u32">${build}</ver>
+            <title>${name} $${name}</title>
+            <pos __type="3s32">1 ${y} 3</pos>
+        </root>"#;
+        let options = Options::builder()
+            .variable("version", "1.2")
+            .variable("build", "42")
+            .variable("name", "Game")
+            .variable("y", "-2")
+            .build();
+        let (collection, _) =
+            crate::from_text_xml_with_options(options.clone(), input).expect("Failed to read");
+        let node = collection.as_node().expect("Failed to convert");
+        assert_eq!(node.attr("version"), Some("1.2"));
+        assert_eq!(
+            node.get_child("ver").and_then(Node::value),
+            Some(&Value::U32(42))
+        );
+        assert_eq!(
+            node.get_child("title").and_then(Node::value),
+            Some(&Value::String("Game ${name}".into()))
+        );
+        assert_eq!(
+            node.get_child("pos").and_then(Node::value),
+            Some(&Value::S32_3([1, -2, 3]))
+        );
+
+        let input = br#"<root><ver __type="u32">${name}</ver></root>"#;
+        match crate::from_text_xml_with_options(options.clone(), input) {
+            Err(KbinError::TextReader {
+                source: TextReaderError::ValueDecode { node_type, .. },
+            }) => assert_eq!(node_type, StandardType::U32),
+            result => panic!("Unexpected result: {:?}", result),
+        };
+        let input = br#"<root><ver>${missing}</ver></root>"#;
+        match crate::from_text_xml_with_options(options, input) {
+            Err(KbinError::TextReader {
+                source: TextReaderError::UnknownVariable { name },
+            }) => assert_eq!(name, "missing"),
+            result => panic!("Unexpected result: {:?}", result),
+        };
+
+        let (collection, _) = crate::from_text_xml(input).expect("Failed to read");
+        assert_eq!(
+            collection.as_node().expect("Failed to convert").children()[0].value(),
+            Some(&Value::String("${missing}".into()))
+        );
+    }
+}
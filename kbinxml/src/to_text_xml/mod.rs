@@ -1,10 +1,14 @@
 use std::io::{Cursor, Write};
 
-use quick_xml::events::{BytesDecl, Event};
+use quick_xml::events::{BytesDecl, BytesText, Event};
 use quick_xml::Writer;
 
 use crate::encoding_type::EncodingType;
 use crate::error::KbinError;
+use crate::node::{is_xml_name, Markup, MarkupKind};
+use crate::options::{FloatFormat, Options};
+use crate::value::Value;
+use crate::ARRAY_MASK;
 
 mod node;
 mod node_collection;
@@ -12,18 +16,147 @@ mod node_collection;
 pub trait ToTextXml {
     fn encoding(&self) -> EncodingType;
     fn write<W: Write>(&self, writer: &mut Writer<W>) -> Result<(), KbinError>;
+
+    /// Like `write`, following the text XML settings of `options`. Ignores
+    /// `options` unless implemented.
+    fn write_with_options<W: Write>(
+        &self,
+        writer: &mut Writer<W>,
+        options: &Options,
+    ) -> Result<(), KbinError> {
+        let _ = options;
+        self.write(writer)
+    }
+}
+
+/// Write the children of a node with its markup placed between them.
+fn write_children<'a, W, T, I>(
+    writer: &mut Writer<W>,
+    options: &Options,
+    children: I,
+    markup: &[Markup],
+) -> Result<(), KbinError>
+where
+    W: Write,
+    T: ToTextXml + 'a,
+    I: IntoIterator<Item = &'a T>,
+{
+    let mut markup = markup.iter().peekable();
+    let mut write_until = |writer: &mut Writer<W>, position: usize| -> Result<(), KbinError> {
+        while let Some(item) = markup.next_if(|item| item.position <= position) {
+            let text = BytesText::from_escaped_str(&item.text);
+            writer.write_event(match item.kind {
+                MarkupKind::Comment => Event::Comment(text),
+                MarkupKind::ProcessingInstruction => Event::PI(text),
+            })?;
+        }
+
+        Ok(())
+    };
+
+    for (position, child) in children.into_iter().enumerate() {
+        write_until(writer, position)?;
+        child.write_with_options(writer, options)?;
+    }
+    write_until(writer, usize::MAX)
+}
+
+/// The text of a node value, with floats formatted by `options`.
+fn value_text(value: &Value, options: &Options) -> String {
+    match options.float_format {
+        FloatFormat::Fixed => value.to_string(),
+        FloatFormat::Shortest => format!("{:#}", value),
+    }
+}
+
+/// The attributes the type of a node is written to.
+const TYPE_ATTRIBUTES: &[&str] = &["__type", "__count", "__size"];
+
+/// Can text XML hold `text` without losing characters?
+fn has_control_characters(text: &str) -> bool {
+    text.chars()
+        .any(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r'))
+}
+
+/// Check that a node reads back from text XML as it is written, see
+/// `OptionsBuilder::strict_text_xml`. `text` is the written value.
+fn check_lossless<'a, I>(
+    key: &str,
+    attributes: I,
+    value: Option<&Value>,
+    text: &str,
+) -> Result<(), KbinError>
+where
+    I: IntoIterator<Item = (&'a str, &'a str)>,
+{
+    let lossy = |reason: String| {
+        Err(KbinError::LossyTextXml {
+            name: key.to_owned(),
+            reason,
+        })
+    };
+
+    if !is_xml_name(key) {
+        return lossy("the name is not a valid XML name".to_owned());
+    }
+    for (name, value) in attributes {
+        if !is_xml_name(name) {
+            return lossy(format!("attribute {:?} is not a valid XML name", name));
+        }
+        if TYPE_ATTRIBUTES.contains(&name) {
+            return lossy(format!("attribute {:?} is read as type information", name));
+        }
+        if has_control_characters(value) {
+            return lossy(format!("attribute {:?} holds a control character", name));
+        }
+    }
+
+    match value {
+        Some(Value::String(_)) if has_control_characters(text) => {
+            lossy("the value holds a control character".to_owned())
+        },
+        Some(Value::String(_)) if text.trim() != text => {
+            lossy("surrounding whitespace in the value is trimmed".to_owned())
+        },
+        Some(Value::String(_)) | None => Ok(()),
+        Some(Value::Unknown { id, .. }) => lossy(format!(
+            "unknown node type {} is written as bin",
+            id & !ARRAY_MASK
+        )),
+        Some(value) => {
+            let (node_type, is_array, count) = match value {
+                Value::Array(values) => (values.standard_type(), true, values.len()),
+                value => (value.standard_type(), false, 0),
+            };
+            let read = Value::from_string(node_type, text, is_array, count)
+                .and_then(|read| read.to_bytes());
+            if read.ok() != value.to_bytes().ok() {
+                return lossy(format!("the value {:?} reads back differently", text));
+            }
+
+            Ok(())
+        },
+    }
 }
 
 pub struct TextXmlWriter {
     xml_writer: Writer<Cursor<Vec<u8>>>,
+    options: Options,
 }
 
 impl TextXmlWriter {
     pub fn new() -> Self {
+        Self::with_options(Options::default())
+    }
+
+    pub fn with_options(options: Options) -> Self {
         let inner = Cursor::new(Vec::new());
         let xml_writer = Writer::new_with_indent(inner, b' ', 2);
 
-        Self { xml_writer }
+        Self {
+            xml_writer,
+            options,
+        }
     }
 
     pub fn into_text_xml<T>(mut self, value: &T) -> Result<Vec<u8>, KbinError>
@@ -36,8 +169,149 @@ impl TextXmlWriter {
             self.xml_writer.write_event(Event::Decl(header))?;
         }
 
-        value.write(&mut self.xml_writer)?;
+        value.write_with_options(&mut self.xml_writer, &self.options)?;
 
         Ok(self.xml_writer.into_inner().into_inner())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding_type::EncodingType;
+    use crate::error::KbinError;
+    use crate::node::Node;
+    use crate::options::{FloatFormat, Options, TypeNamePolicy};
+    use crate::value::{Value, ValueArray};
+
+    #[test]
+    fn test_type_name_round_trip() {
+        let input = br#"<root><name __type="string">a</name><pos __type="vs32">1 2 3 4</pos><id __type="u32">1</id></root>"#;
+        let type_names = TypeNamePolicy::detect(input);
+        assert_eq!(type_names, TypeNamePolicy::Alternate);
+
+        let (collection, _) = crate::from_text_xml(input).expect("Failed to read text XML");
+        let data = crate::to_binary(&collection).expect("Failed to encode");
+        let (collection, _) = crate::from_slice(&data).expect("Failed to decode");
+
+        let options = Options::builder().type_names(type_names).build();
+        let text = crate::to_text_xml_with_options(options, &collection)
+            .expect("Failed to write text XML");
+        let text = String::from_utf8(text).expect("Invalid UTF-8");
+        assert!(text.contains(r#"__type="string""#));
+        assert!(text.contains(r#"__type="vs32""#));
+        assert!(text.contains(r#"__type="u32""#));
+
+        let text = crate::to_text_xml(&collection).expect("Failed to write text XML");
+        let text = String::from_utf8(text).expect("Invalid UTF-8");
+        assert!(text.contains(r#"__type="str""#));
+        assert!(text.contains(r#"__type="4s32""#));
+    }
+
+    #[test]
+    fn test_binary_to_text_xml() {
+        let node = Node::with_nodes("root", vec![
+            Node::with_value("name", Value::String("名前".into())),
+            Node::with_value("data", Value::Binary(vec![1, 2])),
+            Node::with_value("pos", Value::Array(ValueArray::S32(vec![1, 2, 3]))),
+        ]);
+        let options = Options::with_encoding(EncodingType::SHIFT_JIS);
+        let data = crate::to_binary_with_options(options, &node).expect("Failed to encode");
+
+        let text = crate::binary_to_text_xml(&data).expect("Failed to write text XML");
+        let options = Options::with_encoding(EncodingType::SHIFT_JIS);
+        assert_eq!(
+            crate::text_xml_to_binary_with_options(options, &text).expect("Failed to encode"),
+            data
+        );
+        let utf8 = crate::text_xml_to_binary(&text).expect("Failed to encode");
+        let (collection, encoding) = crate::from_slice(&utf8).expect("Failed to decode");
+        assert_eq!(encoding, EncodingType::UTF_8);
+        assert_eq!(collection.as_node().expect("Failed to convert"), node);
+
+        let text = String::from_utf8(text).expect("Invalid UTF-8");
+        assert_eq!(
+            text,
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                "<root>\n",
+                "  <name __type=\"str\">名前</name>\n",
+                "  <data __size=\"2\" __type=\"bin\">0102</data>\n",
+                "  <pos __count=\"3\" __type=\"s32\">1 2 3</pos>\n",
+                "</root>",
+            )
+        );
+    }
+
+    #[test]
+    fn test_shortest_floats() {
+        let node = Node::with_nodes("root", vec![
+            Node::with_value("small", Value::Float(1.0e-7)),
+            Node::with_value("pi", Value::Double(std::f64::consts::PI)),
+            Node::with_value("pos", Value::Float3([0.1, -2.5, 1.0e10])),
+        ]);
+        let options = Options::with_encoding(EncodingType::UTF_8);
+        let data = crate::to_binary_with_options(options, &node).expect("Failed to encode");
+
+        let text = crate::binary_to_text_xml(&data).expect("Failed to write text XML");
+        assert_ne!(
+            crate::text_xml_to_binary(&text).expect("Failed to encode"),
+            data
+        );
+
+        let (collection, _) = crate::from_slice(&data).expect("Failed to decode");
+        let options = Options::builder()
+            .float_format(FloatFormat::Shortest)
+            .build();
+        let text = crate::to_text_xml_with_options(options, &collection)
+            .expect("Failed to write text XML");
+        assert_eq!(
+            crate::text_xml_to_binary(&text).expect("Failed to encode"),
+            data
+        );
+        let text = String::from_utf8(text).expect("Invalid UTF-8");
+        assert!(text.contains(">0.0000001<"));
+        assert!(text.contains(">3.141592653589793<"));
+        assert!(text.contains(">0.1 -2.5 10000000000<"));
+    }
+
+    #[test]
+    fn test_strict_text_xml() {
+        let strict = || Options::builder().strict_text_xml(true).build();
+        let lossy = |node: &Node| {
+            match crate::to_text_xml_with_options(strict(), node) {
+                Err(KbinError::LossyTextXml { reason, .. }) => assert!(!reason.is_empty()),
+                result => panic!("Unexpected result: {:?}", result),
+            };
+        };
+
+        lossy(&Node::with_value("rate", Value::Float(1.0e-7)));
+        lossy(&Node::with_value("name", Value::String(" padded".into())));
+        lossy(&Node::new("1st"));
+        lossy(&Node::with_attrs("root", &[("__type", "u8")]));
+        lossy(&Node::with_attrs("root", &[("id", "\u{1}")]));
+
+        let data = crate::to_binary(&Node::with_nodes("root", vec![Node::with_value(
+            "rate",
+            Value::Double(0.1234567),
+        )]))
+        .expect("Failed to encode");
+        let (collection, _) = crate::from_slice(&data).expect("Failed to decode");
+        match crate::to_text_xml_with_options(strict(), &collection) {
+            Err(KbinError::LossyTextXml { name, .. }) => assert_eq!(name, "rate"),
+            result => panic!("Unexpected result: {:?}", result),
+        };
+
+        let node = Node::with_nodes("root", vec![
+            Node::with_value("rate", Value::Float(1.0e-7)),
+            Node::with_value("name", Value::String("a b".into())),
+            Node::with_value("data", Value::Binary(vec![1, 2])),
+        ]);
+        let options = Options::builder()
+            .strict_text_xml(true)
+            .float_format(FloatFormat::Shortest)
+            .build();
+        let text = crate::to_text_xml_with_options(options, &node).expect("Failed to write");
+        let (collection, _) = crate::from_text_xml(&text).expect("Failed to read");
+        assert_eq!(collection.as_node().expect("Failed to convert"), node);
+    }
+}
@@ -9,7 +9,8 @@ use crate::encoding_type::EncodingType;
 use crate::error::KbinError;
 use crate::node::Node;
 use crate::node_types::StandardType;
-use crate::to_text_xml::ToTextXml;
+use crate::options::Options;
+use crate::to_text_xml::{check_lossless, value_text, write_children, ToTextXml};
 use crate::value::Value;
 
 impl ToTextXml for Node {
@@ -18,8 +19,29 @@ impl ToTextXml for Node {
         EncodingType::UTF_8
     }
 
+    #[inline]
     fn write<W: Write>(&self, writer: &mut Writer<W>) -> Result<(), KbinError> {
+        self.write_with_options(writer, &Options::default())
+    }
+
+    fn write_with_options<W: Write>(
+        &self,
+        writer: &mut Writer<W>,
+        options: &Options,
+    ) -> Result<(), KbinError> {
         let key = self.key();
+        if options.strict_text_xml {
+            let text = self
+                .value()
+                .map(|value| value_text(value, options))
+                .unwrap_or_default();
+            let attributes = self
+                .attributes()
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_str()));
+            check_lossless(key, attributes, self.value(), &text)?;
+        }
+
         let mut elem = BytesStart::borrowed(key.as_bytes(), key.as_bytes().len());
 
         // Write the attributes for the value, but not the value contents.
@@ -27,7 +49,10 @@ impl ToTextXml for Node {
             let node_type = value.standard_type();
 
             match value {
-                Value::Binary(ref data) => {
+                Value::Binary(ref data) |
+                Value::Unknown {
+                    bytes: ref data, ..
+                } => {
                     elem.push_attribute(Attribute {
                         key: b"__size",
                         value: Cow::Owned(data.len().to_string().into_bytes()),
@@ -46,7 +71,7 @@ impl ToTextXml for Node {
             if node_type != StandardType::NodeStart {
                 elem.push_attribute(Attribute {
                     key: b"__type",
-                    value: Cow::Borrowed(node_type.name.as_bytes()),
+                    value: Cow::Borrowed(options.type_names.name(node_type).as_bytes()),
                 });
             }
         }
@@ -64,7 +89,7 @@ impl ToTextXml for Node {
         let start_elem = if let Some(value) = self.value() {
             writer.write_event(Event::Start(elem))?;
 
-            let value = value.to_string();
+            let value = value_text(value, options);
             let elem = BytesText::from_plain_str(&value);
             writer.write_event(Event::Text(elem))?;
 
@@ -74,7 +99,7 @@ impl ToTextXml for Node {
         };
 
         let has_value = start_elem.is_none();
-        let has_children = !self.children().is_empty();
+        let has_children = !self.children().is_empty() || !self.markup().is_empty();
 
         // A `Some` value here means the start element was not written
         if let Some(start_elem) = start_elem {
@@ -85,9 +110,7 @@ impl ToTextXml for Node {
             }
         }
 
-        for child in self.children() {
-            child.write(writer)?;
-        }
+        write_children(writer, options, self.children(), self.markup())?;
 
         if has_value || has_children {
             let end_elem = BytesEnd::borrowed(key.as_bytes());
@@ -9,7 +9,8 @@ use crate::encoding_type::EncodingType;
 use crate::error::KbinError;
 use crate::node::NodeCollection;
 use crate::node_types::StandardType;
-use crate::to_text_xml::ToTextXml;
+use crate::options::Options;
+use crate::to_text_xml::{check_lossless, value_text, write_children, ToTextXml};
 
 impl ToTextXml for NodeCollection {
     /// At the moment, decoding the value of a `NodeDefinition` will decode
@@ -18,9 +19,19 @@ impl ToTextXml for NodeCollection {
         EncodingType::UTF_8
     }
 
+    #[inline]
     fn write<W: Write>(&self, writer: &mut Writer<W>) -> Result<(), KbinError> {
+        self.write_with_options(writer, &Options::default())
+    }
+
+    fn write_with_options<W: Write>(
+        &self,
+        writer: &mut Writer<W>,
+        options: &Options,
+    ) -> Result<(), KbinError> {
         let base = self.base();
         let key = base.key()?.ok_or(KbinError::InvalidState)?;
+        let key = options.decode_name(&key).into_owned();
         let value = match base.value() {
             Ok(value) => Some(value),
             Err(e) => match e {
@@ -29,6 +40,26 @@ impl ToTextXml for NodeCollection {
             },
         };
 
+        if options.strict_text_xml {
+            let text = value
+                .as_ref()
+                .map(|value| value_text(value, options))
+                .unwrap_or_default();
+            let attributes = self
+                .attributes()
+                .iter()
+                .map(|attribute| {
+                    let key = attribute.key()?.ok_or(KbinError::InvalidState)?;
+                    let key = options.decode_name(&key).into_owned();
+                    Ok((key, attribute.value()?.to_string()))
+                })
+                .collect::<Result<Vec<_>, KbinError>>()?;
+            let attributes = attributes
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_str()));
+            check_lossless(&key, attributes, value.as_ref(), &text)?;
+        }
+
         let mut elem = BytesStart::borrowed(key.as_bytes(), key.as_bytes().len());
 
         if base.is_array {
@@ -53,15 +84,13 @@ impl ToTextXml for NodeCollection {
         if base.node_type != StandardType::NodeStart {
             elem.push_attribute(Attribute {
                 key: b"__type",
-                value: Cow::Borrowed(base.node_type.name.as_bytes()),
+                value: Cow::Borrowed(options.type_names.name(base.node_type).as_bytes()),
             });
         }
 
         for attribute in self.attributes() {
-            let key = attribute
-                .key()?
-                .ok_or(KbinError::InvalidState)?
-                .into_bytes();
+            let key = attribute.key()?.ok_or(KbinError::InvalidState)?;
+            let key = options.decode_name(&key).into_owned().into_bytes();
             let value = attribute.value()?.to_string();
             let value = BytesText::from_plain_str(&value);
 
@@ -75,7 +104,7 @@ impl ToTextXml for NodeCollection {
             Some(value) => {
                 writer.write_event(Event::Start(elem))?;
 
-                let value = value.to_string();
+                let value = value_text(&value, options);
                 let elem = BytesText::from_plain_str(&value);
                 writer.write_event(Event::Text(elem))?;
 
@@ -85,7 +114,7 @@ impl ToTextXml for NodeCollection {
         };
 
         let has_value = start_elem.is_none();
-        let has_children = !self.children().is_empty();
+        let has_children = !self.children().is_empty() || !self.markup().is_empty();
 
         // A `Some` value here means the start element was not written
         if let Some(start_elem) = start_elem {
@@ -96,9 +125,7 @@ impl ToTextXml for NodeCollection {
             }
         }
 
-        for child in self.children() {
-            child.write(writer)?;
-        }
+        write_children(writer, options, self.children(), self.markup())?;
 
         if has_value || has_children {
             let end_elem = BytesEnd::borrowed(key.as_bytes());
@@ -0,0 +1,308 @@
+use std::io::{self, BufRead, Write};
+
+use byteorder::{BigEndian, WriteBytesExt};
+use bytes::Bytes;
+use rustc_hex::{FromHex, FromHexError};
+use snafu::{ResultExt, Snafu};
+
+use crate::byte_buffer::{ByteBufferError, ByteBufferWrite};
+use crate::compression_type::{CompressionType, UnknownCompression};
+use crate::encoding_type::{EncodingError, EncodingType};
+use crate::error::Result;
+use crate::node_types::StandardType;
+use crate::reader::{Reader, ReaderError};
+use crate::writer::{write_node_name, WriterError};
+use crate::ARRAY_MASK;
+
+#[derive(Debug, Snafu)]
+pub enum TokenError {
+    #[snafu(display("Failed to write token dump"))]
+    DumpWrite { source: io::Error },
+
+    #[snafu(display("Failed to read token at node buffer offset 0x{:x}", offset))]
+    TokenRead { offset: u64, source: ReaderError },
+
+    #[snafu(display("Failed to read token dump"))]
+    DumpRead { source: io::Error },
+
+    #[snafu(display("Token dump does not start with a header line"))]
+    MissingHeader,
+
+    #[snafu(display("Invalid header on line {}", line))]
+    InvalidHeader { line: usize },
+
+    #[snafu(display("Invalid compression type in header on line {}", line))]
+    HeaderCompression {
+        line: usize,
+        source: UnknownCompression,
+    },
+
+    #[snafu(display("Invalid encoding type in header on line {}", line))]
+    HeaderEncoding { line: usize, source: EncodingError },
+
+    #[snafu(display("Invalid token on line {}: {}", line, reason))]
+    InvalidToken { line: usize, reason: &'static str },
+
+    #[snafu(display("Invalid hex data on line {}", line))]
+    TokenHex { line: usize, source: FromHexError },
+
+    #[snafu(display("Failed to assemble token on line {}", line))]
+    TokenName { line: usize, source: WriterError },
+
+    #[snafu(display("Failed to assemble token data on line {}", line))]
+    TokenData {
+        line: usize,
+        source: ByteBufferError,
+    },
+
+    #[snafu(display("Failed to write assembled data on line {}", line))]
+    AssembleWrite { line: usize, source: io::Error },
+}
+
+struct Token {
+    raw: u8,
+    name: Option<String>,
+    data: Option<Vec<u8>>,
+}
+
+fn parse_hex_byte(line: usize, input: &str) -> std::result::Result<u8, TokenError> {
+    u8::from_str_radix(input, 16).map_err(|_| TokenError::InvalidToken {
+        line,
+        reason: "expected a hex byte",
+    })
+}
+
+fn parse_token(line: usize, input: &str) -> std::result::Result<Token, TokenError> {
+    let fields: Vec<&str> = input.split_whitespace().collect();
+    if fields.len() != 8 {
+        return Err(TokenError::InvalidToken {
+            line,
+            reason: "expected 8 fields",
+        });
+    }
+
+    // The offset, type name, array flag, data offset and data length columns
+    // are informational. The raw type byte is authoritative.
+    let raw = parse_hex_byte(line, fields[1])?;
+    let name = match fields[4] {
+        "-" => None,
+        name => Some(name.to_owned()),
+    };
+    let data = match fields[7] {
+        "-" if fields[6] == "-" => None,
+        "-" => Some(Vec::new()),
+        data => Some(data.from_hex().context(TokenHexSnafu { line })?),
+    };
+
+    Ok(Token { raw, name, data })
+}
+
+/// Assemble a binary document from a token dump produced by `dump_tokens`.
+///
+/// Only the raw type byte, name and data columns of each token are used.
+/// Offsets, lengths and padding are recomputed, so tokens can be edited,
+/// inserted or removed freely. Tokens with a type byte that does not map to
+/// a known type are written with their data as a length-prefixed block.
+pub fn assemble_tokens<R>(reader: R) -> Result<Vec<u8>>
+where
+    R: BufRead,
+{
+    let mut header = None;
+    let mut node_buf = ByteBufferWrite::new(Vec::new());
+    let mut data_buf = ByteBufferWrite::new(Vec::new());
+
+    for (i, line) in reader.lines().enumerate() {
+        let line_num = i + 1;
+        let line = line.context(DumpReadSnafu)?;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(fields) = line.strip_prefix("@header") {
+            let bytes = fields
+                .split_whitespace()
+                .map(|field| parse_hex_byte(line_num, field))
+                .collect::<std::result::Result<Vec<u8>, _>>()?;
+            if bytes.len() != 4 {
+                return Err(TokenError::InvalidHeader { line: line_num }.into());
+            }
+
+            let compression = CompressionType::from_byte(bytes[1])
+                .context(HeaderCompressionSnafu { line: line_num })?;
+            let encoding = EncodingType::from_byte(bytes[2])
+                .context(HeaderEncodingSnafu { line: line_num })?;
+            header = Some((bytes, compression, encoding));
+
+            continue;
+        }
+
+        let (_, compression, encoding) = header.as_ref().ok_or(TokenError::MissingHeader)?;
+        let token = parse_token(line_num, line)?;
+
+        node_buf
+            .write_u8(token.raw)
+            .context(AssembleWriteSnafu { line: line_num })?;
+
+        let is_array = token.raw & ARRAY_MASK == ARRAY_MASK;
+        let node_type = StandardType::from_u8(token.raw & !ARRAY_MASK).ok();
+
+        match node_type {
+            Some(StandardType::NodeEnd) | Some(StandardType::FileEnd) => continue,
+            _ => {},
+        };
+
+        let name = token.name.as_ref().ok_or(TokenError::InvalidToken {
+            line: line_num,
+            reason: "expected a node name",
+        })?;
+        let path = || format!("/{}", name);
+        write_node_name(*compression, *encoding, &mut node_buf, name, path)
+            .context(TokenNameSnafu { line: line_num })?;
+
+        let data = match (node_type, token.data) {
+            (Some(StandardType::NodeStart), _) | (_, None) => continue,
+            (_, Some(data)) => data,
+        };
+
+        match node_type {
+            Some(StandardType::Attribute) |
+            Some(StandardType::String) |
+            Some(StandardType::Binary) |
+            None => data_buf
+                .buf_write(&data)
+                .context(TokenDataSnafu { line: line_num })?,
+            Some(_) if is_array => {
+                data_buf
+                    .write_u32::<BigEndian>(data.len() as u32)
+                    .context(AssembleWriteSnafu { line: line_num })?;
+                data_buf
+                    .write_all(&data)
+                    .context(AssembleWriteSnafu { line: line_num })?;
+                data_buf
+                    .realign_writes(None)
+                    .context(TokenDataSnafu { line: line_num })?;
+            },
+            Some(node_type) => data_buf
+                .write_aligned(node_type, &data)
+                .context(TokenDataSnafu { line: line_num })?,
+        };
+    }
+
+    let (header, _, _) = header.ok_or(TokenError::MissingHeader)?;
+    node_buf
+        .realign_writes(None)
+        .context(TokenDataSnafu { line: 0usize })?;
+
+    let node_buf = node_buf.into_inner();
+    let data_buf = data_buf.into_inner();
+
+    let mut output = Vec::with_capacity(12 + node_buf.len() + data_buf.len());
+    output.extend_from_slice(&header);
+    output
+        .write_u32::<BigEndian>(node_buf.len() as u32)
+        .context(AssembleWriteSnafu { line: 0usize })?;
+    output.extend_from_slice(&node_buf);
+    output
+        .write_u32::<BigEndian>(data_buf.len() as u32)
+        .context(AssembleWriteSnafu { line: 0usize })?;
+    output.extend_from_slice(&data_buf);
+
+    Ok(output)
+}
+
+/// Write a textual listing of every token in the node buffer of a binary
+/// document.
+///
+/// Each line contains the absolute offset of the token, the raw type byte,
+/// the decoded type name, the array flag, the node name and, for tokens that
+/// carry a value, the absolute offset, length and hex contents of the data
+/// buffer extent backing it. Fields that do not apply are written as `-`.
+pub fn dump_tokens<W>(input: &[u8], mut out: W) -> Result<()>
+where
+    W: Write,
+{
+    let mut reader = Reader::new(Bytes::from(input.to_vec()))?;
+
+    writeln!(out, "# kbin token dump").context(DumpWriteSnafu)?;
+    writeln!(
+        out,
+        "# compression: {:?}, encoding: {}",
+        reader.compression(),
+        reader.encoding()
+    )
+    .context(DumpWriteSnafu)?;
+    writeln!(
+        out,
+        "@header {:02x} {:02x} {:02x} {:02x}",
+        input[0], input[1], input[2], input[3]
+    )
+    .context(DumpWriteSnafu)?;
+    writeln!(
+        out,
+        "# node_offset raw type array name data_offset data_len data"
+    )
+    .context(DumpWriteSnafu)?;
+
+    loop {
+        let offset = reader.node_offset();
+        let def = match reader.read_node_definition() {
+            Ok(def) => def,
+            Err(source) => {
+                writeln!(out, "# error at 0x{:08x}: {}", offset, source).context(DumpWriteSnafu)?;
+                return Err(TokenError::TokenRead { offset, source }.into());
+            },
+        };
+
+        let (node_type, is_array) = def.node_type_tuple();
+        let raw = node_type as u8 | if is_array { ARRAY_MASK } else { 0 };
+        let name = def.key()?.unwrap_or_else(|| String::from("-"));
+
+        write!(
+            out,
+            "{:08x} {:02x} {} {} {}",
+            offset, raw, node_type.name, is_array as u8, name
+        )
+        .context(DumpWriteSnafu)?;
+
+        match (node_type, def.value_bytes()) {
+            (StandardType::NodeStart, _) | (_, None) => {
+                writeln!(out, " - - -").context(DumpWriteSnafu)?;
+            },
+            (_, Some(data)) => {
+                write!(out, " {:08x} {} ", reader.last_data_offset(), data.len())
+                    .context(DumpWriteSnafu)?;
+                if data.is_empty() {
+                    write!(out, "-").context(DumpWriteSnafu)?;
+                }
+                for byte in data {
+                    write!(out, "{:02x}", byte).context(DumpWriteSnafu)?;
+                }
+                writeln!(out).context(DumpWriteSnafu)?;
+            },
+        };
+
+        if node_type == StandardType::FileEnd {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assemble_tokens, dump_tokens};
+
+    const TESTCASES: &[u8] = include_bytes!("../../testcases_out.kbin");
+
+    #[test]
+    fn test_dump_assemble_round_trip() {
+        let mut dump = Vec::new();
+        dump_tokens(TESTCASES, &mut dump).expect("Failed to dump tokens");
+
+        let output = assemble_tokens(&dump[..]).expect("Failed to assemble tokens");
+        assert_eq!(output, TESTCASES);
+    }
+}
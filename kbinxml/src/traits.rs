@@ -0,0 +1,111 @@
+use crate::compression_type::CompressionType;
+use crate::encoding_type::EncodingType;
+use crate::error::KbinError;
+use crate::node::{NodeCollection, NodeDefinition};
+use crate::node_types::StandardType;
+use crate::reader::Reader;
+use crate::writer::{Writeable, Writer, WriterPool};
+
+/// A source of node definitions from a binary document.
+///
+/// `Reader` implements this, and the trait can be used as a trait object so
+/// readers can be mocked or wrapped.
+pub trait KbinRead {
+    fn compression(&self) -> CompressionType;
+    fn encoding(&self) -> EncodingType;
+
+    /// Read the next node definition, including node and file end markers.
+    fn read_node_definition(&mut self) -> Result<NodeDefinition, KbinError>;
+
+    /// Read definitions up to and including the file end marker and build a
+    /// `NodeCollection` from them.
+    fn read_collection(&mut self) -> Result<NodeCollection, KbinError> {
+        let mut definitions = Vec::new();
+        loop {
+            let def = self.read_node_definition()?;
+            let is_end = def.node_type == StandardType::FileEnd;
+            definitions.push(def);
+
+            if is_end {
+                break;
+            }
+        }
+
+        definitions
+            .into_iter()
+            .collect::<Option<NodeCollection>>()
+            .ok_or(KbinError::NoNodeCollection)
+    }
+}
+
+/// An encoder of binary documents.
+///
+/// `Writer` and `WriterPool` implement this, and the trait can be used as a
+/// trait object so writers can be mocked or wrapped.
+pub trait KbinWrite {
+    fn to_binary(&mut self, input: &dyn Writeable) -> Result<Vec<u8>, KbinError>;
+}
+
+impl KbinRead for Reader {
+    #[inline]
+    fn compression(&self) -> CompressionType {
+        Reader::compression(self)
+    }
+
+    #[inline]
+    fn encoding(&self) -> EncodingType {
+        Reader::encoding(self)
+    }
+
+    #[inline]
+    fn read_node_definition(&mut self) -> Result<NodeDefinition, KbinError> {
+        Reader::read_node_definition(self).map_err(Into::into)
+    }
+}
+
+impl KbinWrite for Writer {
+    #[inline]
+    fn to_binary(&mut self, input: &dyn Writeable) -> Result<Vec<u8>, KbinError> {
+        Writer::to_binary(self, input).map_err(Into::into)
+    }
+}
+
+impl KbinWrite for WriterPool {
+    #[inline]
+    fn to_binary(&mut self, input: &dyn Writeable) -> Result<Vec<u8>, KbinError> {
+        self.encode(input).map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use crate::node::Node;
+    use crate::reader::Reader;
+    use crate::value::Value;
+    use crate::writer::{Writer, WriterPool};
+
+    use super::{KbinRead, KbinWrite};
+
+    #[test]
+    fn test_trait_objects() {
+        let mut root = Node::new("root");
+        root.append_child(Node::with_value("count", Value::U32(5)));
+
+        let mut writers: Vec<Box<dyn KbinWrite>> =
+            vec![Box::new(Writer::new()), Box::new(WriterPool::new())];
+        for writer in &mut writers {
+            let data = writer.to_binary(&root).expect("Failed to encode");
+
+            let mut reader: Box<dyn KbinRead> =
+                Box::new(Reader::new(Bytes::from(data)).expect("Failed to create reader"));
+            let collection = reader.read_collection().expect("Failed to decode");
+            let node = collection.as_node().expect("Failed to convert");
+            assert_eq!(
+                node.get_child("count").and_then(Node::value),
+                Some(&Value::U32(5))
+            );
+        }
+    }
+}
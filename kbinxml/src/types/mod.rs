@@ -1,5 +1,10 @@
 mod bytes;
 mod string;
+mod typed;
 
 pub use self::bytes::{FromKbinBytes, IntoKbinBytes};
 pub use self::string::FromKbinString;
+pub(crate) use self::typed::TYPED_NAME_PREFIX;
+pub use self::typed::{
+    KbinIp4, KbinS16, KbinS32, KbinS64, KbinS8, KbinTime, KbinU16, KbinU32, KbinU64, KbinU8,
+};
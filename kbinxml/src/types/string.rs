@@ -56,24 +56,21 @@ impl FromKbinString for bool {
 
 impl FromKbinString for Ipv4Addr {
     fn from_kbin_string(input: &str) -> Result<Self> {
-        space_check(input)?;
-
-        let count = input.split('.').count();
-        if count != 4 {
-            return Err(KbinError::SizeMismatch {
-                node_type: "Ipv4Addr",
-                expected: 4,
-                actual: count,
-            });
-        }
-
+        let invalid = || KbinError::InvalidIp4 {
+            input: input.to_owned(),
+        };
         let mut octets = [0; 4];
+        let mut parts = input.split('.');
 
         // IP addresses are split by a period, so do not use `parse_tuple`
-        for (i, part) in input.split('.').enumerate() {
-            octets[i] = part.parse::<u8>().context(StringParseIntSnafu {
-                node_type: "Ipv4Addr",
-            })?;
+        for octet in &mut octets {
+            *octet = parts
+                .next()
+                .and_then(|part| part.parse::<u8>().ok())
+                .ok_or_else(invalid)?;
+        }
+        if parts.next().is_some() {
+            return Err(invalid());
         }
 
         Ok(Ipv4Addr::from(octets))
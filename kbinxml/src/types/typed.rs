@@ -0,0 +1,69 @@
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// The newtype struct name prefix the serializer recognizes as a request for
+/// a specific node type. The rest of the name is a `StandardType` name.
+pub(crate) const TYPED_NAME_PREFIX: &str = "$kbinxml::";
+
+macro_rules! typed_wrappers {
+    ($($wrapper:ident => $konst:ident, $name:literal, $what:literal;)*) => {
+        $(
+            #[doc = concat!("Serializes the wrapped ", $what, " or sequence of ", $what, "s as `", stringify!($konst), "`.")]
+            #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+            pub struct $wrapper<T>(pub T);
+
+            impl<T> Serialize for $wrapper<T>
+            where
+                T: Serialize,
+            {
+                fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+                where
+                    S: Serializer,
+                {
+                    serializer.serialize_newtype_struct(concat!("$kbinxml::", $name), &self.0)
+                }
+            }
+
+            impl<'de, T> Deserialize<'de> for $wrapper<T>
+            where
+                T: Deserialize<'de>,
+            {
+                fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+                where
+                    D: Deserializer<'de>,
+                {
+                    T::deserialize(deserializer).map($wrapper)
+                }
+            }
+
+            impl<T> From<T> for $wrapper<T> {
+                fn from(value: T) -> Self {
+                    $wrapper(value)
+                }
+            }
+
+            impl<T> fmt::Display for $wrapper<T>
+            where
+                T: fmt::Display,
+            {
+                fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    self.0.fmt(f)
+                }
+            }
+        )*
+    };
+}
+
+typed_wrappers! {
+    KbinS8 => S8, "s8", "integer";
+    KbinU8 => U8, "u8", "integer";
+    KbinS16 => S16, "s16", "integer";
+    KbinU16 => U16, "u16", "integer";
+    KbinS32 => S32, "s32", "integer";
+    KbinU32 => U32, "u32", "integer";
+    KbinS64 => S64, "s64", "integer";
+    KbinU64 => U64, "u64", "integer";
+    KbinTime => Time, "time", "integer";
+    KbinIp4 => Ip4, "ip4", "`Ipv4Addr`";
+}
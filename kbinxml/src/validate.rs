@@ -0,0 +1,532 @@
+use std::fmt;
+
+use bytes::Bytes;
+
+use crate::byte_buffer::{ByteBufferError, ByteBufferRead};
+use crate::compression_type::CompressionType;
+use crate::encoding_type::EncodingType;
+use crate::error::KbinError;
+use crate::header::HEADER_LEN;
+use crate::node_types::StandardType;
+use crate::reader::ReaderError;
+use crate::{ARRAY_MASK, SIGNATURE};
+
+/// Something wrong with a binary document, see `validate`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProblemKind {
+    /// The input ends inside the header or the data buffer length.
+    TruncatedHeader,
+    InvalidCompression {
+        byte: u8,
+    },
+    InvalidEncoding {
+        byte: u8,
+    },
+    MismatchedEncoding {
+        encoding: u8,
+        negation: u8,
+    },
+
+    /// The node buffer does not fit in the input. No data is checked.
+    NodeBufferLength {
+        declared: usize,
+        remaining: usize,
+    },
+
+    /// The data buffer length does not match the bytes after it.
+    DataBufferLength {
+        declared: usize,
+        actual: usize,
+    },
+
+    /// A buffer length is not a multiple of four.
+    Misaligned {
+        buffer: &'static str,
+        length: usize,
+    },
+
+    /// A reserved or unsupported type id. The rest of the node buffer is
+    /// not checked.
+    UnknownType {
+        type_id: u8,
+    },
+
+    InvalidName {
+        reason: &'static str,
+    },
+
+    /// A node runs past the end of the node buffer.
+    TruncatedNode,
+
+    /// The value of a node runs past the end of the data buffer. The data of
+    /// later nodes is not checked.
+    DataOutOfBounds {
+        node_type: StandardType,
+        data_offset: u64,
+    },
+
+    InvalidBoolean {
+        data_offset: u64,
+        byte: u8,
+    },
+
+    /// An array whose length is not a multiple of the size of its elements.
+    ArrayLength {
+        node_type: StandardType,
+        length: usize,
+    },
+
+    /// Non-zero bytes in the alignment padding after a value.
+    NonZeroPadding,
+
+    /// A node end without a node to close.
+    UnbalancedEnd,
+
+    /// An attribute before the first node.
+    AttributeOutsideNode,
+
+    /// The file end comes before the end of this many nodes.
+    UnclosedNodes {
+        depth: usize,
+    },
+
+    MissingFileEnd,
+
+    /// Non-zero bytes after the file end.
+    TrailingNodeData,
+
+    /// Non-zero bytes in the data buffer after the last value.
+    TrailingValueData,
+}
+
+impl fmt::Display for ProblemKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProblemKind::TruncatedHeader => f.write_str("input ends inside the header"),
+            ProblemKind::InvalidCompression { byte } => {
+                write!(f, "invalid compression byte 0x{:02x}", byte)
+            },
+            ProblemKind::InvalidEncoding { byte } => {
+                write!(f, "invalid encoding byte 0x{:02x}", byte)
+            },
+            ProblemKind::MismatchedEncoding { encoding, negation } => write!(
+                f,
+                "encoding byte 0x{:02x} does not match inverted value 0x{:02x}",
+                encoding, negation
+            ),
+            ProblemKind::NodeBufferLength {
+                declared,
+                remaining,
+            } => write!(
+                f,
+                "node buffer length of {} bytes exceeds the {} bytes after the header",
+                declared, remaining
+            ),
+            ProblemKind::DataBufferLength { declared, actual } => write!(
+                f,
+                "data buffer length of {} bytes does not match the {} bytes after it",
+                declared, actual
+            ),
+            ProblemKind::Misaligned { buffer, length } => write!(
+                f,
+                "{} buffer length of {} bytes is not a multiple of 4",
+                buffer, length
+            ),
+            ProblemKind::UnknownType { type_id } => write!(f, "unknown node type {}", type_id),
+            ProblemKind::InvalidName { reason } => write!(f, "invalid node name, {}", reason),
+            ProblemKind::TruncatedNode => f.write_str("node runs past the end of the node buffer"),
+            ProblemKind::DataOutOfBounds {
+                node_type,
+                data_offset,
+            } => write!(
+                f,
+                "{} value at data offset 0x{:x} runs past the end of the data buffer",
+                node_type, data_offset
+            ),
+            ProblemKind::InvalidBoolean { data_offset, byte } => write!(
+                f,
+                "boolean at data offset 0x{:x} is 0x{:02x}",
+                data_offset, byte
+            ),
+            ProblemKind::ArrayLength { node_type, length } => write!(
+                f,
+                "{} array of {} bytes is not a whole number of elements",
+                node_type, length
+            ),
+            ProblemKind::NonZeroPadding => f.write_str("non-zero bytes in alignment padding"),
+            ProblemKind::UnbalancedEnd => f.write_str("node end without a node to close"),
+            ProblemKind::AttributeOutsideNode => f.write_str("attribute before the first node"),
+            ProblemKind::UnclosedNodes { depth } => {
+                write!(f, "file end before the end of {} node(s)", depth)
+            },
+            ProblemKind::MissingFileEnd => f.write_str("node buffer has no file end"),
+            ProblemKind::TrailingNodeData => f.write_str("non-zero bytes after the file end"),
+            ProblemKind::TrailingValueData => {
+                f.write_str("non-zero bytes after the last value in the data buffer")
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Problem {
+    /// The absolute offset in the input the problem was found at.
+    pub offset: u64,
+    pub kind: ProblemKind,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "0x{:08x}: {}", self.offset, self.kind)
+    }
+}
+
+/// Every problem found by `validate`, in order of their offsets within the
+/// header, node buffer and data buffer.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub problems: Vec<Problem>,
+
+    /// The number of nodes checked, not counting attributes.
+    pub nodes: usize,
+}
+
+impl ValidationReport {
+    #[inline]
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    fn push(&mut self, offset: usize, kind: ProblemKind) {
+        self.problems.push(Problem {
+            offset: offset as u64,
+            kind,
+        });
+    }
+}
+
+/// The data buffer of a document, as long as its values can be found.
+struct Data {
+    buf: ByteBufferRead,
+    start: usize,
+}
+
+impl Data {
+    fn read(&mut self, node_type: StandardType, is_array: bool) -> Result<Bytes, ByteBufferError> {
+        match node_type {
+            StandardType::Attribute | StandardType::String | StandardType::Binary => {
+                self.buf.buf_read()
+            },
+            _ if is_array => {
+                let size = self.buf.read_length()?;
+                let data = self.buf.get(size as u32)?;
+                self.buf.realign_reads(None)?;
+
+                Ok(data)
+            },
+            node_type => self.buf.get_aligned(node_type),
+        }
+    }
+}
+
+/// Whether `length` bytes hold a whole number of `node_type` elements, or
+/// `node_type` has no fixed size.
+pub(crate) fn is_whole_array(node_type: StandardType, length: usize) -> bool {
+    let size = node_type.size * node_type.count;
+
+    size == 0 || length.is_multiple_of(size)
+}
+
+/// Check that the sixbit name at the start of `input` is not empty and has
+/// zeroed padding bits, returning its length in bytes.
+fn check_sixbit_name(input: &[u8]) -> Result<usize, ProblemKind> {
+    let (&len, data) = input.split_first().ok_or(ProblemKind::TruncatedNode)?;
+    let real_len = (usize::from(len) * 6).div_ceil(8);
+    let data = data.get(..real_len).ok_or(ProblemKind::TruncatedNode)?;
+
+    if len == 0 {
+        return Err(ProblemKind::InvalidName {
+            reason: "it is empty",
+        });
+    }
+    let padding = real_len * 8 - usize::from(len) * 6;
+    if data[real_len - 1] & ((1 << padding) - 1) != 0 {
+        return Err(ProblemKind::InvalidName {
+            reason: "sixbit padding bits are set",
+        });
+    }
+
+    Ok(1 + real_len)
+}
+
+/// Check that the uncompressed name at the start of `input` decodes with
+/// `encoding`, returning its length in bytes.
+fn check_name(input: &[u8], encoding: EncodingType) -> Result<usize, ProblemKind> {
+    let (&len, data) = input.split_first().ok_or(ProblemKind::TruncatedNode)?;
+    let len = usize::from(len & !ARRAY_MASK) + 1;
+    let data = data.get(..len).ok_or(ProblemKind::TruncatedNode)?;
+
+    if encoding.decode_bytes(data).is_err() {
+        return Err(ProblemKind::InvalidName {
+            reason: "it does not decode with the document encoding",
+        });
+    }
+
+    Ok(1 + len)
+}
+
+/// Walk a whole binary document and report every problem found with its
+/// offset, instead of failing on the first one like `from_bytes`.
+///
+/// Checks the header, the buffer lengths and their alignment, the type ids,
+/// names and nesting of the nodes and that every value fits in the data
+/// buffer. Fails only if the input does not start with the kbin signature.
+pub fn validate(input: &[u8]) -> Result<ValidationReport, KbinError> {
+    match input.first() {
+        Some(&SIGNATURE) => {},
+        signature => {
+            return Err(ReaderError::InvalidSignature {
+                signature: signature.copied().unwrap_or_default(),
+            }
+            .into());
+        },
+    };
+
+    let mut report = ValidationReport::default();
+    if input.len() < HEADER_LEN {
+        report.push(input.len(), ProblemKind::TruncatedHeader);
+        return Ok(report);
+    }
+
+    let compression = CompressionType::from_byte(input[1]).unwrap_or_else(|_| {
+        report.push(1, ProblemKind::InvalidCompression { byte: input[1] });
+        CompressionType::Compressed
+    });
+    let encoding = EncodingType::from_byte(input[2]).unwrap_or_else(|_| {
+        report.push(2, ProblemKind::InvalidEncoding { byte: input[2] });
+        EncodingType::None
+    });
+    if input[3] != !input[2] {
+        report.push(3, ProblemKind::MismatchedEncoding {
+            encoding: input[2],
+            negation: input[3],
+        });
+    }
+
+    let node_len = u32::from_be_bytes([input[4], input[5], input[6], input[7]]) as usize;
+    if !node_len.is_multiple_of(4) {
+        report.push(4, ProblemKind::Misaligned {
+            buffer: "node",
+            length: node_len,
+        });
+    }
+    let remaining = input.len() - HEADER_LEN;
+    let mut data = if node_len + 4 > remaining {
+        report.push(4, ProblemKind::NodeBufferLength {
+            declared: node_len,
+            remaining,
+        });
+
+        None
+    } else {
+        let offset = HEADER_LEN + node_len;
+        let declared = u32::from_be_bytes([
+            input[offset],
+            input[offset + 1],
+            input[offset + 2],
+            input[offset + 3],
+        ]) as usize;
+        let start = offset + 4;
+        let actual = input.len() - start;
+        if declared != actual {
+            report.push(offset, ProblemKind::DataBufferLength { declared, actual });
+        }
+        if !declared.is_multiple_of(4) {
+            report.push(offset, ProblemKind::Misaligned {
+                buffer: "data",
+                length: declared,
+            });
+        }
+
+        Some(Data {
+            buf: ByteBufferRead::new(Bytes::copy_from_slice(&input[start..])),
+            start,
+        })
+    };
+
+    let node_end = (HEADER_LEN + node_len).min(input.len());
+    let mut position = HEADER_LEN;
+    let mut depth = 0usize;
+    let mut file_end = false;
+
+    while position < node_end {
+        let offset = position;
+        let raw = input[position];
+        position += 1;
+
+        let is_array = raw & ARRAY_MASK == ARRAY_MASK;
+        let node_type = match StandardType::from_u8(raw & !ARRAY_MASK) {
+            Ok(node_type) => node_type,
+            Err(_) => {
+                report.push(offset, ProblemKind::UnknownType {
+                    type_id: raw & !ARRAY_MASK,
+                });
+                file_end = true;
+                break;
+            },
+        };
+
+        match node_type {
+            StandardType::NodeEnd => {
+                match depth.checked_sub(1) {
+                    Some(parent) => depth = parent,
+                    None => report.push(offset, ProblemKind::UnbalancedEnd),
+                };
+                continue;
+            },
+            StandardType::FileEnd => {
+                if depth > 0 {
+                    report.push(offset, ProblemKind::UnclosedNodes { depth });
+                }
+                if let Some(trailing) = input[position..node_end].iter().position(|&b| b != 0) {
+                    report.push(position + trailing, ProblemKind::TrailingNodeData);
+                }
+                file_end = true;
+                break;
+            },
+            StandardType::Attribute if depth == 0 => {
+                report.push(offset, ProblemKind::AttributeOutsideNode);
+            },
+            StandardType::Attribute => {},
+            _ => {
+                depth += 1;
+                report.nodes += 1;
+            },
+        };
+
+        let name = &input[position..node_end];
+        let name = match compression {
+            CompressionType::Compressed => check_sixbit_name(name),
+            CompressionType::Uncompressed => check_name(name, encoding),
+        };
+        match name {
+            Ok(len) => position += len,
+            Err(ProblemKind::TruncatedNode) => {
+                report.push(offset, ProblemKind::TruncatedNode);
+                file_end = true;
+                break;
+            },
+            Err(kind) => {
+                report.push(offset, kind);
+                // The name length is still known.
+                position += match compression {
+                    CompressionType::Compressed => {
+                        1 + (usize::from(input[position]) * 6).div_ceil(8)
+                    },
+                    CompressionType::Uncompressed => 2 + usize::from(input[position] & !ARRAY_MASK),
+                };
+            },
+        };
+
+        if let (Some(buffer), true) = (&mut data, node_type != StandardType::NodeStart) {
+            let data_offset = (buffer.start + buffer.buf.position() as usize) as u64;
+            match buffer.read(node_type, is_array) {
+                Ok(value) if node_type == StandardType::Boolean => {
+                    let start = buffer.start + buffer.buf.last_read_offset();
+                    for (i, &byte) in value.iter().enumerate().filter(|(_, &b)| b > 1) {
+                        report.push(start + i, ProblemKind::InvalidBoolean {
+                            data_offset: (start + i) as u64,
+                            byte,
+                        });
+                    }
+                },
+                Ok(value) if is_array && !is_whole_array(node_type, value.len()) => {
+                    report.push(offset, ProblemKind::ArrayLength {
+                        node_type,
+                        length: value.len(),
+                    });
+                },
+                Ok(_) => {},
+                Err(_) => {
+                    report.push(offset, ProblemKind::DataOutOfBounds {
+                        node_type,
+                        data_offset,
+                    });
+                    data = None;
+                },
+            };
+        }
+    }
+
+    if !file_end {
+        report.push(node_end, ProblemKind::MissingFileEnd);
+    }
+    report.problems.sort_by_key(|problem| problem.offset);
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::node::Node;
+    use crate::options::Options;
+    use crate::value::Value;
+    use crate::{CompressionType, HEADER_LEN};
+
+    use super::{validate, ProblemKind};
+
+    #[test]
+    fn test_validate() {
+        let node = Node::with_nodes("root", vec![
+            Node::with_attrs_value("flag", &[("id", "1")], Value::Boolean(true)),
+            Node::with_value("name", Value::String("abc".into())),
+        ]);
+        let data = crate::to_binary(&node).expect("Failed to encode");
+        let report = validate(&data).expect("Failed to validate");
+        assert!(report.is_valid(), "{:?}", report);
+        assert_eq!(report.nodes, 3);
+
+        let options = Options::new(CompressionType::Uncompressed, Default::default());
+        let uncompressed = crate::to_binary_with_options(options, &node).expect("Failed to encode");
+        assert!(validate(&uncompressed)
+            .expect("Failed to validate")
+            .is_valid());
+
+        // Break the encoding, a boolean and the data buffer length at once.
+        let mut broken = data.clone();
+        broken[3] ^= 0xff;
+        let data_start = HEADER_LEN + 4 + usize::from(data[7]);
+        let boolean = data
+            .iter()
+            .skip(data_start)
+            .position(|&b| b == 1)
+            .expect("Missing boolean");
+        broken[data_start + boolean] = 2;
+        broken.truncate(data.len() - 4);
+        let report = validate(&broken).expect("Failed to validate");
+        let kinds: Vec<_> = report.problems.iter().map(|p| p.kind.clone()).collect();
+        assert!(matches!(kinds[0], ProblemKind::MismatchedEncoding { .. }));
+        assert!(kinds
+            .iter()
+            .any(|kind| matches!(kind, ProblemKind::InvalidBoolean { byte: 2, .. })));
+        assert!(kinds
+            .iter()
+            .any(|kind| matches!(kind, ProblemKind::DataBufferLength { .. })));
+        assert!(kinds
+            .iter()
+            .any(|kind| matches!(kind, ProblemKind::DataOutOfBounds { .. })));
+        assert!(report.problems[0].to_string().starts_with("0x00000003: "));
+
+        // An unknown type id stops the node walk.
+        let mut unknown = data;
+        unknown[8] = 47;
+        let report = validate(&unknown).expect("Failed to validate");
+        assert_eq!(report.problems.len(), 1);
+        assert_eq!(report.problems[0].offset, 8);
+        assert_eq!(report.problems[0].kind, ProblemKind::UnknownType {
+            type_id: 47
+        });
+
+        assert!(validate(b"<xml/>").is_err());
+    }
+}
@@ -1,3 +1,5 @@
+use std::borrow::Cow;
+use std::convert::TryFrom;
 use std::fmt;
 use std::io::Cursor;
 use std::net::Ipv4Addr;
@@ -6,6 +8,35 @@ use crate::error::KbinError;
 use crate::node_types::StandardType;
 use crate::types::FromKbinString;
 use crate::types::{FromKbinBytes, IntoKbinBytes};
+use crate::value::Value;
+
+/// Split the text of an array of `arr_count` elements into the text of each
+/// element, `count` whitespace separated values each.
+fn split_elements(
+    node_type: StandardType,
+    count: usize,
+    input: &str,
+    arr_count: usize,
+) -> Result<Vec<Cow<'_, str>>, KbinError> {
+    let parts: Vec<_> = input.split_whitespace().collect();
+    if parts.len() != count * arr_count {
+        return Err(KbinError::SizeMismatch {
+            node_type: node_type.name,
+            expected: count * arr_count,
+            actual: parts.len(),
+        });
+    }
+
+    let elements = parts
+        .chunks(count)
+        .map(|values| match values {
+            [value] => Cow::Borrowed(*value),
+            values => Cow::Owned(values.join(" ")),
+        })
+        .collect();
+
+    Ok(elements)
+}
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum ValueArray {
@@ -67,6 +98,11 @@ macro_rules! type_impl {
   ) => {
     pub fn from_standard_type(node_type: StandardType, input: &[u8]) -> Result<Option<Self>, KbinError> {
       let node_size = node_type.size * node_type.count;
+
+      // Node types without a fixed size have no array form
+      if node_size == 0 {
+        return Ok(None);
+      }
       let len = input.len() / node_size;
 
       // Prevent reading incomplete input data
@@ -103,28 +139,6 @@ macro_rules! type_impl {
     pub(super) fn from_string(node_type: StandardType, count: usize, input: &str, arr_count: usize) -> Result<Self, KbinError> {
       trace!("from_string(count: {}, input: {:?}, arr_count: {})", count, input, arr_count);
 
-      // counter of the number of space characters encountered
-      let mut i = 0;
-
-      let iter = input.split(|c| {
-        if c == ' ' {
-          // increment ths space counter
-          i += 1;
-
-          // if the space counter is equal to count, then split
-          let res = i == count;
-
-          // if splitting, then reset the counter
-          if res {
-            i = 0;
-          }
-
-          res
-        } else {
-          false
-        }
-      });
-
       let value = match node_type {
         StandardType::NodeStart |
         StandardType::NodeEnd |
@@ -135,10 +149,10 @@ macro_rules! type_impl {
         StandardType::Time => return Err(KbinError::InvalidState.into()),
         $(
           StandardType::$konst => {
-            let mut values = Vec::new();
+            let mut values = Vec::with_capacity(arr_count);
 
-            for part in iter {
-              values.push(FromKbinString::from_kbin_string(part)?);
+            for part in split_elements(node_type, count, input, arr_count)? {
+              values.push(FromKbinString::from_kbin_string(&part)?);
             }
 
             ValueArray::$konst(values)
@@ -149,6 +163,34 @@ macro_rules! type_impl {
       Ok(value)
     }
 
+    /// Build an array of `node_type` from individual values of that type.
+    pub fn from_values(node_type: StandardType, values: Vec<Value>) -> Result<Self, KbinError> {
+      let value = match node_type {
+        $(
+          StandardType::$konst => {
+            let values = values
+              .into_iter()
+              .map(TryFrom::try_from)
+              .collect::<Result<_, _>>()?;
+
+            ValueArray::$konst(values)
+          },
+        )*
+        node_type => return Err(KbinError::InvalidNodeType { node_type }),
+      };
+
+      Ok(value)
+    }
+
+    /// Split the array into individual values.
+    pub fn to_values(&self) -> Vec<Value> {
+      match self {
+        $(
+          ValueArray::$konst(values) => values.iter().cloned().map(Value::$konst).collect(),
+        )*
+      }
+    }
+
     pub fn to_bytes_into(&self, output: &mut Vec<u8>) -> Result<(), KbinError> {
       let node_size = self.standard_type().size;
 
@@ -0,0 +1,107 @@
+use std::borrow::Cow;
+
+use crate::error::{KbinError, Result};
+use crate::node_types::StandardType;
+use crate::value::{Value, ValueArray};
+
+/// A value borrowing the data of a `NodeDefinition`, see
+/// `NodeDefinition::value_ref`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValueRef<'a> {
+    /// The text of a `str` node, borrowed unless it has to be converted from
+    /// the document encoding.
+    String(Cow<'a, str>),
+
+    /// The text of an attribute, borrowed like `String`.
+    Attribute(Cow<'a, str>),
+
+    Binary(&'a [u8]),
+
+    /// The big endian data of an array node.
+    Array {
+        node_type: StandardType,
+        data: &'a [u8],
+    },
+
+    /// Any other value, none of which allocate.
+    Scalar(Value),
+}
+
+impl<'a> ValueRef<'a> {
+    /// The text of a `String` or `Attribute` value.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            ValueRef::String(text) | ValueRef::Attribute(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// The data of a `Binary` or `Array` value.
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match *self {
+            ValueRef::Binary(data) | ValueRef::Array { data, .. } => Some(data),
+            _ => None,
+        }
+    }
+
+    /// Convert to an owned `Value`, decoding array data.
+    pub fn to_value(&self) -> Result<Value> {
+        match self {
+            ValueRef::String(text) => Ok(Value::String(text.clone().into_owned())),
+            ValueRef::Attribute(text) => Ok(Value::Attribute(text.clone().into_owned())),
+            ValueRef::Binary(data) => Ok(Value::Binary(data.to_vec())),
+            ValueRef::Array { node_type, data } => {
+                match ValueArray::from_standard_type(*node_type, data)? {
+                    Some(array) => Ok(Value::Array(array)),
+                    None => Err(KbinError::InvalidState),
+                }
+            },
+            ValueRef::Scalar(value) => Ok(value.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use crate::encoding_type::EncodingType;
+    use crate::node::Node;
+    use crate::options::Options;
+    use crate::value::{Value, ValueArray};
+
+    use super::ValueRef;
+
+    #[test]
+    fn test_value_ref() {
+        let node = Node::with_nodes("root", vec![
+            Node::with_value("name", Value::String("name".into())),
+            Node::with_value("kana", Value::String("なまえ".into())),
+            Node::with_value("data", Value::Binary(vec![1, 2, 3])),
+            Node::with_value("pos", Value::Array(ValueArray::S32(vec![1, 2, 3]))),
+            Node::with_value("id", Value::U32(7)),
+        ]);
+        let options = Options::with_encoding(EncodingType::SHIFT_JIS);
+        let data = crate::to_binary_with_options(options, &node).expect("Failed to encode");
+        let (collection, _) = crate::from_slice(&data).expect("Failed to decode");
+
+        let values: Vec<ValueRef> = collection
+            .children()
+            .iter()
+            .map(|child| child.base().value_ref().expect("Failed to read value"))
+            .collect();
+        assert!(matches!(values[0], ValueRef::String(Cow::Borrowed("name"))));
+        assert!(matches!(values[1], ValueRef::String(Cow::Owned(_))));
+        assert_eq!(values[1].as_str(), Some("なまえ"));
+        assert_eq!(values[2], ValueRef::Binary(&[1, 2, 3]));
+        assert_eq!(values[3].as_bytes().map(<[u8]>::len), Some(12));
+        assert_eq!(values[4], ValueRef::Scalar(Value::U32(7)));
+
+        for (child, value) in node.children().iter().zip(&values) {
+            assert_eq!(
+                child.value(),
+                Some(&value.to_value().expect("Failed to convert"))
+            );
+        }
+    }
+}
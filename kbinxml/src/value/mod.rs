@@ -3,17 +3,23 @@ use std::convert::TryFrom;
 use std::fmt;
 use std::io::Cursor;
 use std::net::Ipv4Addr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use rustc_hex::FromHex;
 use snafu::ResultExt;
 
 use crate::error::{HexSnafu, KbinError, Result};
+use crate::nested::is_document_header;
 use crate::node_types::StandardType;
 use crate::types::{FromKbinBytes, FromKbinString, IntoKbinBytes};
 
 mod array;
+mod borrowed;
+mod ordering;
 
 pub use self::array::ValueArray;
+pub use self::borrowed::ValueRef;
+pub use self::ordering::compare;
 
 macro_rules! construct_types {
     (
@@ -30,6 +36,11 @@ macro_rules! construct_types {
             Time(u32),
             Attribute(String),
 
+            /// The data of an array of a type this crate does not know, read
+            /// with `OptionsBuilder::lenient_unknown_types`. `id` is the raw type byte, including
+            /// the array flag.
+            Unknown { id: u8, bytes: Vec<u8> },
+
             Array(ValueArray),
         }
 
@@ -82,6 +93,7 @@ macro_rules! construct_types {
                     Value::Binary(_) => StandardType::Binary,
                     Value::Time(_) => StandardType::Time,
                     Value::Attribute(_) => StandardType::Attribute,
+                    Value::Unknown { .. } => StandardType::Binary,
                     Value::Array(ref value) => value.standard_type(),
                 }
             }
@@ -225,7 +237,9 @@ macro_rules! tuple {
                 Value::U32(n) => n.write_kbin_bytes(output),
                 Value::S64(n) => n.write_kbin_bytes(output),
                 Value::U64(n) => n.write_kbin_bytes(output),
-                Value::Binary(data) => output.extend_from_slice(data),
+                Value::Binary(data) | Value::Unknown { bytes: data, .. } => {
+                    output.extend_from_slice(data)
+                },
                 Value::Time(n) => n.write_kbin_bytes(output),
                 Value::Ip4(addr) => addr.write_kbin_bytes(output),
                 Value::Float(n) => n.write_kbin_bytes(output),
@@ -264,6 +278,19 @@ impl Value {
         Double2, Double3, Double4,
     }
 
+    /// The data of a `Binary` value if it starts with a kbin document header.
+    pub fn nested_document(&self) -> Option<&[u8]> {
+        match self {
+            Value::Binary(data) if is_document_header(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn is_nested_document(&self) -> bool {
+        self.nested_document().is_some()
+    }
+
     pub fn to_bytes(&self) -> Result<Vec<u8>> {
         let mut output = Vec::new();
         self.to_bytes_inner(&mut output)?;
@@ -356,9 +383,76 @@ impl Value {
         }
     }
 
+    pub fn as_f32(&self) -> Result<f32> {
+        match self {
+            Value::Float(ref n) => Ok(*n),
+            value => Err(KbinError::ValueTypeMismatch {
+                node_type: StandardType::Float,
+                value: value.clone(),
+            }),
+        }
+    }
+
+    pub fn as_f64(&self) -> Result<f64> {
+        match self {
+            Value::Double(ref n) => Ok(*n),
+            value => Err(KbinError::ValueTypeMismatch {
+                node_type: StandardType::Double,
+                value: value.clone(),
+            }),
+        }
+    }
+
+    pub fn as_bool(&self) -> Result<bool> {
+        match self {
+            Value::Boolean(ref n) => Ok(*n),
+            value => Err(KbinError::ValueTypeMismatch {
+                node_type: StandardType::Boolean,
+                value: value.clone(),
+            }),
+        }
+    }
+
+    pub fn as_ip4(&self) -> Result<Ipv4Addr> {
+        match self {
+            Value::Ip4(ref n) => Ok(*n),
+            value => Err(KbinError::ValueTypeMismatch {
+                node_type: StandardType::Ip4,
+                value: value.clone(),
+            }),
+        }
+    }
+
+    /// The seconds since the Unix epoch of a `Time` value.
+    pub fn as_time(&self) -> Result<u32> {
+        match self {
+            Value::Time(ref n) => Ok(*n),
+            value => Err(KbinError::ValueTypeMismatch {
+                node_type: StandardType::Time,
+                value: value.clone(),
+            }),
+        }
+    }
+
+    /// The instant of a `Time` value.
+    pub fn as_system_time(&self) -> Result<SystemTime> {
+        Ok(UNIX_EPOCH + Duration::from_secs(self.as_time()?.into()))
+    }
+
+    /// A `Time` value for the given instant, truncated to whole seconds.
+    /// Fails if it falls before the Unix epoch or past the range of `u32`.
+    pub fn from_system_time(time: SystemTime) -> Result<Value> {
+        time.duration_since(UNIX_EPOCH)
+            .ok()
+            .and_then(|since| u32::try_from(since.as_secs()).ok())
+            .map(Value::Time)
+            .ok_or(KbinError::TimeOutOfRange { time })
+    }
+
     pub fn as_slice(&self) -> Result<&[u8]> {
         match self {
             Value::Binary(ref data) => Ok(data),
+            Value::Unknown { ref bytes, .. } => Ok(bytes),
             value => Err(KbinError::ValueTypeMismatch {
                 node_type: StandardType::Binary,
                 value: value.clone(),
@@ -424,6 +518,74 @@ impl Value {
             }),
         }
     }
+
+    /// The value of a scalar integer widened to `i128`.
+    pub(crate) fn as_integer(&self) -> Option<i128> {
+        let integer = match *self {
+            Value::S8(v) => v as i128,
+            Value::U8(v) => v as i128,
+            Value::S16(v) => v as i128,
+            Value::U16(v) => v as i128,
+            Value::S32(v) => v as i128,
+            Value::U32(v) => v as i128,
+            Value::S64(v) => v as i128,
+            Value::U64(v) => v as i128,
+            _ => return None,
+        };
+
+        Some(integer)
+    }
+
+    /// Build a scalar integer value of `node_type`, if `integer` fits.
+    pub(crate) fn from_integer(node_type: StandardType, integer: i128) -> Option<Value> {
+        let (min, max) = integer_bounds(node_type)?;
+        if integer < min || integer > max {
+            return None;
+        }
+
+        let value = match node_type {
+            StandardType::S8 => Value::S8(integer as i8),
+            StandardType::U8 => Value::U8(integer as u8),
+            StandardType::S16 => Value::S16(integer as i16),
+            StandardType::U16 => Value::U16(integer as u16),
+            StandardType::S32 => Value::S32(integer as i32),
+            StandardType::U32 => Value::U32(integer as u32),
+            StandardType::S64 => Value::S64(integer as i64),
+            _ => Value::U64(integer as u64),
+        };
+
+        Some(value)
+    }
+
+    /// Convert the value to `node_type` through its text form, keeping
+    /// arrays as arrays.
+    pub(crate) fn retype(&self, node_type: StandardType) -> Result<Value> {
+        match self {
+            Value::Array(values) if values.standard_type() == node_type => Ok(self.clone()),
+            Value::Array(values) => {
+                Value::from_string(node_type, &self.to_string(), true, values.len())
+            },
+            value if value.standard_type() == node_type => Ok(value.clone()),
+            value => Value::from_string(node_type, &value.to_string(), false, 0),
+        }
+    }
+}
+
+/// The inclusive range of a scalar integer type.
+pub(crate) fn integer_bounds(node_type: StandardType) -> Option<(i128, i128)> {
+    let bounds = match node_type {
+        StandardType::S8 => (i8::MIN as i128, i8::MAX as i128),
+        StandardType::U8 => (0, u8::MAX as i128),
+        StandardType::S16 => (i16::MIN as i128, i16::MAX as i128),
+        StandardType::U16 => (0, u16::MAX as i128),
+        StandardType::S32 => (i32::MIN as i128, i32::MAX as i128),
+        StandardType::U32 => (0, u32::MAX as i128),
+        StandardType::S64 => (i64::MIN as i128, i64::MAX as i128),
+        StandardType::U64 => (0, u64::MAX as i128),
+        _ => return None,
+    };
+
+    Some(bounds)
 }
 
 impl TryFrom<Value> for Vec<u8> {
@@ -498,12 +660,56 @@ impl TryFrom<&Value> for Cow<'_, str> {
     }
 }
 
+impl<'a> TryFrom<&'a Value> for &'a str {
+    type Error = KbinError;
+
+    fn try_from(value: &'a Value) -> Result<Self> {
+        value.as_str()
+    }
+}
+
+impl TryFrom<Value> for SystemTime {
+    type Error = KbinError;
+
+    fn try_from(value: Value) -> Result<Self> {
+        value.as_system_time()
+    }
+}
+
+impl TryFrom<&Value> for SystemTime {
+    type Error = KbinError;
+
+    fn try_from(value: &Value) -> Result<Self> {
+        value.as_system_time()
+    }
+}
+
+impl TryFrom<SystemTime> for Value {
+    type Error = KbinError;
+
+    fn try_from(time: SystemTime) -> Result<Self> {
+        Value::from_system_time(time)
+    }
+}
+
 impl From<Vec<u8>> for Value {
     fn from(value: Vec<u8>) -> Value {
         Value::Binary(value)
     }
 }
 
+impl From<&[u8]> for Value {
+    fn from(value: &[u8]) -> Value {
+        Value::Binary(value.to_vec())
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Value {
+        Value::String(value.to_owned())
+    }
+}
+
 impl fmt::Debug for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         macro_rules! field {
@@ -519,6 +725,9 @@ impl fmt::Debug for Value {
                         Value::$konst_debug(ref v) => write!(f, concat!(stringify!($konst_debug), "({:?})"), v),
                     )*
                     Value::Binary(ref v) => write!(f, "Binary(0x{:02x?})", v),
+                    Value::Unknown { id, ref bytes } => {
+                        write!(f, "Unknown {{ id: {}, bytes: 0x{:02x?} }}", id, bytes)
+                    },
                     Value::Array(ref value) => if f.alternate() {
                         write!(f, "Array({:#?})", value)
                     } else {
@@ -547,6 +756,8 @@ impl fmt::Debug for Value {
     }
 }
 
+/// The alternate form, `{:#}`, writes floats in the shortest form that reads
+/// back as the same value instead of with six decimal places.
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         macro_rules! display_value {
@@ -583,13 +794,15 @@ impl fmt::Display for Value {
                             },
                         )*
                     )*
-                    Value::Binary(buf) => {
+                    Value::Binary(buf) | Value::Unknown { bytes: buf, .. } => {
                         for n in buf {
                             write!(f, "{:02x}", n)?;
                         }
                         Ok(())
                     },
+                    Value::Float(n) if f.alternate() => write!(f, "{}", n),
                     Value::Float(n) => write!(f, "{:.6}", n),
+                    Value::Double(n) if f.alternate() => write!(f, "{}", n),
                     Value::Double(n) => write!(f, "{:.6}", n),
                     Value::Boolean(b) => match b {
                         true => f.write_str("1"),
@@ -677,3 +890,112 @@ construct_types! {
     (Boolean4, [bool; 4]);
     (Vb,       [bool; 16]);
 }
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use std::net::Ipv4Addr;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use crate::error::KbinError;
+    use crate::node_types::StandardType;
+
+    use super::{Value, ValueArray};
+
+    #[test]
+    fn test_array_from_string() {
+        assert_eq!(
+            Value::from_string(StandardType::U16, " 1\n2\t 3 ", true, 3).ok(),
+            Some(Value::Array(ValueArray::U16(vec![1, 2, 3])))
+        );
+        let pairs = ValueArray::Float2(vec![[1.0, 2.0], [3.0, 4.0]]);
+        assert_eq!(
+            Value::from_string(StandardType::Float2, "1 2\n3  4", true, 2).ok(),
+            Some(Value::Array(pairs))
+        );
+        assert_eq!(
+            Value::from_string(StandardType::S32, "", true, 0).ok(),
+            Some(Value::Array(ValueArray::S32(Vec::new())))
+        );
+        assert!(matches!(
+            Value::from_string(StandardType::Float3, "1 2 3 4 5", true, 2),
+            Err(KbinError::SizeMismatch {
+                expected: 6,
+                actual: 5,
+                ..
+            })
+        ));
+        assert!(matches!(
+            Value::from_string(StandardType::U8, "1 2 3", true, 2),
+            Err(KbinError::SizeMismatch {
+                expected: 2,
+                actual: 3,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_scalar_conversions() {
+        assert_eq!(Value::from(1.5f32).as_f32().ok(), Some(1.5));
+        assert_eq!(Value::from(true).as_bool().ok(), Some(true));
+        assert_eq!(Value::Time(60).as_time().ok(), Some(60));
+
+        let addr = Ipv4Addr::new(127, 0, 0, 1);
+        assert_eq!(Value::from(addr).as_ip4().ok(), Some(addr));
+        assert_eq!(Ipv4Addr::try_from(Value::from(addr)).ok(), Some(addr));
+        assert!(matches!(
+            Value::from_standard_type(StandardType::Ip4, false, &[127, 0, 0]),
+            Err(KbinError::SizeMismatch {
+                expected: 4,
+                actual: 3,
+                ..
+            })
+        ));
+        assert!(Value::from_standard_type(StandardType::Ip4, true, &[0; 6]).is_err());
+        for input in ["1.2.3", "1.2.3.4.5", "1.2.3.256", "1.2.3.4 5"] {
+            assert!(matches!(
+                Value::from_string(StandardType::Ip4, input, false, 0),
+                Err(KbinError::InvalidIp4 { .. })
+            ));
+        }
+        assert!(matches!(
+            Value::from_standard_type(StandardType::Boolean, false, &[2]),
+            Err(KbinError::InvalidBooleanInput { input: 2 })
+        ));
+
+        let value = Value::from("name");
+        assert_eq!(<&str>::try_from(&value).ok(), Some("name"));
+        assert_eq!(Value::from(&b"data"[..]), Value::Binary(b"data".to_vec()));
+
+        match u32::try_from(&value) {
+            Err(KbinError::ValueTypeMismatch {
+                value: mismatched, ..
+            }) => {
+                assert_eq!(mismatched, value)
+            },
+            result => panic!("Unexpected result: {:?}", result),
+        };
+    }
+
+    #[test]
+    fn test_system_time() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_600_000_000_500);
+        let value = Value::try_from(time).expect("Failed to convert time");
+        assert_eq!(value, Value::Time(1_600_000_000));
+        assert_eq!(value.to_string(), "1600000000");
+        assert_eq!(
+            SystemTime::try_from(&value).ok(),
+            Some(UNIX_EPOCH + Duration::from_secs(1_600_000_000))
+        );
+
+        let before = UNIX_EPOCH - Duration::from_secs(1);
+        let after = UNIX_EPOCH + Duration::from_secs(u64::from(u32::MAX) + 1);
+        for time in [before, after] {
+            assert!(matches!(
+                Value::from_system_time(time),
+                Err(KbinError::TimeOutOfRange { .. })
+            ));
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use std::cmp::Ordering;
+
+use crate::value::Value;
+
+impl PartialOrd for Value {
+    /// Compare values of the same type.
+    ///
+    /// Numbers, times, booleans, addresses, strings and binary data are
+    /// ordered by value. Values of different types and fixed-size or array
+    /// values are not ordered, see `compare` for comparisons across types.
+    fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::S8(a), Value::S8(b)) => a.partial_cmp(b),
+            (Value::U8(a), Value::U8(b)) => a.partial_cmp(b),
+            (Value::S16(a), Value::S16(b)) => a.partial_cmp(b),
+            (Value::U16(a), Value::U16(b)) => a.partial_cmp(b),
+            (Value::S32(a), Value::S32(b)) => a.partial_cmp(b),
+            (Value::U32(a), Value::U32(b)) => a.partial_cmp(b),
+            (Value::S64(a), Value::S64(b)) => a.partial_cmp(b),
+            (Value::U64(a), Value::U64(b)) => a.partial_cmp(b),
+            (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+            (Value::Double(a), Value::Double(b)) => a.partial_cmp(b),
+            (Value::Time(a), Value::Time(b)) => a.partial_cmp(b),
+            (Value::Boolean(a), Value::Boolean(b)) => a.partial_cmp(b),
+            (Value::Ip4(a), Value::Ip4(b)) => a.partial_cmp(b),
+            (Value::String(a), Value::String(b)) | (Value::Attribute(a), Value::Attribute(b)) => {
+                a.partial_cmp(b)
+            },
+            (Value::Binary(a), Value::Binary(b)) => a.partial_cmp(b),
+            _ => None,
+        }
+    }
+}
+
+/// A scalar value as a number, parsing strings so attribute values can be
+/// compared with numbers.
+enum Number {
+    Integer(i128),
+    Float(f64),
+}
+
+impl Number {
+    fn from_value(value: &Value) -> Option<Self> {
+        if let Some(integer) = value.as_integer() {
+            return Some(Number::Integer(integer));
+        }
+
+        let number = match value {
+            Value::Time(v) => Number::Integer(*v as i128),
+            Value::Float(v) => Number::Float(*v as f64),
+            Value::Double(v) => Number::Float(*v),
+            Value::String(s) | Value::Attribute(s) => {
+                let s = s.trim();
+                match s.parse::<i128>() {
+                    Ok(integer) => Number::Integer(integer),
+                    Err(_) => Number::Float(s.parse::<f64>().ok()?),
+                }
+            },
+            _ => return None,
+        };
+
+        Some(number)
+    }
+
+    fn partial_cmp(&self, other: &Number) -> Option<Ordering> {
+        match (self, other) {
+            (Number::Integer(a), Number::Integer(b)) => a.partial_cmp(b),
+            (Number::Integer(a), Number::Float(b)) => (*a as f64).partial_cmp(b),
+            (Number::Float(a), Number::Integer(b)) => a.partial_cmp(&(*b as f64)),
+            (Number::Float(a), Number::Float(b)) => a.partial_cmp(b),
+        }
+    }
+}
+
+/// Compare two values, converting between types where the comparison still
+/// makes sense.
+///
+/// Integers of any width, times, floats and strings or attributes that parse
+/// as numbers are compared as numbers, so `U8(5)` is less than `S64(1000)`
+/// and `Attribute("1001")` is greater than `U32(1000)`. Strings are compared
+/// with attributes as text. Every other pair is compared with `PartialOrd`,
+/// which only orders values of the same type.
+pub fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        // Text on both sides is compared as text, even when it holds numbers.
+        (Value::String(a) | Value::Attribute(a), Value::String(b) | Value::Attribute(b)) => {
+            a.partial_cmp(b)
+        },
+        (a, b) => match (Number::from_value(a), Number::from_value(b)) {
+            (Some(a), Some(b)) => a.partial_cmp(&b),
+            _ => a.partial_cmp(b),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use crate::value::Value;
+
+    use super::compare;
+
+    #[test]
+    fn test_value_ordering() {
+        assert!(Value::U32(5) < Value::U32(1000));
+        assert_eq!(Value::U32(5).partial_cmp(&Value::S64(1000)), None);
+        assert_eq!(Value::Float(f32::NAN).partial_cmp(&Value::Float(0.0)), None);
+
+        assert_eq!(
+            compare(&Value::U8(5), &Value::S64(1000)),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            compare(&Value::Attribute("1001".into()), &Value::U32(1000)),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            compare(&Value::Double(1.5), &Value::S32(1)),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            compare(&Value::String("10".into()), &Value::Attribute("9".into())),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            compare(&Value::Attribute("abc".into()), &Value::U32(1)),
+            None
+        );
+        assert_eq!(compare(&Value::Boolean(true), &Value::U8(1)), None);
+    }
+}
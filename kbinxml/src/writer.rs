@@ -1,21 +1,29 @@
-use std::io::{self, Cursor, Write};
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::mem;
+use std::sync::{Mutex, PoisonError};
 
 use byteorder::{BigEndian, WriteBytesExt};
 use snafu::{ResultExt, Snafu};
 
-use crate::byte_buffer::{ByteBufferError, ByteBufferWrite};
+use crate::byte_buffer::{strip_trailing_null_bytes, ByteBufferError, ByteBufferWrite};
 use crate::compression_type::CompressionType;
 use crate::encoding_type::{EncodingError, EncodingType};
-use crate::node::{Node, NodeCollection};
+use crate::filter::{apply_attribute_filters, apply_filters, child_paths, FilterError};
+use crate::header::Header;
+use crate::nested::{embed_nested, NESTED_ATTRIBUTE};
+use crate::node::{Node, NodeCollection, NodeDefinition};
 use crate::node_types::StandardType;
-use crate::options::Options;
+use crate::options::{describe_conflicts, EmptyArrayPolicy, Endianness, Options, OptionsConflict};
 use crate::sixbit::{Sixbit, SixbitError};
 use crate::value::Value;
 
-use super::{ARRAY_MASK, SIGNATURE};
+use super::ARRAY_MASK;
 
 #[derive(Debug, Snafu)]
 pub enum WriterError {
+    // The header is written at once, so failures to write its fields are
+    // reported as `Header` instead of the five variants below.
     #[snafu(display("Failed to write signature to header"))]
     Signature { source: io::Error },
 
@@ -31,9 +39,18 @@ pub enum WriterError {
     #[snafu(display("Failed to write node buffer length"))]
     NodeBufferLength { source: io::Error },
 
+    #[snafu(display("Failed to write header"))]
+    Header { source: io::Error },
+
     #[snafu(display("Failed to write data buffer length"))]
     DataBufferLength { source: io::Error },
 
+    #[snafu(display("Failed to write node buffer contents"))]
+    NodeBufferContents { source: io::Error },
+
+    #[snafu(display("Failed to write data buffer contents"))]
+    DataBufferContents { source: io::Error },
+
     #[snafu(display(
         "Failed to write node size ({} byte(s)) for node type {}",
         size,
@@ -108,6 +125,46 @@ pub enum WriterError {
         source: Box<crate::KbinError>,
     },
 
+    #[snafu(display("Invalid options: {}", describe_conflicts(conflicts)))]
+    InvalidOptions { conflicts: Vec<OptionsConflict> },
+
+    #[snafu(display(
+        "Node {} is marked as a nested document but does not hold exactly one document",
+        key
+    ))]
+    InvalidNestedDocument { key: String },
+
+    #[snafu(display("Attempted to write empty array for node type {}", node_type))]
+    EmptyArray { node_type: StandardType },
+
+    #[snafu(display("Failed to convert attribute {} from {:?} to {:?}", key, from, to))]
+    AttributeTranscode {
+        key: String,
+        from: EncodingType,
+        to: EncodingType,
+        source: EncodingError,
+    },
+
+    #[snafu(display("String at {} holds a null byte or control character", path))]
+    ControlCharacter { path: String },
+
+    #[snafu(display("Name at {} is {} byte(s) long, the maximum is {}", path, length, max))]
+    NameTooLong {
+        path: String,
+        length: usize,
+        max: usize,
+    },
+
+    #[snafu(display(
+        "Data at {} is {} byte(s) long, more than a length prefix can hold",
+        path,
+        length
+    ))]
+    DataTooLong { path: String, length: usize },
+
+    #[snafu(display("Value filter failed at {}", path))]
+    Filter { path: String, source: FilterError },
+
     #[snafu(display("Attempted to write node definition without key data"))]
     NoNodeKey,
 
@@ -115,9 +172,74 @@ pub enum WriterError {
     NoNodeValue,
 }
 
+/// Sixbit names are prefixed by their length in characters as a byte.
+const MAX_SIXBIT_NAME_LENGTH: usize = u8::MAX as usize;
+
+/// Uncompressed names are prefixed by their length in bytes minus one, which
+/// only has the six bits below `ARRAY_MASK` to itself.
+const MAX_UNCOMPRESSED_NAME_LENGTH: usize = ARRAY_MASK as usize;
+
+/// Strings, attributes, binary data and arrays are prefixed by their length
+/// in bytes as a `u32`.
+const MAX_DATA_LENGTH: usize = u32::MAX as usize;
+
+fn check_data_length<F>(length: usize, path: F) -> Result<u32, WriterError>
+where
+    F: FnOnce() -> String,
+{
+    if length > MAX_DATA_LENGTH {
+        return Err(WriterError::DataTooLong {
+            path: path(),
+            length,
+        });
+    }
+
+    Ok(length as u32)
+}
+
+/// Apply the control character policy of `options` to a string.
+fn check_string<'a, F>(
+    options: &Options,
+    text: &'a str,
+    path: F,
+) -> Result<Cow<'a, str>, WriterError>
+where
+    F: Fn() -> String,
+{
+    let text = options
+        .control_characters
+        .apply(text)
+        .ok_or_else(|| WriterError::ControlCharacter { path: path() })?;
+    // Including the null terminator
+    check_data_length(text.len() + 1, path)?;
+
+    Ok(text)
+}
+
+/// The null terminated data of an attribute in the encoding of `options`,
+/// converting it if it was read from a document in another encoding.
+pub(crate) fn attribute_bytes<'a>(
+    options: &Options,
+    attr: &'a NodeDefinition,
+) -> Result<Cow<'a, [u8]>, EncodingError> {
+    let data = attr.value_bytes().unwrap_or_default();
+    if attr.encoding() == options.encoding {
+        return Ok(Cow::Borrowed(data));
+    }
+
+    let text = attr
+        .encoding()
+        .decode_bytes(strip_trailing_null_bytes(data))?;
+    options
+        .encoding
+        .encode_bytes_with(&text, options.unmappable_characters)
+        .map(Cow::Owned)
+}
+
 fn write_value(
     options: &Options,
     data_buf: &mut ByteBufferWrite,
+    name: &str,
     node_type: StandardType,
     is_array: bool,
     value: &Value,
@@ -126,8 +248,7 @@ fn write_value(
         Value::Binary(data) => {
             trace!("data: 0x{:02x?}", data);
 
-            // TODO: add overflow check
-            let size = (data.len() * node_type.size) as u32;
+            let size = check_data_length(data.len() * node_type.size, || format!("/{}", name))?;
             data_buf
                 .write_u32::<BigEndian>(size)
                 .context(NodeSizeSnafu { node_type, size })?;
@@ -139,28 +260,48 @@ fn write_value(
                 .context(DataBufferSnafu { node_type })?;
         },
         Value::String(text) => {
+            let text = check_string(options, text, || format!("/{}", name))?;
+            data_buf
+                .write_str(options.encoding, options.unmappable_characters, &text)
+                .context(DataBufferSnafu { node_type })?;
+        },
+        Value::Unknown { id, bytes } => {
+            // Arrays of every type are stored with their length
+            if id & ARRAY_MASK == ARRAY_MASK {
+                let size = check_data_length(bytes.len(), || format!("/{}", name))?;
+                data_buf
+                    .write_u32::<BigEndian>(size)
+                    .context(NodeSizeSnafu { node_type, size })?;
+            }
+            data_buf
+                .write_all(bytes)
+                .context(DataWriteSnafu { node_type })?;
             data_buf
-                .write_str(options.encoding, text)
+                .realign_writes(None)
                 .context(DataBufferSnafu { node_type })?;
         },
         Value::Array(values) => {
             if !is_array {
                 panic!("Attempted to write value array but was not marked as array");
             }
+            if values.is_empty() && options.empty_arrays == EmptyArrayPolicy::Error {
+                return Err(WriterError::EmptyArray { node_type });
+            }
 
             let total_size = values.len() * node_type.count * node_type.size;
+            let size = check_data_length(total_size, || format!("/{}", name))?;
 
             let mut data = Vec::with_capacity(total_size);
             values
                 .to_bytes_into(&mut data)
                 .context(ValueEncodeSnafu { node_type })?;
+            if options.endianness.swaps(node_type) {
+                Endianness::swap(node_type, &mut data);
+            }
 
             data_buf
-                .write_u32::<BigEndian>(total_size as u32)
-                .context(NodeSizeSnafu {
-                    node_type,
-                    size: total_size as u32,
-                })?;
+                .write_u32::<BigEndian>(size)
+                .context(NodeSizeSnafu { node_type, size })?;
             data_buf
                 .write_all(&data)
                 .context(DataWriteSnafu { node_type })?;
@@ -173,7 +314,10 @@ fn write_value(
                 panic!("Attempted to write non-array value but was marked as array");
             }
 
-            let data = value.to_bytes().context(ValueEncodeSnafu { node_type })?;
+            let mut data = value.to_bytes().context(ValueEncodeSnafu { node_type })?;
+            if options.endianness.swaps(node_type) {
+                Endianness::swap(node_type, &mut data);
+            }
             data_buf
                 .write_aligned(node_type, &data)
                 .context(DataBufferSnafu { node_type })?;
@@ -183,29 +327,236 @@ fn write_value(
     Ok(())
 }
 
+/// Write the name of the node or attribute at `path`, failing if it is
+/// longer than its length prefix can hold.
+pub(crate) fn write_node_name<F>(
+    compression: CompressionType,
+    encoding: EncodingType,
+    node_buf: &mut ByteBufferWrite,
+    name: &str,
+    path: F,
+) -> Result<(), WriterError>
+where
+    F: FnOnce() -> String,
+{
+    match compression {
+        CompressionType::Compressed => {
+            if name.len() > MAX_SIXBIT_NAME_LENGTH {
+                return Err(WriterError::NameTooLong {
+                    path: path(),
+                    length: name.len(),
+                    max: MAX_SIXBIT_NAME_LENGTH,
+                });
+            }
+            Sixbit::pack(&mut **node_buf, name).context(NodeSixbitNameSnafu)?
+        },
+        CompressionType::Uncompressed => {
+            let mut data = encoding
+                .encode_bytes(name)
+                .context(NodeUncompressedNameEncodeSnafu { encoding })?;
+
+            // Names are not null terminated, the length byte holds the
+            // length minus one
+            data.pop();
+            if data.len() > MAX_UNCOMPRESSED_NAME_LENGTH {
+                return Err(WriterError::NameTooLong {
+                    path: path(),
+                    length: data.len(),
+                    max: MAX_UNCOMPRESSED_NAME_LENGTH,
+                });
+            }
+            let len = data.len().checked_sub(1).ok_or(WriterError::NoNodeKey)? as u8;
+            node_buf
+                .write_u8(len | ARRAY_MASK)
+                .context(NodeUncompressedNameLengthSnafu)?;
+            node_buf
+                .write_all(&data)
+                .context(NodeUncompressedNameDataSnafu)?;
+        },
+    };
+
+    Ok(())
+}
+
+/// Receives events while a `Writer` encodes a document.
+///
+/// Lengths are the number of bytes the node or data buffer grew by, so
+/// summing them gives the size of each buffer before the final padding of
+/// the node buffer. Data lengths include length prefixes and alignment
+/// padding, and may be zero for small values packed into earlier padding.
+pub trait WriteObserver {
+    /// Called after a node type and name are written to the node buffer,
+    /// including attributes and node end markers.
+    fn node_written(&mut self, name: &str, node_type: StandardType, is_array: bool, len: usize) {
+        let _ = (name, node_type, is_array, len);
+    }
+
+    /// Called after a node or attribute value is written to the data buffer.
+    fn value_written(&mut self, name: &str, node_type: StandardType, is_array: bool, len: usize) {
+        let _ = (name, node_type, is_array, len);
+    }
+}
+
+impl WriteObserver for () {}
+
 pub trait Writeable {
     fn write_node(
         &self,
         options: &Options,
+        observer: &mut dyn WriteObserver,
         node_buf: &mut ByteBufferWrite,
         data_buf: &mut ByteBufferWrite,
     ) -> Result<(), WriterError>;
+
+    /// A copy of this input as a `Node` tree with the encode filters and
+    /// derived nodes of `options` applied, or `None` to write it as is.
+    fn filtered(&self, _options: &Options) -> Result<Option<Node>, WriterError> {
+        Ok(None)
+    }
 }
 
+/// Apply the encode filters of `options` to the attributes and values of
+/// `node` and its children, then compute their derived nodes.
+fn filter_node(options: &Options, node: &mut Node, path: &str) -> Result<(), WriterError> {
+    let filters = &options.encode_filters;
+
+    for (key, value) in node.attributes_mut() {
+        let path = format!("{}/@{}", path, key);
+        *value = apply_attribute_filters(filters, &path, mem::take(value))
+            .map_err(|source| WriterError::Filter { path, source })?;
+    }
+    if let Some(value) = node.value_mut() {
+        let taken = mem::replace(value, Value::Boolean(false));
+        *value = apply_filters(filters, path, taken).map_err(|source| WriterError::Filter {
+            path: path.to_owned(),
+            source,
+        })?;
+    }
+
+    let paths = child_paths(path, node.children().iter().map(Node::key));
+    for (child, path) in node.children_mut().iter_mut().zip(paths) {
+        filter_node(options, child, &path)?;
+    }
+
+    for derived in &options.derived_nodes {
+        if !derived.is_parent(path) {
+            continue;
+        }
+
+        let value = derived
+            .compute(node)
+            .map_err(|source| WriterError::Filter {
+                path: format!("{}/{}", path, derived.name()),
+                source,
+            })?;
+        match node
+            .children_mut()
+            .iter_mut()
+            .find(|child| child.key() == derived.name())
+        {
+            Some(child) => {
+                child.set_value(Some(value));
+            },
+            None => node.append_child(Node::with_value(derived.name(), value)),
+        };
+    }
+
+    Ok(())
+}
+
+impl WriterError {
+    /// Prefix the path of an error raised while writing a child of the node
+    /// named `name`.
+    fn in_parent(self, name: &str) -> Self {
+        match self {
+            WriterError::ControlCharacter { path } => WriterError::ControlCharacter {
+                path: format!("/{}{}", name, path),
+            },
+            WriterError::NameTooLong { path, length, max } => WriterError::NameTooLong {
+                path: format!("/{}{}", name, path),
+                length,
+                max,
+            },
+            WriterError::DataTooLong { path, length } => WriterError::DataTooLong {
+                path: format!("/{}{}", name, path),
+                length,
+            },
+            err => err,
+        }
+    }
+}
+
+fn write_node_end(
+    observer: &mut dyn WriteObserver,
+    node_buf: &mut ByteBufferWrite,
+    node_type: StandardType,
+) -> Result<(), WriterError> {
+    // node end always has the array bit set
+    node_buf
+        .write_u8(node_type as u8 | ARRAY_MASK)
+        .context(NodeTypeSnafu { node_type })?;
+    observer.node_written("", node_type, true, 1);
+
+    Ok(())
+}
+
+/// Convert `collection` to a `Node` tree for the `Node` writer, with only
+/// the renames of `options` applied so the writer reverses them.
+fn renamed_node(collection: &NodeCollection, options: &Options) -> Result<Node, WriterError> {
+    let (node_type, _) = collection.base().node_type_tuple();
+    let renames = Options {
+        renames: options.renames.clone(),
+        ..Default::default()
+    };
+
+    collection
+        .as_node_with_options(&renames)
+        .context(DefinitionValueSnafu { node_type })
+}
+
+/// A `NodeCollection` holds the names of the document, so they are written
+/// without the renames of `Options`, see `OptionsBuilder::rename`.
 impl Writeable for NodeCollection {
+    fn filtered(&self, options: &Options) -> Result<Option<Node>, WriterError> {
+        if options.encode_filters.is_empty() && options.derived_nodes.is_empty() {
+            return Ok(None);
+        }
+
+        renamed_node(self, options)?.filtered(options)
+    }
+
     fn write_node(
         &self,
         options: &Options,
+        observer: &mut dyn WriteObserver,
         node_buf: &mut ByteBufferWrite,
         data_buf: &mut ByteBufferWrite,
     ) -> Result<(), WriterError> {
         let (node_type, is_array) = self.base().node_type_tuple();
+        let is_nested = self
+            .attributes()
+            .iter()
+            .any(|attr| matches!(attr.key(), Ok(Some(key)) if key == NESTED_ATTRIBUTE));
+        if is_nested {
+            let node = renamed_node(self, options)?;
+            return node.write_node(options, observer, node_buf, data_buf);
+        }
+
         let array_mask = if is_array { ARRAY_MASK } else { 0 };
+        let type_byte = self
+            .base()
+            .unknown_type()
+            .unwrap_or(node_type as u8 | array_mask);
         let name = self
             .base()
             .key()
             .context(DefinitionValueSnafu { node_type })?
             .ok_or(WriterError::NoNodeKey)?;
+        let name = name.as_str();
+
+        if !self.markup().is_empty() {
+            warn!("Dropping text XML markup in {} from binary output", name);
+        }
 
         debug!("NodeCollection write_node => name: {}, type: {:?}, type_size: {}, type_count: {}, is_array: {}",
             name,
@@ -214,36 +565,38 @@ impl Writeable for NodeCollection {
             node_type.count,
             is_array);
 
+        let node_start = node_buf.get_ref().len();
         node_buf
-            .write_u8(node_type as u8 | array_mask)
+            .write_u8(type_byte)
             .context(DataWriteSnafu { node_type })?;
 
-        match options.compression {
-            CompressionType::Compressed => {
-                Sixbit::pack(&mut **node_buf, &name).context(NodeSixbitNameSnafu)?
-            },
-            CompressionType::Uncompressed => {
-                let data = options.encoding.encode_bytes(&name).context(
-                    NodeUncompressedNameEncodeSnafu {
-                        encoding: options.encoding,
-                    },
-                )?;
-                let len = (data.len() - 1) as u8;
-                node_buf
-                    .write_u8(len | ARRAY_MASK)
-                    .context(NodeUncompressedNameLengthSnafu)?;
-                node_buf
-                    .write_all(&data)
-                    .context(NodeUncompressedNameDataSnafu)?;
-            },
-        };
+        write_node_name(
+            options.compression,
+            options.encoding,
+            node_buf,
+            name,
+            || format!("/{}", name),
+        )?;
+        observer.node_written(
+            name,
+            node_type,
+            is_array,
+            node_buf.get_ref().len() - node_start,
+        );
 
         if node_type != StandardType::NodeStart {
             let value = self
                 .base()
                 .value()
                 .context(DefinitionValueSnafu { node_type })?;
-            write_value(options, data_buf, node_type, is_array, &value)?;
+            let data_start = data_buf.get_ref().len();
+            write_value(options, data_buf, name, node_type, is_array, &value)?;
+            observer.value_written(
+                name,
+                node_type,
+                is_array,
+                data_buf.get_ref().len() - data_start,
+            );
         }
 
         for attr in self.attributes() {
@@ -252,7 +605,32 @@ impl Writeable for NodeCollection {
                 .key()
                 .context(DefinitionKeySnafu { node_type })?
                 .ok_or(WriterError::NoNodeKey)?;
-            let value = attr.value_bytes().ok_or(WriterError::NoNodeValue)?;
+            let key = key.as_str();
+            if attr.value_bytes().is_none() {
+                return Err(WriterError::NoNodeValue);
+            }
+            let value = attribute_bytes(options, attr).context(AttributeTranscodeSnafu {
+                key,
+                from: attr.encoding(),
+                to: options.encoding,
+            })?;
+            let value = match options
+                .control_characters
+                .apply_bytes(strip_trailing_null_bytes(&value))
+            {
+                Some(Cow::Borrowed(_)) => value,
+                Some(Cow::Owned(mut data)) => {
+                    data.push(0);
+                    Cow::Owned(data)
+                },
+                None => {
+                    return Err(WriterError::ControlCharacter {
+                        path: format!("/{}/@{}", name, key),
+                    })
+                },
+            };
+
+            check_data_length(value.len(), || format!("/{}/@{}", name, key))?;
 
             trace!(
                 "NodeCollection write_node => attr: {}, value: 0x{:02x?}",
@@ -260,57 +638,56 @@ impl Writeable for NodeCollection {
                 value
             );
 
+            let data_start = data_buf.get_ref().len();
             data_buf
-                .buf_write(value)
+                .buf_write(&value)
                 .context(DataBufferSnafu { node_type })?;
+            observer.value_written(key, node_type, false, data_buf.get_ref().len() - data_start);
 
+            let node_start = node_buf.get_ref().len();
             node_buf
                 .write_u8(StandardType::Attribute as u8)
                 .context(DataWriteSnafu { node_type })?;
 
-            match options.compression {
-                CompressionType::Compressed => {
-                    Sixbit::pack(&mut **node_buf, &key).context(NodeSixbitNameSnafu)?
-                },
-                CompressionType::Uncompressed => {
-                    let data = options.encoding.encode_bytes(&key).context(
-                        NodeUncompressedNameEncodeSnafu {
-                            encoding: options.encoding,
-                        },
-                    )?;
-                    let len = (data.len() - 1) as u8;
-                    node_buf
-                        .write_u8(len | ARRAY_MASK)
-                        .context(NodeUncompressedNameLengthSnafu)?;
-                    node_buf
-                        .write_all(&data)
-                        .context(NodeUncompressedNameDataSnafu)?;
-                },
-            };
+            write_node_name(options.compression, options.encoding, node_buf, key, || {
+                format!("/{}/@{}", name, key)
+            })?;
+            observer.node_written(key, node_type, false, node_buf.get_ref().len() - node_start);
         }
 
         for child in self.children() {
-            child.write_node(options, node_buf, data_buf)?;
+            child
+                .write_node(options, observer, node_buf, data_buf)
+                .map_err(|err| err.in_parent(name))?;
         }
 
-        // node end always has the array bit set
-        node_buf
-            .write_u8(StandardType::NodeEnd as u8 | ARRAY_MASK)
-            .context(NodeTypeSnafu {
-                node_type: StandardType::NodeEnd,
-            })?;
-
-        Ok(())
+        write_node_end(observer, node_buf, StandardType::NodeEnd)
     }
 }
 
 impl Writeable for Node {
+    fn filtered(&self, options: &Options) -> Result<Option<Node>, WriterError> {
+        if options.encode_filters.is_empty() && options.derived_nodes.is_empty() {
+            return Ok(None);
+        }
+
+        let mut node = self.clone();
+        filter_node(options, &mut node, &format!("/{}", self.key()))?;
+
+        Ok(Some(node))
+    }
+
     fn write_node(
         &self,
         options: &Options,
+        observer: &mut dyn WriteObserver,
         node_buf: &mut ByteBufferWrite,
         data_buf: &mut ByteBufferWrite,
     ) -> Result<(), WriterError> {
+        if let Some(embedded) = embed_nested(self, options)? {
+            return embedded.write_node(options, observer, node_buf, data_buf);
+        }
+
         let (node_type, is_array) = match self.value() {
             Some(Value::Array(values)) => (values.standard_type(), true),
             Some(value) => (value.standard_type(), false),
@@ -318,88 +695,105 @@ impl Writeable for Node {
         };
         let array_mask = if is_array { ARRAY_MASK } else { 0 };
 
+        // Unknown values keep the type byte they were read with
+        let type_byte = match self.value() {
+            Some(Value::Unknown { id, .. }) => *id,
+            _ => node_type as u8 | array_mask,
+        };
+
+        if !self.markup().is_empty() {
+            warn!(
+                "Dropping text XML markup in {} from binary output",
+                self.key()
+            );
+        }
+
+        // Errors report the name written to the document
+        let name = options.encode_name(self.key());
+
         debug!(
             "Node::write_node => name: {}, type: {:?}, type_size: {}, type_count: {}, is_array: {}",
-            self.key(),
+            name,
             node_type,
             node_type.size,
             node_type.count,
             is_array
         );
 
+        let node_start = node_buf.get_ref().len();
         node_buf
-            .write_u8(node_type as u8 | array_mask)
+            .write_u8(type_byte)
             .context(DataWriteSnafu { node_type })?;
-        match options.compression {
-            CompressionType::Compressed => {
-                Sixbit::pack(&mut **node_buf, self.key()).context(NodeSixbitNameSnafu)?
-            },
-            CompressionType::Uncompressed => {
-                let data = options.encoding.encode_bytes(self.key()).context(
-                    NodeUncompressedNameEncodeSnafu {
-                        encoding: options.encoding,
-                    },
-                )?;
-                let len = (data.len() - 1) as u8;
-                node_buf
-                    .write_u8(len | ARRAY_MASK)
-                    .context(NodeUncompressedNameLengthSnafu)?;
-                node_buf
-                    .write_all(&data)
-                    .context(NodeUncompressedNameDataSnafu)?;
-            },
-        };
+        write_node_name(
+            options.compression,
+            options.encoding,
+            node_buf,
+            &name,
+            || format!("/{}", name),
+        )?;
+        observer.node_written(
+            &name,
+            node_type,
+            is_array,
+            node_buf.get_ref().len() - node_start,
+        );
 
         if let Some(value) = self.value() {
-            write_value(options, data_buf, node_type, is_array, value)?;
+            let data_start = data_buf.get_ref().len();
+            write_value(options, data_buf, &name, node_type, is_array, value)?;
+            observer.value_written(
+                &name,
+                node_type,
+                is_array,
+                data_buf.get_ref().len() - data_start,
+            );
         }
 
         for (key, value) in self.attributes() {
+            let key = options.encode_name(key);
+            let value = check_string(options, value, || format!("/{}/@{}", name, key))?;
             trace!("Node write_node => attr: {}, value: {}", key, value);
 
+            let data_start = data_buf.get_ref().len();
             data_buf
-                .write_str(options.encoding, value)
+                .write_str(options.encoding, options.unmappable_characters, &value)
                 .context(DataBufferSnafu { node_type })?;
+            observer.value_written(
+                &key,
+                StandardType::Attribute,
+                false,
+                data_buf.get_ref().len() - data_start,
+            );
 
+            let node_start = node_buf.get_ref().len();
             node_buf
                 .write_u8(StandardType::Attribute as u8)
                 .context(DataWriteSnafu {
                     node_type: StandardType::Attribute,
                 })?;
 
-            match options.compression {
-                CompressionType::Compressed => {
-                    Sixbit::pack(&mut **node_buf, key).context(NodeSixbitNameSnafu)?
-                },
-                CompressionType::Uncompressed => {
-                    let data = options.encoding.encode_bytes(key).context(
-                        NodeUncompressedNameEncodeSnafu {
-                            encoding: options.encoding,
-                        },
-                    )?;
-                    let len = (data.len() - 1) as u8;
-                    node_buf
-                        .write_u8(len | ARRAY_MASK)
-                        .context(NodeUncompressedNameLengthSnafu)?;
-                    node_buf
-                        .write_all(&data)
-                        .context(NodeUncompressedNameDataSnafu)?;
-                },
-            };
+            write_node_name(
+                options.compression,
+                options.encoding,
+                node_buf,
+                &key,
+                || format!("/{}/@{}", name, key),
+            )?;
+            observer.node_written(
+                &key,
+                StandardType::Attribute,
+                false,
+                node_buf.get_ref().len() - node_start,
+            );
         }
 
         for child in self.children() {
-            child.write_node(options, node_buf, data_buf)?;
+            child
+                .write_node(options, observer, node_buf, data_buf)
+                .map_err(|err| err.in_parent(&name))?;
         }
 
-        // node end always has the array bit set
-        node_buf
-            .write_u8(StandardType::NodeEnd as u8 | ARRAY_MASK)
-            .context(NodeTypeSnafu {
-                node_type: StandardType::NodeEnd,
-            })?;
-
-        Ok(())
+        write_node_end(observer, node_buf, StandardType::NodeEnd)
     }
 }
 
@@ -418,65 +812,568 @@ impl Writer {
         Self { options }
     }
 
+    #[inline]
     pub fn to_binary<T>(&mut self, input: &T) -> Result<Vec<u8>, WriterError>
     where
-        T: Writeable,
+        T: Writeable + ?Sized,
     {
-        let mut header = Cursor::new(Vec::with_capacity(8));
-        header.write_u8(SIGNATURE).context(SignatureSnafu)?;
+        self.to_binary_with_observer(input, &mut ())
+    }
 
-        let compression = self.options.compression.to_byte();
-        header.write_u8(compression).context(CompressionSnafu)?;
+    /// Encode `input`, reporting every node and value written to `observer`.
+    #[inline]
+    pub fn to_binary_with_observer<T, O>(
+        &mut self,
+        input: &T,
+        observer: &mut O,
+    ) -> Result<Vec<u8>, WriterError>
+    where
+        T: Writeable + ?Sized,
+        O: WriteObserver,
+    {
+        write_document(&self.options, input, observer, &mut Scratch::default())
+    }
 
-        let encoding = self.options.encoding.to_byte();
-        header.write_u8(encoding).context(EncodingSnafu)?;
-        header
-            .write_u8(0xFF ^ encoding)
-            .context(EncodingNegateSnafu)?;
+    /// Encode `input` directly to `output` instead of returning a buffer.
+    ///
+    /// The node and data buffers are still built in memory, since the length
+    /// of each is written before it, but they are not copied into a single
+    /// buffer holding the whole document.
+    pub fn to_writer<T, W>(&mut self, input: &T, mut output: W) -> Result<(), WriterError>
+    where
+        T: Writeable + ?Sized,
+        W: Write,
+    {
+        let mut scratch = Scratch::default();
+        write_buffers_to_scratch(&self.options, input, &mut (), &mut scratch)?;
 
-        let mut node_buf = ByteBufferWrite::new(Vec::new());
-        let mut data_buf = ByteBufferWrite::new(Vec::new());
+        write_output(&self.options, &scratch, &mut output)
+    }
+}
 
-        input.write_node(&self.options, &mut node_buf, &mut data_buf)?;
+impl Default for Writer {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        node_buf
-            .write_u8(StandardType::FileEnd as u8 | ARRAY_MASK)
-            .context(NodeTypeSnafu {
-                node_type: StandardType::FileEnd,
-            })?;
-        node_buf.realign_writes(None).context(NodeBufferSnafu {
-            node_type: StandardType::FileEnd,
-        })?;
+/// Node and data buffers that can be reused between documents.
+#[derive(Default)]
+struct Scratch {
+    node_buf: Vec<u8>,
+    data_buf: Vec<u8>,
+}
 
-        let mut output = header.into_inner();
+/// A thread safe encoder that keeps the scratch buffers of finished encodes
+/// around for later ones.
+///
+/// Each concurrent call to `encode` takes its own set of buffers, so the pool
+/// holds at most as many sets as there were simultaneous encodes.
+#[derive(Default)]
+pub struct WriterPool {
+    options: Options,
+    scratch: Mutex<Vec<Scratch>>,
+}
 
-        let node_buf = node_buf.into_inner();
-        debug!(
-            "to_binary_internal => node_buf len: {0} (0x{0:x})",
-            node_buf.len()
-        );
-        output
-            .write_u32::<BigEndian>(node_buf.len() as u32)
-            .context(NodeBufferLengthSnafu)?;
-        output.extend_from_slice(&node_buf);
+impl WriterPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-        let data_buf = data_buf.into_inner();
-        debug!(
-            "to_binary_internal => data_buf len: {0} (0x{0:x})",
-            data_buf.len()
-        );
-        output
-            .write_u32::<BigEndian>(data_buf.len() as u32)
-            .context(DataBufferLengthSnafu)?;
-        output.extend_from_slice(&data_buf);
+    pub fn with_options(options: Options) -> Self {
+        Self {
+            options,
+            scratch: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn encode<T>(&self, input: &T) -> Result<Vec<u8>, WriterError>
+    where
+        T: Writeable + ?Sized,
+    {
+        // A poisoned lock only means another encode panicked, the buffers are
+        // cleared before use anyway.
+        let lock = || self.scratch.lock().unwrap_or_else(PoisonError::into_inner);
 
-        Ok(output)
+        let mut scratch = lock().pop().unwrap_or_default();
+        let result = write_document(&self.options, input, &mut (), &mut scratch);
+        lock().push(scratch);
+
+        result
     }
 }
 
-impl Default for Writer {
-    #[inline]
-    fn default() -> Self {
-        Self::new()
+fn write_document<T, O>(
+    options: &Options,
+    input: &T,
+    observer: &mut O,
+    scratch: &mut Scratch,
+) -> Result<Vec<u8>, WriterError>
+where
+    T: Writeable + ?Sized,
+    O: WriteObserver,
+{
+    write_buffers_to_scratch(options, input, observer, scratch)?;
+
+    let mut output = Vec::with_capacity(scratch.node_buf.len() + scratch.data_buf.len() + 16);
+    write_output(options, scratch, &mut output)?;
+
+    Ok(output)
+}
+
+/// Validate the options and encode `input` into the scratch buffers.
+fn write_buffers_to_scratch<T, O>(
+    options: &Options,
+    input: &T,
+    observer: &mut O,
+    scratch: &mut Scratch,
+) -> Result<(), WriterError>
+where
+    T: Writeable + ?Sized,
+    O: WriteObserver,
+{
+    let conflicts = options.conflicts();
+    if !conflicts.is_empty() {
+        return Err(WriterError::InvalidOptions { conflicts });
+    }
+    let filtered = input.filtered(options)?;
+
+    scratch.node_buf.clear();
+    scratch.data_buf.clear();
+    let mut node_buf = ByteBufferWrite::new(mem::take(&mut scratch.node_buf));
+    let mut data_buf = ByteBufferWrite::new(mem::take(&mut scratch.data_buf));
+
+    let result = match filtered {
+        Some(node) => write_buffers(options, &node, observer, &mut node_buf, &mut data_buf),
+        None => write_buffers(options, input, observer, &mut node_buf, &mut data_buf),
+    };
+    scratch.node_buf = node_buf.into_inner();
+    scratch.data_buf = data_buf.into_inner();
+
+    result
+}
+
+/// Write the header followed by the encoded buffers in `scratch`.
+fn write_output<W>(options: &Options, scratch: &Scratch, output: &mut W) -> Result<(), WriterError>
+where
+    W: Write,
+{
+    let node_buf = &scratch.node_buf;
+    let data_buf = &scratch.data_buf;
+
+    debug!(
+        "to_binary_internal => node_buf len: {0} (0x{0:x})",
+        node_buf.len()
+    );
+    let header = Header {
+        compression: options.compression,
+        encoding: options.encoding,
+        node_buffer_len: node_buf.len() as u32,
+    };
+    output.write_all(&header.to_bytes()).context(HeaderSnafu)?;
+    output
+        .write_all(node_buf)
+        .context(NodeBufferContentsSnafu)?;
+
+    debug!(
+        "to_binary_internal => data_buf len: {0} (0x{0:x})",
+        data_buf.len()
+    );
+    output
+        .write_u32::<BigEndian>(data_buf.len() as u32)
+        .context(DataBufferLengthSnafu)?;
+    output
+        .write_all(data_buf)
+        .context(DataBufferContentsSnafu)?;
+
+    Ok(())
+}
+
+fn write_buffers<T, O>(
+    options: &Options,
+    input: &T,
+    observer: &mut O,
+    node_buf: &mut ByteBufferWrite,
+    data_buf: &mut ByteBufferWrite,
+) -> Result<(), WriterError>
+where
+    T: Writeable + ?Sized,
+    O: WriteObserver,
+{
+    input.write_node(options, observer, node_buf, data_buf)?;
+
+    write_node_end(observer, node_buf, StandardType::FileEnd)?;
+    node_buf.realign_writes(None).context(NodeBufferSnafu {
+        node_type: StandardType::FileEnd,
+    })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Write};
+    use std::thread;
+
+    use bytes::Bytes;
+
+    use crate::byte_buffer::ByteBufferWrite;
+    use crate::compression_type::CompressionType;
+    use crate::encoding_type::EncodingType;
+    use crate::error::KbinError;
+    use crate::node::{Key, Node, NodeCollection, NodeData, NodeDefinition};
+    use crate::node_types::StandardType;
+    use crate::options::{ControlCharacterPolicy, Options};
+    use crate::value::{Value, ValueArray};
+
+    use super::{write_node_name, WriteObserver, Writer, WriterError, WriterPool};
+
+    /// Accepts a limited number of bytes before failing.
+    struct Limited(usize);
+
+    impl Write for Limited {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if buf.len() > self.0 {
+                return Err(io::Error::new(io::ErrorKind::WriteZero, "output full"));
+            }
+            self.0 -= buf.len();
+
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_to_writer() {
+        let node = Node::with_nodes("root", vec![
+            Node::with_value("id", Value::U32(1)),
+            Node::with_value("name", Value::String("name".into())),
+        ]);
+        let expected = Writer::new().to_binary(&node).expect("Failed to encode");
+
+        let mut output = Vec::new();
+        Writer::new()
+            .to_writer(&node, &mut output)
+            .expect("Failed to encode");
+        assert_eq!(output, expected);
+
+        match Writer::new().to_writer(&node, Limited(expected.len() - 1)) {
+            Err(WriterError::DataBufferContents { .. }) => {},
+            result => panic!("Unexpected result: {:?}", result),
+        };
+    }
+
+    /// Records the events of a `WriteObserver`, `true` marking values.
+    #[derive(Default)]
+    struct Events(Vec<(bool, String, StandardType, bool, usize)>);
+
+    impl WriteObserver for Events {
+        fn node_written(&mut self, name: &str, ty: StandardType, array: bool, len: usize) {
+            self.0.push((false, name.to_owned(), ty, array, len));
+        }
+
+        fn value_written(&mut self, name: &str, ty: StandardType, array: bool, len: usize) {
+            self.0.push((true, name.to_owned(), ty, array, len));
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        let mut node = Node::with_attrs("root", &[("id", "a")]);
+        node.append_child(Node::with_value("count", Value::U32(1)));
+        node.append_child(Node::with_value(
+            "list",
+            Value::Array(ValueArray::U16(vec![1, 2, 3])),
+        ));
+
+        let mut events = Events::default();
+        let output = Writer::new()
+            .to_binary_with_observer(&node, &mut events)
+            .expect("Failed to encode");
+
+        let event = |is_value, name: &str, node_type, is_array, len| {
+            (is_value, name.to_owned(), node_type, is_array, len)
+        };
+        assert_eq!(events.0, [
+            event(false, "root", StandardType::NodeStart, false, 5),
+            event(true, "id", StandardType::Attribute, false, 8),
+            event(false, "id", StandardType::Attribute, false, 4),
+            event(false, "count", StandardType::U32, false, 6),
+            event(true, "count", StandardType::U32, false, 4),
+            event(false, "", StandardType::NodeEnd, true, 1),
+            event(false, "list", StandardType::U16, true, 5),
+            event(true, "list", StandardType::U16, true, 12),
+            event(false, "", StandardType::NodeEnd, true, 1),
+            event(false, "", StandardType::NodeEnd, true, 1),
+            event(false, "", StandardType::FileEnd, true, 1),
+        ]);
+
+        // The lengths add up to the buffer lengths in the output
+        let sum = |is_value| {
+            let lens = events.0.iter().filter(|event| event.0 == is_value);
+            lens.map(|event| event.4).sum::<usize>()
+        };
+        assert_eq!(output.len(), 8 + sum(false) + 4 + sum(true));
+    }
+
+    #[test]
+    fn test_writer_pool() {
+        let documents: Vec<Node> = (1..=4)
+            .map(|len| {
+                let values = (0..len * 16).map(|i| Node::with_value("entry", Value::U32(i)));
+                Node::with_nodes("root", values.collect::<Vec<_>>())
+            })
+            .collect();
+        let expected: Vec<Vec<u8>> = documents
+            .iter()
+            .map(|node| crate::to_binary(node).expect("Failed to encode"))
+            .collect();
+        let pool = WriterPool::new();
+
+        // A smaller document reuses the buffers of a larger one
+        let encode = |node| pool.encode(node).expect("Failed to encode");
+        assert_eq!(encode(&documents[3]), expected[3]);
+        let buffers = |pool: &WriterPool| -> Vec<_> {
+            let scratch = pool.scratch.lock().expect("Failed to lock");
+            scratch
+                .iter()
+                .map(|s| (s.node_buf.as_ptr(), s.data_buf.as_ptr()))
+                .collect()
+        };
+        let before = buffers(&pool);
+        assert_eq!(before.len(), 1);
+        assert_eq!(encode(&documents[0]), expected[0]);
+        assert_eq!(buffers(&pool), before);
+
+        thread::scope(|scope| {
+            for (node, expected) in documents.iter().zip(&expected) {
+                scope.spawn(move || {
+                    for _ in 0..16 {
+                        assert_eq!(&encode(node), expected);
+                    }
+                });
+            }
+        });
+        let sets = buffers(&pool).len();
+        assert!((1..=documents.len()).contains(&sets));
+    }
+
+    #[test]
+    fn test_node_collection_to_binary() {
+        let mut node = Node::with_attrs("root", &[("kind", "test")]);
+        node.append_child(Node::with_value("id", Value::U8(1)));
+        node.append_child(Node::with_value("pos", Value::S32_2([-1, 2])));
+        node.append_child(Node::with_value("name", Value::String("名前".into())));
+
+        let options = [
+            Options::default(),
+            Options::new(CompressionType::Uncompressed, EncodingType::SHIFT_JIS),
+        ];
+        for options in &options {
+            let expected =
+                crate::to_binary_with_options(options.clone(), &node).expect("Failed to encode");
+            let (collection, _) = crate::from_slice(&expected).expect("Failed to decode");
+            let output = crate::node_collection_to_binary(&collection).expect("Failed to encode");
+            assert_eq!(output, expected);
+        }
+    }
+
+    #[test]
+    fn test_control_characters() {
+        let mut node = Node::new("root");
+        node.append_child(Node::with_attrs("entry", &[("id", "a\0b")]));
+        node.append_child(Node::with_value("name", Value::String("c\x1bd\n".into())));
+
+        match Writer::new().to_binary(&node) {
+            Err(WriterError::ControlCharacter { path }) => assert_eq!(path, "/root/entry/@id"),
+            result => panic!("Unexpected result: {:?}", result),
+        };
+
+        let decode = |policy| {
+            let options = Options::builder().control_characters(policy).build();
+            let data = Writer::with_options(options.clone())
+                .to_binary(&node)
+                .expect("Failed to encode");
+            let (collection, _) = crate::from_slice(&data).expect("Failed to decode");
+            collection
+                .as_node_with_options(&options)
+                .expect("Failed to convert")
+        };
+        let stripped = decode(ControlCharacterPolicy::Strip);
+        assert_eq!(stripped.children()[0].attributes()["id"], "ab");
+        assert_eq!(
+            stripped.children()[1].value(),
+            Some(&Value::String("cd\n".into()))
+        );
+        let escaped = decode(ControlCharacterPolicy::Escape);
+        assert_eq!(escaped.children()[0].attributes()["id"], "a\\u{0}b");
+
+        let collection = NodeCollection::new(NodeDefinition::with_data(
+            EncodingType::UTF_8,
+            StandardType::String,
+            false,
+            NodeData::Some {
+                key: Key::Uncompressed {
+                    encoding: EncodingType::UTF_8,
+                    data: Bytes::from_static(b"name"),
+                },
+                value_data: Bytes::from_static(b"a\0b\0"),
+            },
+        ));
+        match collection.as_node() {
+            Err(KbinError::ControlCharacter { path }) => assert_eq!(path, "/name"),
+            result => panic!("Unexpected result: {:?}", result),
+        };
+    }
+
+    #[test]
+    fn test_renames() {
+        let options = Options::builder()
+            .compression(CompressionType::Uncompressed)
+            .rename("illegal-name", "legal_name")
+            .build();
+        let mut node = Node::new("root");
+        node.append_child(Node::with_attrs_value(
+            "legal_name",
+            &[("legal_name", "1")],
+            Value::U8(2),
+        ));
+
+        let data = Writer::with_options(options.clone())
+            .to_binary(&node)
+            .expect("Failed to encode");
+        let (collection, _) = crate::from_slice(&data).expect("Failed to decode");
+        let child = &collection.children()[0];
+        let key = child.base().key().expect("Failed to decode name");
+        assert_eq!(key.as_deref(), Some("illegal-name"));
+        let attr = child.attributes()[0].key().expect("Failed to decode name");
+        assert_eq!(attr.as_deref(), Some("illegal-name"));
+        let read = collection
+            .as_node_with_options(&options)
+            .expect("Failed to convert");
+        assert_eq!(read, node);
+
+        // Text XML uses the renamed names, a collection the document names
+        let text = crate::to_text_xml_with_options(options.clone(), &collection)
+            .expect("Failed to write text XML");
+        let expected = crate::to_text_xml_with_options(options.clone(), &node)
+            .expect("Failed to write text XML");
+        assert_eq!(text, expected);
+        let (collection, _) = crate::from_text_xml_with_options(options.clone(), &text)
+            .expect("Failed to read text XML");
+        let child = &collection.children()[0];
+        let key = child.base().key().expect("Failed to decode name");
+        assert_eq!(key.as_deref(), Some("illegal-name"));
+        let output = crate::to_binary_with_options(options.clone(), &collection);
+        assert_eq!(output.expect("Failed to encode"), data);
+
+        // Errors report the names written to the document.
+        node.children_mut()[0].set_attr("legal_name", "a\0b");
+        match Writer::with_options(options).to_binary(&node) {
+            Err(WriterError::ControlCharacter { path }) => {
+                assert_eq!(path, "/root/illegal-name/@illegal-name");
+            },
+            result => panic!("Unexpected result: {:?}", result),
+        };
+    }
+
+    #[test]
+    fn test_name_lengths() {
+        let long = "a".repeat(65);
+        let mut node = Node::new("root");
+        node.append_child(Node::with_attrs("entry", &[(long.as_str(), "1")]));
+
+        let uncompressed = Options::builder()
+            .compression(CompressionType::Uncompressed)
+            .build();
+        match Writer::with_options(uncompressed.clone()).to_binary(&node) {
+            Err(WriterError::NameTooLong { path, length, max }) => {
+                assert_eq!(path, format!("/root/entry/@{}", long));
+                assert_eq!((length, max), (65, 64));
+            },
+            result => panic!("Unexpected result: {:?}", result),
+        };
+
+        // Sixbit names may be longer
+        let data = Writer::new().to_binary(&node).expect("Failed to encode");
+        let (collection, _) = crate::from_slice(&data).expect("Failed to decode");
+        assert_eq!(collection.as_node().expect("Failed to convert"), node);
+
+        let max = "a".repeat(64);
+        let node = Node::with_nodes("root", vec![Node::new(max.as_str())]);
+        let data = Writer::with_options(uncompressed)
+            .to_binary(&node)
+            .expect("Failed to encode");
+        let (collection, _) = crate::from_slice(&data).expect("Failed to decode");
+        assert_eq!(collection.as_node().expect("Failed to convert"), node);
+
+        let node = Node::with_nodes("root", vec![Node::new("a".repeat(256))]);
+        match Writer::new().to_binary(&node) {
+            Err(WriterError::NameTooLong { path, max, .. }) => {
+                assert_eq!(path, format!("/root/{}", "a".repeat(256)));
+                assert_eq!(max, 255);
+            },
+            result => panic!("Unexpected result: {:?}", result),
+        };
+    }
+
+    #[test]
+    fn test_name_encoding() {
+        let write = |compression, name| {
+            let encoding = EncodingType::UTF_8;
+            let mut buf = ByteBufferWrite::new(Vec::new());
+            write_node_name(compression, encoding, &mut buf, name, String::new)
+                .expect("Failed to write name");
+            buf.into_inner()
+        };
+
+        // The length byte holds the array flag and the length minus one, the
+        // name is not null terminated.
+        let uncompressed = write(CompressionType::Uncompressed, "ab");
+        assert_eq!(uncompressed, [0x41, b'a', b'b']);
+        assert_eq!(write(CompressionType::Compressed, "ab"), [2, 0x9a, 0x70]);
+
+        // Earlier versions counted a null byte after the name in its length.
+        let uncompressed = Options::builder()
+            .compression(CompressionType::Uncompressed)
+            .build();
+        let mut data = Writer::with_options(uncompressed)
+            .to_binary(&Node::new("abc"))
+            .expect("Failed to encode");
+        assert_eq!(&data[9..13], &[0x42, b'a', b'b', b'c']);
+        data[12] = 0;
+        let (collection, _) = crate::from_slice(&data).expect("Failed to decode");
+        let node = collection.as_node().expect("Failed to convert");
+        assert_eq!(node.key(), "ab");
+    }
+
+    #[test]
+    fn test_collection_encoding() {
+        let node = Node::with_nodes("root", vec![Node::with_attrs("entry", &[("name", "名前")])]);
+        let input = Writer::with_options(Options::with_encoding(EncodingType::UTF_8))
+            .to_binary(&node)
+            .expect("Failed to encode");
+        let (collection, _) = crate::from_slice(&input).expect("Failed to decode");
+
+        let options = Options::with_encoding(EncodingType::SHIFT_JIS);
+        let output = Writer::with_options(options.clone())
+            .to_binary(&collection)
+            .expect("Failed to encode");
+        let expected = Writer::with_options(options.clone())
+            .to_binary(&node)
+            .expect("Failed to encode");
+        assert_eq!(output, expected);
+        assert_eq!(
+            collection.encoded_size_hint_with_options(&options).total(),
+            output.len()
+        );
+
+        let (collection, encoding) = crate::from_slice(&output).expect("Failed to decode");
+        assert_eq!(encoding, EncodingType::SHIFT_JIS);
+        assert_eq!(collection.as_node().expect("Failed to convert"), node);
     }
 }
@@ -5,7 +5,8 @@ use anyhow::Context;
 use byteorder::{BigEndian, ByteOrder};
 use clap::{App, Arg};
 use encoding_rs::Encoding;
-use kbinxml::{EncodingType, Options, Printer};
+use kbinxml::profile::Profile;
+use kbinxml::{CompressionType, EncodingType, Options, Printer};
 
 fn display_buf(buf: &[u8]) -> Result<(), IoError> {
     io::stdout().write_all(buf)?;
@@ -51,6 +52,89 @@ fn compare_slice(left: &[u8], right: &[u8]) {
     }
 }
 
+/// The profile options with the encoding and compression from the command
+/// line, or those of the input if neither sets them.
+fn output_options(
+    profile: &Profile,
+    output_encoding: Option<EncodingType>,
+    input_encoding: EncodingType,
+    output_compression: Option<CompressionType>,
+    input_compression: Option<CompressionType>,
+) -> Result<Options, anyhow::Error> {
+    let mut builder = profile.to_builder()?;
+    match output_encoding {
+        Some(encoding) => {
+            builder.encoding(encoding);
+        },
+        None if profile.encoding.is_none() => {
+            builder.encoding(input_encoding);
+        },
+        None => {},
+    };
+    match (output_compression, input_compression) {
+        (Some(compression), _) => {
+            builder.compression(compression);
+        },
+        (None, Some(compression)) if profile.compression.is_none() => {
+            builder.compression(compression);
+        },
+        _ => {},
+    };
+
+    // Conflicts depend on the compression and encoding set above
+    let options = builder.build();
+    options.validate()?;
+
+    Ok(options)
+}
+
+/// Convert a binary document to text XML, and the text XML back to binary
+/// with the output options for comparison.
+fn binary_to_text(
+    profile: &Profile,
+    output_encoding: Option<EncodingType>,
+    output_compression: Option<CompressionType>,
+    input: &[u8],
+) -> Result<(Vec<u8>, Vec<u8>), anyhow::Error> {
+    let read_options = profile.to_builder()?.build();
+    let (collection, input_encoding) =
+        kbinxml::from_binary_with_options(read_options.clone(), input.to_vec().into())?;
+    let text = kbinxml::to_text_xml_with_options(read_options, &collection)?;
+
+    let options = output_options(
+        profile,
+        output_encoding,
+        input_encoding,
+        output_compression,
+        collection.compression(),
+    )?;
+    let buf = kbinxml::to_binary_with_options(options, &collection)?;
+
+    Ok((text, buf))
+}
+
+/// Convert a text XML document to binary.
+fn text_to_binary(
+    profile: &Profile,
+    output_encoding: Option<EncodingType>,
+    output_compression: Option<CompressionType>,
+    input: &[u8],
+) -> Result<Vec<u8>, anyhow::Error> {
+    let read_options = profile.to_builder()?.build();
+    let (collection, input_encoding) = kbinxml::from_text_xml_with_options(read_options, input)?;
+
+    let options = output_options(
+        profile,
+        output_encoding,
+        input_encoding,
+        output_compression,
+        None,
+    )?;
+    let buf = kbinxml::to_binary_with_options(options, &collection)?;
+
+    Ok(buf)
+}
+
 fn main() -> Result<(), anyhow::Error> {
     pretty_env_logger::init();
 
@@ -71,6 +155,20 @@ fn main() -> Result<(), anyhow::Error> {
                 .long("encoding")
                 .takes_value(true),
         )
+        .arg(
+            Arg::new("compression")
+                .help("Set the compression used when encoding kbin data")
+                .short('c')
+                .long("compression")
+                .takes_value(true)
+                .possible_values(["compressed", "uncompressed"]),
+        )
+        .arg(
+            Arg::new("profile")
+                .help("Load conversion options from a JSON or TOML mapping profile")
+                .long("profile")
+                .takes_value(true),
+        )
         .arg(
             Arg::new("input")
                 .help("The file to convert")
@@ -90,6 +188,20 @@ fn main() -> Result<(), anyhow::Error> {
         None
     };
 
+    let output_compression = match matches.value_of("compression") {
+        Some("compressed") => Some(CompressionType::Compressed),
+        Some("uncompressed") => Some(CompressionType::Uncompressed),
+        _ => None,
+    };
+
+    let profile = match matches.value_of("profile") {
+        Some(path) => Profile::load(path).context("Failed to load mapping profile")?,
+        None => Profile::default(),
+    };
+    // Check the profile before reading any input, its options are validated
+    // once the input compression and encoding are known.
+    profile.to_builder()?;
+
     eprintln!("file_name: {}", file_name);
 
     // Read '-' as standard input.
@@ -107,18 +219,12 @@ fn main() -> Result<(), anyhow::Error> {
             Printer::run(contents.clone())?;
         }
 
-        let (collection, _encoding) = kbinxml::from_slice(&contents)?;
-        let text_original = kbinxml::to_text_xml(&collection)?;
+        let (text_original, buf) =
+            binary_to_text(&profile, output_encoding, output_compression, &contents)?;
         display_buf(&text_original)?;
-
-        let (collection, encoding_original) = kbinxml::from_slice(&contents)?;
-        let options = Options::with_encoding(output_encoding.unwrap_or(encoding_original));
-        let buf = kbinxml::to_binary_with_options(options, &collection)?;
         compare_slice(&buf, &contents);
     } else {
-        let (collection, encoding) = kbinxml::from_text_xml(&contents)?;
-        let options = Options::with_encoding(output_encoding.unwrap_or(encoding));
-        let buf = kbinxml::to_binary_with_options(options, &collection)?;
+        let buf = text_to_binary(&profile, output_encoding, output_compression, &contents)?;
 
         if printer_enabled {
             Printer::run(buf.clone())?;
@@ -129,3 +235,99 @@ fn main() -> Result<(), anyhow::Error> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use kbinxml::profile::Profile;
+    use kbinxml::CompressionType::{self, Compressed, Uncompressed};
+    use kbinxml::{EncodingType, Node, Options};
+
+    use super::{binary_to_text, output_options, text_to_binary};
+
+    /// The compression and encoding of a document encoded with the options for
+    /// the flags, read from a Shift-JIS input.
+    fn header(
+        profile: &Profile,
+        flags: (Option<CompressionType>, Option<EncodingType>),
+        input_compression: Option<CompressionType>,
+    ) -> (u8, u8) {
+        let (compression, encoding) = flags;
+        let options = output_options(
+            profile,
+            encoding,
+            EncodingType::SHIFT_JIS,
+            compression,
+            input_compression,
+        )
+        .expect("Failed to build options");
+        let data =
+            kbinxml::to_binary_with_options(options, &Node::new("root")).expect("Failed to encode");
+
+        (data[1], data[2])
+    }
+
+    #[test]
+    fn test_output_options() {
+        let expected = |compression: CompressionType, encoding: EncodingType| {
+            (compression.to_byte(), encoding.to_byte())
+        };
+        let profile = Profile::default();
+
+        // Binary input keeps its compression, text input uses the default
+        let found = header(&profile, (None, None), Some(Uncompressed));
+        assert_eq!(found, expected(Uncompressed, EncodingType::SHIFT_JIS));
+        let found = header(&profile, (None, None), None);
+        assert_eq!(found, expected(Compressed, EncodingType::SHIFT_JIS));
+
+        let flags = (Some(Compressed), Some(EncodingType::UTF_8));
+        let found = header(&profile, flags, Some(Uncompressed));
+        assert_eq!(found, expected(Compressed, EncodingType::UTF_8));
+
+        // A profile takes precedence over the input, the flags over both
+        let profile = Profile {
+            compression: Some(Uncompressed),
+            encoding: Some("utf-8".into()),
+            ..Profile::default()
+        };
+        let found = header(&profile, (None, None), Some(Compressed));
+        assert_eq!(found, expected(Uncompressed, EncodingType::UTF_8));
+        let flags = (Some(Compressed), Some(EncodingType::EUC_JP));
+        let found = header(&profile, flags, Some(Uncompressed));
+        assert_eq!(found, expected(Compressed, EncodingType::EUC_JP));
+
+        // Sixbit names cannot hold the name renamed from
+        let mut profile = Profile::default();
+        profile
+            .renames
+            .insert("illegal-name".into(), "legal_name".into());
+        let options = |compression| {
+            let encoding = EncodingType::SHIFT_JIS;
+            output_options(&profile, None, encoding, compression, Some(Uncompressed))
+        };
+        assert!(options(None).is_ok());
+        assert!(options(Some(Compressed)).is_err());
+    }
+    #[test]
+    fn test_renames() {
+        let entry = Node::with_attrs("illegal-name", &[("illegal-name", "1")]);
+        let node = Node::with_nodes("root", [entry]);
+        let options = Options::new(Uncompressed, EncodingType::SHIFT_JIS);
+        let input = kbinxml::to_binary_with_options(options, &node).expect("Failed to encode");
+        let mut profile = Profile::default();
+        profile
+            .renames
+            .insert("illegal-name".into(), "legal_name".into());
+
+        let (text, output) =
+            binary_to_text(&profile, None, None, &input).expect("Failed to convert");
+        let text = String::from_utf8(text).expect("Invalid text XML");
+        assert!(text.contains(r#"<legal_name legal_name="1"/>"#));
+        assert_eq!(output, input);
+
+        // Collections are written to text XML as UTF-8
+        let encoding = Some(EncodingType::SHIFT_JIS);
+        let output = text_to_binary(&profile, encoding, Some(Uncompressed), text.as_bytes())
+            .expect("Failed to convert");
+        assert_eq!(output, input);
+    }
+}